@@ -29,7 +29,7 @@ function App() {
       left={
         <>
           <div style={{ flex: 1, overflow: "hidden" }}>
-            <CodeEditor ref={editorRef} value={code} onChange={setCode} error={result.error} implicitNodes={result.implicit_nodes} />
+            <CodeEditor ref={editorRef} value={code} onChange={setCode} diagnostics={result.diagnostics} implicitNodes={result.implicit_nodes} />
           </div>
           <Toolbar code={code} onCodeChange={setCode} />
         </>
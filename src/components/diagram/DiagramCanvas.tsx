@@ -7,6 +7,7 @@ import { useDiagramDrag } from "../../hooks/useDiagramDrag";
 import { SVGNode } from "./SVGNode";
 import { SVGShapeNode } from "./SVGShapeNode";
 import { SVGGroup } from "./SVGGroup";
+import { SVGNote } from "./SVGNote";
 import { EdgeDefs, SVGEdges } from "./SVGEdges";
 import type { CodeEditorRef } from "../editor/CodeEditor";
 import { useTheme } from "../../hooks/useTheme";
@@ -245,11 +246,14 @@ export function DiagramCanvas({ result, code, onCodeChange, editorRef }: Diagram
     setTheme(resolvedTheme === "dark" ? "light" : "dark");
   }, [resolvedTheme, setTheme]);
 
+  const firstError = result.diagnostics?.find(d => d.severity === "error");
+
 
   // Calculate SVG viewport with support for negative coordinates
   const svgViewport = useMemo(() => {
     const nodes = result.nodes ?? [];
     const groups = result.groups ?? [];
+    const notes = result.notes ?? [];
 
     if (nodes.length === 0 && groups.length === 0) {
       return { x: 0, y: 0, width: 800, height: 600 };
@@ -274,6 +278,13 @@ export function DiagramCanvas({ result, code, onCodeChange, editorRef }: Diagram
       maxY = Math.max(maxY, g.bounds.y + g.bounds.h);
     });
 
+    notes.forEach(n => {
+      minX = Math.min(minX, n.bounds.x);
+      minY = Math.min(minY, n.bounds.y);
+      maxX = Math.max(maxX, n.bounds.x + n.bounds.w);
+      maxY = Math.max(maxY, n.bounds.y + n.bounds.h);
+    });
+
     // Add padding
     const padding = 50;
     return {
@@ -282,7 +293,7 @@ export function DiagramCanvas({ result, code, onCodeChange, editorRef }: Diagram
       width: maxX - minX + padding * 2,
       height: maxY - minY + padding * 2,
     };
-  }, [result.nodes, result.groups]);
+  }, [result.nodes, result.groups, result.notes]);
 
   // Global keyboard shortcuts for undo/redo (works in fullscreen mode)
   useEffect(() => {
@@ -496,12 +507,13 @@ export function DiagramCanvas({ result, code, onCodeChange, editorRef }: Diagram
             className={dragState ? "cursor-grabbing" : ""}
           >
             <defs>
-              <EdgeDefs />
+              <EdgeDefs edges={result.edges ?? []} />
             </defs>
 
-            {result.error && (
+            {/* Errors are marked in the editor; only spell one out when nothing could be drawn */}
+            {!result.nodes?.length && firstError && (
               <text x={50} y={50} fill='#ef4444' fontSize={14}>
-                {result.error.message}
+                {firstError.message}
               </text>
             )}
 
@@ -515,7 +527,7 @@ export function DiagramCanvas({ result, code, onCodeChange, editorRef }: Diagram
             })}
 
             {/* Edges (middle layer) */}
-            {result.nodes && result.edges && <SVGEdges edges={result.edges} nodes={result.nodes} dragState={dragState} />}
+            {result.nodes && result.edges && <SVGEdges edges={result.edges} nodes={result.nodes} groups={result.groups ?? []} dragState={dragState} />}
 
             {/* Nodes (top layer) */}
             {result.nodes?.map(node => {
@@ -538,6 +550,9 @@ export function DiagramCanvas({ result, code, onCodeChange, editorRef }: Diagram
                 />
               );
             })}
+
+            {/* Notes sit next to what they annotate, above everything else */}
+            {result.notes?.map((note, index) => <SVGNote key={index} note={note} />)}
           </svg>
         </TransformComponent>
       </TransformWrapper>
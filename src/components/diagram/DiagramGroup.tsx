@@ -20,7 +20,7 @@ export function DiagramGroup({ group, x, y, onMouseDown }: DiagramGroupProps) {
             onMouseDown={onMouseDown}
         >
             <div className="absolute -top-2.5 left-2 bg-neutral-800 px-1.5 text-[11px] font-mono text-neutral-500 pointer-events-none">
-                {group.id}
+                {group.name}
             </div>
         </div>
     );
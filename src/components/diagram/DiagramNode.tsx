@@ -92,7 +92,7 @@ export function DiagramNode({ node, x, y, onMouseDown, onUnlock }: DiagramNodePr
             {/* Node name / label */}
             <div className={`flex justify-between items-center font-bold mb-1 border-b border-neutral-700 pb-1 ${titleColor}`}>
                 <span className={node.modifiers.includes("abstract") ? "italic" : ""}>
-                    {node.label ?? node.id}
+                    {node.label ?? node.name}
                 </span>
                 {node.has_pos && (
                     <Lock
@@ -3,8 +3,8 @@
  * Extracted from EdgeOverlay to work in a unified SVG context
  */
 
-import type { DiagramEdge, DiagramNode, DragState, Bounds } from "../../types/diagram";
-import { getEdgeMarkers, isDashed, getShape, getOptimalConnectionPoints } from "../../utils/geometry";
+import type { DiagramEdge, DiagramGroup, DiagramNode, DragState, Bounds } from "../../types/diagram";
+import { getEdgeMarkers, isDashed, getShape, getOptimalConnectionPoints, offsetRoute, markerSuffix } from "../../utils/geometry";
 /**
  * SVG marker definitions for edge arrows
  * These should be placed in the <defs> section of the parent SVG
@@ -13,29 +13,46 @@ import { getEdgeMarkers, isDashed, getShape, getOptimalConnectionPoints } from "
  * - When used as markerEnd: points forward along the line (normal direction)
  * - When used as markerStart: automatically reverses to point backward (towards start)
  * - refX is set to position the marker correctly at the line endpoint
+ *
+ * Edges with a `@stroke` color get their own set of markers in that color,
+ * with ids suffixed by `markerSuffix`.
  */
-export function EdgeDefs() {
+export function EdgeDefs({ edges = [] }: { edges?: DiagramEdge[] }) {
+  const colors = [...new Set(edges.map(e => e.style.stroke).filter((c): c is string => !!c))];
   return (
     <>
-      <marker id='arrowhead' markerWidth='8' markerHeight='8' refX='7' refY='4' orient='auto-start-reverse'>
-        <polyline points='1 1, 7 4, 1 7' fill='var(--canvas-marker-fill)' stroke='var(--canvas-edge)' strokeWidth='1' />
-        <line x1='0' y1='4' x2='7' y2='4' stroke='var(--canvas-edge)' strokeWidth='1' />
+      <EdgeMarkers color={null} />
+      {colors.map(color => (
+        <EdgeMarkers key={color} color={color} />
+      ))}
+    </>
+  );
+}
+
+function EdgeMarkers({ color }: { color: string | null }) {
+  const suffix = markerSuffix(color);
+  const edge = color ?? "var(--canvas-edge)";
+  return (
+    <>
+      <marker id={`arrowhead${suffix}`} markerWidth='8' markerHeight='8' refX='7' refY='4' orient='auto-start-reverse'>
+        <polyline points='1 1, 7 4, 1 7' fill='var(--canvas-marker-fill)' stroke={edge} strokeWidth='1' />
+        <line x1='0' y1='4' x2='7' y2='4' stroke={edge} strokeWidth='1' />
       </marker>
 
-      <marker id='rounded-arrowhead' markerWidth='8' markerHeight='8' refX='3' refY='4' orient='auto-start-reverse'>
-        <path d='M 1 1 Q 5 4, 1 7' fill='none' stroke='var(--canvas-edge)' strokeWidth='1' />
+      <marker id={`rounded-arrowhead${suffix}`} markerWidth='8' markerHeight='8' refX='3' refY='4' orient='auto-start-reverse'>
+        <path d='M 1 1 Q 5 4, 1 7' fill='none' stroke={edge} strokeWidth='1' />
       </marker>
 
-      <marker id='triangle' markerWidth='10' markerHeight='9' refX='9' refY='4.5' orient='auto-start-reverse'>
-        <polygon points='1 1, 9 4.5, 1 8' fill='var(--canvas-marker-fill)' stroke='var(--canvas-edge)' strokeWidth='1' />
+      <marker id={`triangle${suffix}`} markerWidth='10' markerHeight='9' refX='9' refY='4.5' orient='auto-start-reverse'>
+        <polygon points='1 1, 9 4.5, 1 8' fill='var(--canvas-marker-fill)' stroke={edge} strokeWidth='1' />
       </marker>
 
-      <marker id='diamond' markerWidth='13' markerHeight='10' refX='11' refY='5' orient='auto-start-reverse'>
-        <polygon points='0 5, 6 1, 12 5, 6 9' fill='var(--canvas-edge)' />
+      <marker id={`diamond${suffix}`} markerWidth='13' markerHeight='10' refX='11' refY='5' orient='auto-start-reverse'>
+        <polygon points='0 5, 6 1, 12 5, 6 9' fill={edge} />
       </marker>
 
-      <marker id='diamond-empty' markerWidth='13' markerHeight='10' refX='11' refY='5' orient='auto-start-reverse'>
-        <polygon points='0 5, 6 1, 12 5, 6 9' fill='var(--canvas-marker-fill)' stroke='var(--canvas-edge)' strokeWidth='1' />
+      <marker id={`diamond-empty${suffix}`} markerWidth='13' markerHeight='10' refX='11' refY='5' orient='auto-start-reverse'>
+        <polygon points='0 5, 6 1, 12 5, 6 9' fill='var(--canvas-marker-fill)' stroke={edge} strokeWidth='1' />
       </marker>
     </>
   );
@@ -44,6 +61,7 @@ export function EdgeDefs() {
 interface SVGEdgesProps {
   edges: DiagramEdge[];
   nodes: DiagramNode[];
+  groups: DiagramGroup[];
   dragState: DragState | null;
 }
 
@@ -51,25 +69,32 @@ interface SVGEdgesProps {
  * Render all edges as SVG elements
  * This should be placed inside the parent SVG, not as a wrapper
  */
-export function SVGEdges({ edges, nodes, dragState }: SVGEdgesProps) {
-  // Build a map from node id to bounds
+export function SVGEdges({ edges, nodes, groups, dragState }: SVGEdgesProps) {
+  // Build maps from node/group id to bounds
   const nodeMap = new Map<string, Bounds>();
   nodes.forEach(n => nodeMap.set(n.id, n.bounds));
+  const groupMap = new Map<string, Bounds>();
+  groups.forEach(g => groupMap.set(g.id, g.bounds));
 
   return (
     <g className='edges'>
       {edges.map((edge, i) => {
-        const fromNode = nodes.find(n => n.id === edge.from);
-        const toNode = nodes.find(n => n.id === edge.to);
+        // Group edges attach to group borders; their endpoints are never dragged as nodes
+        const isGroupEdge = edge.kind === "group";
+        const fromNode = isGroupEdge ? undefined : nodes.find(n => n.id === edge.from);
+        const toNode = isGroupEdge ? undefined : nodes.find(n => n.id === edge.to);
 
-        let fromBounds = nodeMap.get(edge.from);
-        let toBounds = nodeMap.get(edge.to);
+        const boundsMap = isGroupEdge ? groupMap : nodeMap;
+        let fromBounds = boundsMap.get(edge.from);
+        let toBounds = boundsMap.get(edge.to);
 
         // Update bounds if node is being dragged
-        if (dragState?.type === "node" && fromNode && dragState.id === fromNode.id) {
+        const fromDragged = dragState?.type === "node" && fromNode !== undefined && dragState.id === fromNode.id;
+        const toDragged = dragState?.type === "node" && toNode !== undefined && dragState.id === toNode.id;
+        if (dragState && fromDragged && fromNode) {
           fromBounds = { ...fromNode.bounds, x: dragState.currentX, y: dragState.currentY };
         }
-        if (dragState?.type === "node" && toNode && dragState.id === toNode.id) {
+        if (dragState && toDragged && toNode) {
           toBounds = { ...toNode.bounds, x: dragState.currentX, y: dragState.currentY };
         }
 
@@ -78,36 +103,47 @@ export function SVGEdges({ edges, nodes, dragState }: SVGEdgesProps) {
         const fromShape = fromNode ? getShape(fromNode.modifiers) : "rectangle";
         const toShape = toNode ? getShape(toNode.modifiers) : "rectangle";
 
-        const { markerStart, markerEnd } = getEdgeMarkers(edge.arrow);
+        const style = edge.style;
+        const { markerStart, markerEnd } = getEdgeMarkers(edge.arrow, style.stroke);
+        const textColor = style.text_color ?? "var(--canvas-text)";
+        const dash = style.dash ?? (isDashed(edge.arrow) ? [8, 4] : []);
 
         // Apply offset only at the arrow-head end (where the marker is)
         const ARROW_OFFSET = 5;
         const startOffset = markerStart ? ARROW_OFFSET : 0;
         const endOffset = markerEnd ? ARROW_OFFSET : 0;
 
-        // Use optimal connection points for shortest arrows
-        const { start, end } = getOptimalConnectionPoints(
-          fromBounds,
-          toBounds,
-          fromShape,
-          toShape,
-          startOffset,
-          endOffset
-        );
-
-        const midX = (start.x + end.x) / 2;
-        const midY = (start.y + end.y) / 2;
+        // Use the route from the core, unless an endpoint is being dragged
+        // (then fall back to a live straight connection until the next compile)
+        let points: { x: number; y: number }[];
+        let midX: number;
+        let midY: number;
+        if (!fromDragged && !toDragged && edge.points?.length >= 2) {
+          points = offsetRoute(edge.points, startOffset, endOffset);
+          midX = edge.label_pos.x;
+          midY = edge.label_pos.y;
+        } else {
+          const { start, end } = getOptimalConnectionPoints(
+            fromBounds,
+            toBounds,
+            fromShape,
+            toShape,
+            startOffset,
+            endOffset
+          );
+          points = [start, end];
+          midX = (start.x + end.x) / 2;
+          midY = (start.y + end.y) / 2;
+        }
 
         return (
           <g key={i}>
-            <line
-              x1={start.x}
-              y1={start.y}
-              x2={end.x}
-              y2={end.y}
-              stroke='var(--canvas-edge)'
-              strokeWidth={1.5}
-              strokeDasharray={isDashed(edge.arrow) ? "8,4" : undefined}
+            <polyline
+              points={points.map(p => `${p.x},${p.y}`).join(" ")}
+              fill='none'
+              stroke={style.stroke ?? "var(--canvas-edge)"}
+              strokeWidth={style.stroke_width ?? 1.5}
+              strokeDasharray={dash.length ? dash.join(",") : undefined}
               markerEnd={markerEnd}
               markerStart={markerStart}
             />
@@ -132,7 +168,7 @@ export function SVGEdges({ edges, nodes, dragState }: SVGEdgesProps) {
                   <text
                     x={midX}
                     y={midY}
-                    fill='var(--canvas-text)'
+                    fill={textColor}
                     fontSize={fontSize}
                     fontFamily='ui-monospace, monospace'
                     textAnchor='middle'
@@ -143,6 +179,26 @@ export function SVGEdges({ edges, nodes, dragState }: SVGEdgesProps) {
                 </g>
               );
             })()}
+            {!fromDragged && !toDragged && ([
+              [edge.from_end, edge.from_label_pos],
+              [edge.to_end, edge.to_label_pos],
+            ] as const).map(([end, pos], k) => {
+              const text = [end.multiplicity, end.role].filter(Boolean).join(" ");
+              return text && (
+                <text
+                  key={k}
+                  x={pos.x}
+                  y={pos.y}
+                  fill={textColor}
+                  fontSize={10}
+                  fontFamily='ui-monospace, monospace'
+                  textAnchor='middle'
+                  dominantBaseline='central'
+                >
+                  {text}
+                </text>
+              );
+            })}
           </g>
         );
       })}
@@ -12,6 +12,8 @@ interface SVGGroupProps {
 export function SVGGroup({ group, x, y, onMouseDown, exportMode = false }: SVGGroupProps) {
     const labelPadding = 6;
     const labelFontSize = 11;
+    const { style } = group;
+    const fill = style.fill ?? "var(--canvas-node-bg)";
 
     return (
         <g
@@ -27,29 +29,30 @@ export function SVGGroup({ group, x, y, onMouseDown, exportMode = false }: SVGGr
                 height={group.bounds.h}
                 rx={6}
                 ry={6}
-                fill="var(--canvas-node-bg)"
-                stroke="var(--canvas-border)"
-                strokeWidth={1}
+                fill={fill}
+                stroke={style.stroke ?? "var(--canvas-border)"}
+                strokeWidth={style.stroke_width ?? 1}
+                strokeDasharray={style.dash?.length ? style.dash.join(",") : undefined}
             />
 
             {/* Label background */}
             <rect
                 x={8}
                 y={-10}
-                width={group.id.length * 7 + labelPadding * 2}
+                width={group.label_width + labelPadding * 2}
                 height={20}
-                fill="var(--canvas-node-bg)"
+                fill={fill}
             />
 
             {/* Label text */}
             <text
                 x={8 + labelPadding}
                 y={4}
-                fill="var(--canvas-text-muted)"
+                fill={style.text_color ?? "var(--canvas-text-muted)"}
                 fontSize={labelFontSize}
-                fontFamily="ui-monospace, monospace"
+                fontFamily={group.rendering_config.font_family}
             >
-                {group.id}
+                {group.name}
             </text>
         </g>
     );
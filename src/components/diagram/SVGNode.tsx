@@ -35,8 +35,12 @@ const TITLE_COLORS: Record<string, string> = {
 
 
 export function SVGNode({ node, x, y, onMouseDown, onUnlock, onResizeStart, exportMode = false }: SVGNodeProps) {
-  const borderColor = BORDER_COLORS[node.kind] ?? BORDER_COLORS.class;
-  const titleColor = TITLE_COLORS[node.kind] ?? TITLE_COLORS.class;
+  // Style directives override the kind's colors
+  const { style } = node;
+  const borderColor = style.stroke ?? BORDER_COLORS[node.kind] ?? BORDER_COLORS.class;
+  const titleColor = style.text_color ?? TITLE_COLORS[node.kind] ?? TITLE_COLORS.class;
+  const textColor = style.text_color ?? "var(--canvas-text)";
+  const fillColor = style.fill ?? "var(--canvas-node-bg)";
 
   const w = node.bounds.w;
   const h = node.bounds.h;
@@ -72,7 +76,18 @@ export function SVGNode({ node, x, y, onMouseDown, onUnlock, onResizeStart, expo
   return (
     <g transform={`translate(${x}, ${y})`} onMouseDown={onMouseDown} style={{ cursor: exportMode ? "default" : "grab" }}>
       {/* Background */}
-      <rect x={0} y={0} width={node.bounds.w} height={node.bounds.h} rx={4} ry={4} fill='var(--canvas-node-bg)' stroke={borderColor} strokeWidth={1} />
+      <rect
+        x={0}
+        y={0}
+        width={node.bounds.w}
+        height={node.bounds.h}
+        rx={4}
+        ry={4}
+        fill={fillColor}
+        stroke={borderColor}
+        strokeWidth={style.stroke_width ?? 1}
+        strokeDasharray={style.dash?.length ? style.dash.join(",") : undefined}
+      />
 
 
 
@@ -144,12 +159,11 @@ export function SVGNode({ node, x, y, onMouseDown, onUnlock, onResizeStart, expo
             return (
               <text
                 key={i}
-                x={node.bounds.w / 2}
+                x={element.data.x}
                 y={element.data.y}
-                textAnchor='middle'
-                fill='var(--canvas-text)'
+                fill={textColor}
                 fontSize={element.data.font_size}
-                fontFamily='ui-monospace, monospace'
+                fontFamily={node.rendering_config.font_family}
                 fontStyle='italic'
               >
                 {element.data.text}
@@ -159,11 +173,11 @@ export function SVGNode({ node, x, y, onMouseDown, onUnlock, onResizeStart, expo
             return (
               <text
                 key={i}
-                x={node.rendering_config.padding}
+                x={element.data.x}
                 y={element.data.y}
                 fill={titleColor}
                 fontSize={element.data.font_size}
-                fontFamily='ui-monospace, monospace'
+                fontFamily={node.rendering_config.font_family}
                 fontWeight='bold'
                 fontStyle={element.data.italic ? "italic" : "normal"}
               >
@@ -178,7 +192,7 @@ export function SVGNode({ node, x, y, onMouseDown, onUnlock, onResizeStart, expo
                 y1={element.data.y1}
                 x2={node.bounds.w}
                 y2={element.data.y2}
-                stroke='var(--canvas-border)'
+                stroke={style.stroke ?? "var(--canvas-border)"}
                 strokeWidth={1}
               />
             );
@@ -186,15 +200,42 @@ export function SVGNode({ node, x, y, onMouseDown, onUnlock, onResizeStart, expo
             return (
               <text
                 key={i}
-                x={node.rendering_config.padding}
+                x={element.data.x}
                 y={element.data.y}
-                fill='var(--canvas-text)'
+                fill={textColor}
                 fontSize={element.data.font_size}
-                fontFamily='ui-monospace, monospace'
+                fontFamily={node.rendering_config.font_family}
+                fontStyle={element.data.italic ? "italic" : "normal"}
+                textDecoration={element.data.underline ? "underline" : undefined}
               >
                 {element.data.text}
               </text>
             );
+          case "TemplateParams":
+            return (
+              <g key={i}>
+                <rect
+                  x={element.data.x}
+                  y={element.data.y}
+                  width={element.data.width}
+                  height={element.data.height}
+                  fill='var(--canvas-node-bg)'
+                  stroke='var(--canvas-border)'
+                  strokeWidth={1}
+                  strokeDasharray='4 2'
+                />
+                <text
+                  x={element.data.x + node.rendering_config.padding}
+                  y={element.data.y + element.data.height - 4}
+                  fill={textColor}
+                  fontSize={element.data.font_size}
+                  fontFamily={node.rendering_config.font_family}
+                  fontStyle='italic'
+                >
+                  {element.data.text}
+                </text>
+              </g>
+            );
         }
       })}
     </g>
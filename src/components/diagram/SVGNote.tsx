@@ -0,0 +1,56 @@
+import type { DiagramNote } from "../../types/diagram";
+
+interface SVGNoteProps {
+    note: DiagramNote;
+}
+
+export function SVGNote({ note }: SVGNoteProps) {
+    const { x, y, w, h } = note.bounds;
+    const fold = note.fold;
+
+    return (
+        <g>
+            {/* Connector to the annotated node, group or relation */}
+            {note.connector.length >= 2 && (
+                <polyline
+                    points={note.connector.map(p => `${p.x},${p.y}`).join(" ")}
+                    fill="none"
+                    stroke="var(--canvas-edge)"
+                    strokeWidth={1}
+                    strokeDasharray="4 2"
+                />
+            )}
+
+            <g transform={`translate(${x}, ${y})`}>
+                {/* Sheet with the top-right corner cut off, and the folded-over corner */}
+                <polygon
+                    points={`0,0 ${w - fold},0 ${w},${fold} ${w},${h} 0,${h}`}
+                    fill="var(--canvas-note-bg)"
+                    stroke="var(--canvas-border)"
+                    strokeWidth={1}
+                />
+                <path
+                    d={`M ${w - fold} 0 L ${w - fold} ${fold} L ${w} ${fold}`}
+                    fill="none"
+                    stroke="var(--canvas-border)"
+                    strokeWidth={1}
+                />
+
+                {note.text_elements.map((el, i) =>
+                    el.type === "BodyText" ? (
+                        <text
+                            key={i}
+                            x={el.data.x}
+                            y={el.data.y}
+                            fill="var(--canvas-text)"
+                            fontSize={el.data.font_size}
+                            fontFamily={note.rendering_config.font_family}
+                        >
+                            {el.data.text}
+                        </text>
+                    ) : null
+                )}
+            </g>
+        </g>
+    );
+}
@@ -17,7 +17,7 @@ interface SVGShapeNodeProps {
 /** SVGShapeNode renders node-kind elements (simple shapes with labels) */
 export function SVGShapeNode({ node, x, y, onMouseDown, onUnlock, onResizeStart, exportMode = false }: SVGShapeNodeProps) {
   const shape = getShape(node.modifiers);
-  const label = node.label ?? node.id;
+  const label = node.label ?? node.name;
   const w = node.bounds.w;
   const h = node.bounds.h;
 
@@ -25,11 +25,14 @@ export function SVGShapeNode({ node, x, y, onMouseDown, onUnlock, onResizeStart,
   const cx = w / 2;
   const cy = h / 2;
 
-  // Text styling
+  // Text styling, overridden by the node's style directives
+  const { style } = node;
   const fontSize = 12;
-  const textColor = "var(--canvas-text)";
-  const strokeColor = "var(--canvas-border)";
-  const fillColor = "var(--canvas-node-bg)";
+  const textColor = style.text_color ?? "var(--canvas-text)";
+  const strokeColor = style.stroke ?? "var(--canvas-border)";
+  const fillColor = style.fill ?? "var(--canvas-node-bg)";
+  const strokeWidth = style.stroke_width ?? 1;
+  const strokeDasharray = style.dash?.length ? style.dash.join(",") : undefined;
 
   // Resize handle styling
   const handleSize = 8;
@@ -58,14 +61,14 @@ export function SVGShapeNode({ node, x, y, onMouseDown, onUnlock, onResizeStart,
   return (
     <g transform={`translate(${x}, ${y})`} onMouseDown={onMouseDown} style={{ cursor: exportMode ? "default" : "grab" }}>
       {/* Shape rendering based on type */}
-      {shape === "circle" && <ellipse cx={cx} cy={cy} rx={w / 2 - 1} ry={h / 2 - 1} fill={fillColor} stroke={strokeColor} strokeWidth={1} />}
+      {shape === "circle" && <ellipse cx={cx} cy={cy} rx={w / 2 - 1} ry={h / 2 - 1} fill={fillColor} stroke={strokeColor} strokeWidth={strokeWidth} strokeDasharray={strokeDasharray} />}
 
-      {shape === "diamond" && <polygon points={`${cx},1 ${w - 1},${cy} ${cx},${h - 1} 1,${cy}`} fill={fillColor} stroke={strokeColor} strokeWidth={1} />}
+      {shape === "diamond" && <polygon points={`${cx},1 ${w - 1},${cy} ${cx},${h - 1} 1,${cy}`} fill={fillColor} stroke={strokeColor} strokeWidth={strokeWidth} strokeDasharray={strokeDasharray} />}
 
-      {shape === "rectangle" && <rect x={0} y={0} width={w} height={h} rx={4} ry={4} fill={fillColor} stroke={strokeColor} strokeWidth={1} />}
+      {shape === "rectangle" && <rect x={0} y={0} width={w} height={h} rx={4} ry={4} fill={fillColor} stroke={strokeColor} strokeWidth={strokeWidth} strokeDasharray={strokeDasharray} />}
 
       {/* Label centered in shape */}
-      <text x={cx} y={cy} textAnchor='middle' dominantBaseline='central' fill={textColor} fontSize={fontSize} fontFamily='ui-monospace, monospace'>
+      <text x={cx} y={cy} textAnchor='middle' dominantBaseline='central' fill={textColor} fontSize={fontSize} fontFamily={node.rendering_config.font_family}>
         {label}
       </text>
 
@@ -2,7 +2,7 @@ import Editor, { type Monaco } from "@monaco-editor/react";
 import type * as monaco from "monaco-editor";
 import { useImperativeHandle, forwardRef, useRef, useCallback, useEffect } from "react";
 import { registerSddLanguage } from "../../syntax";
-import type { ErrorInfo } from "../../types/diagram";
+import type { Diagnostic } from "../../types/diagram";
 import { useTheme } from "../../hooks/useTheme";
 
 export interface CodeEditorRef {
@@ -17,12 +17,13 @@ export interface CodeEditorRef {
 interface CodeEditorProps {
   value: string;
   onChange: (value: string) => void;
-  error?: ErrorInfo;
+  /** Parse/compile errors and warnings to show as markers */
+  diagnostics?: Diagnostic[];
   /** List of implicit node IDs to show info markers for */
   implicitNodes?: string[];
 }
 
-export const CodeEditor = forwardRef<CodeEditorRef, CodeEditorProps>(function CodeEditor({ value, onChange, error, implicitNodes }, ref) {
+export const CodeEditor = forwardRef<CodeEditorRef, CodeEditorProps>(function CodeEditor({ value, onChange, diagnostics, implicitNodes }, ref) {
   const editorRef = useRef<monaco.editor.IStandaloneCodeEditor | null>(null);
   const monacoRef = useRef<Monaco | null>(null);
   const { resolvedTheme } = useTheme();
@@ -44,7 +45,7 @@ export const CodeEditor = forwardRef<CodeEditorRef, CodeEditorProps>(function Co
     []
   );
 
-  // Update Monaco markers when error or implicitNodes changes
+  // Update Monaco markers when diagnostics or implicitNodes change
   useEffect(() => {
     const editor = editorRef.current;
     const monacoInstance = monacoRef.current;
@@ -55,15 +56,14 @@ export const CodeEditor = forwardRef<CodeEditorRef, CodeEditorProps>(function Co
 
     const markers: monaco.editor.IMarkerData[] = [];
 
-    // Add error marker if present
-    if (error) {
+    for (const diagnostic of diagnostics ?? []) {
       markers.push({
-        severity: monacoInstance.MarkerSeverity.Error,
-        message: error.message,
-        startLineNumber: error.line,
-        startColumn: error.column,
-        endLineNumber: error.end_line,
-        endColumn: error.end_column,
+        severity: diagnostic.severity === "error" ? monacoInstance.MarkerSeverity.Error : monacoInstance.MarkerSeverity.Warning,
+        message: diagnostic.message,
+        startLineNumber: diagnostic.line,
+        startColumn: diagnostic.column,
+        endLineNumber: diagnostic.end_line,
+        endColumn: diagnostic.end_column,
       });
     }
 
@@ -105,7 +105,7 @@ export const CodeEditor = forwardRef<CodeEditorRef, CodeEditorProps>(function Co
     }
 
     monacoInstance.editor.setModelMarkers(model, "trident", markers);
-  }, [error, implicitNodes]);
+  }, [diagnostics, implicitNodes]);
 
   const handleEditorDidMount = (editor: monaco.editor.IStandaloneCodeEditor, monaco: Monaco) => {
     editorRef.current = editor;
@@ -1,19 +1,21 @@
 // sddMonaco.ts
 // Monaco language support for SDD/Trident:
-// - keywords: classDiagram, group, class, interface, enum, struct, record, trait, object
+// - keywords: classDiagram, group, note (for/on), style, class, interface, enum, struct, record, trait, object
 // - modifiers: abstract, static, sealed, final, public, private, protected
 // - comments: %% line comment
-// - strings: "..." (no escapes)
+// - strings: "..." with \", \\ and \n escapes (%% inside strings is not a comment)
 // - relations: support arrow tokens even without spaces (A-->B, A<|--B:label)
-// - directive: @pos: (x, y)
+// - directives: @pos: (x, y), @width/@height, @direction: LR, @wrap: 240, style directives (@fill: #fee, @stroke: red, @class: name, ...)
+// - style classes: style NAME { ... } rules, applied with :::NAME
 // - braces: { }
-// - identifiers: [A-Za-z_][A-Za-z0-9_]*
+// - identifiers: Unicode letters, digits and _, or `quoted in backticks`
 //
 // Usage with @monaco-editor/react is shown below.
 
 import type * as monaco from "monaco-editor";
 import * as trident_core from "trident-core";
 import { initArrowRegistry, getArrowRegistry, generateArrowLabel, type ArrowEntry } from "./types/arrows";
+import type { SymbolReference } from "./types/diagram";
 
 export const TRIDENT_ID = "trident";
 
@@ -34,6 +36,8 @@ const KEYWORDS = [
   "circle",
   "diamond",
   "group",
+  "note",
+  "style",
   "abstract",
   "static",
   "sealed",
@@ -75,6 +79,12 @@ const SNIPPETS = [
     detail: "Anonymous group",
     documentation: "Create an anonymous group",
   },
+  {
+    label: "note",
+    insertText: "note for ${1:ClassName} \"${2:text}\"",
+    detail: "Note on a node",
+    documentation: "Attach a note to a node or group; use `note on A --> B` for a relation",
+  },
   {
     label: "abstract-class",
     insertText: "abstract class ${1:ClassName} {\n\t$0\n}",
@@ -105,6 +115,18 @@ const SNIPPETS = [
     detail: "Inheritance relation",
     documentation: "Create an inheritance/extends relation",
   },
+  {
+    label: "relation-styled",
+    insertText: "${1:From} --> ${2:To} { @stroke: ${3:red} }",
+    detail: "Styled relation",
+    documentation: "Create a relation with style directives (@stroke, @stroke_width, @dash, @text_color)",
+  },
+  {
+    label: "style",
+    insertText: "style ${1:warning} { @fill: ${2:#fee}; @stroke: ${3:#c00} }",
+    detail: "Style class",
+    documentation: "Define a style class, applied with `class A :::warning` or `@class: warning`. Naming a node kind or modifier (interface, abstract) styles every such node",
+  },
 ];
 
 /** Build arrow completions from the registry */
@@ -167,7 +189,7 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
     folding: {
       offSide: false,
       markers: {
-        start: new RegExp("^\\s*(group|class|interface|enum|struct|record|trait|object)\\b.*\\{\\s*$"),
+        start: new RegExp("^\\s*(group|note|style|class|interface|enum|struct|record|trait|object)\\b.*\\{\\s*$"),
         end: new RegExp("^\\s*}\\s*$"),
       },
     },
@@ -177,6 +199,7 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
   monacoApi.languages.setMonarchTokensProvider(TRIDENT_ID, {
     defaultToken: "",
     tokenPostfix: ".sdd",
+    unicode: true,
 
     // Node kind keywords
     nodeKinds: ["class", "interface", "enum", "struct", "record", "trait", "object", "node", "rectangle", "circle", "diamond"],
@@ -185,7 +208,7 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
     modifiers: ["abstract", "static", "sealed", "final", "public", "private", "protected"],
 
     // Other keywords
-    keywords: ["classDiagram", "group"],
+    keywords: ["classDiagram", "group", "note", "style"],
 
     // Arrow tokens (from registry, already sorted by length)
     arrows: arrowTokens,
@@ -201,22 +224,43 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
         // directive (currently only @pos:)
         [/[@]pos:/, "annotation"],
 
-        // layout directive (@layout: grid, @layout: hierarchical)
+        // layout directive (@layout: grid, @layout: hierarchical, @layout: force, @layout: tree)
         [/[@]layout:/, "annotation"],
 
+        // routing directive (@routing: straight, @routing: orthogonal, @routing: bus)
+        [/[@]routing:/, "annotation"],
+
+        // direction directive (@direction: LR), at the top of the file or in a group
+        [/[@]direction:/, "annotation"],
+
+        // wrap directive (@wrap: 240), at the top of the file
+        [/[@]wrap:/, "annotation"],
+
         // size directives (@width: and @height:)
         [/[@]width:/, "annotation"],
         [/[@]height:/, "annotation"],
 
+        // style directives (@class:, @fill:, @stroke:, @text_color:, @stroke_width:, @dash:)
+        [/[@](class|fill|stroke_width|stroke|text_color|dash):/, "annotation"],
+
+        // style classes applied in a node header (class A :::warning)
+        [/:::[\p{L}_][\p{L}\p{N}_-]*/, "annotation"],
+
+        // hex colors in style directives
+        [/#[0-9a-fA-F]{3,8}\b/, "number"],
+
         // braces
         [/[{}]/, "@brackets"],
 
         // numbers (for @pos coords)
         [/-?\d+/, "number"],
 
-        // strings (no escapes per v0.0.1)
+        // strings
         [/"/, { token: "string.quote", bracket: "@open", next: "@string" }],
 
+        // backtick-quoted identifiers (may contain spaces, dots, %%)
+        [/`[^`]+`/, "identifier"],
+
         // arrow operators (including when embedded in A-->B)
         [arrowRegex, "operator"],
 
@@ -229,11 +273,17 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
         // Modifiers (highlighted specially)
         [/\b(abstract|static|sealed|final|public|private|protected)\b/, "keyword.modifier"],
 
+        // Note targets: `note for A`, `note on A --> B`
+        [/\b(note)(\s+)(for|on)\b/, ["keyword", "white", "keyword"]],
+
         // Other keywords
-        [/\b(classDiagram|group)\b/, "keyword"],
+        [/\b(classDiagram|group|note)\b/, "keyword"],
+
+        // Style rules: `style warning { ... }`
+        [/\bstyle\b(?=\s+[\p{L}_])/, "keyword"],
 
         // identifiers
-        [/[A-Za-z_][A-Za-z0-9_]*/, "identifier"],
+        [/[\p{L}_][\p{L}\p{N}_]*/, "identifier"],
 
         // commas
         [/\,/, "delimiter"],
@@ -243,7 +293,9 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
       ],
 
       string: [
-        [/[^"]+/, "string"],
+        [/[^"\\]+/, "string"],
+        [/\\["\\n]/, "string.escape"],
+        [/\\./, "string.escape.invalid"],
         [/"/, { token: "string.quote", bracket: "@close", next: "@pop" }],
       ],
     },
@@ -276,8 +328,10 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
         const symbols: string[] = JSON.parse(symbolsJson);
         const lowerWord = word.word.toLowerCase();
         for (const sym of symbols) {
-          // Show all symbols if no word typed, or filter by prefix match
-          if (word.word === "" || sym.toLowerCase().startsWith(lowerWord)) {
+          // Show all symbols if no word typed, or filter by prefix match on
+          // the qualified path or the unqualified name
+          const name = sym.slice(sym.lastIndexOf(".") + 1);
+          if (word.word === "" || sym.toLowerCase().startsWith(lowerWord) || name.toLowerCase().startsWith(lowerWord)) {
             suggestions.push({
               label: sym,
               kind: monacoApi.languages.CompletionItemKind.Reference,
@@ -354,29 +408,35 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
         return { edits: [] };
       }
 
-      const oldName = word.word;
       const source = model.getValue();
 
       try {
-        const newSource = trident_core.rename_symbol(source, oldName, newName);
-
-        // If source unchanged, symbol wasn't found
-        if (newSource === source) {
+        // Qualified path of the symbol under the cursor, so equal names in other groups are left alone
+        const symbol = trident_core.symbol_at(source, position.lineNumber, position.column);
+        if (!symbol) {
           return { edits: [] };
         }
+        const refs: SymbolReference[] = JSON.parse(trident_core.find_references(source, symbol));
+        const text = trident_core.format_identifier(newName);
+        if (text === undefined) {
+          return { edits: [], rejectReason: `'${newName}' can't be used as a name` };
+        }
 
-        // Return a single edit that replaces the entire content
+        // One edit per occurrence; columns are UTF-16 based like Monaco's
         return {
-          edits: [
-            {
-              resource: model.uri,
-              textEdit: {
-                range: model.getFullModelRange(),
-                text: newSource,
+          edits: refs.map(ref => ({
+            resource: model.uri,
+            textEdit: {
+              range: {
+                startLineNumber: ref.line,
+                startColumn: ref.start_col_utf16,
+                endLineNumber: ref.line,
+                endColumn: ref.end_col_utf16,
               },
-              versionId: model.getVersionId(),
+              text,
             },
-          ],
+            versionId: model.getVersionId(),
+          })),
         };
       } catch {
         return { edits: [] };
@@ -401,10 +461,7 @@ export function registerSddLanguage(monacoApi: typeof monaco) {
       // Check if this word is a valid symbol
       const source = model.getValue();
       try {
-        const symbolsJson = trident_core.get_symbols(source);
-        const symbols: string[] = JSON.parse(symbolsJson);
-
-        if (!symbols.includes(word.word)) {
+        if (!trident_core.symbol_at(source, position.lineNumber, position.column)) {
           return {
             range: {
               startLineNumber: position.lineNumber,
@@ -9,21 +9,59 @@ export interface Bounds {
   h: number;
 }
 
+/** `x` is where the text starts and `y` its baseline */
 export type TextElement =
-  | { type: "Stereotype"; data: { text: string; y: number; font_size: number } }
-  | { type: "Title"; data: { text: string; y: number; font_size: number; italic: boolean } }
+  | { type: "Stereotype"; data: { text: string; x: number; y: number; font_size: number } }
+  | { type: "Title"; data: { text: string; x: number; y: number; font_size: number; italic: boolean } }
   | { type: "Separator"; data: { x1: number; y1: number; x2: number; y2: number } }
-  | { type: "BodyText"; data: { text: string; y: number; font_size: number } };
+  | { type: "BodyText"; data: { text: string; x: number; y: number; font_size: number; italic: boolean; underline: boolean } }
+  | { type: "TemplateParams"; data: { text: string; x: number; y: number; width: number; height: number; font_size: number } };
+
+export type MemberKind = "field" | "method" | "enum_variant" | "separator";
+export type Visibility = "public" | "private" | "protected" | "package";
+
+export interface MemberParam {
+  name: string;
+  type: string | null;
+}
+
+/** Typed interpretation of one class body line */
+export interface Member {
+  kind: MemberKind;
+  visibility: Visibility | null;
+  name: string;
+  params: MemberParam[];
+  /** Field type, or return type for methods */
+  type: string | null;
+  is_static: boolean;
+  is_abstract: boolean;
+}
+
+/** Colors and line style overriding the theme; null keeps the theme's value */
+export interface Style {
+  fill: string | null;
+  stroke: string | null;
+  text_color: string | null;
+  stroke_width: number | null;
+  /** Dash lengths; empty for a solid line */
+  dash: number[] | null;
+}
 
 export interface NodeRenderingConfig {
   padding: number;
   line_height: number;
   separator_spacing: number;
-  char_width: number;
+  /** CSS font stack the text was measured for */
+  font_family: string;
+  /** Width at which body and note lines wrap (from @wrap), or null */
+  wrap_width: number | null;
 }
 
 export interface DiagramNode {
+  /** Fully qualified name (`Outer.Inner.Config`), unique in the diagram */
   id: string;
+  /** Unqualified name as declared */
+  name: string;
   /** Node kind: "class" or "node" */
   kind: string;
   /** Modifiers: "abstract", "interface", "enum", "rectangle", "circle", "diamond", etc. */
@@ -36,36 +74,99 @@ export interface DiagramNode {
   parent_offset: { x: number; y: number };
   /** Whether this node was explicitly declared (false for implicit nodes from relations) */
   explicit: boolean;
+  /** Typed members parsed from the body, index-aligned with its lines */
+  members: Member[];
+  style: Style;
+}
+
+export interface Point {
+  x: number;
+  y: number;
+}
+
+/** UML multiplicity and role name at one end of a relation */
+export interface RelationEnd {
+  multiplicity: string | null;
+  role: string | null;
 }
 
 export interface DiagramEdge {
+  /** "group" when `from`/`to` are group ids (package dependencies) */
+  kind: "node" | "group";
   from: string;
   to: string;
   arrow: string;
   label: string | null;
+  /** Routed polyline computed by the core: source port, bends, target port */
+  points: Point[];
+  /** Where to center the label */
+  label_pos: Point;
+  /** Multiplicity and role at the `from` end */
+  from_end: RelationEnd;
+  /** Where to center the `from` end label */
+  from_label_pos: Point;
+  /** Multiplicity and role at the `to` end */
+  to_end: RelationEnd;
+  /** Where to center the `to` end label */
+  to_label_pos: Point;
+  style: Style;
 }
 
 export interface DiagramGroup {
+  /** Fully qualified name */
   id: string;
+  /** Unqualified name as declared, shown as the group's title */
+  name: string;
+  /** Measured width of the title */
+  label_width: number;
+  bounds: Bounds;
+  style: Style;
+  rendering_config: NodeRenderingConfig;
+}
+
+/** A note, drawn as a sheet with a folded top-right corner */
+export interface DiagramNote {
+  /** Text lines with positions relative to the note's top-left corner */
+  text_elements: TextElement[];
   bounds: Bounds;
+  /** Size of the folded corner */
+  fold: number;
+  /** Dashed line from the note to what it is attached to; empty for free-standing notes */
+  connector: Point[];
+  rendering_config: NodeRenderingConfig;
 }
 
-/** Error information for Monaco editor markers */
-export interface ErrorInfo {
+/** A parse/compile problem, positioned for Monaco editor markers */
+export interface Diagnostic {
+  severity: "error" | "warning";
   message: string;
   line: number; // 1-based line number
-  column: number; // 1-based column number
+  column: number; // 1-based column (UTF-16 code units)
   end_line: number; // 1-based end line
-  end_column: number; // 1-based end column
+  end_column: number; // 1-based end column, exclusive (UTF-16 code units)
+}
+
+/** A symbol occurrence returned by `find_references` */
+export interface SymbolReference {
+  line: number;
+  /** 1-based byte columns (end exclusive) */
+  start_col: number;
+  end_col: number;
+  /** 1-based UTF-16 columns (end exclusive), as used by Monaco */
+  start_col_utf16: number;
+  end_col_utf16: number;
+  text: string;
 }
 
 export interface DiagramOutput {
   groups?: DiagramGroup[];
   nodes?: DiagramNode[];
   edges?: DiagramEdge[];
+  notes?: DiagramNote[];
   /** List of implicit node IDs (for editor info diagnostics) */
   implicit_nodes?: string[];
-  error?: ErrorInfo;
+  /** Errors (statement skipped) and warnings, ordered by position */
+  diagnostics?: Diagnostic[];
 }
 
 /** Drag state for tracking node/group dragging */
@@ -182,6 +182,24 @@ export function getOptimalConnectionPoints(
   return { start, end };
 }
 
+/** Shorten the first/last segment of a routed polyline to make room for arrow heads */
+export function offsetRoute(points: { x: number; y: number }[], startOffset: number, endOffset: number): { x: number; y: number }[] {
+  const pullBack = (end: { x: number; y: number }, towards: { x: number; y: number }, by: number) => {
+    const dx = towards.x - end.x;
+    const dy = towards.y - end.y;
+    const len = Math.sqrt(dx * dx + dy * dy);
+    if (len === 0 || by === 0) return end;
+    const d = Math.min(by, len);
+    return { x: end.x + (dx / len) * d, y: end.y + (dy / len) * d };
+  };
+
+  if (points.length < 2) return points;
+  const out = points.slice();
+  out[0] = pullBack(out[0], out[1], startOffset);
+  out[out.length - 1] = pullBack(out[out.length - 1], out[out.length - 2], endOffset);
+  return out;
+}
+
 /** Smart corner snapping for diagonal arrows */
 function smartCornerSnap(
   point: { x: number; y: number },
@@ -232,25 +250,30 @@ export function isDashed(arrow: string): boolean {
 }
 
 /** Map head style to SVG marker ID */
-function headStyleToMarker(headStyle: string): string {
+function headStyleToMarker(headStyle: string, suffix: string): string {
   switch (headStyle) {
     case "arrow":
-      return `url(#arrowhead)`;
+      return `url(#arrowhead${suffix})`;
     case "rounded_arrow":
-      return `url(#rounded-arrowhead)`;
+      return `url(#rounded-arrowhead${suffix})`;
     case "triangle":
-      return `url(#triangle)`;
+      return `url(#triangle${suffix})`;
     case "diamond_filled":
-      return `url(#diamond)`;
+      return `url(#diamond${suffix})`;
     case "diamond_empty":
-      return `url(#diamond-empty)`;
+      return `url(#diamond-empty${suffix})`;
     default:
       return "";
   }
 }
 
-/** Get the marker type for an edge based on arrow type and direction */
-export function getEdgeMarkers(arrow: string): {
+/** Suffix of the marker ids drawn in an edge's `@stroke` color */
+export function markerSuffix(color: string | null): string {
+  return color ? `-${color.replace(/^#/, "")}` : "";
+}
+
+/** Get the marker type for an edge based on arrow type and direction, in its stroke color */
+export function getEdgeMarkers(arrow: string, color: string | null = null): {
   markerStart: string;
   markerEnd: string;
 } {
@@ -261,6 +284,7 @@ export function getEdgeMarkers(arrow: string): {
   }
 
   const isLeft = entry.is_left;
+  const suffix = markerSuffix(color);
   const isDiamond = entry.head_style === "diamond_filled" || entry.head_style === "diamond_empty";
 
   let markerStart = "";
@@ -268,15 +292,15 @@ export function getEdgeMarkers(arrow: string): {
 
   if (isDiamond) {
     if (isLeft) {
-      markerEnd = headStyleToMarker(entry.head_style);
+      markerEnd = headStyleToMarker(entry.head_style, suffix);
     } else {
-      markerStart = headStyleToMarker(entry.head_style);
+      markerStart = headStyleToMarker(entry.head_style, suffix);
     }
   } else {
     if (isLeft) {
-      markerStart = headStyleToMarker(entry.head_style);
+      markerStart = headStyleToMarker(entry.head_style, suffix);
     } else {
-      markerEnd = headStyleToMarker(entry.head_style);
+      markerEnd = headStyleToMarker(entry.head_style, suffix);
     }
   }
 
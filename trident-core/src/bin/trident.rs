@@ -0,0 +1,463 @@
+//! `trident` command-line interface.
+//!
+//! Subcommands:
+//! - `check`:  parse and compile files, reporting errors as `file:line:col: error: msg`
+//! - `fmt`:    rewrite files through the canonical emitter (`--check` only reports)
+//! - `layout`: print the computed `LayoutResult` as JSON
+//! - `export`: render a diagram to one of the supported output formats
+//! - `import`: convert another diagram language into Trident source
+//!
+//! Every subcommand reads stdin when no file (or `-`) is given.
+//! Exit codes: 0 on success, 1 when diagnostics were reported or files need
+//! formatting, 2 on usage or I/O errors.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use trident_core::{
+    compile_recovering, diagram_to_output, emit_file, export_dot, export_mermaid, export_plantuml, import_mermaid,
+    import_plantuml, layout_diagram, layout_settings, parse_file_recovering, render_svg, ConversionWarning, Diagram,
+    FileAst, SvgOptions, SvgTheme,
+};
+
+const USAGE: &str = "\
+Usage: trident <command> [options] [file...]
+
+Commands:
+  check [file...]            Report parse and compile errors
+  fmt [--check] [file...]    Format files in place (stdin is written to stdout)
+  layout [file]              Print the computed layout as JSON
+  export [options] [file]    Render a diagram
+  import [options] [file]    Convert another diagram language to Trident
+
+Export options:
+  -f, --format <svg|json|mermaid|plantuml|dot>
+                             Output format (default: svg)
+  -o, --output <path>        Write to a file instead of stdout
+      --theme <light|dark>   SVG colour theme (default: light)
+
+Import options:
+      --from <mermaid|plantuml>
+                             Source language (default: mermaid)
+  -o, --output <path>        Write to a file instead of stdout
+
+Reads stdin when no file or `-` is given.";
+
+/// Path placeholder meaning "read stdin"
+const STDIN: &str = "-";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Check { files: Vec<String> },
+    Fmt { check: bool, files: Vec<String> },
+    Layout { file: String },
+    Export { format: ExportFormat, theme: SvgTheme, output: Option<String>, file: String },
+    Import { from: ImportFormat, output: Option<String>, file: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Svg,
+    Json,
+    Mermaid,
+    PlantUml,
+    Dot,
+}
+
+impl ExportFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "svg" => Some(ExportFormat::Svg),
+            "json" => Some(ExportFormat::Json),
+            "mermaid" => Some(ExportFormat::Mermaid),
+            "plantuml" => Some(ExportFormat::PlantUml),
+            "dot" => Some(ExportFormat::Dot),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImportFormat {
+    Mermaid,
+    PlantUml,
+}
+
+impl ImportFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mermaid" => Some(ImportFormat::Mermaid),
+            "plantuml" => Some(ImportFormat::PlantUml),
+            _ => None,
+        }
+    }
+}
+
+/// Why a command could not finish normally
+enum Failure {
+    /// Diagnostics were already printed; exit with status 1
+    Reported,
+    /// I/O errors were already printed; exit with status 2
+    ReportedFatal,
+    /// Bad arguments or I/O problems; exit with status 2
+    Fatal(String),
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Self {
+        Failure::Fatal(e.to_string())
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(msg) => {
+            eprintln!("trident: {}\n\n{}", msg, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Reported) => ExitCode::from(1),
+        Err(Failure::ReportedFatal) => ExitCode::from(2),
+        Err(Failure::Fatal(msg)) => {
+            eprintln!("trident: {}", msg);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let Some((name, rest)) = args.split_first() else {
+        return Err("missing command".to_string());
+    };
+
+    let mut files = Vec::new();
+    let mut check = false;
+    let mut format = ExportFormat::Svg;
+    let mut from = ImportFormat::Mermaid;
+    let mut theme = SvgTheme::Light;
+    let mut output = None;
+
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |flag: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("missing value for {}", flag))
+        };
+        match (name.as_str(), arg.as_str()) {
+            (_, "-h" | "--help") => return Ok(Command::Help),
+            ("fmt", "--check") => check = true,
+            ("export", "-f" | "--format") => {
+                let v = value(arg)?;
+                format = ExportFormat::from_name(&v)
+                    .ok_or_else(|| format!("unknown export format '{}'", v))?;
+            }
+            ("export" | "import", "-o" | "--output") => output = Some(value(arg)?),
+            ("import", "--from") => {
+                let v = value(arg)?;
+                from = ImportFormat::from_name(&v)
+                    .ok_or_else(|| format!("unknown import format '{}'", v))?;
+            }
+            ("export", "--theme") => {
+                let v = value(arg)?;
+                theme = match v.as_str() {
+                    "light" => SvgTheme::Light,
+                    "dark" => SvgTheme::Dark,
+                    _ => return Err(format!("unknown theme '{}'", v)),
+                };
+            }
+            (_, a) if a.starts_with('-') && a != STDIN => {
+                return Err(format!("unknown option '{}' for {}", a, name));
+            }
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    let single = |files: Vec<String>| -> Result<String, String> {
+        match files.len() {
+            0 => Ok(STDIN.to_string()),
+            1 => Ok(files.into_iter().next().unwrap()),
+            _ => Err(format!("{} takes a single file", name)),
+        }
+    };
+
+    match name.as_str() {
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        "check" => Ok(Command::Check { files }),
+        "fmt" => Ok(Command::Fmt { check, files }),
+        "layout" => Ok(Command::Layout { file: single(files)? }),
+        "export" => Ok(Command::Export { format, theme, output, file: single(files)? }),
+        "import" => Ok(Command::Import { from, output, file: single(files)? }),
+        other => Err(format!("unknown command '{}'", other)),
+    }
+}
+
+fn run(command: Command) -> Result<(), Failure> {
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Check { files } => run_check(&or_stdin(files)),
+        Command::Fmt { check, files } => run_fmt(&or_stdin(files), check),
+        Command::Layout { file } => {
+            let source = read_source(&file)?;
+            let (ast, diagram) = load(&source)?;
+            let (cfg, layout_name) = layout_settings(&ast);
+            let result = layout_diagram(&diagram, &cfg, layout_name);
+            let json = serde_json::to_string_pretty(&result).map_err(|e| Failure::Fatal(e.to_string()))?;
+            write_output(None, &json)
+        }
+        Command::Export { format, theme, output, file } => {
+            let source = read_source(&file)?;
+            let (ast, diagram) = load(&source)?;
+            let text = match format {
+                ExportFormat::Svg => {
+                    let out = diagram_to_output(&ast, &diagram);
+                    render_svg(&out, &SvgOptions { theme, ..SvgOptions::default() })
+                }
+                ExportFormat::Json => {
+                    let out = diagram_to_output(&ast, &diagram);
+                    serde_json::to_string_pretty(&out).map_err(|e| Failure::Fatal(e.to_string()))?
+                }
+                ExportFormat::Mermaid => {
+                    let converted = export_mermaid(&diagram);
+                    report_warnings(&source, &converted.warnings);
+                    converted.value
+                }
+                ExportFormat::PlantUml => {
+                    let converted = export_plantuml(&ast);
+                    report_warnings(&source, &converted.warnings);
+                    converted.value
+                }
+                ExportFormat::Dot => export_dot(&diagram),
+            };
+            write_output(output.as_deref(), &text)
+        }
+        Command::Import { from, output, file } => {
+            let source = read_source(&file)?;
+            let converted = match from {
+                ImportFormat::Mermaid => import_mermaid(&source.text),
+                ImportFormat::PlantUml => import_plantuml(&source.text),
+            };
+            report_warnings(&source, &converted.warnings);
+            write_output(output.as_deref(), &emit_file(&converted.value))
+        }
+    }
+}
+
+fn run_check(files: &[String]) -> Result<(), Failure> {
+    let mut failed = false;
+    let mut unreadable = false;
+    for file in files {
+        // An unreadable file doesn't stop the others from being checked
+        let source = match read_source(file) {
+            Ok(source) => source,
+            Err(Failure::Fatal(msg)) => {
+                eprintln!("trident: {}", msg);
+                unreadable = true;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if load(&source).is_err() {
+            failed = true;
+        }
+    }
+    if unreadable {
+        Err(Failure::ReportedFatal)
+    } else if failed {
+        Err(Failure::Reported)
+    } else {
+        Ok(())
+    }
+}
+
+fn run_fmt(files: &[String], check: bool) -> Result<(), Failure> {
+    let mut failed = false;
+    let mut unreadable = false;
+    for file in files {
+        // An unreadable file doesn't stop the others from being formatted
+        let source = match read_source(file) {
+            Ok(source) => source,
+            Err(Failure::Fatal(msg)) => {
+                eprintln!("trident: {}", msg);
+                unreadable = true;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        // Formatting a partial AST would drop the lines that failed to parse
+        let (ast, errors) = parse_file_recovering(&source.text);
+        if !errors.is_empty() {
+            for e in &errors {
+                report(&source, e.line, e.col, &e.msg);
+            }
+            failed = true;
+            continue;
+        }
+        let formatted = emit_file(&ast);
+        let changed = formatted != source.text;
+
+        if check {
+            if changed {
+                println!("{}: needs formatting", source.name);
+                failed = true;
+            }
+        } else if file == STDIN {
+            write_output(None, &formatted)?;
+        } else if changed {
+            fs::write(file, formatted).map_err(|e| Failure::Fatal(format!("{}: {}", file, e)))?;
+        }
+    }
+    if unreadable {
+        Err(Failure::ReportedFatal)
+    } else if failed {
+        Err(Failure::Reported)
+    } else {
+        Ok(())
+    }
+}
+
+/// Input text together with the name used in diagnostics
+struct Source {
+    name: String,
+    text: String,
+}
+
+fn or_stdin(files: Vec<String>) -> Vec<String> {
+    if files.is_empty() { vec![STDIN.to_string()] } else { files }
+}
+
+fn read_source(path: &str) -> Result<Source, Failure> {
+    if path == STDIN {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        return Ok(Source { name: "<stdin>".to_string(), text });
+    }
+    let text = fs::read_to_string(path).map_err(|e| Failure::Fatal(format!("{}: {}", path, e)))?;
+    Ok(Source { name: path.to_string(), text })
+}
+
+/// Parse and compile a source, printing every error it has
+fn load(source: &Source) -> Result<(FileAst, Diagram), Failure> {
+    let (ast, parse_errors) = parse_file_recovering(&source.text);
+    let (diagram, compile_errors) = compile_recovering(&ast);
+    for e in &parse_errors {
+        report(source, e.line, e.col, &e.msg);
+    }
+    for e in &compile_errors {
+        report(source, e.line, e.col, &e.msg);
+    }
+    if parse_errors.is_empty() && compile_errors.is_empty() { Ok((ast, diagram)) } else { Err(Failure::Reported) }
+}
+
+fn report(source: &Source, line: usize, col: usize, msg: &str) {
+    eprintln!("{}:{}:{}: error: {}", source.name, line, col, msg);
+}
+
+fn report_warnings(source: &Source, warnings: &[ConversionWarning]) {
+    for w in warnings {
+        match w.line {
+            Some(line) => eprintln!("{}:{}: warning: {}", source.name, line, w.message),
+            None => eprintln!("{}: warning: {}", source.name, w.message),
+        }
+    }
+}
+
+fn write_output(path: Option<&str>, text: &str) -> Result<(), Failure> {
+    match path {
+        Some(path) => fs::write(path, text).map_err(|e| Failure::Fatal(format!("{}: {}", path, e))),
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(text.as_bytes())?;
+            if !text.ends_with('\n') {
+                stdout.write_all(b"\n")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_fmt_check() {
+        let cmd = parse_args(&args(&["fmt", "--check", "a.tri", "b.tri"])).unwrap();
+        assert_eq!(cmd, Command::Fmt { check: true, files: args(&["a.tri", "b.tri"]) });
+    }
+
+    #[test]
+    fn test_parse_export_options() {
+        let cmd = parse_args(&args(&["export", "--format", "json", "-o", "out.json", "-"])).unwrap();
+        assert_eq!(cmd, Command::Export {
+            format: ExportFormat::Json,
+            theme: SvgTheme::Light,
+            output: Some("out.json".to_string()),
+            file: STDIN.to_string(),
+        });
+    }
+
+    #[test]
+    fn test_parse_import_options() {
+        let cmd = parse_args(&args(&["import", "--from", "mermaid", "diagram.mmd"])).unwrap();
+        assert_eq!(cmd, Command::Import {
+            from: ImportFormat::Mermaid,
+            output: None,
+            file: "diagram.mmd".to_string(),
+        });
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["frobnicate"])).is_err());
+        assert!(parse_args(&args(&["check", "--check"])).is_err());
+        assert!(parse_args(&args(&["export", "--format", "png"])).is_err());
+        assert!(parse_args(&args(&["layout", "a.tri", "b.tri"])).is_err());
+    }
+
+    #[test]
+    fn test_check_goes_on_after_an_unreadable_file() {
+        let dir = std::env::temp_dir().join(format!("trident-check-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.tri");
+        fs::write(&good, "class A\n").unwrap();
+        let good = good.to_string_lossy().into_owned();
+        let missing = dir.join("missing.tri").to_string_lossy().into_owned();
+
+        assert!(run_check(&[good.clone()]).is_ok());
+        assert!(matches!(run_check(&[missing.clone(), good.clone()]), Err(Failure::ReportedFatal)));
+        assert!(matches!(run_check(&[good, missing]), Err(Failure::ReportedFatal)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fmt_goes_on_after_an_unreadable_file() {
+        let dir = std::env::temp_dir().join(format!("trident-fmt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let messy = dir.join("messy.tri");
+        fs::write(&messy, "class   A\n").unwrap();
+        let messy = messy.to_string_lossy().into_owned();
+        let missing = dir.join("missing.tri").to_string_lossy().into_owned();
+
+        assert!(matches!(run_fmt(&[missing.clone(), messy.clone()], true), Err(Failure::ReportedFatal)));
+        assert!(matches!(run_fmt(&[missing, messy.clone()], false), Err(Failure::ReportedFatal)));
+        assert_eq!(fs::read_to_string(&messy).unwrap(), "class A\n");
+        assert!(run_fmt(&[messy], true).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,412 @@
+//! Graphviz DOT export.
+//!
+//! Named groups become `subgraph cluster_*`, class nodes become HTML-table
+//! labels with one compartment per separator-delimited section of the body,
+//! and shape nodes map onto Graphviz shapes. Notes become `shape=note` nodes
+//! with a dashed line to what they are attached to. Edges point from parent to child
+//! the same way the hierarchical layout ranks them, so Graphviz's `dot`
+//! engine can be compared against Trident's own layout. Style directives map
+//! onto `fillcolor`, `color`, `fontcolor`, `penwidth` and `style`.
+
+use std::fmt::Write;
+
+use super::INDENT;
+use crate::layout::placement::get_edge_direction;
+use crate::output::{format_modifiers, is_separator_line};
+use crate::parser::compile::{Edge, GroupEdge, Node, Note};
+use crate::parser::{get_arrow_definition, token_from_arrow, Diagram, GroupId, HeadStyle, LineStyle, NodeId, NoteAnchor, Style, ARROW_DEFINITIONS};
+
+/// Trident sizes are in pixels, Graphviz sizes in inches
+const PX_PER_INCH: f64 = 72.0;
+
+/// Convert a compiled diagram into a DOT `digraph`
+pub fn export_dot(diagram: &Diagram) -> String {
+    let mut out = String::from("digraph G {\n");
+    // `compound` lets edges between clusters be clipped at the cluster borders
+    let _ = writeln!(out, "{}graph [rankdir=TB, compound=true, fontname=\"Helvetica\", fontsize=11];", INDENT);
+    let _ = writeln!(out, "{}node [fontname=\"Helvetica\", fontsize=11];", INDENT);
+    let _ = writeln!(out, "{}edge [fontname=\"Helvetica\", fontsize=10];", INDENT);
+
+    write_group_contents(&mut out, diagram, diagram.root, 1);
+
+    for edge in &diagram.edges {
+        write_edge(&mut out, diagram, edge, Vec::new());
+    }
+    for edge in &diagram.group_edges {
+        write_group_edge(&mut out, diagram, edge);
+    }
+    for note in &diagram.notes {
+        write_note(&mut out, diagram, note);
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+enum Child {
+    Group(GroupId),
+    Node(NodeId),
+}
+
+/// Emit a group's nodes and subgroups in source order
+fn write_group_contents(out: &mut String, diagram: &Diagram, gid: GroupId, depth: usize) {
+    let group = &diagram.groups[gid.0];
+    let mut children: Vec<(usize, Child)> = group
+        .children_groups
+        .iter()
+        .map(|&g| (diagram.groups[g.0].order, Child::Group(g)))
+        .chain(group.children_nodes.iter().map(|&n| (diagram.nodes[n.0].order, Child::Node(n))))
+        .collect();
+    children.sort_by_key(|(order, _)| *order);
+
+    let ind = INDENT.repeat(depth);
+    for (_, child) in children {
+        match child {
+            Child::Node(nid) => write_node(out, &diagram.nodes[nid.0], depth),
+            Child::Group(g) => {
+                match &diagram.groups[g.0].path {
+                    Some(path) => {
+                        let _ = writeln!(out, "{}subgraph {} {{", ind, quote(&format!("cluster_{}", path.0)));
+                        let _ = writeln!(out, "{}{}label={};", ind, INDENT, quote(path.name()));
+                        for attr in style_attrs(&diagram.groups[g.0].style, true, false) {
+                            let _ = writeln!(out, "{}{}{};", ind, INDENT, attr);
+                        }
+                    }
+                    // Anonymous groups only scope layout, so they don't get a box
+                    None => {
+                        let _ = writeln!(out, "{}subgraph {{", ind);
+                    }
+                }
+                write_group_contents(out, diagram, g, depth + 1);
+                let _ = writeln!(out, "{}}}", ind);
+            }
+        }
+    }
+}
+
+fn write_node(out: &mut String, node: &Node, depth: usize) {
+    let mut attrs = Vec::new();
+    let title = node.label.as_deref().unwrap_or(node.id.name());
+
+    let shape = node.modifiers.iter().find_map(|m| match m.as_str() {
+        "circle" => Some("circle"),
+        "diamond" => Some("diamond"),
+        "rectangle" => Some("box"),
+        _ => None,
+    });
+    match (node.kind.as_str(), shape) {
+        ("class", _) => {
+            attrs.push("shape=none".to_string());
+            attrs.push("margin=0".to_string());
+            attrs.push(format!("label=<{}>", class_table(node, title)));
+        }
+        (_, shape) => {
+            attrs.push(format!("shape={}", shape.unwrap_or("box")));
+            attrs.push(format!("label={}", quote(title)));
+        }
+    }
+    if let Some(w) = node.width {
+        attrs.push(format!("width={}", w as f64 / PX_PER_INCH));
+    }
+    if let Some(h) = node.height {
+        attrs.push(format!("height={}", h as f64 / PX_PER_INCH));
+    }
+    // Class tables carry their own fill and border colors
+    attrs.extend(style_attrs(&node.style, node.kind != "class", false));
+
+    let _ = writeln!(out, "{}{} [{}];", INDENT.repeat(depth), quote(&node.path.0), attrs.join(", "));
+}
+
+/// HTML-like label: a header cell with stereotypes and title, then one cell per body section
+fn class_table(node: &Node, title: &str) -> String {
+    let mut header = String::new();
+    let stereotypes = format_modifiers(&node.modifiers, &node.kind);
+    if !stereotypes.is_empty() {
+        let _ = write!(header, "{}<br/>", escape_html(&stereotypes));
+    }
+    let title = format!("<b>{}</b>", escape_html(title));
+    if node.modifiers.iter().any(|m| m == "abstract") {
+        let _ = write!(header, "<i>{}</i>", title);
+    } else {
+        header.push_str(&title);
+    }
+
+    let mut sections: Vec<Vec<&str>> = vec![Vec::new()];
+    for line in &node.body_lines {
+        if is_separator_line(line) {
+            sections.push(Vec::new());
+        } else {
+            sections.last_mut().unwrap().push(line);
+        }
+    }
+
+    let mut table = String::from("<table border=\"0\" cellborder=\"1\" cellspacing=\"0\" cellpadding=\"4\"");
+    if let Some(fill) = &node.style.fill {
+        let _ = write!(table, " bgcolor={}", quote(&dot_color(fill)));
+    }
+    if let Some(stroke) = &node.style.stroke {
+        let _ = write!(table, " color={}", quote(&dot_color(stroke)));
+    }
+    table.push('>');
+    let _ = write!(table, "<tr><td>{}</td></tr>", header);
+    if !node.body_lines.is_empty() {
+        for section in sections {
+            let text: Vec<String> = section.iter().map(|l| escape_html(l)).collect();
+            let _ = write!(table, "<tr><td align=\"left\" balign=\"left\">{}</td></tr>", text.join("<br/>"));
+        }
+    }
+    table.push_str("</table>");
+    table
+}
+
+/// Emit an edge; `attrs` come first in its attribute list
+fn write_edge(out: &mut String, diagram: &Diagram, edge: &Edge, mut attrs: Vec<String>) {
+    let (arrow, from, to) = (edge.arrow.as_str(), edge.from, edge.to);
+    let (head_at_from, head_at_to, dashed, hierarchy) = match get_arrow_definition(arrow) {
+        Some(entry) => {
+            let def = &entry.definition;
+            let head = dot_arrow(def.head_style);
+            // Diamonds sit at the owning ("from") end; everything else at the pointed-to end
+            let is_diamond = matches!(def.head_style, HeadStyle::DiamondFilled | HeadStyle::DiamondEmpty);
+            let (at_from, at_to) = if is_diamond != def.is_left { (head, "none") } else { ("none", head) };
+            let hierarchy = ARROW_DEFINITIONS.iter().any(|d| d.name == def.name && d.is_hierarchy_edge);
+            (at_from, at_to, def.line_style == LineStyle::Dashed, hierarchy)
+        }
+        None => ("none", "normal", false, true),
+    };
+
+    // Point the DOT edge from parent to child so `dot` ranks like the hierarchical layout
+    let (tail, head) = get_edge_direction(arrow, from, to);
+    let (arrowtail, arrowhead) = if tail == from { (head_at_from, head_at_to) } else { (head_at_to, head_at_from) };
+    attrs.push("dir=both".to_string());
+    attrs.push(format!("arrowtail={}", arrowtail));
+    attrs.push(format!("arrowhead={}", arrowhead));
+    attrs.extend(style_attrs(&edge.style, false, dashed));
+    if !hierarchy {
+        attrs.push("constraint=false".to_string());
+    }
+    if let Some(label) = &edge.label {
+        attrs.push(format!("label={}", quote(label)));
+    }
+    // Multiplicities/roles stay with their node even when the edge is flipped
+    let (tail_end, head_end) = if tail == from { (&edge.from_end, &edge.to_end) } else { (&edge.to_end, &edge.from_end) };
+    if !tail_end.is_empty() {
+        attrs.push(format!("taillabel={}", quote(&tail_end.text())));
+    }
+    if !head_end.is_empty() {
+        attrs.push(format!("headlabel={}", quote(&head_end.text())));
+    }
+
+    let _ = writeln!(
+        out,
+        "{}{} -> {} [{}];",
+        INDENT,
+        quote(&diagram.nodes[tail.0].path.0),
+        quote(&diagram.nodes[head.0].path.0),
+        attrs.join(", ")
+    );
+}
+
+/// DOT has no edges between clusters; connect a node inside each one and clip
+/// the line at the cluster borders with `ltail`/`lhead`. A group without any
+/// node has nothing to connect, so its edges become a comment.
+fn write_group_edge(out: &mut String, diagram: &Diagram, edge: &GroupEdge) {
+    let (Some(from), Some(to)) = (first_node(diagram, edge.from), first_node(diagram, edge.to)) else {
+        let name = |gid: GroupId| diagram.groups[gid.0].path.as_ref().map_or("<anonymous>", |path| path.0.as_str());
+        let _ = writeln!(
+            out,
+            "{}// {} {} {}: a group without nodes can't be connected",
+            INDENT,
+            name(edge.from),
+            token_from_arrow(&edge.arrow).unwrap_or(&edge.arrow),
+            name(edge.to)
+        );
+        return;
+    };
+    let as_node = Edge {
+        from,
+        to,
+        arrow: edge.arrow.clone(),
+        label: edge.label.clone(),
+        from_end: edge.from_end.clone(),
+        to_end: edge.to_end.clone(),
+        style: edge.style.clone(),
+        order: edge.order,
+    };
+
+    let (tail, head) = get_edge_direction(&edge.arrow, edge.from, edge.to);
+    let cluster = |gid: GroupId| diagram.groups[gid.0].path.as_ref().map(|path| quote(&format!("cluster_{}", path.0)));
+    let clip = match (cluster(tail), cluster(head)) {
+        (Some(ltail), Some(lhead)) => vec![format!("ltail={}", ltail), format!("lhead={}", lhead)],
+        _ => Vec::new(),
+    };
+    write_edge(out, diagram, &as_node, clip);
+}
+
+/// A `shape=note` node with a dashed line to the node or group the note is for.
+/// Graphviz can't attach anything to an edge, so notes on relations stay unconnected.
+fn write_note(out: &mut String, diagram: &Diagram, note: &Note) {
+    let name = quote(&format!("note_{}", note.id.0));
+    // `\l` ends a left-justified line
+    let lines: Vec<String> = note.lines.iter().map(|l| l.replace('\\', "\\\\").replace('"', "\\\"")).collect();
+    let _ = writeln!(out, "{}{} [shape=note, label=\"{}\\l\"];", INDENT, name, lines.join("\\l"));
+
+    let connector = "style=dashed, arrowhead=none, constraint=false";
+    match note.anchor {
+        NoteAnchor::Node(nid) => {
+            let _ = writeln!(out, "{}{} -> {} [{}];", INDENT, name, quote(&diagram.nodes[nid.0].path.0), connector);
+        }
+        NoteAnchor::Group(gid) => {
+            let cluster = diagram.groups[gid.0].path.as_ref().map(|path| quote(&format!("cluster_{}", path.0)));
+            if let (Some(nid), Some(cluster)) = (first_node(diagram, gid), cluster) {
+                let target = quote(&diagram.nodes[nid.0].path.0);
+                let _ = writeln!(out, "{}{} -> {} [lhead={}, {}];", INDENT, name, target, cluster, connector);
+            }
+        }
+        NoteAnchor::Edge(_) | NoteAnchor::None => {}
+    }
+}
+
+/// First node in a group, searching subgroups in order
+fn first_node(diagram: &Diagram, gid: GroupId) -> Option<NodeId> {
+    let group = &diagram.groups[gid.0];
+    group.children_nodes.first().copied().or_else(|| group.children_groups.iter().find_map(|&g| first_node(diagram, g)))
+}
+
+fn dot_arrow(style: HeadStyle) -> &'static str {
+    match style {
+        HeadStyle::None => "none",
+        HeadStyle::Arrow => "vee",
+        HeadStyle::RoundedArrow => "curve",
+        HeadStyle::Triangle => "onormal",
+        HeadStyle::DiamondFilled => "diamond",
+        HeadStyle::DiamondEmpty => "odiamond",
+    }
+}
+
+/// Attributes for the style directives; `filled` fills the shape with `@fill`,
+/// `dashed` is whether the line is dashed unless `@dash` says otherwise
+fn style_attrs(style: &Style, filled: bool, dashed: bool) -> Vec<String> {
+    let mut flags = Vec::new();
+    let mut attrs = Vec::new();
+    if let Some(fill) = style.fill.as_ref().filter(|_| filled) {
+        flags.push("filled");
+        attrs.push(format!("fillcolor={}", quote(&dot_color(fill))));
+    }
+    if style.dash.as_ref().map_or(dashed, |d| !d.is_empty()) {
+        flags.push("dashed");
+    }
+    if let Some(stroke) = &style.stroke {
+        attrs.push(format!("color={}", quote(&dot_color(stroke))));
+    }
+    if let Some(text) = &style.text_color {
+        attrs.push(format!("fontcolor={}", quote(&dot_color(text))));
+    }
+    if let Some(width) = style.stroke_width {
+        attrs.push(format!("penwidth={}", width));
+    }
+    match flags.as_slice() {
+        [] => {}
+        [flag] => attrs.insert(0, format!("style={}", flag)),
+        _ => attrs.insert(0, format!("style={}", quote(&flags.join(",")))),
+    }
+    attrs
+}
+
+/// Graphviz only knows the long hex forms, so `#f80` becomes `#ff8800`
+fn dot_color(color: &str) -> String {
+    match color.strip_prefix('#') {
+        Some(hex) if hex.len() <= 4 => hex.chars().fold(String::from("#"), |mut out, c| {
+            out.push(c);
+            out.push(c);
+            out
+        }),
+        _ => color.to_string(),
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, parse_file};
+
+    fn export(src: &str) -> String {
+        export_dot(&compile(&parse_file(src).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_class_nodes_become_tables() {
+        let out = export("abstract class Shape {\n    +id: int\n    ---\n    +area(): List<f64>\n}\ncircle Start\n");
+        assert!(out.contains(
+            "\"Shape\" [shape=none, margin=0, label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\" cellpadding=\"4\">\
+             <tr><td>«abstract»<br/><i><b>Shape</b></i></td></tr>\
+             <tr><td align=\"left\" balign=\"left\">+id: int</td></tr>\
+             <tr><td align=\"left\" balign=\"left\">+area(): List&lt;f64&gt;</td></tr></table>>];"
+        ));
+        assert!(out.contains("\"Start\" [shape=circle, label=\"Start\"];"));
+    }
+
+    #[test]
+    fn test_groups_become_clusters() {
+        let out = export("group Domain {\n    class A\n    group {\n        class B\n    }\n}\n");
+        assert!(out.contains("    subgraph \"cluster_Domain\" {\n        label=\"Domain\";\n"));
+        // Nodes are keyed by their qualified path
+        assert!(out.contains("        subgraph {\n            \"Domain.B\""));
+    }
+
+    #[test]
+    fn test_group_edges_are_clipped_at_clusters() {
+        let out = export("group Model {\n    class User\n}\ngroup Persistence {\n    class Repo\n}\nModel ..> Persistence\n");
+        assert!(out.contains(
+            "\"Model.User\" -> \"Persistence.Repo\" [ltail=\"cluster_Model\", lhead=\"cluster_Persistence\", dir=both, arrowtail=none, arrowhead=vee, style=dashed];"
+        ));
+    }
+
+    #[test]
+    fn test_group_edges_with_brackets_in_ids_and_without_nodes() {
+        let out = export("group `A[1]` {\n    class X\n}\ngroup B {\n    class Y\n}\ngroup Empty {\n}\n`A[1]` --> B\nB --> Empty\n");
+        assert!(out.contains(
+            "\"A[1].X\" -> \"B.Y\" [ltail=\"cluster_A[1]\", lhead=\"cluster_B\", dir=both, arrowtail=none, arrowhead=vee];"
+        ));
+        assert!(out.contains("    // B --> Empty: a group without nodes can't be connected\n"));
+    }
+
+    #[test]
+    fn test_edges_follow_head_and_line_styles() {
+        let out = export("Child --|> Parent\nCar \"1\" *-- \"4\" Wheel : has\nA ..> B\nA --- C\n");
+        // Inheritance points from the parent down, with the triangle at the parent
+        assert!(out.contains("\"Parent\" -> \"Child\" [dir=both, arrowtail=onormal, arrowhead=none];"));
+        assert!(out.contains("\"Car\" -> \"Wheel\" [dir=both, arrowtail=diamond, arrowhead=none, label=\"has\", taillabel=\"1\", headlabel=\"4\"];"));
+        assert!(out.contains("\"A\" -> \"B\" [dir=both, arrowtail=none, arrowhead=vee, style=dashed];"));
+        assert!(out.contains("\"A\" -> \"C\" [dir=both, arrowtail=none, arrowhead=none, constraint=false];"));
+    }
+
+    #[test]
+    fn test_notes_become_note_shapes() {
+        let out = export("group Model {\n    class User\n}\nnote for User \"one\\ntwo\"\nnote for Model \"domain\"\n");
+        assert!(out.contains("\"note_0\" [shape=note, label=\"one\\ltwo\\l\"];"));
+        assert!(out.contains("\"note_0\" -> \"Model.User\" [style=dashed, arrowhead=none, constraint=false];"));
+        assert!(out.contains("\"note_1\" -> \"Model.User\" [lhead=\"cluster_Model\", style=dashed, arrowhead=none, constraint=false];"));
+    }
+
+    #[test]
+    fn test_styles_map_to_attributes() {
+        let out = export(
+            "group G {\n    @fill: #eef\n    circle A {\n        @fill: #f80\n        @dash: 4 2\n    }\n    class B {\n        @stroke: red\n        @text_color: #333333\n    }\n}\nA ..> B { @dash: none; @stroke_width: 2 }\n",
+        );
+        assert!(out.contains("        style=filled;\n        fillcolor=\"#eeeeff\";\n"));
+        assert!(out.contains("\"G.A\" [shape=circle, label=\"A\", style=\"filled,dashed\", fillcolor=\"#ff8800\"];"));
+        assert!(out.contains("<table border=\"0\" cellborder=\"1\" cellspacing=\"0\" cellpadding=\"4\" color=\"red\">"));
+        assert!(out.contains("</table>>, color=\"red\", fontcolor=\"#333333\"];"));
+        // `@dash: none` makes the dependency line solid
+        assert!(out.contains("\"G.A\" -> \"G.B\" [dir=both, arrowtail=none, arrowhead=vee, penwidth=2];"));
+    }
+}
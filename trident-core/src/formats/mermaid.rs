@@ -0,0 +1,1124 @@
+//! Mermaid `classDiagram` conversion.
+//!
+//! Import maps Mermaid annotations onto Trident modifiers/kinds and relation
+//! tokens onto `ARROW_DEFINITIONS` entries; cardinalities become relation
+//! end labels, `note` / `note for` become Trident notes, `style` lines
+//! become style directives and `classDef` becomes a `style` rule applied with
+//! `:::` (`classDef default` styles every class). `direction` becomes the
+//! `@direction` of the file, or of the group when written in a namespace.
+//!
+//! Export goes the other way from a compiled `Diagram`: modifiers become
+//! `<<annotations>>`, body lines become members, named groups become
+//! `namespace` blocks and resolved node styles become `style` lines. Layout
+//! hints, shapes and the styles of groups and relations are dropped with a
+//! warning.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::{is_blank, sanitize_ident, ConversionWarning, Converted, INDENT};
+use crate::parser::{
+    arrow_from_token, is_class_name, token_from_arrow, CommentAst, Diagram, Direction, FileAst, GroupAst, GroupId, Ident, NodeAst,
+    NodeId, NoteAnchor, NoteAst, NoteTarget, RelationAst, RelationEnd, Stmt, Style, StyleRuleAst, TypeParam, NODE_KEYWORDS,
+};
+
+/// Convert Mermaid `classDiagram` source into a Trident AST.
+/// Never fails: lines that can't be understood are skipped with a warning.
+pub fn import_mermaid(input: &str) -> Converted<FileAst> {
+    let mut im = Importer::default();
+    let mut lines = input.lines().enumerate().map(|(i, l)| (i + 1, l)).peekable();
+
+    // YAML front matter (--- title: ... ---)
+    if lines.peek().is_some_and(|(_, l)| l.trim() == "---") {
+        let (start, _) = lines.next().unwrap();
+        for (_, l) in lines.by_ref() {
+            if l.trim() == "---" {
+                break;
+            }
+        }
+        im.warn(start, "front matter is not supported; ignored");
+    }
+
+    for (line_no, raw) in lines {
+        im.line(line_no, raw.trim());
+    }
+
+    if let Some(class) = im.open_class.take() {
+        im.warn(input.lines().count(), format!("unterminated body for class '{}'", class));
+    }
+    if !im.seen_header {
+        im.warn_at(None, "missing 'classDiagram' header; treated as a class diagram");
+    }
+
+    // Drop trailing blank lines
+    while matches!(im.root.last(), Some(Stmt::Comment(c)) if is_blank(c)) {
+        im.root.pop();
+    }
+
+    Converted {
+        value: FileAst { layout: None, routing: None, direction: im.direction, wrap: None, items: im.root, tokens: Default::default(), comments: Default::default() },
+        warnings: im.warnings,
+    }
+}
+
+/// Where a node lives: root index of its namespace group (if any) and index within that scope
+type Location = (Option<usize>, usize);
+
+#[derive(Default)]
+struct Importer {
+    root: Vec<Stmt>,
+    warnings: Vec<ConversionWarning>,
+    /// Mermaid name -> location of the node statement
+    nodes: HashMap<String, Location>,
+    /// Root index of the namespace currently being filled
+    namespace: Option<usize>,
+    /// Namespaces opened inside `namespace` (flattened into it)
+    nested_namespaces: usize,
+    /// Mermaid name of the class whose `{ ... }` body is open
+    open_class: Option<String>,
+    seen_header: bool,
+    /// Top-level `direction`
+    direction: Option<String>,
+}
+
+impl Importer {
+    fn warn(&mut self, line: usize, message: impl Into<String>) {
+        self.warn_at(Some(line), message);
+    }
+
+    fn warn_at(&mut self, line: Option<usize>, message: impl Into<String>) {
+        self.warnings.push(ConversionWarning { line, message: message.into() });
+    }
+
+    fn items_mut(&mut self, scope: Option<usize>) -> &mut Vec<Stmt> {
+        match scope {
+            Some(i) => match &mut self.root[i] {
+                Stmt::Group(g) => &mut g.items,
+                _ => unreachable!("namespace scope always points at a group"),
+            },
+            None => &mut self.root,
+        }
+    }
+
+    fn push(&mut self, stmt: Stmt) {
+        let scope = self.namespace;
+        self.items_mut(scope).push(stmt);
+    }
+
+    fn push_comment(&mut self, text: String) {
+        let prefix = if self.namespace.is_some() { INDENT.to_string() } else { String::new() };
+        self.push(Stmt::Comment(CommentAst { prefix, text }));
+    }
+
+    fn push_blank(&mut self) {
+        let scope = self.namespace;
+        let items = self.items_mut(scope);
+        // Skip leading blanks and collapse runs
+        match items.last() {
+            None => {}
+            Some(Stmt::Comment(c)) if is_blank(c) => {}
+            Some(_) => items.push(Stmt::Comment(CommentAst { prefix: String::new(), text: String::new() })),
+        }
+    }
+
+    /// Get the node for a Mermaid class name, declaring it in the current scope if needed
+    fn node_mut(&mut self, name: &str) -> &mut NodeAst {
+        let (scope, idx) = match self.nodes.get(name) {
+            Some(&loc) => loc,
+            None => {
+                let node = new_node(name);
+                let scope = self.namespace;
+                let items = self.items_mut(scope);
+                items.push(Stmt::Node(node));
+                let loc = (scope, items.len() - 1);
+                self.nodes.insert(name.to_string(), loc);
+                loc
+            }
+        };
+        match &mut self.items_mut(scope)[idx] {
+            Stmt::Node(n) => n,
+            _ => unreachable!("node locations always point at nodes"),
+        }
+    }
+
+    fn line(&mut self, line_no: usize, t: &str) {
+        if let Some(class) = self.open_class.clone() {
+            self.class_body_line(line_no, &class, t);
+            return;
+        }
+
+        if t.is_empty() {
+            self.push_blank();
+            return;
+        }
+        if let Some(text) = t.strip_prefix("%%") {
+            // %%{init: ...}%% directives only configure the Mermaid renderer
+            if !text.starts_with('{') {
+                self.push_comment(text.to_string());
+            }
+            return;
+        }
+        if t == "classDiagram" || t == "classDiagram-v2" {
+            self.seen_header = true;
+            return;
+        }
+
+        let keyword = t.split_whitespace().next().unwrap_or("");
+        match keyword {
+            "direction" => self.direction(line_no, t["direction".len()..].trim()),
+            "namespace" => self.namespace_start(line_no, t),
+            "class" => self.class_declaration(line_no, t["class".len()..].trim()),
+            "note" => self.note(line_no, t),
+            "style" => self.style(line_no, t),
+            "classDef" => self.class_def(line_no, t),
+            "cssClass" => self.css_class(line_no, t),
+            "click" | "callback" | "link" => {
+                self.warn(line_no, format!("'{}' interactions are not supported; dropped", keyword));
+            }
+            "}" if self.nested_namespaces > 0 => self.nested_namespaces -= 1,
+            "}" if self.namespace.is_some() => self.namespace = None,
+            _ if t.starts_with("<<") => self.standalone_annotation(line_no, t),
+            _ => self.relation_or_member(line_no, t),
+        }
+    }
+
+    /// `note "text"` or `note for Class "text"`; `\n` and `<br>` break lines
+    fn note(&mut self, line_no: usize, t: &str) {
+        let rest = t["note".len()..].trim();
+        let (target, text) = match rest.strip_prefix("for ") {
+            Some(after) => {
+                let (name, text) = split_name(after.trim_start());
+                (Some(name), text.trim())
+            }
+            None => (None, rest),
+        };
+        let Some(text) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) else {
+            self.warn(line_no, format!("unrecognised note: {}", t));
+            return;
+        };
+        let target = match target {
+            Some(name) => NoteTarget::Node(self.node_mut(&name).id.clone()),
+            None => NoteTarget::None,
+        };
+        let text = text.replace("\\n", "\n").replace("<br/>", "\n").replace("<br>", "\n");
+        self.push(Stmt::Note(NoteAst {
+            target,
+            lines: text.split('\n').map(|l| l.trim().to_string()).collect(),
+            span: None,
+            tokens: Default::default(),
+            comments: Default::default(),
+        }));
+    }
+
+    /// `style Class fill:#f9f,stroke:#333,stroke-width:2px`
+    fn style(&mut self, line_no: usize, t: &str) {
+        let (name, props) = split_name(t["style".len()..].trim());
+        if name.is_empty() {
+            self.warn(line_no, "style without a class name; ignored");
+            return;
+        }
+        let style = self.css_style(line_no, &name, props);
+        self.node_mut(&name).style.merge(&style);
+    }
+
+    /// `classDef warning,error fill:#fee,stroke:#c00`; `default` applies to every class
+    fn class_def(&mut self, line_no: usize, t: &str) {
+        let rest = t["classDef".len()..].trim();
+        let (names, props) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let style = self.css_style(line_no, name, props.trim().trim_end_matches(';'));
+            let selector = if name == "default" { "class" } else { name };
+            if !is_class_name(selector) {
+                self.warn(line_no, format!("style class '{}' dropped", name));
+                continue;
+            }
+            self.root.push(Stmt::Style(StyleRuleAst {
+                selector: selector.to_string(),
+                style,
+                span: None,
+                tokens: Default::default(),
+                comments: Default::default(),
+            }));
+        }
+    }
+
+    /// `cssClass "A,B" warning`
+    fn css_class(&mut self, line_no: usize, t: &str) {
+        let rest = t["cssClass".len()..].trim();
+        let Some((names, class)) = rest.strip_prefix('"').and_then(|r| r.split_once('"')) else {
+            self.warn(line_no, format!("unrecognised cssClass: {}", t));
+            return;
+        };
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            self.apply_class(line_no, name, class.trim());
+        }
+    }
+
+    /// Add style class `class` to the node for Mermaid class `name`
+    fn apply_class(&mut self, line_no: usize, name: &str, class: &str) {
+        if !is_class_name(class) {
+            self.warn(line_no, format!("style class '{}' of '{}' dropped", class, name));
+            return;
+        }
+        let node = self.node_mut(name);
+        if !node.classes.iter().any(|c| c == class) {
+            node.classes.push(class.to_string());
+        }
+    }
+
+    /// Style directives for CSS-like properties `fill:#fee,stroke-width:2px` of `name`
+    fn css_style(&mut self, line_no: usize, name: &str, props: &str) -> Style {
+        let mut style = Style::default();
+        for prop in props.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = prop.split_once(':').unwrap_or((prop, ""));
+            let directive = STYLE_PROPERTIES.iter().find(|(css, _)| *css == key.trim()).map(|(_, d)| *d);
+            if directive.is_none_or(|d| style.set(d, value.trim().trim_end_matches("px")).is_err()) {
+                self.warn(line_no, format!("style property '{}' of '{}' dropped", prop, name));
+            }
+        }
+        style
+    }
+
+    fn namespace_start(&mut self, line_no: usize, t: &str) {
+        if self.namespace.is_some() {
+            self.warn(line_no, "nested namespaces are not supported; contents merged into the outer one");
+            self.nested_namespaces += 1;
+            return;
+        }
+        let name = t["namespace".len()..].trim().trim_end_matches('{').trim();
+        if name.is_empty() {
+            self.warn(line_no, "namespace without a name; ignored");
+            return;
+        }
+        let id = match sanitize_ident(name) {
+            Some(id) => {
+                self.warn(line_no, format!("namespace '{}' renamed to '{}'", name, id));
+                id
+            }
+            None => name.to_string(),
+        };
+        self.root.push(Stmt::Group(GroupAst {
+            id: Some(Ident(id)),
+            pos: None,
+            style: Default::default(),
+            direction: None,
+            items: Vec::new(),
+            span: None,
+            tokens: Default::default(),
+            comments: Default::default(),
+        }));
+        self.namespace = Some(self.root.len() - 1);
+    }
+
+    /// `direction LR`, for the whole diagram or the current namespace
+    fn direction(&mut self, line_no: usize, name: &str) {
+        let Some(direction) = Direction::from_name(name) else {
+            self.warn(line_no, format!("unknown direction '{}'; ignored", name));
+            return;
+        };
+        match self.namespace {
+            Some(i) => match &mut self.root[i] {
+                Stmt::Group(g) => g.direction = Some(direction),
+                _ => unreachable!("namespace scope always points at a group"),
+            },
+            None => self.direction = Some(direction.name().to_string()),
+        }
+    }
+
+    /// `class Name~T~["Label"]:::style {`
+    fn class_declaration(&mut self, line_no: usize, rest: &str) {
+        let (name, mut rest) = split_name(rest);
+        if name.is_empty() {
+            self.warn(line_no, "class declaration without a name; ignored");
+            return;
+        }
+
+        let mut generic = None;
+        if rest.starts_with('~') {
+            let end = rest.find(['[', '{']).or_else(|| rest.find(":::")).unwrap_or(rest.len());
+            generic = Some(convert_generics(rest[..end].trim()));
+            rest = &rest[end..];
+        }
+
+        let mut label = None;
+        if let Some(after) = rest.trim_start().strip_prefix("[\"") {
+            match after.split_once("\"]") {
+                Some((text, tail)) => {
+                    label = Some(text.to_string());
+                    rest = tail;
+                }
+                None => self.warn(line_no, "unterminated class label; ignored"),
+            }
+        }
+
+        let mut rest = rest.trim();
+        let mut class = None;
+        if let Some(styled) = rest.strip_prefix(":::") {
+            let end = styled.find('{').unwrap_or(styled.len());
+            class = Some(styled[..end].trim());
+            rest = styled[end..].trim();
+        }
+
+        let type_params = generic.map(|g| TypeParam::parse_list(g.trim_start_matches('<').trim_end_matches('>')));
+        if let Some(Err(msg)) = &type_params {
+            self.warn(line_no, format!("generic parameters of '{}' dropped: {}", name, msg));
+        }
+        let node = self.node_mut(&name);
+        if let Some(label) = label {
+            node.label = Some(label);
+        }
+        if let Some(Ok(type_params)) = type_params {
+            node.type_params = type_params;
+        }
+        if let Some(class) = class {
+            self.apply_class(line_no, &name, class);
+        }
+
+        match rest {
+            "" | "{}" | "{ }" => {}
+            "{" => self.open_class = Some(name),
+            _ => self.warn(line_no, format!("unexpected text after class '{}': {}", name, rest)),
+        }
+    }
+
+    fn class_body_line(&mut self, line_no: usize, class: &str, t: &str) {
+        if t == "}" {
+            self.open_class = None;
+        } else if t.is_empty() || t.starts_with("%%") {
+            // Trident node bodies can't hold comments
+        } else if let Some(annotation) = parse_annotation(t) {
+            self.apply_annotation(line_no, class, annotation);
+        } else {
+            let member = convert_generics(t);
+            self.node_mut(class).body_lines.push(member);
+        }
+    }
+
+    /// `<<interface>> Name`
+    fn standalone_annotation(&mut self, line_no: usize, t: &str) {
+        let Some(end) = t.find(">>") else {
+            self.warn(line_no, "unterminated annotation; ignored");
+            return;
+        };
+        let annotation = t[2..end].trim();
+        let (name, _) = split_name(t[end + 2..].trim());
+        if name.is_empty() {
+            self.warn(line_no, format!("annotation '<<{}>>' is not attached to a class; ignored", annotation));
+            return;
+        }
+        self.apply_annotation(line_no, &name, annotation);
+    }
+
+    fn apply_annotation(&mut self, line_no: usize, class: &str, annotation: &str) {
+        let lower = annotation.to_ascii_lowercase();
+        let node = self.node_mut(class);
+        match lower.as_str() {
+            "interface" | "struct" | "record" | "trait" | "object" => node.original_kind = lower,
+            "enumeration" | "enum" => node.original_kind = "enum".to_string(),
+            _ if is_plain_word(annotation) => {
+                if !node.modifiers.iter().any(|m| m == &lower) {
+                    node.modifiers.push(lower);
+                }
+            }
+            _ => self.warn(
+                line_no,
+                format!("annotation '<<{}>>' can't be expressed as a modifier; dropped", annotation),
+            ),
+        }
+    }
+
+    /// `A "1" --> "*" B : label` or `A : +member`
+    fn relation_or_member(&mut self, line_no: usize, t: &str) {
+        let (head, label) = match t.split_once(':') {
+            Some((head, label)) => (head.trim(), Some(label.trim())),
+            None => (t, None),
+        };
+
+        let Some(rel) = split_relation(head) else {
+            // `Name : member`
+            let (name, rest) = split_name(head);
+            match label {
+                Some(member) if !name.is_empty() && rest.trim().is_empty() => {
+                    if let Some(annotation) = parse_annotation(member) {
+                        self.apply_annotation(line_no, &name, annotation);
+                    } else if !member.is_empty() {
+                        let member = convert_generics(member);
+                        self.node_mut(&name).body_lines.push(member);
+                    }
+                }
+                _ => self.warn(line_no, format!("unrecognised line: {}", t)),
+            }
+            return;
+        };
+
+        let token = if rel.token == "--" { "---" } else { rel.token };
+        let arrow = match arrow_from_token(token) {
+            Some(arrow) => arrow,
+            None => {
+                let fallback = if token.contains('.') { "dotted" } else { "line" };
+                self.warn(
+                    line_no,
+                    format!("relation '{}' has no Trident equivalent; imported as a plain {}", rel.token, fallback),
+                );
+                fallback
+            }
+        };
+
+        // Relations don't declare nodes in Mermaid either, but reusing the
+        // declared ones keeps renamed identifiers consistent
+        let from = self.ident_for(rel.from);
+        let to = self.ident_for(rel.to);
+        self.push(Stmt::Relation(RelationAst {
+            from,
+            arrow: arrow.to_string(),
+            to,
+            label: label.filter(|l| !l.is_empty()).map(str::to_string),
+            from_end: rel.from_card.map(RelationEnd::parse).unwrap_or_default(),
+            to_end: rel.to_card.map(RelationEnd::parse).unwrap_or_default(),
+            style: Default::default(),
+            span: None,
+            tokens: Default::default(),
+            comments: Default::default(),
+        }));
+    }
+
+    fn ident_for(&self, name: &str) -> Ident {
+        let name = strip_generics(name);
+        match self.nodes.get(name) {
+            Some(&(scope, idx)) => {
+                let items = match scope {
+                    Some(i) => match &self.root[i] {
+                        Stmt::Group(g) => &g.items,
+                        _ => unreachable!("namespace scope always points at a group"),
+                    },
+                    None => &self.root,
+                };
+                match &items[idx] {
+                    Stmt::Node(n) => n.id.clone(),
+                    _ => unreachable!("node locations always point at nodes"),
+                }
+            }
+            None => Ident(sanitize_ident(name).unwrap_or_else(|| name.to_string())),
+        }
+    }
+}
+
+fn new_node(name: &str) -> NodeAst {
+    let (id, label) = match sanitize_ident(name) {
+        Some(id) => (id, Some(name.to_string())),
+        None => (name.to_string(), None),
+    };
+    NodeAst {
+        kind: "class".to_string(),
+        original_kind: "class".to_string(),
+        modifiers: Vec::new(),
+        id: Ident(id),
+        label,
+        type_params: Vec::new(),
+        classes: Vec::new(),
+        pos: None,
+        width: None,
+        height: None,
+        style: Default::default(),
+        body_lines: Vec::new(),
+        span: None,
+        tokens: Default::default(),
+        comments: Default::default(),
+    }
+}
+
+fn is_plain_word(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `<<interface>>` -> `interface`
+fn parse_annotation(t: &str) -> Option<&str> {
+    t.strip_prefix("<<")?.strip_suffix(">>").map(str::trim)
+}
+
+/// Split a leading class name (plain or backtick-quoted) from the rest
+fn split_name(s: &str) -> (String, &str) {
+    if let Some(quoted) = s.strip_prefix('`')
+        && let Some((name, rest)) = quoted.split_once('`')
+    {
+        return (name.to_string(), rest);
+    }
+    let end = s
+        .find(|c: char| c.is_whitespace() || matches!(c, '~' | '[' | '{' | ':' | '"'))
+        .unwrap_or(s.len());
+    (s[..end].to_string(), &s[end..])
+}
+
+fn strip_generics(name: &str) -> &str {
+    let name = name.trim().trim_matches('`');
+    name.split('~').next().unwrap_or(name)
+}
+
+/// Mermaid writes generics with tildes: `List~Map~K, V~~` -> `List<Map<K, V>>`
+fn convert_generics(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+    let mut depth = 0usize;
+    let mut out = String::with_capacity(s.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c != '~' {
+            out.push(c);
+        } else if (i == 0 || word(chars.get(i - 1))) && word(chars.get(i + 1)) {
+            depth += 1;
+            out.push('<');
+        } else if depth > 0 {
+            depth -= 1;
+            out.push('>');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+struct MermaidRelation<'a> {
+    from: &'a str,
+    from_card: Option<&'a str>,
+    token: &'a str,
+    to_card: Option<&'a str>,
+    to: &'a str,
+}
+
+/// Split `A "1" <|-- "*" B` into its parts. Returns None if there is no arrow.
+fn split_relation(head: &str) -> Option<MermaidRelation<'_>> {
+    let bytes = head.as_bytes();
+    let boundary = |i: Option<usize>| i.is_none_or(|i| bytes[i].is_ascii_whitespace() || bytes[i] == b'"');
+
+    // The line part of the arrow is a run of '-' or '.' outside quotes
+    let mut in_quotes = false;
+    let mut line_start = None;
+    for i in 0..bytes.len().saturating_sub(1) {
+        match bytes[i] {
+            b'"' => in_quotes = !in_quotes,
+            b'-' | b'.' if !in_quotes && bytes[i + 1] == bytes[i] => {
+                line_start = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let line_start = line_start?;
+    let mut end = line_start;
+    while end < bytes.len() && bytes[end] == bytes[line_start] {
+        end += 1;
+    }
+
+    // Heads on either side; a lone 'o' only counts when it isn't part of a name
+    let mut start = line_start;
+    while start > 0 {
+        match bytes[start - 1] {
+            b'<' | b'|' | b'*' | b'(' | b')' => start -= 1,
+            b'o' if boundary(start.checked_sub(2)) => start -= 1,
+            _ => break,
+        }
+    }
+    while end < bytes.len() {
+        match bytes[end] {
+            b'>' | b'|' | b'*' | b'(' | b')' => end += 1,
+            b'o' if boundary(Some(end + 1).filter(|&i| i < bytes.len())) => end += 1,
+            _ => break,
+        }
+    }
+
+    let (from, from_card) = take_trailing_card(head[..start].trim());
+    let (to, to_card) = take_leading_card(head[end..].trim());
+    if from.is_empty() || to.is_empty() {
+        return None;
+    }
+    Some(MermaidRelation { from, from_card, token: &head[start..end], to_card, to })
+}
+
+/// `A "1"` -> (`A`, Some(`1`))
+fn take_trailing_card(s: &str) -> (&str, Option<&str>) {
+    if let Some(inner) = s.strip_suffix('"')
+        && let Some(open) = inner.rfind('"')
+    {
+        return (inner[..open].trim(), Some(&inner[open + 1..]));
+    }
+    (s, None)
+}
+
+/// `"*" B` -> (`B`, Some(`*`))
+fn take_leading_card(s: &str) -> (&str, Option<&str>) {
+    if let Some(inner) = s.strip_prefix('"')
+        && let Some(close) = inner.find('"')
+    {
+        return (inner[close + 1..].trim(), Some(&inner[..close]));
+    }
+    (s, None)
+}
+
+/// Convert a compiled diagram into Mermaid `classDiagram` text.
+/// Anything Mermaid can't express is dropped and listed in the warnings.
+pub fn export_mermaid(diagram: &Diagram) -> Converted<String> {
+    let mut ex = Exporter { diagram, out: String::from("classDiagram\n"), warnings: Vec::new() };
+    ex.group_contents(diagram.root, 1, false);
+
+    for node in diagram.nodes.iter().filter(|n| !n.style.is_empty()) {
+        let _ = writeln!(ex.out, "{}style {} {}", INDENT, node.id.0, mermaid_style(&node.style));
+    }
+    for group in diagram.groups.iter().filter(|g| !g.style.is_empty()) {
+        let name = group.id.as_ref().map_or("anonymous", |id| id.0.as_str());
+        ex.warn(format!("style of group '{}' dropped", name));
+    }
+
+    for edge in &diagram.edges {
+        let from = &diagram.nodes[edge.from.0].id.0;
+        let to = &diagram.nodes[edge.to.0].id.0;
+        let token = match token_from_arrow(&edge.arrow).unwrap_or("-->") {
+            "---" => "--",
+            "--)" | "(--" => {
+                ex.warn(format!("rounded arrow {} -> {} exported as a plain association", from, to));
+                if edge.arrow.ends_with("_left") { "<--" } else { "-->" }
+            }
+            token => token,
+        };
+        if !edge.style.is_empty() {
+            ex.warn(format!("style of relation {} -> {} dropped", from, to));
+        }
+        let _ = write!(ex.out, "{}{}{} {} {}{}", INDENT, from, card(&edge.from_end, true), token, card(&edge.to_end, false), to);
+        if let Some(label) = &edge.label {
+            let _ = write!(ex.out, " : {}", label);
+        }
+        ex.out.push('\n');
+    }
+    for edge in &diagram.group_edges {
+        let name = |gid: GroupId| diagram.groups[gid.0].id.as_ref().map_or("", |id| id.0.as_str());
+        ex.warn(format!("relation between groups '{}' and '{}' dropped", name(edge.from), name(edge.to)));
+    }
+    for note in &diagram.notes {
+        let text = mermaid_text(&note.lines.join("\\n"));
+        match note.anchor {
+            NoteAnchor::None => {
+                let _ = writeln!(ex.out, "{}note \"{}\"", INDENT, text);
+            }
+            NoteAnchor::Node(nid) => {
+                let _ = writeln!(ex.out, "{}note for {} \"{}\"", INDENT, diagram.nodes[nid.0].id.0, text);
+            }
+            NoteAnchor::Group(_) | NoteAnchor::Edge(_) => {
+                ex.warn(format!("note \"{}\" on a group or relation dropped", note.lines.join(" ")));
+            }
+        }
+    }
+
+    Converted { value: ex.out, warnings: ex.warnings }
+}
+
+struct Exporter<'a> {
+    diagram: &'a Diagram,
+    out: String,
+    warnings: Vec<ConversionWarning>,
+}
+
+enum Child {
+    Group(GroupId),
+    Node(NodeId),
+}
+
+impl Exporter<'_> {
+    fn warn(&mut self, message: String) {
+        self.warnings.push(ConversionWarning { line: None, message });
+    }
+
+    /// Emit a group's nodes and subgroups in source order.
+    /// `in_namespace` is set once a Mermaid namespace is open, since those can't nest.
+    fn group_contents(&mut self, gid: GroupId, depth: usize, in_namespace: bool) {
+        let group = &self.diagram.groups[gid.0];
+        let mut children: Vec<(usize, Child)> = group
+            .children_groups
+            .iter()
+            .map(|&g| (self.diagram.groups[g.0].order, Child::Group(g)))
+            .chain(group.children_nodes.iter().map(|&n| (self.diagram.nodes[n.0].order, Child::Node(n))))
+            .collect();
+        children.sort_by_key(|(order, _)| *order);
+
+        for (_, child) in children {
+            match child {
+                Child::Node(nid) => self.node(nid, depth),
+                Child::Group(g) => {
+                    let group = &self.diagram.groups[g.0];
+                    if group.pos.is_some() {
+                        let name = group.id.as_ref().map_or("anonymous", |id| id.0.as_str());
+                        self.warn(format!("@pos of group '{}' dropped", name));
+                    }
+                    match &group.id {
+                        Some(id) if !in_namespace => {
+                            let _ = writeln!(self.out, "{}namespace {} {{", INDENT.repeat(depth), id.0);
+                            self.group_contents(g, depth + 1, true);
+                            let _ = writeln!(self.out, "{}}}", INDENT.repeat(depth));
+                        }
+                        Some(id) => {
+                            self.warn(format!("nested group '{}' flattened into its parent namespace", id.0));
+                            self.group_contents(g, depth, in_namespace);
+                        }
+                        // Anonymous groups only scope layout
+                        None => self.group_contents(g, depth, in_namespace),
+                    }
+                }
+            }
+        }
+    }
+
+    fn node(&mut self, nid: NodeId, depth: usize) {
+        let node = &self.diagram.nodes[nid.0];
+        // Implicit nodes are declared by the relations that mention them
+        if !node.explicit {
+            return;
+        }
+        let ind = INDENT.repeat(depth);
+        let id = &node.id.0;
+
+        if node.pos.is_some() || node.width.is_some() || node.height.is_some() {
+            self.warn(format!("layout hints (@pos/@width/@height) of '{}' dropped", id));
+        }
+
+        let mut annotations = Vec::new();
+        for modifier in &node.modifiers {
+            if NODE_KEYWORDS.contains(&modifier.as_str()) {
+                self.warn(format!("shape '{}' of '{}' dropped; exported as a class", modifier, id));
+            } else if modifier == "enum" {
+                annotations.push("enumeration".to_string());
+            } else {
+                annotations.push(modifier.clone());
+            }
+        }
+        if node.kind == "node" && !node.modifiers.iter().any(|m| NODE_KEYWORDS.contains(&m.as_str())) {
+            self.warn(format!("node '{}' exported as a class", id));
+        }
+
+        let mut members = Vec::new();
+        let mut dropped_separator = false;
+        for line in &node.body_lines {
+            if line.trim().chars().all(|c| c == '-') {
+                dropped_separator = true;
+            } else {
+                members.push(to_mermaid_generics(line));
+            }
+        }
+        if dropped_separator {
+            self.warn(format!("separator lines in '{}' dropped", id));
+        }
+
+        let _ = write!(self.out, "{}class {}", ind, id);
+        if !node.type_params.is_empty() {
+            if node.type_params.iter().any(|p| p.bound.is_some()) {
+                self.warn(format!("type parameter bounds of '{}' dropped", id));
+            }
+            let names: Vec<&str> = node.type_params.iter().map(|p| p.name.as_str()).collect();
+            let _ = write!(self.out, "~{}~", names.join(", "));
+        }
+        if let Some(label) = &node.label {
+            let _ = write!(self.out, "[\"{}\"]", label);
+        }
+        if annotations.is_empty() && members.is_empty() {
+            self.out.push('\n');
+            return;
+        }
+        self.out.push_str(" {\n");
+        for annotation in annotations {
+            let _ = writeln!(self.out, "{}{}<<{}>>", ind, INDENT, annotation);
+        }
+        for member in members {
+            let _ = writeln!(self.out, "{}{}{}", ind, INDENT, member);
+        }
+        let _ = writeln!(self.out, "{}}}", ind);
+    }
+}
+
+/// ` "1..*"` after the source or `"1..*" ` before the target; empty if the end has no text
+fn card(end: &RelationEnd, at_from: bool) -> String {
+    match (end.is_empty(), at_from) {
+        (true, _) => String::new(),
+        (false, true) => format!(" \"{}\"", mermaid_text(&end.text())),
+        (false, false) => format!("\"{}\" ", mermaid_text(&end.text())),
+    }
+}
+
+/// Text for inside a Mermaid string. Mermaid has no escape for '"' there, only
+/// the #quot; entity
+fn mermaid_text(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+/// Mermaid `style` properties and the style directives they map to
+const STYLE_PROPERTIES: [(&str, &str); 5] = [
+    ("fill", "fill"),
+    ("stroke", "stroke"),
+    ("color", "text_color"),
+    ("stroke-width", "stroke_width"),
+    ("stroke-dasharray", "dash"),
+];
+
+/// `fill:#fee,stroke:#c00,stroke-width:2px`; a solid `@dash` is Mermaid's default
+fn mermaid_style(style: &Style) -> String {
+    let props: Vec<String> = STYLE_PROPERTIES
+        .into_iter()
+        .filter_map(|(prop, directive)| match style.value(directive)?.as_str() {
+            "none" if directive == "dash" => None,
+            value if directive == "stroke_width" => Some(format!("{}:{}px", prop, value)),
+            value => Some(format!("{}:{}", prop, value)),
+        })
+        .collect();
+    props.join(",")
+}
+
+/// `Map<K, List<V>>` -> `Map~K, List~V~~`; other angle brackets are left alone
+fn to_mermaid_generics(s: &str) -> String {
+    let mut depth = 0usize;
+    let mut prev: Option<char> = None;
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' if prev.is_some_and(|p| p.is_alphanumeric() || p == '_') => {
+                depth += 1;
+                out.push('~');
+            }
+            '>' if depth > 0 => {
+                depth -= 1;
+                out.push('~');
+            }
+            _ => out.push(c),
+        }
+        prev = Some(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, emit_file, parse_file};
+
+    fn import(src: &str) -> (String, Vec<ConversionWarning>) {
+        let converted = import_mermaid(src);
+        (emit_file(&converted.value), converted.warnings)
+    }
+
+    #[test]
+    fn test_import_classes_annotations_and_members() {
+        let (out, warnings) = import(
+            "classDiagram\n\
+             class Shape {\n\
+             \x20   <<interface>>\n\
+             \x20   +area() double\n\
+             }\n\
+             class Base\n\
+             <<abstract>> Base\n\
+             Base : +List~int~ ids\n\
+             class Color {\n\
+             \x20   <<enumeration>>\n\
+             \x20   RED\n\
+             }\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(
+            out,
+            "interface Shape {\n    +area() double\n}\n\
+             abstract class Base {\n    +List<int> ids\n}\n\
+             enum Color {\n    RED\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_import_relations_map_to_arrow_registry() {
+        let (out, warnings) = import(
+            "classDiagram\n\
+             Animal <|-- Duck\n\
+             Car *-- Wheel : has\n\
+             Pond o-- Duck\n\
+             A ..|> B\n\
+             A -- B\n\
+             A .. B\n\
+             A --o B\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let ast = parse_file(&out).unwrap();
+        let arrows: Vec<&str> = ast.items.iter().filter_map(|s| match s {
+            Stmt::Relation(r) => Some(r.arrow.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(
+            arrows,
+            ["extends_left", "compose_right", "aggregate_right", "implements_right", "line", "dotted", "aggregate_left"]
+        );
+        assert!(out.contains("Car *-- Wheel : has"));
+    }
+
+    #[test]
+    fn test_import_reports_unsupported_constructs() {
+        let (out, warnings) = import(
+            "classDiagram\n\
+             click Customer call notify()\n\
+             class Square~Shape~:::highlight\n\
+             Customer \"1\" --> \"*\" Ticket\n\
+             note for Customer \"VIP only\"\n\
+             classDef highlight fill:#f9f\n",
+        );
+        let lines: Vec<Option<usize>> = warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, [Some(2)]);
+        assert!(out.contains("class Square<Shape> :::highlight\n"));
+        assert!(out.contains("Customer \"1\" --> \"*\" Ticket"));
+        assert!(out.contains("note for Customer \"VIP only\""));
+        assert!(out.ends_with("style highlight { @fill: #f9f }\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_import_style_classes() {
+        let (out, warnings) = import(
+            "classDiagram\n\
+             class A\n\
+             class B\n\
+             cssClass \"A,B\" warn\n\
+             classDef warn,error stroke:#c00,font-weight:bold;\n\
+             classDef default fill:#eee\n",
+        );
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[0].message.contains("font-weight"));
+        assert_eq!(
+            out,
+            "class A :::warn\nclass B :::warn\nstyle warn { @stroke: #c00 }\nstyle error { @stroke: #c00 }\nstyle class { @fill: #eee }\n"
+        );
+        let diagram = compile(&parse_file(&out).unwrap()).unwrap();
+        assert_eq!(diagram.nodes[0].style, Style { fill: Some("#eee".into()), stroke: Some("#c00".into()), ..Style::default() });
+    }
+
+    #[test]
+    fn test_import_direction() {
+        let (out, warnings) = import(
+            "classDiagram\n\
+             direction RL\n\
+             namespace Shapes {\n\
+             \x20   direction TB\n\
+             \x20   class Triangle\n\
+             }\n\
+             direction UP\n",
+        );
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0].line, Some(7));
+        assert_eq!(out, "@direction: RL\ngroup Shapes {\n    @direction: TB\n    class Triangle\n}\n");
+    }
+
+    #[test]
+    fn test_import_namespaces_become_groups() {
+        let converted = import_mermaid(
+            "classDiagram\n\
+             namespace Shapes {\n\
+             \x20   class Triangle\n\
+             \x20   class `Odd Shape`\n\
+             }\n\
+             Triangle --> `Odd Shape`\n",
+        );
+        let out = emit_file(&converted.value);
+        assert_eq!(
+            out,
+            "group Shapes {\n    class Triangle\n    class Odd_Shape \"Odd Shape\"\n}\nTriangle --> Odd_Shape\n"
+        );
+        // The emitted Trident must itself be valid
+        compile(&parse_file(&out).unwrap()).unwrap();
+    }
+
+    fn export(src: &str) -> (String, Vec<ConversionWarning>) {
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let converted = export_mermaid(&diagram);
+        (converted.value, converted.warnings)
+    }
+
+    #[test]
+    fn test_export_classes_and_relations() {
+        let (out, warnings) = export(
+            "interface Shape {\n    +area() double\n}\n\
+             abstract class Base \"Base type\" {\n    +List<int> ids\n}\n\
+             enum Color\n\
+             Base --|> Shape\n\
+             Base --- Color : uses\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(
+            out,
+            "classDiagram\n\
+             \x20   class Shape {\n        <<interface>>\n        +area() double\n    }\n\
+             \x20   class Base[\"Base type\"] {\n        <<abstract>>\n        +List~int~ ids\n    }\n\
+             \x20   class Color {\n        <<enumeration>>\n    }\n\
+             \x20   Base --|> Shape\n\
+             \x20   Base -- Color : uses\n"
+        );
+    }
+
+    #[test]
+    fn test_export_escapes_quotes_in_relation_ends() {
+        let (out, _) = export("class A\nclass B\nA \"1 \\\"x\\\"\" --> \"*\" B\n");
+        assert!(out.contains("    A \"1 #quot;x#quot;\" --> \"*\" B\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_export_groups_as_namespaces() {
+        let (out, warnings) = export(
+            "group Outer {\n    class A\n    group Inner {\n        class B\n    }\n}\ngroup {\n    class C\n}\nA --> D\n",
+        );
+        assert_eq!(
+            out,
+            "classDiagram\n    namespace Outer {\n        class A\n        class B\n    }\n    class C\n    A --> D\n"
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("Inner"));
+    }
+
+    #[test]
+    fn test_export_reports_dropped_features() {
+        let (out, warnings) = export(
+            "circle Start {\n    @pos: (10, 20)\n}\nclass A {\n    x\n    ---\n    y()\n}\nStart --) A\n",
+        );
+        assert!(out.contains("class Start\n"));
+        assert!(out.contains("Start --> A"));
+        assert!(!out.contains("---"));
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_export_notes() {
+        let (out, warnings) = export(
+            "class A\nclass B\nA --> B\nnote for A {\n    says \"hi\"\n    twice\n}\nnote on A --> B \"why\"\nnote \"legend\"\n",
+        );
+        assert!(out.ends_with("    note for A \"says #quot;hi#quot;\\ntwice\"\n    note \"legend\"\n"), "{}", out);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+
+        let converted = import_mermaid(&out);
+        assert!(converted.warnings.is_empty(), "{:?}", converted.warnings);
+        assert!(emit_file(&converted.value).contains("note for A {\n    says #quot;hi#quot;\n    twice\n}\n"));
+    }
+
+    #[test]
+    fn test_styles_roundtrip_as_style_lines() {
+        let (out, warnings) = export(
+            "group G {\n    @fill: #eef\n    class A {\n        @fill: #fee\n        @stroke_width: 2\n        @dash: 4 2\n    }\n}\nA --> B { @stroke: red }\n",
+        );
+        assert!(out.contains("    style A fill:#fee,stroke-width:2px,stroke-dasharray:4 2\n"), "{}", out);
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+
+        let (trident, warnings) = import("classDiagram\nclass A\nstyle A fill:#f9f,color:#333,stroke-width:4px,font-size:20px\n");
+        assert_eq!(trident, "class A {\n    @fill: #f9f\n    @text_color: #333\n    @stroke_width: 4\n}\n");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("font-size"));
+    }
+
+    #[test]
+    fn test_export_then_import_roundtrips() {
+        let src = "interface Repo<T> {\n    +find(id) Option<T>\n}\nclass SqlRepo\nSqlRepo ..|> Repo : implements\n";
+        let (mermaid, _) = export(src);
+        let converted = import_mermaid(&mermaid);
+        assert!(converted.warnings.is_empty(), "{:?}", converted.warnings);
+        assert_eq!(emit_file(&converted.value), src);
+    }
+
+    #[test]
+    fn test_convert_generics() {
+        assert_eq!(convert_generics("List~int~ ids"), "List<int> ids");
+        assert_eq!(convert_generics("Map~K, List~V~~"), "Map<K, List<V>>");
+        assert_eq!(convert_generics("~T~"), "<T>");
+    }
+}
@@ -0,0 +1,58 @@
+//! Conversions between Trident and other diagram languages.
+//!
+//! Importers produce a `FileAst` so the result can be written out as
+//! idiomatic Trident through `emit_file`. Constructs that have no Trident
+//! equivalent are dropped or approximated and reported as warnings instead
+//! of failing the whole conversion.
+//!
+//! Submodules:
+//! - dot: Graphviz DOT (export only)
+//! - mermaid: Mermaid `classDiagram` (import and export)
+//! - plantuml: PlantUML class diagrams (import and export)
+
+pub mod dot;
+pub mod mermaid;
+pub mod plantuml;
+
+use serde::Serialize;
+
+use crate::parser::CommentAst;
+
+const INDENT: &str = "    ";
+
+/// Something that was dropped or approximated during a conversion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConversionWarning {
+    /// 1-based line in the source text, if the construct came from one
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Output of a conversion together with everything that did not carry over
+#[derive(Debug, Clone)]
+pub struct Converted<T> {
+    pub value: T,
+    pub warnings: Vec<ConversionWarning>,
+}
+
+/// Turn an arbitrary name into a valid Trident identifier.
+/// Returns None if the name already is one.
+fn sanitize_ident(name: &str) -> Option<String> {
+    // `group` at the start of a line always opens a group block
+    if name == "group" {
+        return Some("group_".to_string());
+    }
+    let mut out: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_numeric()) {
+        out.insert(0, '_');
+    }
+    if out == name { None } else { Some(out) }
+}
+
+/// Blank lines are kept in the AST as comments with no text
+fn is_blank(c: &CommentAst) -> bool {
+    c.text.is_empty() && c.prefix.trim().is_empty()
+}
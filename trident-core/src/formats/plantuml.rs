@@ -0,0 +1,1279 @@
+//! PlantUML class diagram conversion.
+//!
+//! Import understands `class`, `abstract class`, `interface`, `enum`,
+//! `package`/`namespace`, stereotypes, `extends`/`implements` and relation
+//! arrows with cardinalities and labels. Arrows are normalised (direction
+//! hints and lengths stripped) and looked up in the arrow registry.
+//! `left to right direction` becomes `@direction: LR`.
+//!
+//! Export works on the `FileAst` so every dropped construct can be reported
+//! with the line it came from. Comments are carried over in both directions.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::{is_blank, sanitize_ident, ConversionWarning, Converted, INDENT};
+use crate::parser::{
+    arrow_from_token, format_type_params, token_from_arrow, CommentAst, FileAst, GroupAst, Ident, NodeAst, NoteAst, NoteTarget,
+    RelationAst, RelationEnd, Span, Stmt, TypeParam,
+};
+
+/// Keywords that open a package-like block and become a named group
+const PACKAGE_KEYWORDS: &[&str] = &["package", "namespace", "folder", "frame", "cloud", "database"];
+
+/// Statements that only affect PlantUML rendering
+const RENDER_DIRECTIVES: &[&str] = &[
+    "title", "skinparam", "hide", "show", "remove", "scale", "caption", "header", "footer", "set",
+    "allowmixing", "!theme", "!include", "!define", "!pragma",
+];
+
+/// Convert PlantUML class diagram source into a Trident AST.
+/// Never fails: constructs that can't be mapped are skipped with a warning.
+pub fn import_plantuml(input: &str) -> Converted<FileAst> {
+    let mut im = Importer::default();
+    for (i, raw) in input.lines().enumerate() {
+        im.line(i + 1, raw.trim());
+    }
+
+    let last_line = input.lines().count();
+    match std::mem::replace(&mut im.mode, Mode::Normal) {
+        Mode::Normal => {}
+        Mode::ClassBody(node) => {
+            im.warn(last_line, format!("unterminated body for class '{}'", node.id.0));
+            im.push(Stmt::Node(*node));
+        }
+        Mode::Note(note) => {
+            im.warn(last_line, "unterminated note at end of file");
+            im.push(Stmt::Note(*note));
+        }
+        Mode::Legend | Mode::BlockComment | Mode::Skip(_) => {
+            im.warn(last_line, "unterminated block at end of file");
+        }
+    }
+    while let Some(group) = im.stack.pop() {
+        im.warn(last_line, "unterminated package block");
+        im.push(Stmt::Group(group));
+    }
+    while matches!(im.root.last(), Some(Stmt::Comment(c)) if is_blank(c)) {
+        im.root.pop();
+    }
+
+    Converted {
+        value: FileAst { layout: None, routing: None, direction: im.direction, wrap: None, items: im.root, tokens: Default::default(), comments: Default::default() },
+        warnings: im.warnings,
+    }
+}
+
+#[derive(Default)]
+enum Mode {
+    #[default]
+    Normal,
+    /// Inside `class X {`; the node is pushed when the block closes
+    ClassBody(Box<NodeAst>),
+    /// Inside a multi-line `note ... end note`; the note is pushed at `end note`
+    Note(Box<NoteAst>),
+    /// Inside `legend ... endlegend`
+    Legend,
+    /// Inside `/' ... '/`
+    BlockComment,
+    /// Inside a `{ ... }` block of a render directive (e.g. `skinparam class {`); tracks depth
+    Skip(usize),
+}
+
+#[derive(Default)]
+struct Importer {
+    root: Vec<Stmt>,
+    /// Open package blocks, innermost last
+    stack: Vec<GroupAst>,
+    warnings: Vec<ConversionWarning>,
+    /// PlantUML name or alias -> Trident identifier
+    names: HashMap<String, String>,
+    /// Aliases of notes, so relations attaching them can be dropped
+    note_aliases: Vec<String>,
+    /// Endpoints and arrow of the last relation, for `note on link`
+    last_relation: Option<(Ident, String, Ident)>,
+    /// Relations from `extends`/`implements` waiting for the class body to close
+    deferred: Vec<Stmt>,
+    mode: Mode,
+    /// From `left to right direction` / `top to bottom direction`
+    direction: Option<String>,
+}
+
+impl Importer {
+    fn warn(&mut self, line: usize, message: impl Into<String>) {
+        self.warnings.push(ConversionWarning { line: Some(line), message: message.into() });
+    }
+
+    fn push(&mut self, stmt: Stmt) {
+        match self.stack.last_mut() {
+            Some(group) => group.items.push(stmt),
+            None => self.root.push(stmt),
+        }
+    }
+
+    fn push_comment(&mut self, text: String) {
+        let prefix = INDENT.repeat(self.stack.len());
+        self.push(Stmt::Comment(CommentAst { prefix, text }));
+    }
+
+    fn push_blank(&mut self) {
+        let items = match self.stack.last_mut() {
+            Some(group) => &mut group.items,
+            None => &mut self.root,
+        };
+        match items.last() {
+            None => {}
+            Some(Stmt::Comment(c)) if is_blank(c) => {}
+            Some(_) => items.push(Stmt::Comment(CommentAst { prefix: String::new(), text: String::new() })),
+        }
+    }
+
+    /// Find an already declared node anywhere in the tree being built
+    fn find_node(&mut self, id: &str) -> Option<&mut NodeAst> {
+        if let Mode::ClassBody(node) = &mut self.mode
+            && node.id.0 == id
+        {
+            return Some(node);
+        }
+        let scopes = std::iter::once(&mut self.root).chain(self.stack.iter_mut().map(|g| &mut g.items));
+        for items in scopes {
+            if let Some(node) = find_node_in(items, id) {
+                return Some(node);
+            }
+        }
+        None
+    }
+
+    /// Trident identifier for a PlantUML name, remembering renames
+    fn ident_for(&mut self, name: &str) -> String {
+        if let Some(id) = self.names.get(name) {
+            return id.clone();
+        }
+        let id = sanitize_ident(name).unwrap_or_else(|| name.to_string());
+        self.names.insert(name.to_string(), id.clone());
+        id
+    }
+
+    fn line(&mut self, line_no: usize, t: &str) {
+        match &mut self.mode {
+            Mode::Normal => {}
+            Mode::ClassBody(_) => return self.class_body_line(line_no, t),
+            Mode::Note(note) => {
+                if t == "end note" || t == "endnote" {
+                    let Mode::Note(note) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+                        unreachable!()
+                    };
+                    self.push(Stmt::Note(*note));
+                } else if !t.is_empty() {
+                    note.lines.push(t.to_string());
+                }
+                return;
+            }
+            Mode::Legend => {
+                if t == "endlegend" || t == "end legend" {
+                    self.mode = Mode::Normal;
+                }
+                return;
+            }
+            Mode::BlockComment => {
+                match t.strip_suffix("'/") {
+                    Some(text) => {
+                        self.mode = Mode::Normal;
+                        if !text.is_empty() {
+                            self.push_comment(format!(" {}", text.trim()));
+                        }
+                    }
+                    None => self.push_comment(format!(" {}", t)),
+                }
+                return;
+            }
+            Mode::Skip(depth) => {
+                if t.ends_with('{') {
+                    *depth += 1;
+                } else if t == "}" {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        self.mode = Mode::Normal;
+                    }
+                }
+                return;
+            }
+        }
+
+        if t.is_empty() {
+            self.push_blank();
+            return;
+        }
+        if let Some(text) = t.strip_prefix('\'') {
+            self.push_comment(text.to_string());
+            return;
+        }
+        if let Some(text) = t.strip_prefix("/'") {
+            match text.strip_suffix("'/") {
+                Some(text) => self.push_comment(format!(" {}", text.trim())),
+                None => {
+                    if !text.trim().is_empty() {
+                        self.push_comment(format!(" {}", text.trim()));
+                    }
+                    self.mode = Mode::BlockComment;
+                }
+            }
+            return;
+        }
+        if t.starts_with("@startuml") || t.starts_with("@enduml") {
+            return;
+        }
+
+        let keyword = t.split_whitespace().next().unwrap_or("");
+        match keyword {
+            "}" => match self.stack.pop() {
+                Some(group) => self.push(Stmt::Group(group)),
+                None => self.warn(line_no, "unmatched '}'"),
+            },
+            "note" => self.note(line_no, t),
+            "legend" => {
+                self.warn(line_no, "legends are not supported; dropped");
+                self.mode = Mode::Legend;
+            }
+            "together" => self.stack.push(GroupAst { id: None, pos: None, style: Default::default(), direction: None, items: Vec::new(), span: None, tokens: Default::default(), comments: Default::default() }),
+            kw if PACKAGE_KEYWORDS.contains(&kw) => self.package(line_no, t[kw.len()..].trim()),
+            _ if t == "left to right direction" => self.direction = Some("LR".to_string()),
+            _ if t == "top to bottom direction" => self.direction = Some("TB".to_string()),
+            kw if RENDER_DIRECTIVES.contains(&kw) => {
+                self.warn(line_no, format!("'{}' only affects PlantUML rendering; dropped", kw));
+                if t.ends_with('{') {
+                    self.mode = Mode::Skip(1);
+                }
+            }
+            _ => {
+                if !self.try_declaration(line_no, t) {
+                    self.relation_or_member(line_no, t);
+                }
+            }
+        }
+    }
+
+    fn package(&mut self, line_no: usize, rest: &str) {
+        let Some((name, after)) = take_name(rest) else {
+            self.warn(line_no, "package without a name; ignored");
+            return;
+        };
+        let mut after = after.trim();
+        if let Some(stereo_end) = after.strip_prefix("<<").and_then(|s| s.find(">>")) {
+            after = after[stereo_end + 4..].trim();
+        }
+        if after != "{" {
+            self.warn(line_no, "package without a '{' block; ignored");
+            return;
+        }
+        let id = match sanitize_ident(&name) {
+            Some(id) => {
+                self.warn(line_no, format!("package '{}' renamed to '{}'", name, id));
+                id
+            }
+            None => name,
+        };
+        self.stack.push(GroupAst { id: Some(Ident(id)), pos: None, style: Default::default(), direction: None, items: Vec::new(), span: None, tokens: Default::default(), comments: Default::default() });
+    }
+
+    /// `note left of A : text`, `note on link`, `note "text" as N1`, or the
+    /// header of a multi-line `note ... end note`
+    fn note(&mut self, line_no: usize, t: &str) {
+        let mut rest = t["note".len()..].trim_start();
+        let mut target = NoteTarget::None;
+        let mut text = None;
+
+        if let Some(after) = rest.strip_prefix("on link") {
+            match self.last_relation.clone() {
+                Some((from, arrow, to)) => target = NoteTarget::Relation { from, arrow, to },
+                None => self.warn(line_no, "'note on link' without a preceding relation; kept as a free note"),
+            }
+            rest = after.trim_start();
+        } else if let Some(position) = ["left", "right", "top", "bottom"].into_iter().find(|p| rest.starts_with(p)) {
+            rest = rest[position.len()..].trim_start();
+            let of_target = rest.strip_prefix("of ").and_then(|after| take_name(after.trim_start()));
+            match of_target {
+                Some((name, after)) => {
+                    target = NoteTarget::Node(Ident(self.ident_for(&name)));
+                    rest = after.trim_start();
+                }
+                None => self.warn(line_no, "note without a target; kept as a free note"),
+            }
+            self.warn(line_no, format!("note position '{}' dropped; Trident places notes itself", position));
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            match quoted.split_once('"') {
+                Some((quoted, after)) => {
+                    text = Some(quoted.to_string());
+                    rest = after.trim_start();
+                }
+                None => {
+                    self.warn(line_no, format!("unrecognised note: {}", t));
+                    return;
+                }
+            }
+        }
+
+        // `note "text" as N1` / `note as N1` introduce an alias other lines can link to
+        if let Some(after) = rest.strip_prefix("as ") {
+            let after = after.trim_start();
+            let end = after.find(|c: char| c.is_whitespace() || c == ':').unwrap_or(after.len());
+            self.note_aliases.push(after[..end].to_string());
+            rest = after[end..].trim_start();
+        }
+        if rest.starts_with('#') {
+            self.warn(line_no, "colours are not supported; dropped");
+            rest = rest.find(char::is_whitespace).map_or("", |i| rest[i..].trim_start());
+        }
+        if let Some(after) = rest.strip_prefix(':') {
+            text = Some(after.trim().to_string());
+        } else if !rest.is_empty() {
+            self.warn(line_no, format!("unexpected text in note: {}", rest));
+        }
+
+        let mut note = NoteAst {
+            target,
+            lines: Vec::new(),
+            span: None,
+            tokens: Default::default(),
+            comments: Default::default(),
+        };
+        match text {
+            // PlantUML writes line breaks inside note text as `\n`
+            Some(text) => {
+                note.lines = text.split("\\n").map(|l| l.trim().to_string()).collect();
+                self.push(Stmt::Note(note));
+            }
+            None => self.mode = Mode::Note(Box::new(note)),
+        }
+    }
+
+    /// Parse a class-like declaration. Returns false if the line isn't one.
+    fn try_declaration(&mut self, line_no: usize, t: &str) -> bool {
+        let mut words = t.splitn(2, char::is_whitespace);
+        let first = words.next().unwrap_or("");
+        let mut rest = words.next().unwrap_or("").trim_start();
+
+        let mut modifiers = Vec::new();
+        let (kind, original_kind) = match first {
+            "abstract" => {
+                modifiers.push("abstract".to_string());
+                if let Some(after) = rest.strip_prefix("class ") {
+                    rest = after.trim_start();
+                }
+                ("class", "class")
+            }
+            "class" => ("class", "class"),
+            "interface" => ("class", "interface"),
+            "enum" => ("class", "enum"),
+            "struct" => ("class", "struct"),
+            "annotation" | "entity" | "exception" | "protocol" | "metaclass" | "stereotype" => {
+                modifiers.push(first.to_string());
+                ("class", "class")
+            }
+            "circle" | "diamond" => ("node", first),
+            _ => return false,
+        };
+
+        let name_quoted = rest.starts_with('"');
+        let Some((name, after)) = take_name(rest) else {
+            return false;
+        };
+        let mut rest = after;
+
+        // Generic parameters directly after the name: `class Foo<T>`
+        let mut generic = None;
+        if rest.starts_with('<') && !rest.starts_with("<<") {
+            match matching_angle(rest) {
+                Some(end) => {
+                    generic = Some(rest[..=end].to_string());
+                    rest = &rest[end + 1..];
+                }
+                None => self.warn(line_no, "unterminated generic parameters; ignored"),
+            }
+        }
+
+        let mut alias: Option<(String, bool)> = None;
+        let mut parents: Vec<(String, &'static str)> = Vec::new();
+        let mut opens_body = false;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(after) = rest.strip_prefix("as ") {
+                let after = after.trim_start();
+                match take_name(after) {
+                    Some((a, tail)) => {
+                        alias = Some((a, after.starts_with('"')));
+                        rest = tail;
+                    }
+                    None => break,
+                }
+            } else if let Some(after) = rest.strip_prefix("<<") {
+                let Some(end) = after.find(">>") else {
+                    self.warn(line_no, "unterminated stereotype; ignored");
+                    break;
+                };
+                // `<< (S,#FF7700) Singleton >>` carries a spot before the name
+                let stereo = after[..end].rsplit(')').next().unwrap_or("").trim();
+                if is_plain_word(stereo) {
+                    let stereo = stereo.to_ascii_lowercase();
+                    if !modifiers.contains(&stereo) {
+                        modifiers.push(stereo);
+                    }
+                } else {
+                    self.warn(line_no, format!("stereotype '<<{}>>' can't be expressed as a modifier; dropped", stereo));
+                }
+                rest = &after[end + 2..];
+            } else if rest.starts_with('#') {
+                self.warn(line_no, "colours are not supported; dropped");
+                rest = rest.find(char::is_whitespace).map_or("", |i| &rest[i..]);
+            } else if let Some((list, arrow)) = rest
+                .strip_prefix("extends ")
+                .map(|l| (l, "extends_right"))
+                .or_else(|| rest.strip_prefix("implements ").map(|l| (l, "implements_right")))
+            {
+                let end = list.find(['{', '<', '#']).unwrap_or(list.len());
+                let end = [" implements ", " extends "].iter().filter_map(|kw| list[..end].find(kw)).min().unwrap_or(end);
+                for parent in list[..end].split(',') {
+                    let parent = parent.trim().trim_matches('"');
+                    if !parent.is_empty() {
+                        parents.push((parent.to_string(), arrow));
+                    }
+                }
+                rest = &list[end..];
+            } else if rest == "{}" || rest == "{ }" {
+                break;
+            } else if rest == "{" {
+                opens_body = true;
+                break;
+            } else {
+                self.warn(line_no, format!("unexpected text in declaration of '{}': {}", name, rest));
+                break;
+            }
+        }
+
+        // `class "Long Name" as LN` and `class LN as "Long Name"` both give id LN
+        let (id_name, display) = match alias {
+            Some((alias, true)) if !name_quoted => (name.clone(), Some(alias)),
+            Some((alias, _)) => (alias, Some(name.clone())),
+            None => (name.clone(), None),
+        };
+        let id = self.ident_for(&id_name);
+        self.names.insert(name.clone(), id.clone());
+        let label = display.or_else(|| (id != id_name).then(|| id_name.clone()));
+        let type_params = match generic.map(|g| TypeParam::parse_list(&g[1..g.len() - 1])) {
+            Some(Ok(params)) => Some(params),
+            Some(Err(msg)) => {
+                self.warn(line_no, format!("generic parameters of '{}' dropped: {}", id_name, msg));
+                None
+            }
+            None => None,
+        };
+
+        let node = match self.find_node(&id).map(|n| n.clone()) {
+            Some(existing) => {
+                self.remove_node(&id);
+                existing
+            }
+            None => NodeAst {
+                kind: String::new(),
+                original_kind: String::new(),
+                modifiers: Vec::new(),
+                id: Ident(id.clone()),
+                label: None,
+                type_params: Vec::new(),
+                classes: Vec::new(),
+                pos: None,
+                width: None,
+                height: None,
+                style: Default::default(),
+                body_lines: Vec::new(),
+                span: None,
+                tokens: Default::default(),
+                comments: Default::default(),
+            },
+        };
+        let mut node = NodeAst {
+            kind: kind.to_string(),
+            original_kind: original_kind.to_string(),
+            label: label.or(node.label),
+            type_params: type_params.unwrap_or(node.type_params),
+            ..node
+        };
+        for m in modifiers {
+            if !node.modifiers.contains(&m) {
+                node.modifiers.push(m);
+            }
+        }
+
+        let relations: Vec<Stmt> = parents
+            .into_iter()
+            .map(|(parent, arrow)| {
+                Stmt::Relation(RelationAst {
+                    from: Ident(id.clone()),
+                    arrow: arrow.to_string(),
+                    to: Ident(self.ident_for(&parent)),
+                    label: None,
+                    from_end: RelationEnd::default(),
+                    to_end: RelationEnd::default(),
+                    style: Default::default(),
+                    span: None,
+                    tokens: Default::default(),
+                    comments: Default::default(),
+                })
+            })
+            .collect();
+
+        if opens_body {
+            self.mode = Mode::ClassBody(Box::new(node));
+        } else {
+            self.push(Stmt::Node(node));
+        }
+        // Relations from `extends` are emitted right after the declaration
+        // (or after its body, see class_body_line)
+        for rel in relations {
+            self.pending_relations(rel);
+        }
+        true
+    }
+
+    /// Queue a relation; while a class body is open it waits until the body closes
+    fn pending_relations(&mut self, rel: Stmt) {
+        match &self.mode {
+            Mode::ClassBody(_) => self.deferred.push(rel),
+            _ => self.push(rel),
+        }
+    }
+
+    fn remove_node(&mut self, id: &str) {
+        let scopes = std::iter::once(&mut self.root).chain(self.stack.iter_mut().map(|g| &mut g.items));
+        for items in scopes {
+            if remove_node_in(items, id) {
+                return;
+            }
+        }
+    }
+
+    fn class_body_line(&mut self, line_no: usize, t: &str) {
+        let Mode::ClassBody(node) = &mut self.mode else {
+            return;
+        };
+        if t == "}" {
+            let Mode::ClassBody(node) = std::mem::replace(&mut self.mode, Mode::Normal) else {
+                unreachable!()
+            };
+            self.push(Stmt::Node(*node));
+            for rel in std::mem::take(&mut self.deferred) {
+                self.push(rel);
+            }
+            return;
+        }
+        if t.is_empty() || t.starts_with('\'') {
+            return;
+        }
+        if let Some(separator) = separator_title(t) {
+            node.body_lines.push("---".to_string());
+            if !separator.is_empty() {
+                self.warn(line_no, format!("separator title '{}' dropped", separator));
+            }
+            return;
+        }
+        node.body_lines.push(t.to_string());
+    }
+
+    /// `A "1" *-- "many" B : label >` or `A : +member`
+    fn relation_or_member(&mut self, line_no: usize, t: &str) {
+        let (head, label) = split_label(t);
+
+        let Some(rel) = split_relation(head) else {
+            match (take_name(head), label) {
+                (Some((name, rest)), Some(member)) if rest.trim().is_empty() => {
+                    let id = self.ident_for(&name);
+                    let member = member.to_string();
+                    match self.find_node(&id) {
+                        Some(node) => node.body_lines.push(member),
+                        None => {
+                            let mut node = plain_class(&id);
+                            node.body_lines.push(member);
+                            self.push(Stmt::Node(node));
+                        }
+                    }
+                }
+                _ => self.warn(line_no, format!("unrecognised line: {}", t)),
+            }
+            return;
+        };
+
+        if self.note_aliases.iter().any(|n| *n == rel.from || *n == rel.to) {
+            self.warn(line_no, "link to a note dropped");
+            return;
+        }
+        if rel.hidden {
+            self.warn(line_no, "hidden layout relation dropped");
+            return;
+        }
+        if rel.hints {
+            self.warn(line_no, "arrow direction and style hints are not supported; dropped");
+        }
+
+        let arrow = match arrow_from_token(&rel.token) {
+            Some(arrow) => arrow,
+            None => {
+                let fallback = if rel.dashed { "dotted" } else { "line" };
+                self.warn(
+                    line_no,
+                    format!("arrow '{}' has no Trident equivalent; imported as a plain {}", rel.raw, fallback),
+                );
+                fallback
+            }
+        };
+
+        let from = self.ident_for(&rel.from);
+        let to = self.ident_for(&rel.to);
+        let label = label
+            .map(|l| l.trim_start_matches('<').trim_end_matches('>').trim())
+            .filter(|l| !l.is_empty())
+            .map(str::to_string);
+        self.last_relation = Some((Ident(from.clone()), arrow.to_string(), Ident(to.clone())));
+        self.push(Stmt::Relation(RelationAst {
+            from: Ident(from),
+            arrow: arrow.to_string(),
+            to: Ident(to),
+            label,
+            from_end: rel.from_card.as_deref().map(RelationEnd::parse).unwrap_or_default(),
+            to_end: rel.to_card.as_deref().map(RelationEnd::parse).unwrap_or_default(),
+            style: Default::default(),
+            span: Some(Span { start_line: line_no, end_line: line_no }),
+            tokens: Default::default(),
+            comments: Default::default(),
+        }));
+    }
+}
+
+fn plain_class(id: &str) -> NodeAst {
+    NodeAst {
+        kind: "class".to_string(),
+        original_kind: "class".to_string(),
+        modifiers: Vec::new(),
+        id: Ident(id.to_string()),
+        label: None,
+        type_params: Vec::new(),
+        classes: Vec::new(),
+        pos: None,
+        width: None,
+        height: None,
+        style: Default::default(),
+        body_lines: Vec::new(),
+        span: None,
+        tokens: Default::default(),
+        comments: Default::default(),
+    }
+}
+
+fn find_node_in<'a>(items: &'a mut [Stmt], id: &str) -> Option<&'a mut NodeAst> {
+    items.iter_mut().find_map(|s| match s {
+        Stmt::Node(n) if n.id.0 == id => Some(n),
+        _ => None,
+    })
+}
+
+fn remove_node_in(items: &mut Vec<Stmt>, id: &str) -> bool {
+    match items.iter().position(|s| matches!(s, Stmt::Node(n) if n.id.0 == id)) {
+        Some(i) => {
+            items.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+fn is_plain_word(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Take a leading quoted or bare name (bare names may be dotted: `com.acme.Foo`)
+fn take_name(s: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = s.strip_prefix('"') {
+        let (name, rest) = quoted.split_once('"')?;
+        return Some((name.to_string(), rest));
+    }
+    let end = s
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '$' | ':')))
+        .unwrap_or(s.len());
+    // A trailing ':' belongs to a `Name : member` line, not the name
+    let name = s[..end].trim_end_matches(['.', ':']);
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.to_string(), &s[name.len()..]))
+}
+
+/// Index of the '>' closing the '<' at the start of `s`
+fn matching_angle(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `--`, `..`, `==`, `__` and titled forms like `-- Accessors --`.
+/// Returns the title (possibly empty) if the line is a separator.
+fn separator_title(t: &str) -> Option<&str> {
+    for sep in ["--", "..", "==", "__"] {
+        if let Some(inner) = t.strip_prefix(sep) {
+            if inner.is_empty() {
+                return Some("");
+            }
+            if let Some(title) = inner.strip_suffix(sep) {
+                return Some(title.trim_matches(sep.chars().next().unwrap()).trim());
+            }
+        }
+    }
+    None
+}
+
+/// Split off the `: label` part, ignoring colons inside quotes
+fn split_label(t: &str) -> (&str, Option<&str>) {
+    let mut in_quotes = false;
+    for (i, c) in t.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ':' if !in_quotes => return (t[..i].trim(), Some(t[i + 1..].trim())),
+            _ => {}
+        }
+    }
+    (t.trim(), None)
+}
+
+struct PlantRelation {
+    from: String,
+    from_card: Option<String>,
+    /// Normalised Trident token (e.g. `-->`, `<|..`)
+    token: String,
+    /// The arrow as written
+    raw: String,
+    to_card: Option<String>,
+    to: String,
+    dashed: bool,
+    /// Direction words (`-up->`) or `[#color]` were present
+    hints: bool,
+    hidden: bool,
+}
+
+/// Split `A "1" *-up- "many" B` into its parts. Returns None if there is no arrow.
+fn split_relation(head: &str) -> Option<PlantRelation> {
+    let chars: Vec<(usize, char)> = head.char_indices().collect();
+    let word = |i: usize| chars.get(i).is_some_and(|&(_, c)| c.is_alphanumeric() || c == '_');
+    let boundary = |i: Option<usize>| i.is_none_or(|i| chars.get(i).is_none_or(|&(_, c)| c.is_whitespace() || c == '"'));
+
+    // The arrow body starts at a '-' or '.' that isn't inside a dotted name
+    let mut in_quotes = false;
+    let mut start = None;
+    for (k, &(_, c)) in chars.iter().enumerate() {
+        let inside_name = k > 0 && word(k - 1) && word(k + 1);
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '-' | '.' if !in_quotes && !inside_name => {
+                start = Some(k);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let body_start = start?;
+
+    // Body: dashes/dots, bracketed styles and direction words between them
+    let mut end = body_start;
+    let mut hints = false;
+    let mut hidden = false;
+    while end < chars.len() {
+        match chars[end].1 {
+            '-' | '.' => end += 1,
+            '[' => {
+                let close = chars[end..].iter().position(|&(_, c)| c == ']')? + end;
+                let style: String = chars[end + 1..close].iter().map(|&(_, c)| c).collect();
+                if style.contains("hidden") {
+                    hidden = true;
+                } else {
+                    hints = true;
+                }
+                end = close + 1;
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut k = end;
+                while k < chars.len() && chars[k].1.is_ascii_alphabetic() {
+                    k += 1;
+                }
+                let direction: String = chars[end..k].iter().map(|&(_, c)| c).collect();
+                let is_direction =
+                    matches!(direction.as_str(), "up" | "down" | "left" | "right" | "u" | "d" | "l" | "r");
+                if is_direction && chars.get(k).is_some_and(|&(_, c)| c == '-' || c == '.') {
+                    hints = true;
+                    end = k;
+                } else {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    let body: String = chars[body_start..end].iter().map(|&(_, c)| c).collect();
+    let dashed = body.contains('.');
+
+    // Heads; 'o' and 'x' only count when they aren't part of a name
+    let mut left = body_start;
+    while left > 0 {
+        match chars[left - 1].1 {
+            '<' | '|' | '*' | '+' | '#' | '{' | '^' | '(' | ')' => left -= 1,
+            'o' | 'x' if boundary(left.checked_sub(2)) => left -= 1,
+            _ => break,
+        }
+    }
+    let mut right = end;
+    while right < chars.len() {
+        match chars[right].1 {
+            '>' | '|' | '*' | '+' | '#' | '}' | '^' | '(' | ')' => right += 1,
+            'o' | 'x' if boundary(Some(right + 1)) => right += 1,
+            _ => break,
+        }
+    }
+
+    let byte = |k: usize| chars.get(k).map_or(head.len(), |&(i, _)| i);
+    let left_head = &head[byte(left)..byte(body_start)];
+    let right_head = &head[byte(end)..byte(right)];
+    let line = if dashed { ".." } else { "--" };
+    let token = match (left_head, right_head, dashed) {
+        ("", "", false) => "---".to_string(),
+        _ => format!("{}{}{}", left_head, line, right_head),
+    };
+
+    let (from, from_card) = take_trailing_card(head[..byte(left)].trim());
+    let (to, to_card) = take_leading_card(head[byte(right)..].trim());
+    let from = take_name(from).filter(|(_, rest)| rest.trim().is_empty())?.0;
+    let to = take_name(to).filter(|(_, rest)| rest.trim().is_empty())?.0;
+
+    Some(PlantRelation {
+        from,
+        from_card: from_card.map(str::to_string),
+        token,
+        raw: head[byte(left)..byte(right)].to_string(),
+        to_card: to_card.map(str::to_string),
+        to,
+        dashed,
+        hints,
+        hidden,
+    })
+}
+
+/// `A "1"` -> (`A`, Some(`1`))
+fn take_trailing_card(s: &str) -> (&str, Option<&str>) {
+    if let Some(inner) = s.strip_suffix('"')
+        && let Some(open) = inner.rfind('"')
+        && !inner[..open].trim().is_empty()
+    {
+        return (inner[..open].trim(), Some(&inner[open + 1..]));
+    }
+    (s, None)
+}
+
+/// `"*" B` -> (`B`, Some(`*`))
+fn take_leading_card(s: &str) -> (&str, Option<&str>) {
+    if let Some(inner) = s.strip_prefix('"')
+        && let Some(close) = inner.find('"')
+        && !inner[close + 1..].trim().is_empty()
+    {
+        return (inner[close + 1..].trim(), Some(&inner[..close]));
+    }
+    (s, None)
+}
+
+// ============================================================================
+// Export
+// ============================================================================
+
+/// Convert a Trident AST into a PlantUML class diagram.
+/// Anything PlantUML can't express is dropped and listed in the warnings.
+pub fn export_plantuml(ast: &FileAst) -> Converted<String> {
+    let mut ex = Exporter { out: String::from("@startuml\n"), warnings: Vec::new(), last_relation: None, free_notes: 0 };
+    if ast.layout.is_some() || ast.routing.is_some() || ast.wrap.is_some() {
+        ex.warn(None, "@layout/@routing/@wrap directives dropped");
+    }
+    match ast.direction.as_deref() {
+        None => {}
+        Some("LR") => ex.out.push_str("left to right direction\n"),
+        Some("TB") => ex.out.push_str("top to bottom direction\n"),
+        Some(other) => ex.warn(None, format!("@direction: {} dropped", other)),
+    }
+    for stmt in &ast.items {
+        ex.stmt(stmt, 0);
+    }
+    ex.out.push_str("@enduml\n");
+    Converted { value: ex.out, warnings: ex.warnings }
+}
+
+struct Exporter {
+    out: String,
+    warnings: Vec<ConversionWarning>,
+    /// Endpoints and arrow of the last relation written, which `note on link` attaches to
+    last_relation: Option<(Ident, String, Ident)>,
+    /// Free-standing notes written so far, for their `N<k>` aliases
+    free_notes: usize,
+}
+
+impl Exporter {
+    fn warn(&mut self, span: Option<Span>, message: impl Into<String>) {
+        self.warnings.push(ConversionWarning { line: span.map(|s| s.start_line), message: message.into() });
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize) {
+        let ind = INDENT.repeat(depth);
+        match stmt {
+            Stmt::Comment(c) if is_blank(c) => self.out.push('\n'),
+            Stmt::Comment(c) => {
+                let _ = writeln!(self.out, "{}'{}", ind, c.text);
+            }
+            Stmt::Group(g) => {
+                if g.pos.is_some() {
+                    self.warn(g.span, "@pos of group dropped");
+                }
+                if !g.style.is_empty() {
+                    self.warn(g.span, "style of group dropped");
+                }
+                if g.direction.is_some() {
+                    self.warn(g.span, "@direction of group dropped");
+                }
+                match &g.id {
+                    Some(id) => {
+                        let _ = writeln!(self.out, "{}package {} {{", ind, id.0);
+                    }
+                    None => {
+                        let _ = writeln!(self.out, "{}together {{", ind);
+                    }
+                }
+                for item in &g.items {
+                    self.stmt(item, depth + 1);
+                }
+                let _ = writeln!(self.out, "{}}}", ind);
+            }
+            Stmt::Node(n) => self.node(n, depth),
+            Stmt::Note(n) => self.note(n, depth),
+            Stmt::Style(s) => self.warn(s.span, format!("style '{}' dropped", s.selector)),
+            Stmt::Relation(r) => {
+                self.last_relation = Some((r.from.clone(), r.arrow.clone(), r.to.clone()));
+                let token = match token_from_arrow(&r.arrow).unwrap_or("-->") {
+                    "---" => "--",
+                    "--)" | "(--" => {
+                        self.warn(r.span, "rounded arrow exported as a plain association");
+                        if r.arrow.ends_with("_left") { "<--" } else { "-->" }
+                    }
+                    token => token,
+                };
+                if !r.style.is_empty() {
+                    self.warn(r.span, format!("style of relation '{} {} {}' dropped", r.from.0, token, r.to.0));
+                }
+                let _ = write!(self.out, "{}{}", ind, r.from.0);
+                if !r.from_end.is_empty() {
+                    let _ = write!(self.out, " \"{}\"", plantuml_text(&r.from_end.text()));
+                }
+                let _ = write!(self.out, " {} ", token);
+                if !r.to_end.is_empty() {
+                    let _ = write!(self.out, "\"{}\" ", plantuml_text(&r.to_end.text()));
+                }
+                self.out.push_str(&r.to.0);
+                if let Some(label) = &r.label {
+                    let _ = write!(self.out, " : {}", label);
+                }
+                self.out.push('\n');
+            }
+        }
+    }
+
+    fn note(&mut self, n: &NoteAst, depth: usize) {
+        let ind = INDENT.repeat(depth);
+        let header = match &n.target {
+            NoteTarget::None => {
+                self.free_notes += 1;
+                let alias = format!("N{}", self.free_notes);
+                if n.lines.len() == 1 {
+                    let _ = writeln!(self.out, "{}note \"{}\" as {}", ind, plantuml_text(&n.lines[0]), alias);
+                    return;
+                }
+                format!("note as {}", alias)
+            }
+            NoteTarget::Node(id) => format!("note right of {}", id.0),
+            NoteTarget::Relation { from, arrow, to } => {
+                // PlantUML can only attach a note to the relation written just before it
+                if self.last_relation.as_ref() != Some(&(from.clone(), arrow.clone(), to.clone())) {
+                    self.warn(n.span, format!("note on relation '{} {} {}' dropped", from.0, token_from_arrow(arrow).unwrap_or("-->"), to.0));
+                    return;
+                }
+                "note on link".to_string()
+            }
+        };
+        if n.lines.len() == 1 {
+            let _ = writeln!(self.out, "{}{} : {}", ind, header, n.lines[0]);
+            return;
+        }
+        let _ = writeln!(self.out, "{}{}", ind, header);
+        for line in &n.lines {
+            let _ = writeln!(self.out, "{}{}{}", ind, INDENT, line);
+        }
+        let _ = writeln!(self.out, "{}end note", ind);
+    }
+
+    fn node(&mut self, n: &NodeAst, depth: usize) {
+        let ind = INDENT.repeat(depth);
+        if n.pos.is_some() || n.width.is_some() || n.height.is_some() {
+            self.warn(n.span, format!("layout hints (@pos/@width/@height) of '{}' dropped", n.id.0));
+        }
+        if !n.style.is_empty() || !n.classes.is_empty() {
+            self.warn(n.span, format!("style of '{}' dropped", n.id.0));
+        }
+
+        let mut stereotypes: Vec<&str> = Vec::new();
+        let mut abstract_ = false;
+        for m in &n.modifiers {
+            if m == "abstract" {
+                abstract_ = true;
+            } else {
+                stereotypes.push(m);
+            }
+        }
+        let keyword = match n.original_kind.as_str() {
+            "class" | "interface" | "enum" | "struct" | "circle" | "diamond" => n.original_kind.as_str(),
+            "record" | "trait" | "object" => {
+                stereotypes.push(&n.original_kind);
+                "class"
+            }
+            other => {
+                self.warn(n.span, format!("'{}' node '{}' exported as a class", other, n.id.0));
+                "class"
+            }
+        };
+
+        let _ = write!(self.out, "{}", ind);
+        if abstract_ {
+            self.out.push_str("abstract ");
+        }
+        match &n.label {
+            Some(label) => {
+                let _ = write!(self.out, "{} \"{}\" as {}", keyword, plantuml_text(label), n.id.0);
+            }
+            None => {
+                let _ = write!(self.out, "{} {}", keyword, n.id.0);
+            }
+        }
+        if !n.type_params.is_empty() {
+            let _ = write!(self.out, "<{}>", format_type_params(&n.type_params));
+        }
+        for s in stereotypes {
+            let _ = write!(self.out, " <<{}>>", s);
+        }
+        if n.body_lines.is_empty() {
+            self.out.push('\n');
+            return;
+        }
+        if keyword == "circle" || keyword == "diamond" {
+            self.warn(n.span, format!("body of {} '{}' dropped", keyword, n.id.0));
+            self.out.push('\n');
+            return;
+        }
+        self.out.push_str(" {\n");
+        for line in &n.body_lines {
+            let line = if line.trim().chars().all(|c| c == '-') { "--" } else { line.as_str() };
+            let _ = writeln!(self.out, "{}{}{}", ind, INDENT, line);
+        }
+        let _ = writeln!(self.out, "{}}}", ind);
+    }
+}
+
+/// Text for inside a PlantUML string, which has no escape for '"'; the
+/// character entity renders the same
+fn plantuml_text(s: &str) -> String {
+    s.replace('"', "&#34;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, emit_file, parse_file};
+
+    fn import(src: &str) -> (String, Vec<ConversionWarning>) {
+        let converted = import_plantuml(src);
+        (emit_file(&converted.value), converted.warnings)
+    }
+
+    #[test]
+    fn test_import_declarations() {
+        let (out, warnings) = import(
+            "@startuml\n\
+             ' domain model\n\
+             abstract class Shape <<Entity>> {\n\
+             \x20 - id : int\n\
+             \x20 --\n\
+             \x20 + {abstract} area() : double\n\
+             }\n\
+             interface Drawable\n\
+             enum Color {\n\
+             \x20 RED\n\
+             }\n\
+             class \"Circle Shape\" as Circle extends Shape implements Drawable\n\
+             class Box<T>\n\
+             @enduml\n",
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(
+            out,
+            "%% domain model\n\
+             abstract entity class Shape {\n    - id : int\n    ---\n    + {abstract} area() : double\n}\n\
+             interface Drawable\n\
+             enum Color {\n    RED\n}\n\
+             class Circle \"Circle Shape\"\n\
+             Circle --|> Shape\n\
+             Circle ..|> Drawable\n\
+             class Box<T>\n"
+        );
+        compile(&parse_file(&out).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_import_relations_map_to_arrow_registry() {
+        let (out, warnings) = import(
+            "@startuml\n\
+             Animal <|-- Duck\n\
+             Car \"1\" *-- \"4\" Wheel : has >\n\
+             Pond o-- Duck\n\
+             A ..|> B\n\
+             A -- B\n\
+             A .. B\n\
+             A -> B\n\
+             A -up-> B\n\
+             A +-- B\n\
+             @enduml\n",
+        );
+        let ast = parse_file(&out).unwrap();
+        let arrows: Vec<&str> = ast.items.iter().filter_map(|s| match s {
+            Stmt::Relation(r) => Some(r.arrow.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(
+            arrows,
+            ["extends_left", "compose_right", "aggregate_right", "implements_right", "line", "dotted",
+             "assoc_right", "assoc_right", "line"]
+        );
+        assert!(out.contains("Car \"1\" *-- \"4\" Wheel : has\n"));
+        let lines: Vec<Option<usize>> = warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, [Some(9), Some(10)]);
+    }
+
+    #[test]
+    fn test_import_packages_and_notes() {
+        let (out, warnings) = import(
+            "@startuml\n\
+             skinparam class {\n\
+             \x20 BackgroundColor White\n\
+             }\n\
+             package \"com.acme\" {\n\
+             \x20 class Order\n\
+             \x20 Order : +total() : int\n\
+             }\n\
+             note left of Order\n\
+             \x20 Orders are immutable\n\
+             end note\n\
+             note \"shared\\nby all\" as N1\n\
+             N1 .. Order\n\
+             Order --> Item\n\
+             note on link : one per line\n\
+             @enduml\n",
+        );
+        assert_eq!(
+            out,
+            "group com_acme {\n    class Order {\n        +total() : int\n    }\n}\n\
+             note for Order \"Orders are immutable\"\n\
+             note {\n    shared\n    by all\n}\n\
+             Order --> Item\n\
+             note on Order --> Item \"one per line\"\n"
+        );
+        let lines: Vec<Option<usize>> = warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, [Some(2), Some(5), Some(9), Some(13)]);
+        compile(&parse_file(&out).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_export_notes() {
+        let src = "class A\nclass B\nA --> B\nnote on A --> B \"why\"\nnote for A {\n    first\n    second\n}\nB ..> A\nnote on A --> B \"late\"\nnote \"legend\"\n";
+        let converted = export_plantuml(&parse_file(src).unwrap());
+        assert_eq!(
+            converted.value,
+            "@startuml\nclass A\nclass B\nA --> B\nnote on link : why\n\
+             note right of A\n    first\n    second\nend note\n\
+             B ..> A\n\
+             note \"legend\" as N1\n@enduml\n"
+        );
+        let lines: Vec<Option<usize>> = converted.warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, [Some(10)]);
+    }
+
+    #[test]
+    fn test_export_escapes_quotes_in_strings() {
+        let src = "class A \"say \\\"hi\\\"\"\nclass B\nA \"1 \\\"x\\\"\" --> \"*\" B\nnote \"a \\\"b\\\"\"\n";
+        let converted = export_plantuml(&parse_file(src).unwrap());
+        assert_eq!(
+            converted.value,
+            "@startuml\nclass \"say &#34;hi&#34;\" as A\nclass B\nA \"1 &#34;x&#34;\" --> \"*\" B\nnote \"a &#34;b&#34;\" as N1\n@enduml\n"
+        );
+    }
+
+    #[test]
+    fn test_export_with_line_numbers() {
+        let src = "%% shapes\n\
+                   group Model {\n\
+                   \x20   abstract class Shape \"Any shape\" {\n\
+                   \x20       +area()\n\
+                   \x20       ---\n\
+                   \x20   }\n\
+                   \x20   circle Dot {\n\
+                   \x20       @pos: (1, 2)\n\
+                   \x20       @fill: #fee\n\
+                   \x20   }\n\
+                   }\n\
+                   Dot --|> Shape : is a { @stroke: red }\n\
+                   Dot --) Shape\n";
+        let converted = export_plantuml(&parse_file(src).unwrap());
+        assert_eq!(
+            converted.value,
+            "@startuml\n\
+             ' shapes\n\
+             package Model {\n\
+             \x20   abstract class \"Any shape\" as Shape {\n        +area()\n        --\n    }\n\
+             \x20   circle Dot\n\
+             }\n\
+             Dot --|> Shape : is a\n\
+             Dot --> Shape\n\
+             @enduml\n"
+        );
+        let lines: Vec<Option<usize>> = converted.warnings.iter().map(|w| w.line).collect();
+        assert_eq!(lines, [Some(7), Some(7), Some(12), Some(13)]);
+    }
+
+    #[test]
+    fn test_export_then_import_roundtrips() {
+        let src = "@direction: LR\ninterface Repo<T, ID extends Key> {\n    +find(id) : T\n}\nclass SqlRepo \"SQL repo\"\nSqlRepo ..|> Repo : implements\n";
+        let converted = export_plantuml(&parse_file(src).unwrap());
+        assert!(converted.warnings.is_empty(), "{:?}", converted.warnings);
+        let (out, warnings) = import(&converted.value);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(out, src);
+    }
+}
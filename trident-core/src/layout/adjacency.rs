@@ -162,8 +162,11 @@ mod tests {
             groups: vec![Group {
                 gid: GroupId(0),
                 id: None,
+                path: None,
                 parent: None,
                 pos: None,
+                style: Default::default(),
+                direction: None,
                 children_groups: vec![],
                 children_nodes: vec![NodeId(0), NodeId(1), NodeId(2)],
                 order: 0,
@@ -174,12 +177,16 @@ mod tests {
                     kind: "class".to_string(),
                     modifiers: vec![],
                     id: Ident("A".to_string()),
+                    path: Ident("A".to_string()),
                     label: None,
+                    type_params: vec![],
                     group: GroupId(0),
                     pos: None,
                     width: None,
                     height: None,
+                    style: Default::default(),
                     body_lines: vec![],
+                    members: vec![],
                     explicit: true,
                     order: 0,
                 },
@@ -188,12 +195,16 @@ mod tests {
                     kind: "class".to_string(),
                     modifiers: vec![],
                     id: Ident("B".to_string()),
+                    path: Ident("B".to_string()),
                     label: None,
+                    type_params: vec![],
                     group: GroupId(0),
                     pos: None,
                     width: None,
                     height: None,
+                    style: Default::default(),
                     body_lines: vec![],
+                    members: vec![],
                     explicit: true,
                     order: 1,
                 },
@@ -202,20 +213,26 @@ mod tests {
                     kind: "class".to_string(),
                     modifiers: vec![],
                     id: Ident("C".to_string()),
+                    path: Ident("C".to_string()),
                     label: None,
+                    type_params: vec![],
                     group: GroupId(0),
                     pos: None,
                     width: None,
                     height: None,
+                    style: Default::default(),
                     body_lines: vec![],
+                    members: vec![],
                     explicit: true,
                     order: 2,
                 },
             ],
             edges: vec![
-                Edge { from: NodeId(0), to: NodeId(1), arrow: "line".to_string(), label: None, order: 3 },
-                Edge { from: NodeId(1), to: NodeId(2), arrow: "line".to_string(), label: None, order: 4 },
+                Edge { from: NodeId(0), to: NodeId(1), arrow: "line".to_string(), label: None, from_end: Default::default(), to_end: Default::default(), style: Default::default(), order: 3 },
+                Edge { from: NodeId(1), to: NodeId(2), arrow: "line".to_string(), label: None, from_end: Default::default(), to_end: Default::default(), style: Default::default(), order: 4 },
             ],
+            group_edges: vec![],
+            notes: vec![],
         }
     }
 
@@ -0,0 +1,397 @@
+//! Force-directed layout algorithm.
+//!
+//! Suited to dense association networks without a clear hierarchy. Each group
+//! is laid out on its own, children first, so a child group moves as one
+//! rectangle among its siblings and every child stays inside its group.
+//!
+//! Per group:
+//! 1. Items (child nodes and child groups) start on a sunflower spiral, so the
+//!    result is the same on every run
+//! 2. Fruchterman–Reingold: every pair of items repels, relations pull their
+//!    ends together and a weak pull towards the middle keeps unrelated parts
+//!    close. Ideal distances grow with item sizes, and the step size cools
+//!    down over the iterations. `@pos` items are pinned: they push and pull
+//!    but don't move
+//! 3. Overlaps left over are pushed apart along the axis where they overlap
+//!    least, then anything still overlapping moves down until it is free
+//!
+//! `@direction` has no effect here.
+
+use std::collections::{BTreeMap, HashMap};
+use crate::parser::{PointI, Diagram, GroupId, NodeId};
+use crate::layout::{RectI, SizeI, LayoutConfig, LayoutResult, LayoutStrategy};
+use crate::layout::get_node_size;
+use super::{child_item_of_group, child_item_of_node, layout_per_group};
+
+/// Simulation steps per group
+const ITERATIONS: usize = 300;
+
+/// Strength of the pull towards the middle of the group
+const GRAVITY: f64 = 0.05;
+
+/// Passes of pairwise overlap removal before falling back to moving items down
+const OVERLAP_PASSES: usize = 100;
+
+/// Force-directed layout implementation.
+/// Related nodes end up close together without being put into ranks.
+pub struct ForceLayout;
+
+impl LayoutStrategy for ForceLayout {
+    fn layout(&self, diagram: &Diagram, cfg: &LayoutConfig) -> LayoutResult {
+        layout_force(diagram, cfg)
+    }
+}
+
+/// Layout nodes with a force simulation per group.
+pub fn layout_force(diagram: &Diagram, cfg: &LayoutConfig) -> LayoutResult {
+    layout_per_group(diagram, cfg, |gid, group_local_pos, node_local_pos, group_local_bounds| {
+        layout_group_children_force(diagram, gid, cfg, group_local_pos, node_local_pos, group_local_bounds);
+    })
+}
+
+/// A child node or child group being simulated, by its center
+struct Item {
+    center: (f64, f64),
+    size: SizeI,
+    pinned: bool,
+}
+
+impl Item {
+    /// Radius of the circle around the item, for ideal distances
+    fn radius(&self) -> f64 {
+        (self.size.w as f64).hypot(self.size.h as f64) / 2.0
+    }
+}
+
+/// Layout children of a group with a force simulation.
+/// Fixed nodes and groups keep their position; everything else moves around them.
+fn layout_group_children_force(
+    diagram: &Diagram,
+    gid: GroupId,
+    cfg: &LayoutConfig,
+    group_local_pos: &mut HashMap<GroupId, PointI>,
+    node_local_pos: &mut HashMap<NodeId, PointI>,
+    group_local_bounds: &HashMap<GroupId, RectI>,
+) {
+    let g = &diagram.groups[gid.0];
+    let group_bounds = |cgid: GroupId| {
+        group_local_bounds.get(&cgid).copied().unwrap_or(RectI {
+            x: 0, y: 0, w: cfg.min_group_size.w, h: cfg.min_group_size.h,
+        })
+    };
+
+    // 1. Items: child nodes first, then child groups (a group's box is its position plus its bounds offset)
+    let mut items: Vec<Item> = Vec::new();
+    for &nid in &g.children_nodes {
+        let node = &diagram.nodes[nid.0];
+        let size = get_node_size(node, cfg);
+        let p = node.pos.unwrap_or(PointI { x: 0, y: 0 });
+        items.push(Item {
+            center: (p.x as f64 + size.w as f64 / 2.0, p.y as f64 + size.h as f64 / 2.0),
+            size,
+            pinned: node.pos.is_some(),
+        });
+    }
+    for &cgid in &g.children_groups {
+        let lb = group_bounds(cgid);
+        let p = diagram.groups[cgid.0].pos.unwrap_or(PointI { x: 0, y: 0 });
+        items.push(Item {
+            center: ((p.x + lb.x) as f64 + lb.w as f64 / 2.0, (p.y + lb.y) as f64 + lb.h as f64 / 2.0),
+            size: SizeI { w: lb.w, h: lb.h },
+            pinned: diagram.groups[cgid.0].pos.is_some(),
+        });
+    }
+    if items.is_empty() {
+        return;
+    }
+
+    // 2. Relations between items, counted once per pair
+    let edges = item_edges(diagram, gid);
+    let any_pinned = items.iter().any(|it| it.pinned);
+
+    // 3. Spiral start around the pinned items, or the origin
+    let gap = cfg.gap as f64;
+    let spacing = items.iter().map(Item::radius).sum::<f64>() / items.len() as f64 + gap;
+    let origin = if any_pinned {
+        let pinned: Vec<&Item> = items.iter().filter(|it| it.pinned).collect();
+        let n = pinned.len() as f64;
+        (pinned.iter().map(|it| it.center.0).sum::<f64>() / n, pinned.iter().map(|it| it.center.1).sum::<f64>() / n)
+    } else {
+        (0.0, 0.0)
+    };
+    let golden_angle = std::f64::consts::PI * (3.0 - 5f64.sqrt());
+    for (i, item) in items.iter_mut().filter(|it| !it.pinned).enumerate() {
+        let r = spacing * ((i + 1) as f64).sqrt();
+        let a = golden_angle * (i + 1) as f64;
+        item.center = (origin.0 + r * a.cos(), origin.1 + r * a.sin());
+    }
+
+    // 4. Simulate
+    simulate(&mut items, &edges, gap);
+
+    // 5. Remove overlaps
+    let mut rects: Vec<RectI> = items.iter().map(|it| RectI {
+        x: (it.center.0 - it.size.w as f64 / 2.0).round() as i32,
+        y: (it.center.1 - it.size.h as f64 / 2.0).round() as i32,
+        w: it.size.w,
+        h: it.size.h,
+    }).collect();
+    let pinned: Vec<bool> = items.iter().map(|it| it.pinned).collect();
+    remove_overlaps(&mut rects, &pinned, cfg.gap);
+
+    // 6. Move the free items to the group's padding. Pinned items don't move, so
+    // with any of them the free ones only move back when they stick out past the
+    // padding, and then further down until they clear the pinned ones again
+    let free: Vec<usize> = (0..rects.len()).filter(|&i| !pinned[i]).collect();
+    let min_x = free.iter().map(|&i| rects[i].x).min().unwrap_or(cfg.group_padding);
+    let min_y = free.iter().map(|&i| rects[i].y).min().unwrap_or(cfg.group_padding);
+    let (dx, dy) = if any_pinned {
+        ((cfg.group_padding - min_x).max(0), (cfg.group_padding - min_y).max(0))
+    } else {
+        (cfg.group_padding - min_x, cfg.group_padding - min_y)
+    };
+    for &i in &free {
+        rects[i].x += dx;
+        rects[i].y += dy;
+    }
+    if any_pinned && (dx != 0 || dy != 0) {
+        let stay: Vec<RectI> = (0..rects.len()).filter(|&i| pinned[i]).map(|i| {
+            let r = rects[i];
+            RectI { x: r.x - cfg.gap, y: r.y - cfg.gap, w: r.w + 2 * cfg.gap, h: r.h + 2 * cfg.gap }
+        }).collect();
+        while free.iter().any(|&i| stay.iter().any(|s| s.overlaps(&rects[i]))) {
+            for &i in &free {
+                rects[i].y += cfg.gap.max(1);
+            }
+        }
+    }
+
+    // 7. Write back positions of free items
+    let (node_rects, group_rects) = rects.split_at(g.children_nodes.len());
+    for (&nid, r) in g.children_nodes.iter().zip(node_rects) {
+        node_local_pos.insert(nid, diagram.nodes[nid.0].pos.unwrap_or(PointI { x: r.x, y: r.y }));
+    }
+    for (&cgid, r) in g.children_groups.iter().zip(group_rects) {
+        let lb = group_bounds(cgid);
+        let p = diagram.groups[cgid.0].pos.unwrap_or(PointI { x: r.x - lb.x, y: r.y - lb.y });
+        group_local_pos.insert(cgid, p);
+    }
+}
+
+/// Relations among the children of `gid` as (item, item, count), where items
+/// are indexed like `layout_group_children_force` builds them. A relation to
+/// something inside a child group counts for that group.
+fn item_edges(diagram: &Diagram, gid: GroupId) -> Vec<(usize, usize, f64)> {
+    let node_item = |nid| child_item_of_node(diagram, gid, nid);
+    let group_item = |cgid| child_item_of_group(diagram, gid, cgid);
+
+    let node_pairs = diagram.edges.iter().map(|e| (node_item(e.from), node_item(e.to)));
+    let group_pairs = diagram.group_edges.iter().map(|e| (group_item(e.from), group_item(e.to)));
+    let mut counts: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+    for (a, b) in node_pairs.chain(group_pairs) {
+        if let (Some(a), Some(b)) = (a, b)
+            && a != b
+        {
+            *counts.entry((a.min(b), a.max(b))).or_default() += 1.0;
+        }
+    }
+    counts.into_iter().map(|((a, b), w)| (a, b, w)).collect()
+}
+
+/// Fruchterman–Reingold with size-aware ideal distances; pinned items don't move
+fn simulate(items: &mut [Item], edges: &[(usize, usize, f64)], gap: f64) {
+    let n = items.len();
+    let ideal = |a: &Item, b: &Item| a.radius() + b.radius() + gap;
+    let max_step = items.iter().map(Item::radius).fold(gap, f64::max);
+
+    for iter in 0..ITERATIONS {
+        let temperature = max_step * (1.0 - iter as f64 / ITERATIONS as f64);
+        let mut disp = vec![(0.0, 0.0); n];
+
+        // Every pair repels
+        for i in 0..n {
+            for j in i + 1..n {
+                let (dx, dy, d) = offset(&items[i], &items[j], i, j);
+                let k = ideal(&items[i], &items[j]);
+                let f = k * k / d;
+                disp[i].0 += dx / d * f;
+                disp[i].1 += dy / d * f;
+                disp[j].0 -= dx / d * f;
+                disp[j].1 -= dy / d * f;
+            }
+        }
+
+        // Relations attract
+        for &(a, b, w) in edges {
+            let (dx, dy, d) = offset(&items[a], &items[b], a, b);
+            let f = d * d / ideal(&items[a], &items[b]) * w;
+            disp[a].0 -= dx / d * f;
+            disp[a].1 -= dy / d * f;
+            disp[b].0 += dx / d * f;
+            disp[b].1 += dy / d * f;
+        }
+
+        // Weak pull towards the middle keeps unrelated items together
+        let mid_x = items.iter().map(|it| it.center.0).sum::<f64>() / n as f64;
+        let mid_y = items.iter().map(|it| it.center.1).sum::<f64>() / n as f64;
+        for (item, d) in items.iter().zip(disp.iter_mut()) {
+            d.0 -= (item.center.0 - mid_x) * GRAVITY;
+            d.1 -= (item.center.1 - mid_y) * GRAVITY;
+        }
+
+        // Move free items, at most `temperature` far
+        for (item, (dx, dy)) in items.iter_mut().zip(disp) {
+            let len = dx.hypot(dy);
+            if item.pinned || len < f64::EPSILON {
+                continue;
+            }
+            let step = len.min(temperature);
+            item.center.0 += dx / len * step;
+            item.center.1 += dy / len * step;
+        }
+    }
+}
+
+/// Vector from `b` to `a` and its length; items on the same spot are nudged apart deterministically
+fn offset(a: &Item, b: &Item, i: usize, j: usize) -> (f64, f64, f64) {
+    let (dx, dy) = (a.center.0 - b.center.0, a.center.1 - b.center.1);
+    let d = dx.hypot(dy);
+    if d > 0.01 {
+        return (dx, dy, d);
+    }
+    let angle = (i * 31 + j * 17) as f64;
+    (angle.cos() * 0.01, angle.sin() * 0.01, 0.01)
+}
+
+/// Push overlapping rectangles (with `gap` between them) apart; pinned ones stay put
+fn remove_overlaps(rects: &mut [RectI], pinned: &[bool], gap: i32) {
+    let n = rects.len();
+    for _ in 0..OVERLAP_PASSES {
+        let mut moved = false;
+        for i in 0..n {
+            for j in i + 1..n {
+                if pinned[i] && pinned[j] {
+                    continue;
+                }
+                let (a, b) = (rects[i], rects[j]);
+                let dx = (b.x * 2 + b.w) - (a.x * 2 + a.w);
+                let dy = (b.y * 2 + b.h) - (a.y * 2 + a.h);
+                let over_x = (a.w + b.w) / 2 + gap - dx.abs() / 2;
+                let over_y = (a.h + b.h) / 2 + gap - dy.abs() / 2;
+                if over_x <= 0 || over_y <= 0 {
+                    continue;
+                }
+
+                // Along the axis of least overlap, each free side takes its share
+                let (push, along_x) = if over_x <= over_y { (over_x, true) } else { (over_y, false) };
+                let sign = if (if along_x { dx } else { dy }) >= 0 { 1 } else { -1 };
+                let (share_i, share_j) = match (pinned[i], pinned[j]) {
+                    (true, _) => (0, push),
+                    (_, true) => (push, 0),
+                    _ => (push / 2, push - push / 2),
+                };
+                if along_x {
+                    rects[i].x -= sign * share_i;
+                    rects[j].x += sign * share_j;
+                } else {
+                    rects[i].y -= sign * share_i;
+                    rects[j].y += sign * share_j;
+                }
+                moved = true;
+            }
+        }
+        if !moved {
+            return;
+        }
+    }
+
+    // Still overlapping: move free items down until they are clear of everything settled
+    let mut settled: Vec<RectI> = (0..n).filter(|&i| pinned[i]).map(|i| rects[i]).collect();
+    for i in (0..n).filter(|&i| !pinned[i]) {
+        while settled.iter().any(|s| s.overlaps(&rects[i])) {
+            rects[i].y += gap.max(1);
+        }
+        settled.push(rects[i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, parse_file};
+    use crate::layout::test_support::assert_disjoint;
+
+    #[test]
+    fn test_force_layout_is_deterministic_and_overlap_free() {
+        // A ring of twelve classes with chords, no clear hierarchy
+        let mut src = String::new();
+        for i in 0..12 {
+            src.push_str(&format!("C{} --> C{}\n", i, (i + 1) % 12));
+            src.push_str(&format!("C{} .. C{}\n", i, (i + 5) % 12));
+        }
+        let diagram = compile(&parse_file(&src).unwrap()).unwrap();
+        let cfg = LayoutConfig::default();
+        let layout = layout_force(&diagram, &cfg);
+
+        let rects: Vec<RectI> = (0..12).map(|i| layout.node_world_bounds[&NodeId(i)]).collect();
+        assert_disjoint(&rects);
+        assert!(rects.iter().all(|r| r.x >= cfg.group_padding && r.y >= cfg.group_padding));
+        let again = layout_force(&diagram, &cfg);
+        assert_eq!(again.node_world_bounds, layout.node_world_bounds);
+    }
+
+    #[test]
+    fn test_related_nodes_end_up_closer() {
+        let src = "A --> B\nB --> C\nC --> A\nX --> Y\nY --> Z\nZ --> X\n";
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let layout = layout_force(&diagram, &LayoutConfig::default());
+        let center = |i: usize| {
+            let r = layout.node_world_bounds[&NodeId(i)];
+            ((r.x + r.w / 2) as f64, (r.y + r.h / 2) as f64)
+        };
+        let dist = |a: usize, b: usize| {
+            let ((ax, ay), (bx, by)) = (center(a), center(b));
+            (ax - bx).hypot(ay - by)
+        };
+
+        // A, B, C are 0..3 and X, Y, Z are 3..6
+        let within = (dist(0, 1) + dist(1, 2) + dist(3, 4) + dist(4, 5)) / 4.0;
+        let across = (dist(0, 3) + dist(1, 4) + dist(2, 5)) / 3.0;
+        assert!(within < across, "within {} across {}", within, across);
+    }
+
+    #[test]
+    fn test_pinned_nodes_and_groups() {
+        let src = "class Fixed {\n    @pos: (400, 300)\n}\ngroup G {\n    class A\n    class B\n    class C\n    A --> B\n    B --> C\n}\nclass D\nD --> Fixed\nD --> A\n";
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let layout = crate::layout::layout_diagram(&diagram, &LayoutConfig::default(), "force");
+
+        assert_eq!(layout.node_local_pos[&NodeId(0)], PointI { x: 400, y: 300 });
+
+        // The group's children stay inside it, and nothing overlaps the group
+        let g = layout.group_world_bounds[&GroupId(1)];
+        for i in 1..4 {
+            let r = layout.node_world_bounds[&NodeId(i)];
+            assert!(r.x >= g.x && r.y >= g.y && r.right() <= g.right() && r.bottom() <= g.bottom());
+        }
+        for i in [0, 4] {
+            assert!(!layout.node_world_bounds[&NodeId(i)].overlaps(&g));
+        }
+    }
+
+    #[test]
+    fn test_free_neighbours_of_a_pinned_node_stay_inside_the_padding() {
+        let src = "class Fixed {\n    @pos: (0, 0)\n}\nclass A\nclass B\nclass C\nclass D\n\
+                   A --> Fixed\nB --> Fixed\nC --> Fixed\nD --> Fixed\n";
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let cfg = LayoutConfig::default();
+        let layout = layout_force(&diagram, &cfg);
+
+        assert_eq!(layout.node_local_pos[&NodeId(0)], PointI { x: 0, y: 0 });
+        let rects: Vec<RectI> = (0..5).map(|i| layout.node_world_bounds[&NodeId(i)]).collect();
+        assert_disjoint(&rects);
+        for r in &rects[1..] {
+            assert!(r.x >= cfg.group_padding && r.y >= cfg.group_padding, "{:?}", r);
+        }
+    }
+}
@@ -9,8 +9,9 @@
 
 use std::collections::HashMap;
 use crate::parser::{PointI, Diagram, GroupId, NodeId};
-use crate::layout::{RectI, LayoutConfig, LayoutResult, LayoutStrategy};
-use crate::layout::{post_order_groups, pre_order_groups, compute_group_local_bounds};
+use crate::layout::{RectI, SizeI, LayoutConfig, LayoutResult, LayoutStrategy};
+use crate::layout::get_node_size;
+use super::layout_per_group;
 
 /// Simple grid layout implementation.
 /// Places nodes in a left-to-right, top-to-bottom grid pattern.
@@ -24,91 +25,9 @@ impl LayoutStrategy for GridLayout {
 
 /// Layout nodes in a simple grid pattern.
 pub fn layout_grid(diagram: &Diagram, cfg: &LayoutConfig) -> LayoutResult {
-    let mut group_local_pos: HashMap<GroupId, PointI> = HashMap::new();
-    let mut node_local_pos: HashMap<NodeId, PointI> = HashMap::new();
-
-    group_local_pos.insert(diagram.root, PointI { x: 0, y: 0 });
-
-    // Layout groups bottom-up (children first)
-    let post = post_order_groups(diagram);
-    let mut group_local_bounds: HashMap<GroupId, RectI> = HashMap::new();
-
-    for gid in post {
-        let g = &diagram.groups[gid.0];
-
-        // Set group position
-        if gid != diagram.root {
-            let p = g.pos.unwrap_or(PointI { x: 0, y: 0 });
-            group_local_pos.insert(gid, p);
-        }
-
-        // Layout children in this group using simple grid
-        layout_group_children_grid(
-            diagram,
-            gid,
-            cfg,
-            &mut group_local_pos,
-            &mut node_local_pos,
-            &group_local_bounds,
-        );
-
-        // Compute this group's local bounds
-        let bounds = compute_group_local_bounds(
-            diagram,
-            gid,
-            cfg,
-            &group_local_pos,
-            &node_local_pos,
-            &group_local_bounds,
-        );
-        group_local_bounds.insert(gid, bounds);
-    }
-
-    // Second pass: accumulate world positions
-    let mut group_world_pos: HashMap<GroupId, PointI> = HashMap::new();
-    let mut node_world_pos: HashMap<NodeId, PointI> = HashMap::new();
-    let mut group_world_bounds: HashMap<GroupId, RectI> = HashMap::new();
-    let mut node_world_bounds: HashMap<NodeId, RectI> = HashMap::new();
-
-    group_world_pos.insert(diagram.root, PointI { x: 0, y: 0 });
-
-    let pre = pre_order_groups(diagram);
-
-    for gid in pre {
-        let g_local = *group_local_pos.get(&gid).unwrap_or(&PointI { x: 0, y: 0 });
-        let g_world = if gid == diagram.root {
-            PointI { x: 0, y: 0 }
-        } else {
-            let parent = diagram.groups[gid.0].parent.expect("non-root group must have parent");
-            let pw = *group_world_pos.get(&parent).unwrap();
-            PointI { x: pw.x + g_local.x, y: pw.y + g_local.y }
-        };
-
-        group_world_pos.insert(gid, g_world);
-
-        let lb = *group_local_bounds.get(&gid).unwrap();
-        let wb = RectI { x: g_world.x + lb.x, y: g_world.y + lb.y, w: lb.w, h: lb.h };
-        group_world_bounds.insert(gid, wb);
-
-        for &nid in &diagram.groups[gid.0].children_nodes {
-            let n_local = *node_local_pos.get(&nid).unwrap_or(&PointI { x: 0, y: 0 });
-            let n_world = PointI { x: g_world.x + n_local.x, y: g_world.y + n_local.y };
-            node_world_pos.insert(nid, n_world);
-
-            let node = &diagram.nodes[nid.0];
-            let sz = crate::layout::get_node_size(node, cfg);
-            node_world_bounds.insert(nid, RectI { x: n_world.x, y: n_world.y, w: sz.w, h: sz.h });
-        }
-    }
-
-    LayoutResult {
-        group_local_pos,
-        node_local_pos,
-        group_world_pos,
-        node_world_pos,
-        group_world_bounds,
-        node_world_bounds,
-    }
+    layout_per_group(diagram, cfg, |gid, group_local_pos, node_local_pos, group_local_bounds| {
+        layout_group_children_grid(diagram, gid, cfg, group_local_pos, node_local_pos, group_local_bounds);
+    })
 }
 
 /// Layout children of a group in a simple grid pattern.
@@ -124,11 +43,7 @@ fn layout_group_children_grid(
     let g = &diagram.groups[gid.0];
     let padding = cfg.group_padding;
     let gap = cfg.gap;
-    let node_size = cfg.node_size;
-    let max_row_w = cfg.max_row_w;
-
-    // Calculate how many nodes fit per row
-    let nodes_per_row = ((max_row_w - 2 * padding) / (node_size.w + gap)).max(1) as usize;
+    let max_row_w = if cfg.max_row_w > 0 { cfg.max_row_w } else { 1200 };
 
     // Separate fixed and auto-placed items
     let mut auto_nodes: Vec<NodeId> = Vec::new();
@@ -154,31 +69,27 @@ fn layout_group_children_grid(
         }
     }
 
-    // Place auto-layout nodes in a grid
-    let mut x = padding;
-    let mut y = padding;
-    let mut col = 0;
-
-    for nid in auto_nodes {
-        node_local_pos.insert(nid, PointI { x, y });
-        
-        col += 1;
-        if col >= nodes_per_row {
-            col = 0;
-            x = padding;
-            y += node_size.h + gap;
-        } else {
-            x += node_size.w + gap;
-        }
+    // Place auto-layout nodes in a grid: each column is as wide as its widest
+    // node and each row as tall as its tallest, so large classes never overlap
+    let sizes: Vec<SizeI> = auto_nodes.iter().map(|nid| get_node_size(&diagram.nodes[nid.0], cfg)).collect();
+    let cols = grid_columns(&sizes, max_row_w - 2 * padding, gap);
+    let mut col_w = vec![0; cols];
+    let mut row_h = vec![0; sizes.len().div_ceil(cols)];
+    for (i, sz) in sizes.iter().enumerate() {
+        col_w[i % cols] = col_w[i % cols].max(sz.w);
+        row_h[i / cols] = row_h[i / cols].max(sz.h);
     }
+    let col_x = offsets(padding, &col_w, gap);
+    let row_y = offsets(padding, &row_h, gap);
 
-    // Move to next row if we have groups
-    if !auto_groups.is_empty() && col > 0 {
-        y += node_size.h + gap;
-        x = padding;
+    for (i, nid) in auto_nodes.into_iter().enumerate() {
+        node_local_pos.insert(nid, PointI { x: col_x[i % cols], y: row_y[i / cols] });
     }
 
-    // Place auto-layout groups in a row below nodes
+    // Place auto-layout groups in rows below the nodes, each row as tall as its tallest group
+    let mut x = padding;
+    let mut y = row_y[row_h.len()];
+    let mut row_bottom = y;
     for cgid in auto_groups {
         let bounds = group_local_bounds.get(&cgid).copied().unwrap_or(RectI {
             x: 0,
@@ -190,10 +101,78 @@ fn layout_group_children_grid(
         // Check if group fits on current row
         if x + bounds.w > max_row_w && x > padding {
             x = padding;
-            y += bounds.h + gap;
+            y = row_bottom + gap;
         }
 
-        group_local_pos.insert(cgid, PointI { x, y });
+        // The group's box starts at its position plus the offset of its bounds
+        group_local_pos.insert(cgid, PointI { x: x - bounds.x, y: y - bounds.y });
+        row_bottom = row_bottom.max(y + bounds.h);
         x += bounds.w + gap;
     }
 }
+
+/// The most columns (at most one per node) whose widest members fit in `avail_w`
+fn grid_columns(sizes: &[SizeI], avail_w: i32, gap: i32) -> usize {
+    (2..=sizes.len())
+        .rev()
+        .find(|&cols| {
+            let mut col_w = vec![0; cols];
+            for (i, sz) in sizes.iter().enumerate() {
+                col_w[i % cols] = col_w[i % cols].max(sz.w);
+            }
+            col_w.iter().sum::<i32>() + gap * (cols as i32 - 1) <= avail_w
+        })
+        .unwrap_or(1)
+}
+
+/// Start of each column or row laid out from `start`, plus the start of the next one
+fn offsets(start: i32, lengths: &[i32], gap: i32) -> Vec<i32> {
+    let mut out = Vec::with_capacity(lengths.len() + 1);
+    let mut at = start;
+    out.push(at);
+    for len in lengths {
+        at += len + gap;
+        out.push(at);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, parse_file};
+    use crate::layout::test_support::assert_disjoint;
+
+    #[test]
+    fn test_rows_and_columns_fit_their_largest_member() {
+        let fields: String = (0..12).map(|i| format!("    + field{}: int\n", i)).collect();
+        let src = format!("class Tall {{\n{}}}\nclass A\nclass B\nclass C\nclass D\nclass E\nnode N\n", fields);
+        let diagram = compile(&parse_file(&src).unwrap()).unwrap();
+        let layout = layout_grid(&diagram, &LayoutConfig::default());
+
+        let rects: Vec<RectI> = layout.node_world_bounds.values().copied().collect();
+        assert_disjoint(&rects);
+
+        // Everything in the row after the tall class starts below it
+        let tall = layout.node_world_bounds[&NodeId(0)];
+        let last = layout.node_world_bounds[&NodeId(6)];
+        assert!(tall.h > LayoutConfig::default().class_size.h);
+        assert!(last.y >= tall.bottom());
+    }
+
+    #[test]
+    fn test_group_rows_use_group_bounds() {
+        let src = "group G1 {\n  class A\n  class B\n  A --> B\n}\ngroup G2 {\n  class C\n  class X {\n    @pos: (-300, -200)\n  }\n}\ngroup G3 {\n  class D\n  class E\n  class F\n}\nclass Free\n";
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let cfg = LayoutConfig { max_row_w: 700, ..LayoutConfig::default() };
+        let layout = layout_grid(&diagram, &cfg);
+
+        let groups: Vec<RectI> = diagram.groups.iter().enumerate()
+            .filter(|(i, g)| GroupId(*i) != diagram.root && g.id.is_some())
+            .map(|(i, _)| layout.group_world_bounds[&GroupId(i)])
+            .collect();
+        assert_disjoint(&groups);
+        let free = layout.node_world_bounds[&NodeId(diagram.nodes.len() - 1)];
+        assert!(groups.iter().all(|g| !g.overlaps(&free)));
+    }
+}
@@ -3,9 +3,124 @@
 //! This module contains all available layout algorithms:
 //! - `graph_driven`: Default hierarchical layout that places connected nodes closer together
 //! - `grid`: Simple left-to-right, top-to-bottom grid layout
+//! - `force`: Force-directed layout for networks without a clear hierarchy
+//! - `tree`: Tidy tree layout for inheritance hierarchies
 
 mod graph_driven;
 mod grid;
+mod force;
+mod tree;
 
 pub use graph_driven::{GraphDrivenLayout, layout_graph_driven};
 pub use grid::{GridLayout, layout_grid};
+pub use force::ForceLayout;
+pub use tree::TreeLayout;
+
+use std::collections::HashMap;
+use crate::parser::{PointI, Diagram, GroupId, NodeId};
+use crate::layout::{RectI, LayoutConfig, LayoutResult};
+use crate::layout::{get_node_size, post_order_groups, pre_order_groups, compute_group_local_bounds};
+use crate::layout::notes::place_notes;
+
+/// Lay out every group with `place_children` and accumulate world positions.
+///
+/// Groups are visited bottom-up, so when `place_children` runs for a group the
+/// local bounds of its child groups are known. It only has to set the local
+/// positions of the group's direct children; a group without a `@pos` starts at
+/// (0, 0) and is moved by its parent.
+fn layout_per_group(
+    diagram: &Diagram,
+    cfg: &LayoutConfig,
+    mut place_children: impl FnMut(
+        GroupId,
+        &mut HashMap<GroupId, PointI>,
+        &mut HashMap<NodeId, PointI>,
+        &HashMap<GroupId, RectI>,
+    ),
+) -> LayoutResult {
+    let mut group_local_pos: HashMap<GroupId, PointI> = HashMap::new();
+    let mut node_local_pos: HashMap<NodeId, PointI> = HashMap::new();
+    let mut group_local_bounds: HashMap<GroupId, RectI> = HashMap::new();
+
+    group_local_pos.insert(diagram.root, PointI { x: 0, y: 0 });
+
+    // First pass: local positions for everything, children before their parent
+    for gid in post_order_groups(diagram) {
+        if gid != diagram.root {
+            let p = diagram.groups[gid.0].pos.unwrap_or(PointI { x: 0, y: 0 });
+            group_local_pos.insert(gid, p);
+        }
+
+        place_children(gid, &mut group_local_pos, &mut node_local_pos, &group_local_bounds);
+
+        let bounds = compute_group_local_bounds(
+            diagram,
+            gid,
+            cfg,
+            &group_local_pos,
+            &node_local_pos,
+            &group_local_bounds,
+        );
+        group_local_bounds.insert(gid, bounds);
+    }
+
+    // Second pass: world positions, parents before their children
+    let mut group_world_pos: HashMap<GroupId, PointI> = HashMap::new();
+    let mut node_world_pos: HashMap<NodeId, PointI> = HashMap::new();
+    let mut group_world_bounds: HashMap<GroupId, RectI> = HashMap::new();
+    let mut node_world_bounds: HashMap<NodeId, RectI> = HashMap::new();
+
+    for gid in pre_order_groups(diagram) {
+        let g_local = *group_local_pos.get(&gid).unwrap_or(&PointI { x: 0, y: 0 });
+        let g_world = if gid == diagram.root {
+            PointI { x: 0, y: 0 }
+        } else {
+            let parent = diagram.groups[gid.0].parent.expect("non-root group must have parent");
+            let pw = group_world_pos[&parent];
+            PointI { x: pw.x + g_local.x, y: pw.y + g_local.y }
+        };
+        group_world_pos.insert(gid, g_world);
+
+        let lb = group_local_bounds[&gid];
+        group_world_bounds.insert(gid, RectI { x: g_world.x + lb.x, y: g_world.y + lb.y, w: lb.w, h: lb.h });
+
+        for &nid in &diagram.groups[gid.0].children_nodes {
+            let n_local = *node_local_pos.get(&nid).unwrap_or(&PointI { x: 0, y: 0 });
+            let n_world = PointI { x: g_world.x + n_local.x, y: g_world.y + n_local.y };
+            node_world_pos.insert(nid, n_world);
+
+            let sz = get_node_size(&diagram.nodes[nid.0], cfg);
+            node_world_bounds.insert(nid, RectI { x: n_world.x, y: n_world.y, w: sz.w, h: sz.h });
+        }
+    }
+
+    LayoutResult {
+        group_local_pos,
+        node_local_pos,
+        group_world_pos,
+        node_world_pos,
+        note_world_bounds: place_notes(diagram, cfg, &node_world_bounds, &group_world_bounds),
+        group_world_bounds,
+        node_world_bounds,
+    }
+}
+
+/// Index of the child of `gid` that holds group `cgid` (itself or an ancestor),
+/// counting the group's nodes first and then its groups
+fn child_item_of_group(diagram: &Diagram, gid: GroupId, mut cgid: GroupId) -> Option<usize> {
+    let g = &diagram.groups[gid.0];
+    loop {
+        let parent = diagram.groups[cgid.0].parent?;
+        if parent == gid {
+            return g.children_groups.iter().position(|&c| c == cgid).map(|i| i + g.children_nodes.len());
+        }
+        cgid = parent;
+    }
+}
+
+/// Index of the child of `gid` that is or holds node `nid`, like `child_item_of_group`
+fn child_item_of_node(diagram: &Diagram, gid: GroupId, nid: NodeId) -> Option<usize> {
+    let g = &diagram.groups[gid.0];
+    g.children_nodes.iter().position(|&n| n == nid)
+        .or_else(|| child_item_of_group(diagram, gid, diagram.nodes[nid.0].group))
+}
@@ -0,0 +1,358 @@
+//! Tidy tree layout algorithm.
+//!
+//! Suited to inheritance hierarchies: every subclass sits below its parent and
+//! each parent is centered over its subclasses. Each group is laid out on its
+//! own, children first, so a child group moves as one rectangle in its
+//! parent's tree.
+//!
+//! Per group:
+//! 1. A spanning forest is picked from the hierarchy relations between items
+//!    (child nodes and child groups): arrows with `is_hierarchy_edge`, parent
+//!    and child as `get_edge_direction` gives them. Inheritance relations
+//!    (`hierarchy_reversed`) are taken first, then the others in source order.
+//!    An item keeps the first parent it gets, and relations that would close
+//!    a cycle are skipped
+//! 2. Reingold–Tilford: subtrees are packed left to right as close as their
+//!    contours allow, and every parent is centered over its first and last
+//!    child
+//! 3. Depths become rows, each as tall as its tallest item. Items outside the
+//!    forest are packed in rows below
+//!
+//! Associations, dependencies, compositions and aggregations count as
+//! hierarchy relations too, so they give a parent to any item that inheritance
+//! left without one. Only relations left out of the forest (undirected ones,
+//! ones to an item that already has a parent, and cycles) don't move anything;
+//! they are routed over the finished tree. `@pos` items stay where they are
+//! and the tree moves down until it clears them. `@direction` has no effect
+//! here.
+
+use std::collections::HashMap;
+use crate::parser::{PointI, Diagram, GroupId, NodeId, ARROW_DEFINITIONS, get_base_arrow_name};
+use crate::layout::{RectI, LayoutConfig, LayoutResult, LayoutStrategy};
+use crate::layout::get_node_size;
+use crate::layout::placement::get_edge_direction;
+use super::{child_item_of_group, child_item_of_node, layout_per_group};
+
+/// Tidy tree layout implementation.
+/// Hierarchy relations form trees that grow downwards.
+pub struct TreeLayout;
+
+impl LayoutStrategy for TreeLayout {
+    fn layout(&self, diagram: &Diagram, cfg: &LayoutConfig) -> LayoutResult {
+        layout_tree(diagram, cfg)
+    }
+}
+
+/// Layout nodes as tidy trees per group.
+pub fn layout_tree(diagram: &Diagram, cfg: &LayoutConfig) -> LayoutResult {
+    layout_per_group(diagram, cfg, |gid, group_local_pos, node_local_pos, group_local_bounds| {
+        layout_group_children_tree(diagram, gid, cfg, group_local_pos, node_local_pos, group_local_bounds);
+    })
+}
+
+/// Layout children of a group as a forest of tidy trees.
+/// Fixed nodes and groups keep their position and stay out of the forest.
+fn layout_group_children_tree(
+    diagram: &Diagram,
+    gid: GroupId,
+    cfg: &LayoutConfig,
+    group_local_pos: &mut HashMap<GroupId, PointI>,
+    node_local_pos: &mut HashMap<NodeId, PointI>,
+    group_local_bounds: &HashMap<GroupId, RectI>,
+) {
+    let g = &diagram.groups[gid.0];
+    let group_bounds = |cgid: GroupId| {
+        group_local_bounds.get(&cgid).copied().unwrap_or(RectI {
+            x: 0, y: 0, w: cfg.min_group_size.w, h: cfg.min_group_size.h,
+        })
+    };
+
+    // 1. Items: child nodes first, then child groups (a group's box is its position plus its bounds offset)
+    let mut rects: Vec<RectI> = Vec::new();
+    let mut pinned: Vec<bool> = Vec::new();
+    for &nid in &g.children_nodes {
+        let node = &diagram.nodes[nid.0];
+        let size = get_node_size(node, cfg);
+        let p = node.pos.unwrap_or(PointI { x: 0, y: 0 });
+        rects.push(RectI { x: p.x, y: p.y, w: size.w, h: size.h });
+        pinned.push(node.pos.is_some());
+    }
+    for &cgid in &g.children_groups {
+        let lb = group_bounds(cgid);
+        let p = diagram.groups[cgid.0].pos.unwrap_or(PointI { x: 0, y: 0 });
+        rects.push(RectI { x: p.x + lb.x, y: p.y + lb.y, w: lb.w, h: lb.h });
+        pinned.push(diagram.groups[cgid.0].pos.is_some());
+    }
+    if rects.is_empty() {
+        return;
+    }
+
+    // 2. Spanning forest of the free items
+    let forest = Forest::new(&tree_edges(diagram, gid), &pinned);
+    let roots: Vec<usize> = (0..rects.len())
+        .filter(|&i| forest.parent[i].is_none() && !forest.children[i].is_empty())
+        .collect();
+
+    // 3. Tidy x positions: each item relative to its parent, each tree relative to the first
+    let widths: Vec<i32> = rects.iter().map(|r| r.w).collect();
+    let mut offsets = vec![0; rects.len()];
+    let contours: Vec<Vec<(i32, i32)>> = roots.iter()
+        .map(|&root| place_subtree(&forest, root, &widths, cfg.gap, &mut offsets))
+        .collect();
+    let (root_lefts, merged) = pack(&contours, cfg.gap);
+
+    // 4. Depths as rows, from the roots down
+    let start_x = cfg.group_padding - merged.iter().map(|&(l, _)| l).min().unwrap_or(0);
+    let mut depth = vec![0; rects.len()];
+    let mut order: Vec<usize> = Vec::new();
+    for (&root, &left) in roots.iter().zip(&root_lefts) {
+        rects[root].x = start_x + left;
+        order.push(root);
+    }
+    let mut k = 0;
+    while k < order.len() {
+        let v = order[k];
+        for &c in &forest.children[v] {
+            rects[c].x = rects[v].x + offsets[c];
+            depth[c] = depth[v] + 1;
+            order.push(c);
+        }
+        k += 1;
+    }
+    let mut row_h: Vec<i32> = vec![0; merged.len()];
+    for &v in &order {
+        row_h[depth[v]] = row_h[depth[v]].max(rects[v].h);
+    }
+    let mut row_y = vec![cfg.group_padding; merged.len()];
+    for d in 1..row_y.len() {
+        row_y[d] = row_y[d - 1] + row_h[d - 1] + cfg.gap * 2;
+    }
+    for &v in &order {
+        rects[v].y = row_y[depth[v]];
+    }
+
+    // 5. Items outside the forest in rows below, wrapping at the row limit
+    let limit_w = if cfg.max_row_w > 0 { cfg.max_row_w } else { 1200 };
+    let mut current_x = cfg.group_padding;
+    let mut current_y = row_y.last().zip(row_h.last()).map_or(cfg.group_padding, |(y, h)| y + h + cfg.gap * 2);
+    let mut row_bottom = current_y;
+    for i in (0..rects.len()).filter(|&i| !pinned[i] && forest.parent[i].is_none() && forest.children[i].is_empty()) {
+        if current_x + rects[i].w > limit_w && current_x > cfg.group_padding {
+            current_x = cfg.group_padding;
+            current_y = row_bottom + cfg.gap * 2;
+        }
+        rects[i].x = current_x;
+        rects[i].y = current_y;
+        current_x += rects[i].w + cfg.gap;
+        row_bottom = row_bottom.max(rects[i].bottom());
+        order.push(i);
+    }
+
+    // 6. Move everything free down until it clears the pinned items
+    let blockers: Vec<RectI> = (0..rects.len()).filter(|&i| pinned[i])
+        .map(|i| RectI { x: rects[i].x - cfg.gap, y: rects[i].y - cfg.gap, w: rects[i].w + cfg.gap * 2, h: rects[i].h + cfg.gap * 2 })
+        .collect();
+    let mut dy = 0;
+    loop {
+        let needed = order.iter()
+            .map(|&i| RectI { y: rects[i].y + dy, ..rects[i] })
+            .flat_map(|r| blockers.iter().filter(move |b| b.overlaps(&r)).map(move |b| b.bottom() - r.y))
+            .max();
+        match needed {
+            Some(step) => dy += step,
+            None => break,
+        }
+    }
+
+    // 7. Write back positions of free items
+    let (node_rects, group_rects) = rects.split_at(g.children_nodes.len());
+    for (&nid, r) in g.children_nodes.iter().zip(node_rects) {
+        node_local_pos.insert(nid, diagram.nodes[nid.0].pos.unwrap_or(PointI { x: r.x, y: r.y + dy }));
+    }
+    for (&cgid, r) in g.children_groups.iter().zip(group_rects) {
+        let lb = group_bounds(cgid);
+        let p = diagram.groups[cgid.0].pos.unwrap_or(PointI { x: r.x - lb.x, y: r.y + dy - lb.y });
+        group_local_pos.insert(cgid, p);
+    }
+}
+
+/// Hierarchy relations among the children of `gid` as (parent item, child item),
+/// inheritance first and otherwise in source order. Items are indexed like
+/// `layout_group_children_tree` builds them, and a relation to something inside
+/// a child group counts for that group.
+fn tree_edges(diagram: &Diagram, gid: GroupId) -> Vec<(usize, usize)> {
+    let node_item = |nid| child_item_of_node(diagram, gid, nid);
+    let group_item = |cgid| child_item_of_group(diagram, gid, cgid);
+
+    let node_pairs = diagram.edges.iter().map(|e| (e.arrow.as_str(), node_item(e.from), node_item(e.to)));
+    let group_pairs = diagram.group_edges.iter().map(|e| (e.arrow.as_str(), group_item(e.from), group_item(e.to)));
+    let mut edges: Vec<(bool, usize, usize)> = Vec::new();
+    for (arrow, from, to) in node_pairs.chain(group_pairs) {
+        let def = ARROW_DEFINITIONS.iter().find(|d| d.name == get_base_arrow_name(arrow));
+        if let Some(def) = def.filter(|d| d.is_hierarchy_edge)
+            && let (Some(from), Some(to)) = (from, to)
+        {
+            let (parent, child) = get_edge_direction(arrow, from, to);
+            edges.push((def.hierarchy_reversed, parent, child));
+        }
+    }
+
+    // The sort is stable, so relations of each kind keep their order
+    edges.sort_by_key(|&(inheritance, _, _)| !inheritance);
+    edges.into_iter().map(|(_, parent, child)| (parent, child)).collect()
+}
+
+/// Parent of each item in the spanning forest, and its children in relation order
+struct Forest {
+    parent: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl Forest {
+    /// Take relations in order, skipping those to an item that already has a
+    /// parent, to pinned items, or that would close a cycle
+    fn new(edges: &[(usize, usize)], pinned: &[bool]) -> Self {
+        let mut forest = Forest { parent: vec![None; pinned.len()], children: vec![Vec::new(); pinned.len()] };
+        for &(parent, child) in edges {
+            if pinned[parent] || pinned[child] || forest.parent[child].is_some() || forest.is_ancestor(child, parent) {
+                continue;
+            }
+            forest.parent[child] = Some(parent);
+            forest.children[parent].push(child);
+        }
+        forest
+    }
+
+    /// Whether `a` is `b` or one of its ancestors
+    fn is_ancestor(&self, a: usize, mut b: usize) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.parent[b] {
+                Some(p) => b = p,
+                None => return false,
+            }
+        }
+    }
+}
+
+/// Reingold–Tilford placement of the subtree under `v`. Stores the left edge
+/// of each of its items relative to their parent's in `offsets`, and returns
+/// the subtree's contour: the (left, right) extent per depth relative to `v`.
+fn place_subtree(forest: &Forest, v: usize, widths: &[i32], gap: i32, offsets: &mut [i32]) -> Vec<(i32, i32)> {
+    let kids = &forest.children[v];
+    let contours: Vec<Vec<(i32, i32)>> = kids.iter()
+        .map(|&c| place_subtree(forest, c, widths, gap, offsets))
+        .collect();
+    let mut contour = vec![(0, widths[v])];
+    if let (Some(&first), Some(&last)) = (kids.first(), kids.last()) {
+        let (lefts, merged) = pack(&contours, gap);
+        let mid = (lefts[0] + widths[first] / 2 + lefts[lefts.len() - 1] + widths[last] / 2) / 2;
+        let left = mid - widths[v] / 2;
+        for (&c, &l) in kids.iter().zip(&lefts) {
+            offsets[c] = l - left;
+        }
+        contour.extend(merged.iter().map(|&(l, r)| (l - left, r - left)));
+    }
+    contour
+}
+
+/// Pack contours left to right, each as close to the previous ones as `gap`
+/// allows at every depth. Returns where each one starts and the merged contour.
+fn pack(contours: &[Vec<(i32, i32)>], gap: i32) -> (Vec<i32>, Vec<(i32, i32)>) {
+    let mut lefts = Vec::with_capacity(contours.len());
+    let mut merged: Vec<(i32, i32)> = Vec::new();
+    for contour in contours {
+        let shift = merged.iter().zip(contour).map(|(m, c)| m.1 + gap - c.0).max().unwrap_or(0);
+        for (d, &(l, r)) in contour.iter().enumerate() {
+            match merged.get_mut(d) {
+                Some(m) => *m = (m.0.min(l + shift), m.1.max(r + shift)),
+                None => merged.push((l + shift, r + shift)),
+            }
+        }
+        lefts.push(shift);
+    }
+    (lefts, merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, parse_file};
+    use crate::layout::test_support::assert_disjoint;
+
+    fn bounds(src: &str) -> Vec<RectI> {
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let layout = layout_tree(&diagram, &LayoutConfig::default());
+        (0..diagram.nodes.len()).map(|i| layout.node_world_bounds[&NodeId(i)]).collect()
+    }
+
+    fn center_x(r: &RectI) -> i32 {
+        r.x + r.w / 2
+    }
+
+    #[test]
+    fn test_subclasses_sit_centered_below_their_parent() {
+        // Shape is 0, Circle 1, Polygon 2, Triangle 3, Square 4, Named 5
+        let src = "class Shape\nclass Circle\nclass Polygon\nclass Triangle\nclass Square\ninterface Named\n\
+                   Circle --|> Shape\nPolygon --|> Shape\nTriangle --|> Polygon\nShape <|-- Square\nShape ..|> Named\n";
+        let b = bounds(src);
+
+        for (child, parent) in [(1, 0), (2, 0), (4, 0), (3, 2), (0, 5)] {
+            assert!(b[child].y >= b[parent].bottom(), "{} should be below {}", child, parent);
+        }
+        // Siblings keep their order, and parents are centered over them
+        assert!(b[1].right() < b[2].x && b[2].right() < b[4].x);
+        assert!((center_x(&b[0]) - (center_x(&b[1]) + center_x(&b[4])) / 2).abs() <= 1);
+        assert_eq!(center_x(&b[3]), center_x(&b[2]));
+        assert_disjoint(&b);
+    }
+
+    #[test]
+    fn test_relations_left_out_of_the_forest_do_not_change_the_tree() {
+        // Targets that already have a parent, cycles and undirected links
+        let tree = "class Base\nclass A\nclass B\nclass C\nA --|> Base\nB --|> Base\nC --|> A\n";
+        let with_others = format!("{}C --> B\nB .. A\nBase --> C\nA ..> Base\n", tree);
+        assert_eq!(bounds(tree), bounds(&with_others));
+    }
+
+    #[test]
+    fn test_associations_give_an_item_without_a_parent_one() {
+        let src = "class Base\nclass A\nclass Loner\nA --|> Base\n";
+        let before = bounds(src);
+        let after = bounds(&format!("{}Base --> Loner\n", src));
+
+        assert!(before[2].y >= before[1].bottom(), "unrelated items are packed below the tree");
+        // Loner becomes A's sibling under Base
+        assert_eq!(after[1].y, after[2].y);
+        assert!(after[1].right() < after[2].x);
+        assert!((center_x(&after[0]) - (center_x(&after[1]) + center_x(&after[2])) / 2).abs() <= 1);
+    }
+
+    #[test]
+    fn test_contours_keep_subtrees_apart_at_every_depth() {
+        // Two parents whose wide families would overlap if only the parents were spaced
+        let mut src = String::from("class Root\nclass Left\nclass Right\nLeft --|> Root\nRight --|> Root\n");
+        for i in 0..3 {
+            src.push_str(&format!("L{} --|> Left\nR{} --|> Right\n", i, i));
+        }
+        let b = bounds(&src);
+        assert_disjoint(&b);
+        // Grandchildren share one row
+        assert!(b[3..].iter().all(|r| r.y == b[3].y));
+    }
+
+    #[test]
+    fn test_unrelated_and_pinned_items() {
+        let src = "class Fixed {\n    @pos: (0, 0)\n}\nclass Base\nclass Sub\nclass Loner\nSub --|> Base\nSub --|> Fixed\n";
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let layout = crate::layout::layout_diagram(&diagram, &LayoutConfig::default(), "tree");
+        let b: Vec<RectI> = (0..4).map(|i| layout.node_world_bounds[&NodeId(i)]).collect();
+
+        assert_eq!(layout.node_local_pos[&NodeId(0)], PointI { x: 0, y: 0 });
+        assert!(b[2].y >= b[1].bottom());
+        assert!(b[3].y >= b[2].bottom());
+        assert!(b[1..].iter().all(|r| !r.overlaps(&b[0])));
+    }
+}
@@ -0,0 +1,623 @@
+// Layered (Sugiyama-style) graph layout.
+//
+// Lays out a directed graph in ranks from top to bottom. The hierarchical
+// placement uses it for the nodes of each group and rotates the result for
+// other @direction values, so this module only sees indices, sizes and edges:
+// 1. Cycle breaking: edges that close a cycle in a depth-first search are reversed
+// 2. Ranking: longest path from the sources, then each source moves down to
+//    just above its highest child so edges stay short
+// 3. Normalisation: edges spanning several ranks get a dummy node on every
+//    rank in between, so they take up room and can be untangled like nodes
+// 4. Crossing reduction: barycenter sweeps down and up, each followed by
+//    transposing neighbours while that removes crossings; the best order wins
+// 5. Coordinates: Brandes–Köpf vertical alignment and horizontal compaction in
+//    all four directions using the real node widths, balanced by averaging the
+//    two median candidates; ranks are as tall as their tallest node
+
+use std::collections::{HashSet, VecDeque};
+
+use super::SizeI;
+use crate::parser::PointI;
+
+/// Barycenter sweeps (alternating down and up) before keeping the best order
+const CROSSING_ITERATIONS: usize = 24;
+
+/// Upper bound on transposition passes per sweep
+const TRANSPOSE_ROUNDS: usize = 8;
+
+/// Result of `layout_layered`, index-aligned with the input sizes
+#[derive(Debug, Clone)]
+pub struct LayeredLayout {
+    /// Top-left corner of each node; the leftmost node and the first rank are at 0
+    pub pos: Vec<PointI>,
+    /// Rank of each node, 0 at the top
+    pub rank: Vec<usize>,
+}
+
+/// Lay out nodes of the given sizes with `edges` (parent, child) pointing down.
+/// `node_gap` separates neighbours in a rank, `rank_gap` consecutive ranks.
+pub fn layout_layered(sizes: &[SizeI], edges: &[(usize, usize)], node_gap: i32, rank_gap: i32) -> LayeredLayout {
+    let dag = break_cycles(sizes.len(), edges);
+    let rank = assign_ranks(sizes.len(), &dag);
+    let mut graph = LayerGraph::new(sizes, &dag, &rank);
+    graph.reduce_crossings();
+    let x = graph.assign_x(node_gap as f64);
+    let y = graph.assign_y(rank_gap);
+
+    let left = |v: usize| x[v] - sizes[v].w as f64 / 2.0;
+    let min_left = (0..sizes.len()).map(left).fold(f64::INFINITY, f64::min);
+    let pos = (0..sizes.len())
+        .map(|v| PointI { x: (left(v) - min_left).round() as i32, y: y[v] })
+        .collect();
+    LayeredLayout { pos, rank }
+}
+
+/// The edges with every edge that closes a cycle reversed, without self-loops or duplicates
+fn break_cycles(n: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut out: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for &(u, v) in edges {
+        if u != v {
+            out[u].push(v);
+        }
+    }
+
+    // 0 = unvisited, 1 = on the search stack, 2 = done
+    let mut state = vec![0u8; n];
+    let mut back: HashSet<(usize, usize)> = HashSet::new();
+    for start in 0..n {
+        if state[start] != 0 {
+            continue;
+        }
+        state[start] = 1;
+        let mut stack = vec![(start, 0)];
+        while let Some((u, next)) = stack.last_mut() {
+            let u = *u;
+            match out[u].get(*next) {
+                Some(&v) => {
+                    *next += 1;
+                    match state[v] {
+                        0 => {
+                            state[v] = 1;
+                            stack.push((v, 0));
+                        }
+                        1 => {
+                            back.insert((u, v));
+                        }
+                        _ => {}
+                    }
+                }
+                None => {
+                    state[u] = 2;
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    edges
+        .iter()
+        .filter(|(u, v)| u != v)
+        .map(|&(u, v)| if back.contains(&(u, v)) { (v, u) } else { (u, v) })
+        .filter(|e| seen.insert(*e))
+        .collect()
+}
+
+/// Longest-path ranks of an acyclic graph, with sources moved down next to their highest child
+fn assign_ranks(n: usize, dag: &[(usize, usize)]) -> Vec<usize> {
+    let mut preds: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut succs: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for &(u, v) in dag {
+        succs[u].push(v);
+        preds[v].push(u);
+    }
+
+    // Topological order, lowest index first among the ready nodes
+    let mut indegree: Vec<usize> = preds.iter().map(Vec::len).collect();
+    let mut ready: VecDeque<usize> = (0..n).filter(|&v| indegree[v] == 0).collect();
+    let mut order = Vec::with_capacity(n);
+    while let Some(u) = ready.pop_front() {
+        order.push(u);
+        for &v in &succs[u] {
+            indegree[v] -= 1;
+            if indegree[v] == 0 {
+                ready.push_back(v);
+            }
+        }
+    }
+
+    let mut rank = vec![0; n];
+    for &u in &order {
+        for &v in &succs[u] {
+            rank[v] = rank[v].max(rank[u] + 1);
+        }
+    }
+    for &u in order.iter().rev() {
+        if preds[u].is_empty()
+            && let Some(highest) = succs[u].iter().map(|&v| rank[v]).min()
+        {
+            rank[u] = highest - 1;
+        }
+    }
+    rank
+}
+
+/// The ranked graph with dummy nodes, and the order of the nodes in each rank
+struct LayerGraph {
+    /// Size of every node; dummies come after the real nodes and are 0 x 0
+    sizes: Vec<SizeI>,
+    real: usize,
+    rank: Vec<usize>,
+    layers: Vec<Vec<usize>>,
+    /// Index of each node within its layer
+    pos: Vec<usize>,
+    /// Neighbours in the rank above
+    up: Vec<Vec<usize>>,
+    /// Neighbours in the rank below
+    down: Vec<Vec<usize>>,
+}
+
+impl LayerGraph {
+    fn new(sizes: &[SizeI], dag: &[(usize, usize)], rank: &[usize]) -> Self {
+        let n = sizes.len();
+        let mut g = LayerGraph {
+            sizes: sizes.to_vec(),
+            real: n,
+            rank: rank.to_vec(),
+            layers: Vec::new(),
+            pos: Vec::new(),
+            up: vec![Vec::new(); n],
+            down: vec![Vec::new(); n],
+        };
+
+        // Split long edges into a chain through one dummy per rank
+        for &(u, v) in dag {
+            let mut prev = u;
+            for r in rank[u] + 1..rank[v] {
+                let dummy = g.sizes.len();
+                g.sizes.push(SizeI { w: 0, h: 0 });
+                g.rank.push(r);
+                g.up.push(Vec::new());
+                g.down.push(Vec::new());
+                g.link(prev, dummy);
+                prev = dummy;
+            }
+            g.link(prev, v);
+        }
+
+        // Initial order: depth-first from the sources, so subtrees start out together
+        let ranks = g.rank.iter().max().map_or(0, |r| r + 1);
+        g.layers = vec![Vec::new(); ranks];
+        let mut visited = vec![false; g.len()];
+        let sources: Vec<usize> = (0..g.len()).filter(|&v| g.up[v].is_empty()).collect();
+        for start in sources.into_iter().chain(0..g.len()) {
+            let mut stack = vec![start];
+            while let Some(v) = stack.pop() {
+                if visited[v] {
+                    continue;
+                }
+                visited[v] = true;
+                g.layers[g.rank[v]].push(v);
+                stack.extend(g.down[v].iter().rev().filter(|&&w| !visited[w]));
+            }
+        }
+        g.update_pos();
+        g
+    }
+
+    fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    fn is_dummy(&self, v: usize) -> bool {
+        v >= self.real
+    }
+
+    fn link(&mut self, upper: usize, lower: usize) {
+        self.down[upper].push(lower);
+        self.up[lower].push(upper);
+    }
+
+    fn update_pos(&mut self) {
+        self.pos = vec![0; self.len()];
+        for layer in &self.layers {
+            for (i, &v) in layer.iter().enumerate() {
+                self.pos[v] = i;
+            }
+        }
+    }
+
+    /// Crossings between the edges from layer `r` to layer `r + 1`
+    fn crossings_below(&self, r: usize) -> usize {
+        let edges: Vec<(usize, usize)> = self.layers[r]
+            .iter()
+            .flat_map(|&u| self.down[u].iter().map(move |&v| (u, v)))
+            .map(|(u, v)| (self.pos[u], self.pos[v]))
+            .collect();
+        let mut count = 0;
+        for (i, a) in edges.iter().enumerate() {
+            for b in &edges[i + 1..] {
+                if (a.0 < b.0 && a.1 > b.1) || (a.0 > b.0 && a.1 < b.1) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    fn crossings(&self) -> usize {
+        (0..self.layers.len().saturating_sub(1)).map(|r| self.crossings_below(r)).sum()
+    }
+
+    fn reduce_crossings(&mut self) {
+        let mut best = self.layers.clone();
+        let mut best_crossings = self.crossings();
+        for iteration in 0..CROSSING_ITERATIONS {
+            if best_crossings == 0 {
+                break;
+            }
+            if iteration % 2 == 0 {
+                for r in 1..self.layers.len() {
+                    self.sort_by_barycenter(r, true);
+                }
+            } else {
+                for r in (0..self.layers.len().saturating_sub(1)).rev() {
+                    self.sort_by_barycenter(r, false);
+                }
+            }
+            self.transpose();
+
+            let crossings = self.crossings();
+            if crossings < best_crossings {
+                best = self.layers.clone();
+                best_crossings = crossings;
+            }
+        }
+        self.layers = best;
+        self.update_pos();
+    }
+
+    /// Reorder layer `r` by the average position of each node's neighbours in
+    /// the layer above (or below); nodes without any keep their position
+    fn sort_by_barycenter(&mut self, r: usize, from_above: bool) {
+        let mut keyed: Vec<(f64, usize)> = self.layers[r]
+            .iter()
+            .map(|&v| {
+                let neighbors = if from_above { &self.up[v] } else { &self.down[v] };
+                let key = if neighbors.is_empty() {
+                    self.pos[v] as f64
+                } else {
+                    neighbors.iter().map(|&u| self.pos[u] as f64).sum::<f64>() / neighbors.len() as f64
+                };
+                (key, v)
+            })
+            .collect();
+        // Stable, so ties keep their current order
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.layers[r] = keyed.into_iter().map(|(_, v)| v).collect();
+        for (i, &v) in self.layers[r].iter().enumerate() {
+            self.pos[v] = i;
+        }
+    }
+
+    /// Swap neighbours within each layer while that removes crossings
+    fn transpose(&mut self) {
+        for _ in 0..TRANSPOSE_ROUNDS {
+            let mut improved = false;
+            for r in 0..self.layers.len() {
+                for i in 0..self.layers[r].len().saturating_sub(1) {
+                    let (v, w) = (self.layers[r][i], self.layers[r][i + 1]);
+                    if self.pair_crossings(w, v) < self.pair_crossings(v, w) {
+                        self.layers[r].swap(i, i + 1);
+                        self.pos[v] = i + 1;
+                        self.pos[w] = i;
+                        improved = true;
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+    }
+
+    /// Crossings among the edges of `v` and `w` when `v` is left of `w`
+    fn pair_crossings(&self, v: usize, w: usize) -> usize {
+        let count = |a: &[usize], b: &[usize]| -> usize {
+            a.iter().map(|&x| b.iter().filter(|&&y| self.pos[y] < self.pos[x]).count()).sum()
+        };
+        count(&self.up[v], &self.up[w]) + count(&self.down[v], &self.down[w])
+    }
+
+    /// Edges that cross an edge between two dummies. Alignment skips them so
+    /// long edges stay straight.
+    fn type1_conflicts(&self) -> HashSet<(usize, usize)> {
+        let mut marked = HashSet::new();
+        for i in 0..self.layers.len().saturating_sub(1) {
+            let (upper, lower) = (&self.layers[i], &self.layers[i + 1]);
+            let mut k0 = 0;
+            let mut l = 0;
+            for (l1, &v) in lower.iter().enumerate() {
+                let inner = self.up[v].iter().copied().find(|&u| self.is_dummy(v) && self.is_dummy(u));
+                if l1 + 1 < lower.len() && inner.is_none() {
+                    continue;
+                }
+                let k1 = inner.map_or(upper.len().saturating_sub(1), |u| self.pos[u]);
+                while l <= l1 {
+                    let w = lower[l];
+                    for &u in &self.up[w] {
+                        let k = self.pos[u];
+                        if (k < k0 || k > k1) && !(self.is_dummy(u) && self.is_dummy(w)) {
+                            marked.insert((u, w));
+                        }
+                    }
+                    l += 1;
+                }
+                k0 = k1;
+            }
+        }
+        marked
+    }
+
+    /// Horizontal centers of all nodes
+    fn assign_x(&self, gap: f64) -> Vec<f64> {
+        let conflicts = self.type1_conflicts();
+        let mut candidates: Vec<Vec<f64>> = Vec::with_capacity(4);
+        for from_above in [true, false] {
+            for leftmost in [true, false] {
+                candidates.push(self.compact(from_above, leftmost, &conflicts, gap));
+            }
+        }
+
+        // Align every candidate to the narrowest one: left-leaning ones on its
+        // left edge, right-leaning ones on its right edge
+        let extent = |xs: &[f64]| {
+            (0..self.len()).fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                let half = self.sizes[v].w as f64 / 2.0;
+                (lo.min(xs[v] - half), hi.max(xs[v] + half))
+            })
+        };
+        let narrowest = (0..candidates.len())
+            .min_by(|&a, &b| {
+                let (wa, wb) = (extent(&candidates[a]), extent(&candidates[b]));
+                (wa.1 - wa.0).total_cmp(&(wb.1 - wb.0))
+            })
+            .unwrap_or(0);
+        let (min_x, max_x) = extent(&candidates[narrowest]);
+        for (k, xs) in candidates.iter_mut().enumerate() {
+            let (lo, hi) = extent(xs);
+            let delta = if k % 2 == 0 { min_x - lo } else { max_x - hi };
+            xs.iter_mut().for_each(|x| *x += delta);
+        }
+
+        let mut x: Vec<f64> = (0..self.len())
+            .map(|v| {
+                let mut c = [candidates[0][v], candidates[1][v], candidates[2][v], candidates[3][v]];
+                c.sort_by(f64::total_cmp);
+                (c[1] + c[2]) / 2.0
+            })
+            .collect();
+
+        // Balancing can bring neighbours closer than allowed; push them apart again
+        for layer in &self.layers {
+            for pair in layer.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let min_b = x[a] + self.separation(a, b, gap);
+                if x[b] < min_b {
+                    x[b] = min_b;
+                }
+            }
+        }
+        x
+    }
+
+    /// Distance between the centers of two neighbours in a layer
+    fn separation(&self, a: usize, b: usize, gap: f64) -> f64 {
+        (self.sizes[a].w + self.sizes[b].w) as f64 / 2.0 + gap
+    }
+
+    /// One Brandes–Köpf candidate: align each node with a median neighbour in
+    /// the previous layer (from above or below), then pack the aligned blocks
+    /// to the left (or right)
+    fn compact(&self, from_above: bool, leftmost: bool, conflicts: &HashSet<(usize, usize)>, gap: f64) -> Vec<f64> {
+        let mut layers: Vec<Vec<usize>> = self.layers.clone();
+        if !from_above {
+            layers.reverse();
+        }
+        if !leftmost {
+            layers.iter_mut().for_each(|l| l.reverse());
+        }
+        let mut pos = vec![0; self.len()];
+        let mut layer_of = vec![0; self.len()];
+        for (li, layer) in layers.iter().enumerate() {
+            for (i, &v) in layer.iter().enumerate() {
+                pos[v] = i;
+                layer_of[v] = li;
+            }
+        }
+
+        // Vertical alignment
+        let mut root: Vec<usize> = (0..self.len()).collect();
+        let mut align: Vec<usize> = (0..self.len()).collect();
+        for layer in layers.iter().skip(1) {
+            let mut r: Option<usize> = None;
+            for &v in layer {
+                let mut neighbors: Vec<usize> = if from_above { self.up[v].clone() } else { self.down[v].clone() };
+                if neighbors.is_empty() {
+                    continue;
+                }
+                neighbors.sort_by_key(|&u| pos[u]);
+                let d = neighbors.len();
+                for m in [(d - 1) / 2, d / 2] {
+                    if align[v] != v {
+                        break;
+                    }
+                    let u = neighbors[m];
+                    let conflict = conflicts.contains(&(u, v)) || conflicts.contains(&(v, u));
+                    if !conflict && r.is_none_or(|r| r < pos[u]) {
+                        align[u] = v;
+                        root[v] = root[u];
+                        align[v] = root[v];
+                        r = Some(pos[u]);
+                    }
+                }
+            }
+        }
+
+        // Horizontal compaction
+        let mut c = Compaction {
+            graph: self,
+            layers: &layers,
+            layer_of,
+            pos,
+            root,
+            align,
+            sink: (0..self.len()).collect(),
+            shift: vec![f64::INFINITY; self.len()],
+            x: vec![None; self.len()],
+            gap,
+        };
+        for v in 0..self.len() {
+            if c.root[v] == v {
+                c.place_block(v);
+            }
+        }
+        (0..self.len())
+            .map(|v| {
+                let root = c.root[v];
+                let shift = c.shift[c.sink[root]];
+                let x = c.x[root].unwrap_or(0.0) + if shift < f64::INFINITY { shift } else { 0.0 };
+                if leftmost { x } else { -x }
+            })
+            .collect()
+    }
+
+    /// Top edge of every node: ranks are as tall as their tallest node, and
+    /// nodes are centered in their rank
+    fn assign_y(&self, rank_gap: i32) -> Vec<i32> {
+        let mut y = vec![0; self.len()];
+        let mut top = 0;
+        for layer in &self.layers {
+            let height = layer.iter().map(|&v| self.sizes[v].h).max().unwrap_or(0);
+            for &v in layer {
+                y[v] = top + (height - self.sizes[v].h) / 2;
+            }
+            top += height + rank_gap;
+        }
+        y
+    }
+}
+
+/// Horizontal compaction state for one Brandes–Köpf candidate
+struct Compaction<'a> {
+    graph: &'a LayerGraph,
+    /// Layers in the order this candidate sweeps them
+    layers: &'a [Vec<usize>],
+    layer_of: Vec<usize>,
+    pos: Vec<usize>,
+    root: Vec<usize>,
+    align: Vec<usize>,
+    sink: Vec<usize>,
+    shift: Vec<f64>,
+    x: Vec<Option<f64>>,
+    gap: f64,
+}
+
+impl Compaction<'_> {
+    fn place_block(&mut self, v: usize) {
+        if self.x[v].is_some() {
+            return;
+        }
+        self.x[v] = Some(0.0);
+        let mut w = v;
+        loop {
+            if self.pos[w] > 0 {
+                let pred = self.layers[self.layer_of[w]][self.pos[w] - 1];
+                let u = self.root[pred];
+                self.place_block(u);
+                if self.sink[v] == v {
+                    self.sink[v] = self.sink[u];
+                }
+                let sep = self.graph.separation(pred, w, self.gap);
+                let (xv, xu) = (self.x[v].unwrap_or(0.0), self.x[u].unwrap_or(0.0));
+                if self.sink[v] != self.sink[u] {
+                    let sink = self.sink[u];
+                    self.shift[sink] = self.shift[sink].min(xv - xu - sep);
+                } else {
+                    self.x[v] = Some(xv.max(xu + sep));
+                }
+            }
+            w = self.align[w];
+            if w == v {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::RectI;
+    use crate::layout::test_support::assert_disjoint;
+
+    fn rect(layout: &LayeredLayout, sizes: &[SizeI], v: usize) -> RectI {
+        let p = layout.pos[v];
+        RectI { x: p.x, y: p.y, w: sizes[v].w, h: sizes[v].h }
+    }
+
+    #[test]
+    fn test_ranks_break_cycles_and_keep_edges_short() {
+        // a -> b -> c -> a is a cycle; d only points at c
+        let sizes = vec![SizeI { w: 100, h: 40 }; 4];
+        let layout = layout_layered(&sizes, &[(0, 1), (1, 2), (2, 0), (3, 2)], 20, 40);
+        assert_eq!(&layout.rank[..3], [0, 1, 2]);
+        // The source is pulled down next to its child instead of sitting at the top
+        assert_eq!(layout.rank[3], 1);
+    }
+
+    #[test]
+    fn test_real_sizes_never_overlap() {
+        let sizes = vec![
+            SizeI { w: 300, h: 200 },
+            SizeI { w: 80, h: 80 },
+            SizeI { w: 220, h: 120 },
+            SizeI { w: 40, h: 300 },
+            SizeI { w: 150, h: 60 },
+            SizeI { w: 220, h: 120 },
+        ];
+        let edges = [(0, 1), (0, 2), (0, 3), (1, 4), (2, 5), (3, 5), (0, 5), (4, 5)];
+        let layout = layout_layered(&sizes, &edges, 24, 48);
+        let rects: Vec<RectI> = (0..sizes.len()).map(|i| rect(&layout, &sizes, i)).collect();
+        assert_disjoint(&rects);
+        // Ranks are stacked by their tallest node
+        assert!(layout.pos[1].y >= 200 + 48);
+        assert!(layout.pos.iter().any(|p| p.x == 0));
+    }
+
+    #[test]
+    fn test_crossings_are_removed() {
+        // Two parents whose children start out in crossed order
+        let sizes = vec![SizeI { w: 100, h: 40 }; 6];
+        let edges = [(0, 3), (0, 4), (1, 2), (1, 5)];
+        let layout = layout_layered(&sizes, &edges, 20, 40);
+        let x = |v: usize| layout.pos[v].x;
+        let left_parent_children = [x(3), x(4)];
+        let right_parent_children = [x(2), x(5)];
+        if x(0) < x(1) {
+            assert!(left_parent_children.iter().max() < right_parent_children.iter().min());
+        } else {
+            assert!(right_parent_children.iter().max() < left_parent_children.iter().min());
+        }
+    }
+
+    #[test]
+    fn test_long_edges_get_dummies() {
+        let sizes = vec![SizeI { w: 100, h: 40 }; 3];
+        let dag = break_cycles(3, &[(0, 1), (1, 2), (0, 2)]);
+        let rank = assign_ranks(3, &dag);
+        let graph = LayerGraph::new(&sizes, &dag, &rank);
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.layers.iter().map(Vec::len).collect::<Vec<_>>(), [1, 2, 1]);
+    }
+}
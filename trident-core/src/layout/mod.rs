@@ -4,25 +4,41 @@
 // Available layout algorithms:
 // - "hierarchical" (default): Graph-driven layout that places connected nodes closer together
 // - "grid": Simple left-to-right, top-to-bottom grid layout
+// - "force": Force-directed layout for networks without a clear hierarchy
+// - "tree": Tidy tree layout for inheritance hierarchies
 //
 // Submodules:
 // - spatial_grid: O(1) overlap detection
 // - adjacency: Edge weight computation
 // - placement: Graph-driven placement algorithm
+// - layered: Sugiyama-style layered layout used by the placement for nodes
 // - graph_driven: Default hierarchical layout
 // - grid: Simple grid layout
+// - force: Force-directed layout
+// - tree: Tidy tree layout
+// - notes: Note placement (runs at the end of every layout algorithm)
+// - text: Text measurement from embedded font metrics, for node and note sizes
+// - routing: Edge routing (runs after layout)
+// - test_support: Assertions shared by the layout tests
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::parser::{PointI, Diagram, GroupId, NodeId};
-use serde::Serialize;
+use crate::parser::{PointI, Diagram, Direction, GroupId, NodeId, NoteId};
+use serde::{Serialize, Serializer};
 
 mod spatial_grid;
 pub mod adjacency;
 pub mod placement;
+mod layered;
 pub mod algorithms;
+pub mod notes;
+pub mod routing;
+pub mod text;
+#[cfg(test)]
+mod test_support;
 
-pub use algorithms::{GraphDrivenLayout, layout_graph_driven, GridLayout, layout_grid};
+pub use algorithms::{GraphDrivenLayout, layout_graph_driven, GridLayout, layout_grid, ForceLayout, TreeLayout};
+pub use routing::{route_edges, route_note_connectors, EdgeRoute, EdgeRouting};
 
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
@@ -73,8 +89,13 @@ pub struct LayoutConfig {
     pub node_size: SizeI,
     /// Minimum size for groups (even if empty).
     pub min_group_size: SizeI,
-    /// Rendering constants for content-based sizing (mirrored from SVGNode.tsx)
+    /// Rendering constants for content-based sizing
     pub node_rendering: NodeRenderingConfig,
+    /// How edges are routed after layout (from the @routing directive)
+    pub edge_routing: EdgeRouting,
+    /// Rank direction of the hierarchical layout (from the @direction directive);
+    /// groups with their own @direction override it for their contents
+    pub direction: Direction,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -85,10 +106,30 @@ pub struct NodeRenderingConfig {
     pub line_height: i32,
     /// Space between lines for separator (---) lines
     pub separator_spacing: i32,
-    /// Average character width in monospace font (pixels)
-    pub char_width: i32,
+    /// CSS font stack the text is measured for
+    pub font_family: &'static str,
+    /// Width at which body and note lines wrap onto the next line (from the @wrap directive);
+    /// None keeps every line whole
+    pub wrap_width: Option<i32>,
 }
 
+impl NodeRenderingConfig {
+    /// The lines `text` is drawn as at `font_size`, wrapped if `wrap_width` is set
+    pub fn wrap(&self, text: &str, font_size: i32) -> Vec<String> {
+        match self.wrap_width {
+            Some(width) => wrap_text(text, font_size, width),
+            None => vec![text.to_string()],
+        }
+    }
+}
+
+/// Height of the UML template box of a generic node. It straddles the top
+/// border, so content starts half of it lower.
+pub const TEMPLATE_BOX_HEIGHT: i32 = 16;
+
+/// Size of the folded top-right corner of a note
+pub const NOTE_FOLD: i32 = 10;
+
 impl Default for LayoutConfig {
     fn default() -> Self {
         Self {
@@ -102,8 +143,11 @@ impl Default for LayoutConfig {
                 padding: 8,
                 line_height: 14,
                 separator_spacing: 10,
-                char_width: 7, // Approximate width of monospace character
+                font_family: text::FONT_FAMILY,
+                wrap_width: None,
             },
+            edge_routing: EdgeRouting::default(),
+            direction: Direction::default(),
         }
     }
 }
@@ -111,18 +155,38 @@ impl Default for LayoutConfig {
 #[derive(Debug, Clone, Serialize)]
 pub struct LayoutResult {
     /// Local positions (relative to parent group) for all groups/nodes.
+    #[serde(serialize_with = "serialize_sorted")]
     pub group_local_pos: HashMap<GroupId, PointI>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub node_local_pos: HashMap<NodeId, PointI>,
 
     /// World positions for all groups/nodes (after accumulation).
+    #[serde(serialize_with = "serialize_sorted")]
     pub group_world_pos: HashMap<GroupId, PointI>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub node_world_pos: HashMap<NodeId, PointI>,
 
     /// Group bounds in world coordinates (including padding).
+    #[serde(serialize_with = "serialize_sorted")]
     pub group_world_bounds: HashMap<GroupId, RectI>,
 
     /// Node bounds in world coordinates.
+    #[serde(serialize_with = "serialize_sorted")]
     pub node_world_bounds: HashMap<NodeId, RectI>,
+
+    /// Note bounds in world coordinates (notes are placed after everything else).
+    #[serde(serialize_with = "serialize_sorted")]
+    pub note_world_bounds: HashMap<NoteId, RectI>,
+}
+
+/// Serialize a map with its keys in ascending order so the JSON is stable across runs.
+fn serialize_sorted<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Serialize + Ord,
+    V: Serialize,
+    S: Serializer,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
 }
 
 // ============================================================================
@@ -135,15 +199,20 @@ pub trait LayoutStrategy {
     fn layout(&self, diagram: &Diagram, cfg: &LayoutConfig) -> LayoutResult;
 }
 
+/// Names accepted by `layout_diagram`; anything else falls back to "hierarchical"
+pub const LAYOUT_ALGORITHMS: &[&str] = &["hierarchical", "grid", "force", "tree"];
+
 /// Main entry point - dispatches to the appropriate layout algorithm.
 /// 
 /// # Arguments
 /// * `diagram` - The diagram to layout
 /// * `cfg` - Layout configuration
-/// * `algorithm` - Layout algorithm name: "hierarchical" (default) or "grid"
+/// * `algorithm` - Layout algorithm name: "hierarchical" (default), "grid", "force" or "tree"
 pub fn layout_diagram(diagram: &Diagram, cfg: &LayoutConfig, algorithm: &str) -> LayoutResult {
     match algorithm {
         "grid" => layout_grid(diagram, cfg),
+        "force" => layout_diagram_with_strategy(diagram, cfg, &ForceLayout),
+        "tree" => layout_diagram_with_strategy(diagram, cfg, &TreeLayout),
         "hierarchical" | _ => layout_graph_driven(diagram, cfg),
     }
 }
@@ -161,7 +230,9 @@ pub fn layout_diagram_with_strategy<S: LayoutStrategy>(
 // Shared Utilities
 // ============================================================================
 
-use crate::parser::compile::Node;
+use crate::parser::compile::{Node, Note};
+use crate::parser::format_type_params;
+use text::{text_width, wrap_text, BODY_FONT_SIZE, STEREOTYPE_FONT_SIZE, TEMPLATE_FONT_SIZE, TITLE_FONT_SIZE};
 
 /// Get the size for a node, considering kind, custom dimensions, and config defaults.
 /// For unconstrained nodes (no explicit width/height), calculates size based on content.
@@ -185,6 +256,22 @@ pub fn get_node_size(node: &Node, cfg: &LayoutConfig) -> SizeI {
     SizeI { w: width, h: height }
 }
 
+/// Size of a note: room for its longest line and every line, plus the folded corner
+pub fn get_note_size(note: &Note, cfg: &LayoutConfig) -> SizeI {
+    let r = &cfg.node_rendering;
+    let lines: Vec<String> = note.lines.iter().flat_map(|l| r.wrap(l, BODY_FONT_SIZE)).collect();
+    let longest = lines.iter().map(|l| text_width(l, BODY_FONT_SIZE)).max().unwrap_or(0);
+    SizeI {
+        w: (r.padding + longest + r.padding + NOTE_FOLD).max(cfg.node_size.w),
+        h: r.padding + lines.len().max(1) as i32 * r.line_height + r.padding,
+    }
+}
+
+/// Width of the template box of a generic node, sticking out `padding` past the right border
+pub fn template_box_width(node: &Node, r: &NodeRenderingConfig) -> i32 {
+    text_width(&format_type_params(&node.type_params), TEMPLATE_FONT_SIZE) + 2 * r.padding
+}
+
 /// Calculate width based on content for unconstrained nodes
 fn calculate_content_width(node: &Node, cfg: &LayoutConfig, min_width: i32) -> i32 {
     // For shape nodes ("node" kind), maintain square aspect ratio
@@ -193,8 +280,26 @@ fn calculate_content_width(node: &Node, cfg: &LayoutConfig, min_width: i32) -> i
         return height; // Make width equal to height for squares
     }
 
-    // For class nodes, use minimum width for now
-    min_width
+    // For class nodes, room for the widest line of text
+    let r = &cfg.node_rendering;
+    let title = node.label.clone().unwrap_or_else(|| node.id.name().to_string());
+    let mut widest = text_width(&title, TITLE_FONT_SIZE);
+    if !node.modifiers.is_empty() || node.kind != "class" {
+        widest = widest.max(text_width(&format_modifiers(&node.modifiers, &node.kind), STEREOTYPE_FONT_SIZE));
+    }
+    for line in node.body_lines.iter().filter(|l| !is_separator_line(l)) {
+        for row in r.wrap(line, BODY_FONT_SIZE) {
+            widest = widest.max(text_width(&row, BODY_FONT_SIZE));
+        }
+    }
+    let mut width = r.padding + widest + r.padding;
+
+    // Keep the template box clear of the title
+    if !node.type_params.is_empty() {
+        let title_end = r.padding + text_width(&title, TITLE_FONT_SIZE) + r.padding;
+        width = width.max(title_end + template_box_width(node, r) - r.padding);
+    }
+    width.max(min_width)
 }
 
 /// Calculate height based on content for unconstrained nodes
@@ -207,10 +312,16 @@ fn calculate_content_height(node: &Node, r: &NodeRenderingConfig) -> i32 {
     }
     num_lines += 1; // title
     num_lines += 1; // separator
-    num_lines += node.body_lines.len(); // body lines
+    // body lines, each as many rows as it wraps onto
+    num_lines += node.body_lines.iter()
+        .map(|l| if is_separator_line(l) { 1 } else { r.wrap(l, BODY_FONT_SIZE).len() })
+        .sum::<usize>();
+
+    // Room for the lower half of the template box
+    let template = if node.type_params.is_empty() { 0 } else { TEMPLATE_BOX_HEIGHT / 2 };
 
     // Height = padding + (num_lines * line_height) + padding
-    r.padding + (num_lines as i32 * r.line_height) + r.padding
+    template + r.padding + (num_lines as i32 * r.line_height) + r.padding
 }
 
 /// Format modifiers and kind for stereotype display (mirrored from SVGNode.tsx)
@@ -259,7 +370,7 @@ pub fn compute_group_local_bounds(
             w: cfg.min_group_size.w,
             h: cfg.min_group_size.h,
         });
-        let r = RectI { x: p.x, y: p.y, w: lb.w, h: lb.h };
+        let r = RectI { x: p.x + lb.x, y: p.y + lb.y, w: lb.w, h: lb.h };
         bb = if any { bb.union(&r) } else { any = true; r };
     }
 
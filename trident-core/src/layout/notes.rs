@@ -0,0 +1,147 @@
+// Note placement for Trident diagrams.
+//
+// Runs at the end of every layout algorithm, once nodes and groups have their
+// world bounds, so notes never push diagram elements around:
+// - A note for a node or group goes next to it: to the right, else to the
+//   left, below or above, whichever spot is free first.
+// - A note on a relation goes next to the point halfway between its endpoints.
+// - A free-standing note goes next to the named group it is written in; at
+//   top level, free-standing notes are lined up below the diagram.
+
+use std::collections::HashMap;
+
+use crate::parser::compile::Note;
+use crate::parser::{Diagram, GroupId, NodeId, NoteAnchor, NoteId};
+use super::{get_note_size, LayoutConfig, RectI, SizeI};
+
+/// World bounds for every note, avoiding nodes and notes placed before it
+pub fn place_notes(
+    diagram: &Diagram,
+    cfg: &LayoutConfig,
+    node_world_bounds: &HashMap<NodeId, RectI>,
+    group_world_bounds: &HashMap<GroupId, RectI>,
+) -> HashMap<NoteId, RectI> {
+    let mut taken: Vec<RectI> = node_world_bounds.values().copied().collect();
+    let bottom = node_world_bounds.values().chain(group_world_bounds.values()).map(RectI::bottom).max().unwrap_or(0);
+    let mut row_x = cfg.group_padding;
+
+    let mut placed = HashMap::new();
+    for note in &diagram.notes {
+        let size = get_note_size(note, cfg);
+        let rect = match anchor_rect(diagram, note, node_world_bounds, group_world_bounds) {
+            Some(target) => free_spot_next_to(&target, size, &taken, cfg.gap),
+            None => {
+                let rect = RectI { x: row_x, y: bottom + cfg.gap, w: size.w, h: size.h };
+                row_x += size.w + cfg.gap;
+                rect
+            }
+        };
+        taken.push(rect);
+        placed.insert(note.id, rect);
+    }
+    placed
+}
+
+/// What a note is placed next to; None for free-standing notes at top level
+fn anchor_rect(
+    diagram: &Diagram,
+    note: &Note,
+    node_world_bounds: &HashMap<NodeId, RectI>,
+    group_world_bounds: &HashMap<GroupId, RectI>,
+) -> Option<RectI> {
+    match note.anchor {
+        NoteAnchor::Node(nid) => node_world_bounds.get(&nid).copied(),
+        NoteAnchor::Group(gid) => group_world_bounds.get(&gid).copied(),
+        NoteAnchor::Edge(i) => {
+            let (from, to) = match diagram.edges.get(i) {
+                Some(e) => (node_world_bounds.get(&e.from)?, node_world_bounds.get(&e.to)?),
+                None => {
+                    let e = diagram.group_edges.get(i - diagram.edges.len())?;
+                    (group_world_bounds.get(&e.from)?, group_world_bounds.get(&e.to)?)
+                }
+            };
+            let mid_x = (from.x + from.w / 2 + to.x + to.w / 2) / 2;
+            let mid_y = (from.y + from.h / 2 + to.y + to.h / 2) / 2;
+            Some(RectI { x: mid_x, y: mid_y, w: 0, h: 0 })
+        }
+        NoteAnchor::None => {
+            // Nearest named group around the note (anonymous groups aren't drawn)
+            let mut gid = note.group;
+            loop {
+                let group = &diagram.groups[gid.0];
+                if gid == diagram.root {
+                    return None;
+                }
+                if group.id.is_some() {
+                    return group_world_bounds.get(&gid).copied();
+                }
+                gid = group.parent?;
+            }
+        }
+    }
+}
+
+/// The first free spot of `size` beside `target`, vertically or horizontally centered on it
+fn free_spot_next_to(target: &RectI, size: SizeI, taken: &[RectI], gap: i32) -> RectI {
+    let center_x = target.x + (target.w - size.w) / 2;
+    let center_y = target.y + (target.h - size.h) / 2;
+    let candidates = [
+        (target.right() + gap, center_y),
+        (target.x - gap - size.w, center_y),
+        (center_x, target.bottom() + gap),
+        (center_x, target.y - gap - size.h),
+    ];
+    let is_free = |r: &RectI| !taken.iter().any(|t| t.overlaps(r));
+    let at = |(x, y): (i32, i32)| RectI { x, y, w: size.w, h: size.h };
+    if let Some(rect) = candidates.into_iter().map(at).find(is_free) {
+        return rect;
+    }
+
+    // Everything around is taken: move down on the right until there is room
+    let mut rect = at(candidates[0]);
+    while !is_free(&rect) {
+        rect.y += gap.max(1);
+    }
+    rect
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::layout_diagram;
+    use crate::layout::test_support::assert_disjoint;
+    use crate::parser::{compile, parse_file};
+
+    #[test]
+    fn test_notes_are_placed_next_to_their_targets() {
+        let diagram = compile(&parse_file(
+            "class A\nclass B\nA --|> B\nnote for A \"first\"\nnote for A \"second\"\nnote on A --|> B \"why\"\nnote \"legend\"\n",
+        ).unwrap()).unwrap();
+        let layout = layout_diagram(&diagram, &LayoutConfig::default(), "hierarchical");
+        let a = layout.node_world_bounds[&NodeId(0)];
+        let notes: Vec<RectI> = (0..4).map(|i| layout.note_world_bounds[&NoteId(i)]).collect();
+
+        // Right of A, then the next free side
+        assert_eq!(notes[0].x, a.right() + 24);
+        assert!(!notes[1].overlaps(&notes[0]) && !notes[1].overlaps(&a));
+        assert!(notes[1].x + notes[1].w <= a.x || notes[1].y >= a.bottom() || notes[1].bottom() <= a.y);
+
+        // Nothing overlaps nodes or other notes
+        for (i, n) in notes.iter().enumerate() {
+            assert!(layout.node_world_bounds.values().all(|r| !r.overlaps(n)), "note {} overlaps a node", i);
+        }
+        assert_disjoint(&notes);
+
+        // The free-standing note sits below everything else
+        let bottom = layout.group_world_bounds.values().map(RectI::bottom).max().unwrap();
+        assert!(notes[3].y > bottom);
+    }
+
+    #[test]
+    fn test_crowded_target_without_gap() {
+        let target = RectI { x: 0, y: 0, w: 100, h: 50 };
+        let taken = [RectI { x: -100, y: -100, w: 400, h: 200 }];
+        let rect = free_spot_next_to(&target, SizeI { w: 40, h: 20 }, &taken, 0);
+        assert_eq!(rect, RectI { x: 100, y: 100, w: 40, h: 20 });
+    }
+}
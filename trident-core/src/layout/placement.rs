@@ -4,11 +4,15 @@
 // Goals:
 // - Structure: Nodes align in clearer rows/columns (ranks).
 // - Flow: Dependency direction (Inheritance, Compositions) flows Top-Down (or Left-Right).
-// - Simplicity: Strict grid placement, no complex 2D searching.
+// - Nodes: layered layout with their real sizes (see layered.rs); nodes without
+//   relations are packed in rows below it.
+// - Groups: ranked rows with barycenter ordering.
 
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet, VecDeque};
-use crate::parser::{PointI, Diagram, GroupId, NodeId};
-use super::{RectI, LayoutConfig, get_node_size};
+use crate::parser::{PointI, Diagram, Direction, GroupId, NodeId};
+use super::{RectI, SizeI, LayoutConfig, get_node_size};
+use super::layered::layout_layered;
 
 use super::spatial_grid::SpatialGrid;
 use super::adjacency::Adjacency;
@@ -24,6 +28,13 @@ pub fn layout_group_children_graph_driven(
     group_local_bounds: &HashMap<GroupId, RectI>,
 ) {
     let g = &diagram.groups[gid.0];
+    let frame = RankFrame(group_direction(diagram, gid, cfg));
+    // A child group's box sits at its position plus the offset of its local bounds
+    let group_bounds = |cgid: &GroupId| {
+        group_local_bounds.get(cgid).copied().unwrap_or(RectI {
+            x: 0, y: 0, w: cfg.min_group_size.w, h: cfg.min_group_size.h,
+        })
+    };
 
     // 1. Separate fixed and free items
     let mut fixed_groups: Vec<GroupId> = Vec::new();
@@ -57,23 +68,21 @@ pub fn layout_group_children_graph_driven(
         node_local_pos.insert(*nid, p);
     }
 
-    // 3. Initialize spatial grid with fixed items
+    // 3. Initialize spatial grid with fixed items, in the rank frame placement works in
     // Use max of class and node sizes for cell sizing
     let cell_size = cfg.class_size.w.max(cfg.class_size.h).max(cfg.node_size.w).max(cfg.node_size.h);
     let mut spatial = SpatialGrid::new(cell_size);
 
     for cgid in &fixed_groups {
         let p = *group_local_pos.get(cgid).unwrap();
-        let lb = group_local_bounds.get(cgid).copied().unwrap_or(RectI {
-            x: 0, y: 0, w: cfg.min_group_size.w, h: cfg.min_group_size.h,
-        });
-        spatial.insert(RectI { x: p.x, y: p.y, w: lb.w, h: lb.h });
+        let lb = group_bounds(cgid);
+        spatial.insert(frame.to_frame(RectI { x: p.x + lb.x, y: p.y + lb.y, w: lb.w, h: lb.h }));
     }
     for nid in &fixed_nodes {
         let p = *node_local_pos.get(nid).unwrap();
         let node = &diagram.nodes[nid.0];
         let sz = get_node_size(node, cfg);
-        spatial.insert(RectI { x: p.x, y: p.y, w: sz.w, h: sz.h });
+        spatial.insert(frame.to_frame(RectI { x: p.x, y: p.y, w: sz.w, h: sz.h }));
     }
 
     // 4. Place free groups FIRST (Hierarchical)
@@ -83,6 +92,7 @@ pub fn layout_group_children_graph_driven(
             diagram,
             &free_groups,
             cfg,
+            frame,
             &mut spatial,
             group_local_pos,
             group_local_bounds,
@@ -90,22 +100,143 @@ pub fn layout_group_children_graph_driven(
     }
 
     // 5. Place free nodes (Hierarchical)
+    let mut anchored: Vec<NodeId> = Vec::new();
     if !free_nodes.is_empty() {
-        layout_nodes_hierarchical(
+        anchored = layout_nodes_hierarchical(
              diagram,
              &free_nodes,
              &fixed_nodes,
              cfg,
+             frame,
              &mut spatial,
              node_local_pos,
-             group_local_pos,
         );
     }
+
+    // 6. Map free items from the rank frame back to local coordinates
+    let mut placed_groups: Vec<(GroupId, RectI)> = free_groups.iter().map(|cgid| {
+        let p = group_local_pos[cgid];
+        let lb = group_bounds(cgid);
+        let sz = frame.size(SizeI { w: lb.w, h: lb.h });
+        (*cgid, frame.to_local(RectI { x: p.x, y: p.y, w: sz.w, h: sz.h }))
+    }).collect();
+    let mut placed_nodes: Vec<(NodeId, RectI)> = free_nodes.iter().map(|nid| {
+        let p = node_local_pos[nid];
+        let sz = frame.size(get_node_size(&diagram.nodes[nid.0], cfg));
+        (*nid, frame.to_local(RectI { x: p.x, y: p.y, w: sz.w, h: sz.h }))
+    }).collect();
+
+    // Mirrored directions end up at negative coordinates; move the free items
+    // back next to the padding, and further along the rank axis until they clear
+    // the fixed items and the nodes placed around those, which stay where they are
+    if frame.is_mirrored() {
+        let horizontal = frame.0.is_horizontal();
+        let along = |r: &RectI| if horizontal { (r.x, r.right()) } else { (r.y, r.bottom()) };
+        let mut stay: Vec<RectI> = fixed_groups.iter().map(|cgid| {
+            let p = group_local_pos[cgid];
+            let lb = group_bounds(cgid);
+            RectI { x: p.x + lb.x, y: p.y + lb.y, w: lb.w, h: lb.h }
+        }).collect();
+        stay.extend(fixed_nodes.iter().map(|nid| {
+            let p = node_local_pos[nid];
+            let sz = get_node_size(&diagram.nodes[nid.0], cfg);
+            RectI { x: p.x, y: p.y, w: sz.w, h: sz.h }
+        }));
+        stay.extend(placed_nodes.iter().filter(|(nid, _)| anchored.contains(nid)).map(|&(_, r)| r));
+        let stay: Vec<RectI> = stay.iter()
+            .map(|r| RectI { x: r.x - cfg.gap, y: r.y - cfg.gap, w: r.w + cfg.gap * 2, h: r.h + cfg.gap * 2 })
+            .collect();
+
+        let groups = placed_groups.iter_mut().map(|(_, r)| r);
+        let nodes = placed_nodes.iter_mut().filter(|(nid, _)| !anchored.contains(nid)).map(|(_, r)| r);
+        let mut rects: Vec<&mut RectI> = groups.chain(nodes).collect();
+        let mut shift = cfg.group_padding - rects.iter().map(|r| along(r).0).min().unwrap_or(0);
+        loop {
+            let shifted = |r: &RectI| if horizontal { RectI { x: r.x + shift, ..*r } } else { RectI { y: r.y + shift, ..*r } };
+            let needed = rects.iter()
+                .map(|r| shifted(r))
+                .flat_map(|r| stay.iter().filter(move |s| s.overlaps(&r)).map(move |s| along(s).1 - along(&r).0))
+                .max();
+            match needed {
+                Some(step) => shift += step,
+                None => break,
+            }
+        }
+        for r in rects.iter_mut() {
+            if horizontal {
+                r.x += shift;
+            } else {
+                r.y += shift;
+            }
+        }
+    }
+
+    for (cgid, r) in placed_groups {
+        let lb = group_bounds(&cgid);
+        group_local_pos.insert(cgid, PointI { x: r.x - lb.x, y: r.y - lb.y });
+    }
+    for (nid, r) in placed_nodes {
+        node_local_pos.insert(nid, PointI { x: r.x, y: r.y });
+    }
+}
+
+/// Rank direction for the contents of `gid`: its own @direction, else the
+/// nearest enclosing group's, else the file's
+fn group_direction(diagram: &Diagram, mut gid: GroupId, cfg: &LayoutConfig) -> Direction {
+    loop {
+        let g = &diagram.groups[gid.0];
+        if let Some(direction) = g.direction {
+            return direction;
+        }
+        match g.parent {
+            Some(parent) => gid = parent,
+            None => return cfg.direction,
+        }
+    }
+}
+
+/// Hierarchical placement always works top-down: ranks grow along y and
+/// siblings within a rank along x. This maps group-local rectangles into that
+/// frame and back for the other directions, transposing for LR/RL and
+/// mirroring around 0 for BT/RL.
+#[derive(Debug, Clone, Copy)]
+struct RankFrame(Direction);
+
+impl RankFrame {
+    fn is_mirrored(self) -> bool {
+        matches!(self.0, Direction::BT | Direction::RL)
+    }
+
+    /// Size of an item in the frame
+    fn size(self, sz: SizeI) -> SizeI {
+        if self.0.is_horizontal() { SizeI { w: sz.h, h: sz.w } } else { sz }
+    }
+
+    /// Group-local rectangle -> frame rectangle
+    fn to_frame(self, r: RectI) -> RectI {
+        match self.0 {
+            Direction::TB => r,
+            Direction::BT => RectI { y: -r.bottom(), ..r },
+            Direction::LR => RectI { x: r.y, y: r.x, w: r.h, h: r.w },
+            Direction::RL => RectI { x: r.y, y: -r.right(), w: r.h, h: r.w },
+        }
+    }
+
+    /// Frame rectangle -> group-local rectangle
+    fn to_local(self, r: RectI) -> RectI {
+        match self.0 {
+            Direction::TB => r,
+            Direction::BT => RectI { y: -r.bottom(), ..r },
+            Direction::LR => RectI { x: r.y, y: r.x, w: r.h, h: r.w },
+            Direction::RL => RectI { x: -r.bottom(), y: r.x, w: r.h, h: r.w },
+        }
+    }
 }
 
 /// Helper function to get arrow direction for ranking.
 /// Returns (parent_id, child_id) where parent should be above child.
-fn get_edge_direction(arrow: &str, from: NodeId, to: NodeId) -> (NodeId, NodeId) {
+/// Works for node and group endpoints alike.
+pub(crate) fn get_edge_direction<T>(arrow: &str, from: T, to: T) -> (T, T) {
     use crate::parser::{get_arrow_definition, get_base_arrow_name, ARROW_DEFINITIONS};
     
     if let Some(def) = get_arrow_definition(arrow) {
@@ -152,6 +283,7 @@ fn layout_groups_hierarchical(
     diagram: &Diagram,
     groups: &[GroupId],
     cfg: &LayoutConfig,
+    frame: RankFrame,
     spatial: &mut SpatialGrid,
     group_local_pos: &mut HashMap<GroupId, PointI>,
     group_local_bounds: &HashMap<GroupId, RectI>,
@@ -182,6 +314,23 @@ fn layout_groups_hierarchical(
         }
     }
 
+    // Group-to-group relations rank like node edges, lifted to the groups laid out here
+    let sibling_of = |mut gid: GroupId| loop {
+        if group_set.contains(&gid) {
+            return Some(gid);
+        }
+        gid = diagram.groups[gid.0].parent?;
+    };
+    for edge in &diagram.group_edges {
+        let (parent, child) = get_edge_direction(&edge.arrow, edge.from, edge.to);
+        if let (Some(pgid), Some(cgid)) = (sibling_of(parent), sibling_of(child))
+            && pgid != cgid
+        {
+            adj.entry(pgid).or_default().push(cgid);
+            rev_adj.entry(cgid).or_default().push(pgid);
+        }
+    }
+
     // Assign Ranks (BFS)
     let mut ranks: HashMap<GroupId, i32> = HashMap::new();
     let mut visited: HashSet<GroupId> = HashSet::new();
@@ -267,19 +416,21 @@ fn layout_groups_hierarchical(
     for r in 0..=max_rank {
         if let Some(row_groups) = rank_map.get_mut(&r) {
             let mut current_x = start_x;
-            let _row_start_y = next_y;
             let mut current_y = next_y;
+            // Bottom of the tallest group in the current row, for wrapping
+            let mut row_bottom = next_y;
             let limit_w = if cfg.max_row_w > 0 { cfg.max_row_w } else { 1200 };
 
             for &gid in row_groups.iter() {
-                 let bounds = group_local_bounds.get(&gid).copied().unwrap_or(RectI { 
+                 let lb = group_local_bounds.get(&gid).copied().unwrap_or(RectI { 
                      x: 0, y: 0, w: cfg.min_group_size.w, h: cfg.min_group_size.h 
                 });
+                 let bounds = frame.size(SizeI { w: lb.w, h: lb.h });
 
                  // Check wrap
                  if current_x + bounds.w > limit_w && current_x > start_x {
                      current_x = start_x;
-                     current_y += 300;
+                     current_y = row_bottom + cfg.gap * 2;
                  }
 
                  let mut pos = PointI { x: current_x, y: current_y };
@@ -287,10 +438,10 @@ fn layout_groups_hierarchical(
                  // Overlap check and shift
                  let mut rect = RectI { x: pos.x, y: pos.y, w: bounds.w, h: bounds.h };
                  while spatial.overlaps_any(&rect) {
-                     pos.x += 50;
+                     pos.x += cfg.gap.max(1);
                      if pos.x + bounds.w > limit_w {
                          pos.x = start_x;
-                         pos.y += 100;
+                         pos.y += cfg.gap.max(1);
                      }
                      rect = RectI { x: pos.x, y: pos.y, w: bounds.w, h: bounds.h };
                  }
@@ -305,198 +456,167 @@ fn layout_groups_hierarchical(
                  
                  current_x = pos.x + bounds.w + cfg.gap;
                  if pos.y > current_y { current_y = pos.y; }
+                 row_bottom = row_bottom.max(pos.y + bounds.h);
             }
         }
     }
 }
 
-/// Layout nodes using strict Hierarchical (Ranked) approach.
+/// Layout nodes as a layered graph (see `layered`). Nodes related to a fixed
+/// node, directly or through other nodes here, are laid out together with it
+/// and placed relative to its position; nodes without relations are packed in
+/// rows below the rest. Returns the nodes placed relative to a fixed node.
 fn layout_nodes_hierarchical(
     diagram: &Diagram,
     nodes: &[NodeId],
     fixed_nodes: &[NodeId],
     cfg: &LayoutConfig,
+    frame: RankFrame,
     spatial: &mut SpatialGrid,
     node_local_pos: &mut HashMap<NodeId, PointI>,
-    _group_local_pos: &HashMap<GroupId, PointI>,
-) {
-    // 1. Build Dependency Graph
-    let mut all_nodes = Vec::with_capacity(nodes.len() + fixed_nodes.len());
-    all_nodes.extend_from_slice(nodes);
-    all_nodes.extend_from_slice(fixed_nodes);
-
-    let (adj, rev_adj) = build_dependency_graph(diagram, &all_nodes);
-
-    // 2. Assign Ranks
-    let mut ranks: HashMap<NodeId, i32> = HashMap::new();
-    let mut visited: HashSet<NodeId> = HashSet::new();
-    let mut queue: VecDeque<(NodeId, i32)> = VecDeque::new();
-
-    // Use class_size for layer calculations (largest node type)
-    let layer_height = cfg.class_size.h + cfg.gap * 2;
-
-    // Seed Fixed Nodes as Visited/Ranked based on Y position
-    for &fnid in fixed_nodes {
-        if let Some(pos) = node_local_pos.get(&fnid) {
-            let r = (pos.y as f32 / layer_height as f32).max(0.0) as i32;
-            ranks.insert(fnid, r);
-            visited.insert(fnid);
-            queue.push_back((fnid, r));
-        }
-    }
-
-    // Find roots among FREE nodes
-    for &nid in nodes {
-        if !visited.contains(&nid) {
-             if !rev_adj.contains_key(&nid) {
-                 queue.push_back((nid, 0));
-                 visited.insert(nid);
-             }
-        }
-    }
-    
-    if queue.is_empty() {
-         for &nid in nodes {
-             if !visited.contains(&nid) {
-                 queue.push_back((nid, 0));
-                 visited.insert(nid);
-                 break;
-             }
-         }
+) -> Vec<NodeId> {
+    let start_x = cfg.group_padding;
+    let start_y = cfg.group_padding;
+    let rank_gap = cfg.gap * 2;
+    let size_of = |nid: NodeId| frame.size(get_node_size(&diagram.nodes[nid.0], cfg));
+
+    // 1. Relations among these nodes and the fixed ones; a set of related nodes
+    //    is anchored at its first fixed node
+    let all: Vec<NodeId> = nodes.iter().chain(fixed_nodes).copied().collect();
+    let (adj, rev_adj) = build_dependency_graph(diagram, &all);
+    let related = |nid: &NodeId| adj.contains_key(nid) || rev_adj.contains_key(nid);
+    let component = components(&all, &adj);
+    let mut anchors: HashMap<usize, NodeId> = HashMap::new();
+    for nid in fixed_nodes.iter().filter(|nid| related(nid)) {
+        anchors.entry(component[nid]).or_insert(*nid);
     }
-
-    while let Some((nid, rank)) = queue.pop_front() {
-        if !fixed_nodes.contains(&nid) {
-             ranks.insert(nid, rank);
+    let is_anchored = |nid: &NodeId| anchors.contains_key(&component[nid]);
+    let (anchored, connected): (Vec<NodeId>, Vec<NodeId>) = nodes.iter().filter(|nid| related(nid)).partition(|nid| is_anchored(nid));
+    let loose: Vec<NodeId> = nodes.iter().filter(|nid| !related(nid)).copied().collect();
+
+    // Layered layout of a set of nodes closed under relations
+    let layered_of = |members: &[NodeId]| {
+        let slot: HashMap<NodeId, usize> = members.iter().enumerate().map(|(k, &nid)| (nid, k)).collect();
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        for (k, nid) in members.iter().enumerate() {
+            for child in adj.get(nid).into_iter().flatten() {
+                edges.push((k, slot[child]));
+            }
         }
-        
-        if let Some(neighbors) = adj.get(&nid) {
-            for &neighbor in neighbors {
-                if !visited.contains(&neighbor) && all_nodes.contains(&neighbor) {
-                    visited.insert(neighbor);
-                    queue.push_back((neighbor, rank + 1));
-                }
+        let sizes: Vec<SizeI> = members.iter().map(|&nid| size_of(nid)).collect();
+        (layout_layered(&sizes, &edges, cfg.gap, rank_gap), sizes)
+    };
+
+    // 2. Anchored nodes: each set is moved so that its fixed node lands on its
+    //    position; a node that would overlap something moves down until it is clear
+    if !anchored.is_empty() {
+        let members: Vec<NodeId> = all.iter().filter(|nid| related(nid) && is_anchored(nid)).copied().collect();
+        let index: HashMap<NodeId, usize> = members.iter().enumerate().map(|(k, &nid)| (nid, k)).collect();
+        let (layered, sizes) = layered_of(&members);
+        for (k, nid) in members.iter().enumerate().filter(|(_, nid)| nodes.contains(nid)) {
+            let anchor = anchors[&component[nid]];
+            let p = diagram.nodes[anchor.0].pos.unwrap_or(PointI { x: 0, y: 0 });
+            let sz = get_node_size(&diagram.nodes[anchor.0], cfg);
+            let fixed = frame.to_frame(RectI { x: p.x, y: p.y, w: sz.w, h: sz.h });
+            let at = layered.pos[index[&anchor]];
+            let mut placed = RectI {
+                x: fixed.x - at.x + layered.pos[k].x,
+                y: fixed.y - at.y + layered.pos[k].y,
+                w: sizes[k].w,
+                h: sizes[k].h,
+            };
+            while spatial.overlaps_any(&placed) {
+                placed.y += cfg.gap.max(1);
             }
+            node_local_pos.insert(*nid, PointI { x: placed.x, y: placed.y });
+            spatial.insert(placed);
         }
     }
 
-    // Handle remaining unvisited (cycles or disconnected)
-    for &nid in nodes {
-        if !visited.contains(&nid) {
-             let mut sub_queue = VecDeque::new();
-             sub_queue.push_back((nid, 0));
-             visited.insert(nid);
-             while let Some((snid, srank)) = sub_queue.pop_front() {
-                 if !fixed_nodes.contains(&snid) {
-                    ranks.insert(snid, srank);
-                 }
-                if let Some(neighbors) = adj.get(&snid) {
-                    for &neighbor in neighbors {
-                        if !visited.contains(&neighbor) && all_nodes.contains(&neighbor) {
-                            visited.insert(neighbor);
-                            sub_queue.push_back((neighbor, srank + 1));
-                        }
-                    }
-                }
-             }
+    // 3. Other related nodes: a rank that would overlap fixed items or groups
+    //    moves down together with every rank after it
+    let mut next_y = start_y;
+    if !connected.is_empty() {
+        let (layered, connected_sizes) = layered_of(&connected);
+        let rect = |k: usize, offset_y: i32| {
+            let p = layered.pos[k];
+            RectI { x: start_x + p.x, y: offset_y + p.y, w: connected_sizes[k].w, h: connected_sizes[k].h }
+        };
+
+        let mut offset_y = start_y;
+        let ranks = layered.rank.iter().max().map_or(0, |r| r + 1);
+        for r in 0..ranks {
+            let in_rank: Vec<usize> = (0..connected.len()).filter(|&k| layered.rank[k] == r).collect();
+            while in_rank.iter().any(|&k| spatial.overlaps_any(&rect(k, offset_y))) {
+                offset_y += cfg.gap.max(1);
+            }
+            for &k in &in_rank {
+                let placed = rect(k, offset_y);
+                node_local_pos.insert(connected[k], PointI { x: placed.x, y: placed.y });
+                spatial.insert(placed);
+                next_y = next_y.max(placed.bottom() + rank_gap);
+            }
         }
     }
 
-    let max_rank = ranks.values().max().copied().unwrap_or(0);
-
-    // 3. Group by Rank (only FREE nodes)
-    let mut rank_map: HashMap<i32, Vec<NodeId>> = HashMap::new();
-    for &nid in nodes {
-        if let Some(&r) = ranks.get(&nid) {
-            rank_map.entry(r).or_default().push(nid);
+    // 4. Unrelated nodes in rows, wrapping at the row limit
+    let limit_w = if cfg.max_row_w > 0 { cfg.max_row_w } else { 1200 };
+    let mut current_x = start_x;
+    let mut current_y = next_y;
+    let mut row_bottom = next_y;
+    for &nid in &loose {
+        let sz = size_of(nid);
+        if current_x + sz.w > limit_w && current_x > start_x {
+            current_x = start_x;
+            current_y = row_bottom + rank_gap;
         }
-    }
-    
-    // 3.5. Minimize Edge Crossings (Barycenter Heuristic)
-    let iterations = 12;
-    for _ in 0..iterations {
-        // Down-sweep
-        for r in 1..=max_rank {
-            if let Some(prev_row_nodes) = rank_map.get(&(r - 1)).cloned() { 
-                let prev_pos: HashMap<NodeId, usize> = prev_row_nodes.iter().enumerate().map(|(i, &n)| (n, i)).collect();
-                
-                if let Some(row_nodes) = rank_map.get_mut(&r) {
-                    row_nodes.sort_by(|&a, &b| {
-                        let avg_a = get_barycenter(a, &rev_adj, &prev_pos);
-                        let avg_b = get_barycenter(b, &rev_adj, &prev_pos);
-                        avg_a.partial_cmp(&avg_b).unwrap_or(std::cmp::Ordering::Equal)
-                    });
-                }
+
+        // Check overlap (backup)
+        let mut rect = RectI { x: current_x, y: current_y, w: sz.w, h: sz.h };
+        while spatial.overlaps_any(&rect) {
+            rect.x += cfg.gap.max(1);
+            if rect.right() > limit_w {
+                rect.x = start_x;
+                rect.y += cfg.gap.max(1);
             }
         }
 
-        // Up-sweep
-        for r in (0..max_rank).rev() {
-            if let Some(next_row_nodes) = rank_map.get(&(r + 1)).cloned() {
-                let next_pos: HashMap<NodeId, usize> = next_row_nodes.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        current_x = rect.right() + cfg.gap;
+        current_y = rect.y;
+        row_bottom = row_bottom.max(rect.bottom());
+        node_local_pos.insert(nid, PointI { x: rect.x, y: rect.y });
+        spatial.insert(rect);
+    }
+    anchored
+}
 
-                if let Some(row_nodes) = rank_map.get_mut(&r) {
-                    row_nodes.sort_by(|&a, &b| {
-                        let avg_a = get_barycenter(a, &adj, &next_pos);
-                        let avg_b = get_barycenter(b, &adj, &next_pos);
-                        avg_a.partial_cmp(&avg_b).unwrap_or(std::cmp::Ordering::Equal)
-                    });
-                }
-            }
+/// Connected component of every node in `scope`, named by the index of its first node
+fn components(scope: &[NodeId], adj: &HashMap<NodeId, Vec<NodeId>>) -> HashMap<NodeId, usize> {
+    let mut neighbors: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    for (&parent, children) in adj {
+        for &child in children {
+            neighbors.entry(parent).or_default().push(child);
+            neighbors.entry(child).or_default().push(parent);
         }
     }
 
-    // 4. Assign Grid Positions
-    let start_x = cfg.group_padding;
-    let start_y = cfg.group_padding;
-    // Use class_size for grid calculations (largest node type)
-    let col_width = cfg.class_size.w + cfg.gap;
-    let row_height = cfg.class_size.h + cfg.gap * 2;
-
-    let mut next_y = start_y;
-
-    for r in 0..=max_rank {
-        if let Some(row_nodes) = rank_map.get_mut(&r) {
-            let rank_start_y = next_y;
-            let mut current_y = rank_start_y;
-            let mut current_x = start_x;
-            
-            let limit_w = if cfg.max_row_w > 0 { cfg.max_row_w } else { 1200 };
-
-            for &nid in row_nodes.iter() {
-                 let node = &diagram.nodes[nid.0];
-                 let node_sz = get_node_size(node, cfg);
-                 let mut pos = PointI { x: current_x, y: current_y };
-                 
-                 // Check wrapping
-                 if current_x + node_sz.w > limit_w && current_x > start_x {
-                     current_x = start_x;
-                     current_y += row_height;
-                     pos.x = current_x;
-                     pos.y = current_y;
-                 }
-                 
-                 // Check overlap (backup)
-                 let mut safe_pos = pos;
-                 let mut rect = RectI { x: safe_pos.x, y: safe_pos.y, w: node_sz.w, h: node_sz.h };
-                 while spatial.overlaps_any(&rect) {
-                     safe_pos.x += col_width;
-                      if safe_pos.x + node_sz.w > limit_w {
-                         safe_pos.x = start_x;
-                         safe_pos.y += row_height;
-                      }
-                      rect = RectI { x: safe_pos.x, y: safe_pos.y, w: node_sz.w, h: node_sz.h };
-                 }
-
-                 current_x = safe_pos.x + node_sz.w + cfg.gap;
-                 current_y = safe_pos.y;
-                 
-                 node_local_pos.insert(nid, safe_pos);
-                 spatial.insert(RectI { x: safe_pos.x, y: safe_pos.y, w: node_sz.w, h: node_sz.h });
+    let mut component: HashMap<NodeId, usize> = HashMap::new();
+    for (i, &start) in scope.iter().enumerate() {
+        if component.contains_key(&start) {
+            continue;
+        }
+        component.insert(start, i);
+        let mut stack = vec![start];
+        while let Some(nid) = stack.pop() {
+            for &next in neighbors.get(&nid).into_iter().flatten() {
+                if let Entry::Vacant(entry) = component.entry(next) {
+                    entry.insert(i);
+                    stack.push(next);
+                }
             }
-            next_y = current_y + row_height;
         }
     }
+    component
 }
 
 /// Build directed adjacency for hierarchy.
@@ -516,28 +636,6 @@ fn build_dependency_graph(diagram: &Diagram, scope: &[NodeId]) -> (HashMap<NodeI
     (adj, rev_adj)
 }
 
-/// Calculate average position (barycenter) of connected nodes in the adjacent rank.
-fn get_barycenter(
-    nid: NodeId,
-    adj: &HashMap<NodeId, Vec<NodeId>>,
-    neighbor_pos: &HashMap<NodeId, usize>,
-) -> f32 {
-    if let Some(neighbors) = adj.get(&nid) {
-        let mut sum = 0.0;
-        let mut count = 0.0;
-        for neighbor in neighbors {
-            if let Some(&pos) = neighbor_pos.get(neighbor) {
-                sum += pos as f32;
-                count += 1.0;
-            }
-        }
-        if count > 0.0 {
-            return sum / count;
-        }
-    }
-    0.0
-}
-
 fn get_group_barycenter(
     gid: GroupId,
     adj: &HashMap<GroupId, Vec<GroupId>>,
@@ -558,3 +656,117 @@ fn get_group_barycenter(
     }
     0.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::layout_diagram;
+    use crate::layout::test_support::assert_disjoint;
+    use crate::parser::{compile, parse_file};
+
+    fn node_bounds(src: &str, direction: Direction) -> Vec<RectI> {
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let cfg = LayoutConfig { direction, ..LayoutConfig::default() };
+        let layout = layout_diagram(&diagram, &cfg, "hierarchical");
+        (0..diagram.nodes.len()).map(|i| layout.node_world_bounds[&NodeId(i)]).collect()
+    }
+
+    #[test]
+    fn test_direction_flips_rank_axis() {
+        let src = "class A\nclass B\nclass C\nA --> B\nA --> C\n";
+
+        let tb = node_bounds(src, Direction::TB);
+        assert!(tb[1].y >= tb[0].bottom() && tb[1].y == tb[2].y);
+
+        let lr = node_bounds(src, Direction::LR);
+        assert!(lr[1].x >= lr[0].right() && lr[1].x == lr[2].x);
+        assert!(lr[1].bottom() <= lr[2].y);
+
+        let bt = node_bounds(src, Direction::BT);
+        assert!(bt[1].bottom() <= bt[0].y);
+
+        let rl = node_bounds(src, Direction::RL);
+        assert!(rl[1].right() <= rl[0].x);
+
+        // Mirrored layouts start at the padding like the others
+        for bounds in [&tb, &lr, &bt, &rl] {
+            assert_eq!(bounds.iter().map(|r| r.x.min(r.y)).min(), Some(24));
+        }
+    }
+
+    #[test]
+    fn test_pinned_items_in_the_way_without_gap() {
+        // Every kind of free item starts where the pinned node is
+        let src = "class P {\n    @pos: (24, 24)\n    @size: (400, 400)\n}\nclass A\nclass B\nA --> B\nclass L\ngroup G {\n    class X\n}\n";
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let cfg = LayoutConfig { gap: 0, ..LayoutConfig::default() };
+        let layout = layout_diagram(&diagram, &cfg, "hierarchical");
+
+        // P, A, B and L are nodes 0 to 3, X is inside G
+        let mut rects: Vec<RectI> = (0..4).map(|i| layout.node_world_bounds[&NodeId(i)]).collect();
+        rects.push(layout.group_world_bounds[&GroupId(1)]);
+        assert_disjoint(&rects);
+    }
+
+    #[test]
+    fn test_mirrored_layouts_with_pinned_items_start_at_the_padding() {
+        let src = "class Pinned {\n    @pos: (24, 24)\n}\nclass A\nclass B\nclass C\nA --> B\nA --> C\n";
+        for direction in [Direction::BT, Direction::RL] {
+            let b = node_bounds(src, direction);
+            assert_eq!((b[0].x, b[0].y), (24, 24));
+            for r in &b[1..] {
+                assert!(r.x >= 24 && r.y >= 24, "{:?} {:?}", direction, r);
+                assert!(!r.overlaps(&b[0]));
+            }
+        }
+        let bt = node_bounds(src, Direction::BT);
+        assert!(bt[1].y >= bt[2].bottom(), "still flows bottom to top");
+    }
+
+    #[test]
+    fn test_group_direction_overrides_parent() {
+        let src = "@direction: LR\ngroup G {\n    @direction: TB\n    class A\n    class B\n    A --> B\n}\nclass C\nclass D\nC --> D\n";
+        let ast = parse_file(src).unwrap();
+        let diagram = compile(&ast).unwrap();
+        let (cfg, name) = crate::output::layout_settings(&ast);
+        let layout = layout_diagram(&diagram, &cfg, name);
+        let [a, b, c, d] = [0, 1, 2, 3].map(|i| layout.node_world_bounds[&NodeId(i)]);
+
+        assert!(b.y >= a.bottom(), "group contents flow top to bottom");
+        assert!(d.x >= c.right(), "top-level nodes flow left to right");
+    }
+
+    #[test]
+    fn test_nodes_related_to_a_pinned_node_are_placed_around_it() {
+        let src = "class Pinned {\n    @pos: (400, 400)\n}\nclass A\nclass B\nclass C\nA --> Pinned\nPinned --> B\nB --> C\nclass Loose\n";
+        let b = node_bounds(src, Direction::TB);
+        let [pinned, a, nb, c, loose] = [b[0], b[1], b[2], b[3], b[4]];
+
+        assert_eq!((pinned.x, pinned.y), (400, 400));
+        assert!(a.bottom() <= pinned.y, "A ranks above the node it points at");
+        assert!(nb.y >= pinned.bottom() && c.y >= nb.bottom());
+        // Single parents and children line up with the pinned node
+        assert_eq!(a.x + a.w / 2, pinned.x + pinned.w / 2);
+        assert_eq!(nb.x + nb.w / 2, pinned.x + pinned.w / 2);
+        assert!(!loose.overlaps(&pinned));
+    }
+
+    #[test]
+    fn test_wrapped_group_rows_clear_the_tallest_group() {
+        let fields: String = (0..16).map(|i| format!("        + field{}: int\n", i)).collect();
+        let src = format!(
+            "group Tall {{\n    class T {{\n{}    }}\n}}\ngroup G1 {{\n    class A\n}}\ngroup G2 {{\n    class B\n}}\ngroup G3 {{\n    class C\n}}\n",
+            fields,
+        );
+        let diagram = compile(&parse_file(&src).unwrap()).unwrap();
+        let layout = layout_diagram(&diagram, &LayoutConfig::default(), "hierarchical");
+
+        let groups: Vec<RectI> = (1..diagram.groups.len()).map(|i| layout.group_world_bounds[&GroupId(i)]).collect();
+        assert_disjoint(&groups);
+
+        // The group that wraps starts a new row under the tall one
+        let (tall, wrapped) = (groups[0], groups[3]);
+        assert_eq!(wrapped.x, tall.x);
+        assert!(wrapped.y >= tall.bottom());
+    }
+}
@@ -0,0 +1,983 @@
+// Edge routing for Trident diagrams.
+//
+// Runs after layout and turns every edge into a polyline in world coordinates:
+// source port, bends, target port, plus an anchor for the edge label.
+//
+// Three modes are available:
+// - "straight" (default): a direct line between the node outlines. When another
+//   node is in the way, the shortest path around the obstacles' corners is used.
+// - "orthogonal": horizontal/vertical segments only, found with A* over a sparse
+//   grid built from obstacle borders, with a penalty per bend.
+// - "bus": like "orthogonal", but the inheritance relations of a parent node or
+//   group share one horizontal line above its subclasses and meet in a single
+//   arrow head.
+//
+// All other nodes are treated as obstacles (with a small clearance), so routes
+// go around node bounds instead of cutting through them.
+//
+// Note connectors are drawn last: a straight line from the note to the outline
+// of its node or group, or to the middle of its relation's route.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::parser::compile::Node;
+use crate::parser::{get_base_arrow_name, Diagram, GroupId, NoteAnchor, PointI, ARROW_DEFINITIONS};
+use super::placement::get_edge_direction;
+use super::{LayoutConfig, LayoutResult, RectI};
+use serde::Serialize;
+
+/// Clearance kept between a route and the nodes it passes.
+const MARGIN: i32 = 12;
+/// Extra cost per bend in orthogonal routing (in pixels of path length).
+const BEND_PENALTY: i64 = 40;
+/// How many times obstacles missed by the initial search window are added back.
+const MAX_REFINEMENTS: usize = 4;
+/// How far along the route multiplicity/role labels sit from the node border.
+const END_LABEL_DISTANCE: f64 = 18.0;
+/// How far those labels are pushed off the line.
+const END_LABEL_OFFSET: f64 = 10.0;
+
+/// How edges are drawn between nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeRouting {
+    /// Direct lines, bending around obstacles only when needed
+    #[default]
+    Straight,
+    /// Horizontal and vertical segments only
+    Orthogonal,
+    /// Orthogonal, with a shared line for the subclasses of each parent
+    Bus,
+}
+
+impl EdgeRouting {
+    /// Parse a routing mode from its directive name (`@routing: orthogonal`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "straight" => Some(EdgeRouting::Straight),
+            "orthogonal" => Some(EdgeRouting::Orthogonal),
+            "bus" => Some(EdgeRouting::Bus),
+            _ => None,
+        }
+    }
+}
+
+/// The routed geometry of a single edge, in world coordinates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EdgeRoute {
+    /// Source port, bends and target port
+    pub points: Vec<PointI>,
+    /// Center point for the edge label (halfway along the route)
+    pub label_pos: PointI,
+    /// Center point for the multiplicity/role label at the source end
+    pub from_label_pos: PointI,
+    /// Center point for the multiplicity/role label at the target end
+    pub to_label_pos: PointI,
+}
+
+/// Route all edges of a laid out diagram.
+/// The result is index-aligned with `diagram.edges` followed by `diagram.group_edges`.
+pub fn route_edges(diagram: &Diagram, layout: &LayoutResult, cfg: &LayoutConfig) -> Vec<EdgeRoute> {
+    let empty = RectI { x: 0, y: 0, w: 0, h: 0 };
+    let bounds: Vec<RectI> = diagram.nodes.iter()
+        .map(|n| layout.node_world_bounds.get(&n.nid).copied().unwrap_or(empty))
+        .collect();
+
+    let group_bounds: Vec<RectI> = (0..diagram.groups.len())
+        .map(|i| layout.group_world_bounds.get(&GroupId(i)).copied().unwrap_or(empty))
+        .collect();
+
+    let buses = if cfg.edge_routing == EdgeRouting::Bus { bus_routes(diagram, &bounds, &group_bounds) } else { HashMap::new() };
+
+    let node_routes = diagram.edges.iter().enumerate().map(|(i, e)| {
+        if let Some(points) = buses.get(&i) {
+            return edge_route(points.clone());
+        }
+        let from = Endpoint { rect: bounds[e.from.0], shape: node_shape(&diagram.nodes[e.from.0]) };
+        let to = Endpoint { rect: bounds[e.to.0], shape: node_shape(&diagram.nodes[e.to.0]) };
+        let others: Vec<RectI> = bounds.iter().enumerate()
+            .filter(|(i, _)| *i != e.from.0 && *i != e.to.0)
+            .map(|(_, r)| *r)
+            .collect();
+        route_edge(&from, &to, e.from == e.to, &others, cfg.edge_routing)
+    });
+
+    // Group edges attach to the group borders and only avoid nodes outside both groups
+    let group_routes = diagram.group_edges.iter().enumerate().map(|(i, e)| {
+        if let Some(points) = buses.get(&(diagram.edges.len() + i)) {
+            return edge_route(points.clone());
+        }
+        let from = Endpoint { rect: group_bounds[e.from.0], shape: Shape::Rectangle };
+        let to = Endpoint { rect: group_bounds[e.to.0], shape: Shape::Rectangle };
+        let others: Vec<RectI> = bounds.iter()
+            .filter(|r| !contains(&from.rect, r) && !contains(&to.rect, r))
+            .copied()
+            .collect();
+        route_edge(&from, &to, e.from == e.to, &others, cfg.edge_routing)
+    });
+
+    node_routes.chain(group_routes).collect()
+}
+
+/// Connector of every note to what it is attached to, index-aligned with `diagram.notes`.
+/// Free-standing notes get no points. `routes` are the edge routes from `route_edges`.
+pub fn route_note_connectors(diagram: &Diagram, layout: &LayoutResult, routes: &[EdgeRoute]) -> Vec<Vec<PointI>> {
+    diagram.notes.iter().map(|note| {
+        let Some(rect) = layout.note_world_bounds.get(&note.id) else {
+            return Vec::new();
+        };
+        let target = match note.anchor {
+            NoteAnchor::None => return Vec::new(),
+            NoteAnchor::Node(nid) => layout.node_world_bounds.get(&nid)
+                .map(|&r| Endpoint { rect: r, shape: node_shape(&diagram.nodes[nid.0]) }),
+            NoteAnchor::Group(gid) => layout.group_world_bounds.get(&gid)
+                .map(|&r| Endpoint { rect: r, shape: Shape::Rectangle }),
+            NoteAnchor::Edge(i) => routes.get(i)
+                .map(|route| Endpoint { rect: RectI { x: route.label_pos.x, y: route.label_pos.y, w: 0, h: 0 }, shape: Shape::Rectangle }),
+        };
+        let Some(target) = target else {
+            return Vec::new();
+        };
+        vec![
+            boundary_point(rect, Shape::Rectangle, center(&target.rect)).round(),
+            boundary_point(&target.rect, target.shape, center(rect)).round(),
+        ]
+    }).collect()
+}
+
+fn route_edge(from: &Endpoint, to: &Endpoint, self_loop_edge: bool, others: &[RectI], mode: EdgeRouting) -> EdgeRoute {
+    let points = if self_loop_edge {
+        self_loop(&from.rect)
+    } else {
+        route_between(from, to, others, mode)
+    };
+    edge_route(points)
+}
+
+/// Route through `points`, with anchors for its labels
+fn edge_route(points: Vec<PointI>) -> EdgeRoute {
+    let label_pos = point_along(&points, 0.5);
+    let from_label_pos = end_label_pos(&points);
+    let reversed: Vec<PointI> = points.iter().rev().copied().collect();
+    let to_label_pos = end_label_pos(&reversed);
+    EdgeRoute { points, label_pos, from_label_pos, to_label_pos }
+}
+
+/// Whether `inner` lies entirely within `outer`
+fn contains(outer: &RectI, inner: &RectI) -> bool {
+    inner.x >= outer.x && inner.y >= outer.y && inner.right() <= outer.right() && inner.bottom() <= outer.bottom()
+}
+
+/// Outline of a node, used to find where a route leaves it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Rectangle,
+    Circle,
+    Diamond,
+}
+
+fn node_shape(node: &Node) -> Shape {
+    if node.kind != "node" {
+        Shape::Rectangle
+    } else if node.modifiers.iter().any(|m| m == "circle") {
+        Shape::Circle
+    } else if node.modifiers.iter().any(|m| m == "diamond") {
+        Shape::Diamond
+    } else {
+        Shape::Rectangle
+    }
+}
+
+struct Endpoint {
+    rect: RectI,
+    shape: Shape,
+}
+
+/// Route between two distinct endpoints, widening the obstacle set until the
+/// route clears every node (obstacles far outside the endpoints' area are
+/// skipped at first to keep the search small).
+fn route_between(from: &Endpoint, to: &Endpoint, others: &[RectI], mode: EdgeRouting) -> Vec<PointI> {
+    let area = inflate(&from.rect.union(&to.rect), 2 * MARGIN);
+    let mut active: Vec<RectI> = others.iter().filter(|r| r.overlaps(&area)).copied().collect();
+
+    let mut refinements = 0;
+    loop {
+        let points = match mode {
+            EdgeRouting::Straight => route_straight(from, to, &active),
+            EdgeRouting::Orthogonal | EdgeRouting::Bus => route_orthogonal(from, to, &active)
+                .unwrap_or_else(|| fallback_orthogonal(&from.rect, &to.rect)),
+        };
+
+        let missed: Vec<RectI> = others.iter()
+            .filter(|r| !active.contains(r) && path_hits_rect(&points, r))
+            .copied()
+            .collect();
+        if missed.is_empty() || refinements == MAX_REFINEMENTS {
+            return points;
+        }
+        active.extend(missed);
+        refinements += 1;
+    }
+}
+
+// ============================================================================
+// Straight routing
+// ============================================================================
+
+/// Floating point helper for straight routing
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pt {
+    x: f64,
+    y: f64,
+}
+
+impl Pt {
+    fn dist(&self, other: &Pt) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
+
+    fn round(&self) -> PointI {
+        PointI { x: self.x.round() as i32, y: self.y.round() as i32 }
+    }
+}
+
+fn center(r: &RectI) -> Pt {
+    Pt { x: r.x as f64 + r.w as f64 / 2.0, y: r.y as f64 + r.h as f64 / 2.0 }
+}
+
+/// Direct line between node outlines, or the shortest path around the
+/// corners of the obstacles when something is in the way.
+fn route_straight(from: &Endpoint, to: &Endpoint, obstacles: &[RectI]) -> Vec<PointI> {
+    let a = center(&from.rect);
+    let b = center(&to.rect);
+    // Segments may touch the clearance zone but not cut into it
+    let blockers: Vec<RectI> = obstacles.iter().map(|r| inflate(r, MARGIN - 1)).collect();
+
+    let mut path = if segment_clear(a, b, &blockers) {
+        vec![a, b]
+    } else {
+        shortest_path_around(a, b, obstacles, &blockers).unwrap_or_else(|| vec![a, b])
+    };
+
+    let n = path.len();
+    path[0] = boundary_point(&from.rect, from.shape, path[1]);
+    path[n - 1] = boundary_point(&to.rect, to.shape, path[n - 2]);
+    path.iter().map(Pt::round).collect()
+}
+
+/// Dijkstra over the visibility graph of inflated obstacle corners.
+fn shortest_path_around(a: Pt, b: Pt, obstacles: &[RectI], blockers: &[RectI]) -> Option<Vec<Pt>> {
+    let mut verts = vec![a, b];
+    for r in obstacles {
+        let r = inflate(r, MARGIN);
+        for (x, y) in [(r.x, r.y), (r.right(), r.y), (r.right(), r.bottom()), (r.x, r.bottom())] {
+            let p = Pt { x: x as f64, y: y as f64 };
+            if !blockers.iter().any(|bl| strictly_inside(bl, p)) {
+                verts.push(p);
+            }
+        }
+    }
+
+    let n = verts.len();
+    let mut dist = vec![f64::INFINITY; n];
+    let mut prev = vec![usize::MAX; n];
+    let mut done = vec![false; n];
+    dist[0] = 0.0;
+
+    loop {
+        let u = (0..n).filter(|&i| !done[i] && dist[i].is_finite())
+            .min_by(|&i, &j| dist[i].total_cmp(&dist[j]))?;
+        if u == 1 {
+            break;
+        }
+        done[u] = true;
+        for v in 0..n {
+            if done[v] || v == u {
+                continue;
+            }
+            let d = dist[u] + verts[u].dist(&verts[v]);
+            if d < dist[v] && segment_clear(verts[u], verts[v], blockers) {
+                dist[v] = d;
+                prev[v] = u;
+            }
+        }
+    }
+
+    let mut path = vec![verts[1]];
+    let mut cur = 1;
+    while prev[cur] != usize::MAX {
+        cur = prev[cur];
+        path.push(verts[cur]);
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// Intersection of the ray from the center of `r` towards `target` with the shape outline
+fn boundary_point(r: &RectI, shape: Shape, target: Pt) -> Pt {
+    let c = center(r);
+    let (dx, dy) = (target.x - c.x, target.y - c.y);
+    if dx == 0.0 && dy == 0.0 {
+        return c;
+    }
+    let (hw, hh) = (r.w as f64 / 2.0, r.h as f64 / 2.0);
+    let t = match shape {
+        Shape::Circle => {
+            let angle = dy.atan2(dx);
+            return Pt { x: c.x + hw * angle.cos(), y: c.y + hh * angle.sin() };
+        }
+        Shape::Diamond => 1.0 / (dx.abs() / hw + dy.abs() / hh),
+        Shape::Rectangle => {
+            let tx = if dx != 0.0 { hw / dx.abs() } else { f64::INFINITY };
+            let ty = if dy != 0.0 { hh / dy.abs() } else { f64::INFINITY };
+            tx.min(ty)
+        }
+    };
+    Pt { x: c.x + dx * t, y: c.y + dy * t }
+}
+
+// ============================================================================
+// Orthogonal routing
+// ============================================================================
+
+/// Unit steps for the four directions: right, down, left, up
+const DIRS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+fn opposite(dir: usize) -> usize {
+    (dir + 2) % 4
+}
+
+/// A connection point on a node side; `dir` points away from the node
+#[derive(Debug, Clone, Copy)]
+struct Port {
+    at: PointI,
+    dir: usize,
+}
+
+impl Port {
+    /// First point outside the node's clearance zone
+    fn stub(&self) -> PointI {
+        let (dx, dy) = DIRS[self.dir];
+        PointI { x: self.at.x + dx * MARGIN, y: self.at.y + dy * MARGIN }
+    }
+}
+
+/// Candidate ports: the middle of each side, plus points facing the other
+/// node's span so that aligned nodes get a straight connection.
+fn ports(ep: &Endpoint, other: &RectI) -> Vec<Port> {
+    let r = &ep.rect;
+    let c = PointI { x: r.x + r.w / 2, y: r.y + r.h / 2 };
+    let mut xs = vec![c.x];
+    let mut ys = vec![c.y];
+
+    if ep.shape == Shape::Rectangle {
+        let (x0, x1) = (r.x.max(other.x), r.right().min(other.right()));
+        if x1 > x0 && (x0 + x1) / 2 != c.x {
+            xs.push((x0 + x1) / 2);
+        }
+        let (y0, y1) = (r.y.max(other.y), r.bottom().min(other.bottom()));
+        if y1 > y0 && (y0 + y1) / 2 != c.y {
+            ys.push((y0 + y1) / 2);
+        }
+    }
+
+    let mut out = Vec::new();
+    for &x in &xs {
+        out.push(Port { at: PointI { x, y: r.bottom() }, dir: 1 });
+        out.push(Port { at: PointI { x, y: r.y }, dir: 3 });
+    }
+    for &y in &ys {
+        out.push(Port { at: PointI { x: r.right(), y }, dir: 0 });
+        out.push(Port { at: PointI { x: r.x, y }, dir: 2 });
+    }
+    out
+}
+
+/// A* over the sparse grid spanned by obstacle borders, ports and the gaps
+/// between the endpoints. Each search state is a grid point plus the direction
+/// of travel, so that bends can be penalised.
+fn route_orthogonal(from: &Endpoint, to: &Endpoint, obstacles: &[RectI]) -> Option<Vec<PointI>> {
+    let mut blockers: Vec<RectI> = obstacles.iter().map(|r| inflate(r, MARGIN)).collect();
+    blockers.push(inflate(&from.rect, MARGIN));
+    blockers.push(inflate(&to.rect, MARGIN));
+
+    let src_ports = ports(from, &to.rect);
+    let dst_ports = ports(to, &from.rect);
+
+    // Grid lines
+    let mut xs: Vec<i32> = Vec::new();
+    let mut ys: Vec<i32> = Vec::new();
+    for b in &blockers {
+        xs.extend([b.x, b.right()]);
+        ys.extend([b.y, b.bottom()]);
+    }
+    for p in src_ports.iter().chain(dst_ports.iter()) {
+        let s = p.stub();
+        xs.push(s.x);
+        ys.push(s.y);
+    }
+    let (a, b) = (&from.rect, &to.rect);
+    xs.push((a.x + a.w / 2 + b.x + b.w / 2) / 2);
+    ys.push((a.y + a.h / 2 + b.y + b.h / 2) / 2);
+    if a.right() < b.x {
+        xs.push((a.right() + b.x) / 2);
+    } else if b.right() < a.x {
+        xs.push((b.right() + a.x) / 2);
+    }
+    if a.bottom() < b.y {
+        ys.push((a.bottom() + b.y) / 2);
+    } else if b.bottom() < a.y {
+        ys.push((b.bottom() + a.y) / 2);
+    }
+    xs.sort_unstable();
+    xs.dedup();
+    ys.sort_unstable();
+    ys.dedup();
+
+    let (nx, ny) = (xs.len(), ys.len());
+    let index_of = |p: PointI| -> Option<usize> {
+        let i = xs.binary_search(&p.x).ok()?;
+        let j = ys.binary_search(&p.y).ok()?;
+        Some(i * ny + j)
+    };
+    let point_of = |idx: usize| PointI { x: xs[idx / ny], y: ys[idx % ny] };
+    let free = |x2: i64, y2: i64| !blockers.iter().any(|r| strictly_inside2(r, x2, y2));
+    let valid: Vec<bool> = (0..nx * ny)
+        .map(|idx| {
+            let p = point_of(idx);
+            free(2 * p.x as i64, 2 * p.y as i64)
+        })
+        .collect();
+
+    // Goals: target stubs, with the direction a route must arrive in
+    let mut goals: HashMap<usize, Vec<(usize, PointI)>> = HashMap::new();
+    for p in &dst_ports {
+        if let Some(idx) = index_of(p.stub()).filter(|&i| valid[i]) {
+            goals.entry(idx).or_default().push((opposite(p.dir), p.at));
+        }
+    }
+    if goals.is_empty() {
+        return None;
+    }
+    let goal_points: Vec<PointI> = goals.keys().map(|&i| point_of(i)).collect();
+    let heuristic = |idx: usize| -> i64 {
+        let p = point_of(idx);
+        goal_points.iter()
+            .map(|g| ((g.x - p.x).abs() + (g.y - p.y).abs()) as i64)
+            .min()
+            .unwrap_or(0)
+    };
+
+    let state_count = nx * ny * 4;
+    let mut dist = vec![i64::MAX; state_count];
+    let mut prev = vec![usize::MAX; state_count];
+    let mut start_port: HashMap<usize, PointI> = HashMap::new();
+    let mut heap = BinaryHeap::new();
+
+    for p in &src_ports {
+        let Some(idx) = index_of(p.stub()).filter(|&i| valid[i]) else {
+            continue;
+        };
+        let state = idx * 4 + p.dir;
+        let g = MARGIN as i64;
+        if g < dist[state] {
+            dist[state] = g;
+            start_port.insert(state, p.at);
+            heap.push(Reverse((g + heuristic(idx), g, state)));
+        }
+    }
+
+    let mut best: Option<(i64, usize, PointI)> = None;
+    while let Some(Reverse((f, g, state))) = heap.pop() {
+        if g > dist[state] {
+            continue;
+        }
+        if best.is_some_and(|(cost, _, _)| f >= cost) {
+            break;
+        }
+        let (idx, dir) = (state / 4, state % 4);
+
+        if let Some(arrivals) = goals.get(&idx) {
+            for &(inward, port_at) in arrivals {
+                let turn = if dir == inward { 0 } else { BEND_PENALTY };
+                let total = g + MARGIN as i64 + turn;
+                if best.is_none_or(|(cost, _, _)| total < cost) {
+                    best = Some((total, state, port_at));
+                }
+            }
+        }
+
+        let (i, j) = (idx / ny, idx % ny);
+        for (nd, &(dx, dy)) in DIRS.iter().enumerate() {
+            if nd == opposite(dir) {
+                continue;
+            }
+            let (ni, nj) = (i as i64 + dx as i64, j as i64 + dy as i64);
+            if ni < 0 || nj < 0 || ni >= nx as i64 || nj >= ny as i64 {
+                continue;
+            }
+            let nidx = ni as usize * ny + nj as usize;
+            if !valid[nidx] {
+                continue;
+            }
+            let (p, q) = (point_of(idx), point_of(nidx));
+            // Grid lines include every obstacle border, so a step crosses an
+            // obstacle exactly when its midpoint lies inside one
+            if !free(p.x as i64 + q.x as i64, p.y as i64 + q.y as i64) {
+                continue;
+            }
+            let step = ((q.x - p.x).abs() + (q.y - p.y).abs()) as i64;
+            let ng = g + step + if nd == dir { 0 } else { BEND_PENALTY };
+            let nstate = nidx * 4 + nd;
+            if ng < dist[nstate] {
+                dist[nstate] = ng;
+                prev[nstate] = state;
+                heap.push(Reverse((ng + heuristic(nidx), ng, nstate)));
+            }
+        }
+    }
+
+    let (_, end_state, end_port) = best?;
+    let mut points = vec![end_port];
+    let mut state = end_state;
+    loop {
+        points.push(point_of(state / 4));
+        if prev[state] == usize::MAX {
+            break;
+        }
+        state = prev[state];
+    }
+    points.push(*start_port.get(&state)?);
+    points.reverse();
+    Some(simplify(points))
+}
+
+/// Z-shaped connection used when the grid search finds nothing
+fn fallback_orthogonal(a: &RectI, b: &RectI) -> Vec<PointI> {
+    let (ca, cb) = (center(a).round(), center(b).round());
+    let dx = cb.x - ca.x;
+    let dy = cb.y - ca.y;
+    let points = if dx.abs() >= dy.abs() {
+        let (sx, ex) = if dx >= 0 { (a.right(), b.x) } else { (a.x, b.right()) };
+        let mid = (sx + ex) / 2;
+        vec![
+            PointI { x: sx, y: ca.y },
+            PointI { x: mid, y: ca.y },
+            PointI { x: mid, y: cb.y },
+            PointI { x: ex, y: cb.y },
+        ]
+    } else {
+        let (sy, ey) = if dy >= 0 { (a.bottom(), b.y) } else { (a.y, b.bottom()) };
+        let mid = (sy + ey) / 2;
+        vec![
+            PointI { x: ca.x, y: sy },
+            PointI { x: ca.x, y: mid },
+            PointI { x: cb.x, y: mid },
+            PointI { x: cb.x, y: ey },
+        ]
+    };
+    simplify(points)
+}
+
+/// Remove duplicate points and middle points of straight runs
+fn simplify(points: Vec<PointI>) -> Vec<PointI> {
+    let mut out: Vec<PointI> = Vec::with_capacity(points.len());
+    for p in points {
+        if out.last() == Some(&p) {
+            continue;
+        }
+        if out.len() >= 2 {
+            let a = out[out.len() - 2];
+            let b = out[out.len() - 1];
+            let collinear = (a.x == b.x && b.x == p.x) || (a.y == b.y && b.y == p.y);
+            if collinear {
+                out.pop();
+            }
+        }
+        out.push(p);
+    }
+    out
+}
+
+// ============================================================================
+// Bus routing
+// ============================================================================
+
+/// Shared routes for inheritance relations (arrows with `hierarchy_reversed`),
+/// by route index as `route_edges` numbers them. Nodes and groups are items
+/// alike, so group relations get buses too. The subclasses of a parent with
+/// the same arrow hang from one horizontal line halfway between the parent and
+/// their top, which meets the middle of the parent's bottom side. A family
+/// that isn't entirely below its parent, or whose lines would cross another
+/// node or group, is routed orthogonally.
+fn bus_routes(diagram: &Diagram, bounds: &[RectI], group_bounds: &[RectI]) -> HashMap<usize, Vec<PointI>> {
+    // Groups follow the nodes, both among the items and among the relations
+    let items: Vec<RectI> = bounds.iter().chain(group_bounds).copied().collect();
+    let relations: Vec<(usize, usize, &str)> = diagram.edges.iter()
+        .map(|e| (e.from.0, e.to.0, e.arrow.as_str()))
+        .chain(diagram.group_edges.iter().map(|e| (bounds.len() + e.from.0, bounds.len() + e.to.0, e.arrow.as_str())))
+        .collect();
+
+    let mut families: HashMap<(usize, &str), Vec<usize>> = HashMap::new();
+    for (i, &(from, to, arrow)) in relations.iter().enumerate() {
+        let base = get_base_arrow_name(arrow);
+        if from != to && ARROW_DEFINITIONS.iter().any(|d| d.name == base && d.hierarchy_reversed) {
+            let (parent, _) = get_edge_direction(arrow, from, to);
+            families.entry((parent, base)).or_default().push(i);
+        }
+    }
+
+    // Items inside one another (a node and its group, nested groups) never block each other
+    let apart = |r: &RectI, other: &RectI| !contains(r, other) && !contains(other, r);
+
+    let mut routes = HashMap::new();
+    for ((parent, _), members) in families {
+        let p = items[parent];
+        let child_of = |i: usize| {
+            let (from, to, _) = relations[i];
+            if from == parent { to } else { from }
+        };
+        let top = members.iter().map(|&i| items[child_of(i)].y).min().unwrap_or(p.y);
+        if top <= p.bottom() {
+            continue;
+        }
+
+        // The bus runs below anything that reaches into the space between parent and subclasses
+        let px = p.x + p.w / 2;
+        let xs: Vec<i32> = members.iter().map(|&i| items[child_of(i)].x + items[child_of(i)].w / 2).collect();
+        let (x0, x1) = xs.iter().fold((px, px), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+        let above = items.iter()
+            .filter(|r| r.x < x1 && r.right() > x0 && r.y < top)
+            .filter(|r| apart(r, &p) && members.iter().all(|&i| apart(r, &items[child_of(i)])))
+            .map(RectI::bottom)
+            .fold(p.bottom(), i32::max);
+        if above >= top {
+            continue;
+        }
+        let bus_y = (above + top) / 2;
+
+        let family: Vec<(usize, Vec<PointI>)> = members.iter().zip(&xs).map(|(&i, &x)| {
+            let child = items[child_of(i)];
+            let mut points = simplify(vec![
+                PointI { x, y: child.y },
+                PointI { x, y: bus_y },
+                PointI { x: px, y: bus_y },
+                PointI { x: px, y: p.bottom() },
+            ]);
+            if relations[i].0 == parent {
+                points.reverse();
+            }
+            (i, points)
+        }).collect();
+        let clear = family.iter().all(|(i, points)| {
+            let child = items[child_of(*i)];
+            items.iter()
+                .filter(|r| apart(r, &p) && apart(r, &child))
+                .all(|r| !path_hits_rect(points, r))
+        });
+        if clear {
+            routes.extend(family);
+        }
+    }
+    routes
+}
+
+// ============================================================================
+// Shared helpers
+// ============================================================================
+
+/// Loop leaving the right side and re-entering from the top
+fn self_loop(r: &RectI) -> Vec<PointI> {
+    let c = center(r).round();
+    let out_y = c.y - r.h / 4;
+    let in_x = c.x + r.w / 4;
+    let (ex, ey) = (r.right() + 2 * MARGIN, r.y - 2 * MARGIN);
+    vec![
+        PointI { x: r.right(), y: out_y },
+        PointI { x: ex, y: out_y },
+        PointI { x: ex, y: ey },
+        PointI { x: in_x, y: ey },
+        PointI { x: in_x, y: r.y },
+    ]
+}
+
+/// Point at `fraction` of the total length along a polyline
+fn point_along(points: &[PointI], fraction: f64) -> PointI {
+    let pts: Vec<Pt> = points.iter().map(|p| Pt { x: p.x as f64, y: p.y as f64 }).collect();
+    let total: f64 = pts.windows(2).map(|w| w[0].dist(&w[1])).sum();
+    let mut remaining = total * fraction;
+    for w in pts.windows(2) {
+        let len = w[0].dist(&w[1]);
+        if len > 0.0 && remaining <= len {
+            let t = remaining / len;
+            return Pt { x: w[0].x + (w[1].x - w[0].x) * t, y: w[0].y + (w[1].y - w[0].y) * t }.round();
+        }
+        remaining -= len;
+    }
+    points.last().copied().unwrap_or(PointI { x: 0, y: 0 })
+}
+
+/// Anchor for an end label at `points[0]`: a short way along the first segment,
+/// then off to the right of the direction of travel so it doesn't sit on the line
+fn end_label_pos(points: &[PointI]) -> PointI {
+    let Some(&start) = points.first() else {
+        return PointI { x: 0, y: 0 };
+    };
+    let a = Pt { x: start.x as f64, y: start.y as f64 };
+    let Some(b) = points.iter().map(|p| Pt { x: p.x as f64, y: p.y as f64 }).find(|p| p.dist(&a) > 0.0) else {
+        return start;
+    };
+    let len = a.dist(&b);
+    let (dx, dy) = ((b.x - a.x) / len, (b.y - a.y) / len);
+    let along = END_LABEL_DISTANCE.min(len / 2.0);
+    Pt { x: a.x + dx * along - dy * END_LABEL_OFFSET, y: a.y + dy * along + dx * END_LABEL_OFFSET }.round()
+}
+
+fn inflate(r: &RectI, by: i32) -> RectI {
+    RectI { x: r.x - by, y: r.y - by, w: r.w + 2 * by, h: r.h + 2 * by }
+}
+
+fn strictly_inside(r: &RectI, p: Pt) -> bool {
+    p.x > r.x as f64 && p.x < r.right() as f64 && p.y > r.y as f64 && p.y < r.bottom() as f64
+}
+
+/// `strictly_inside` on doubled integer coordinates (so half-way points stay exact)
+fn strictly_inside2(r: &RectI, x2: i64, y2: i64) -> bool {
+    x2 > 2 * r.x as i64 && x2 < 2 * r.right() as i64 && y2 > 2 * r.y as i64 && y2 < 2 * r.bottom() as i64
+}
+
+/// Whether the segment a-b avoids the interior of every rectangle
+fn segment_clear(a: Pt, b: Pt, rects: &[RectI]) -> bool {
+    !rects.iter().any(|r| segment_hits_rect(a, b, r))
+}
+
+/// Liang-Barsky clip: does the segment pass through the open interior of `r`?
+fn segment_hits_rect(a: Pt, b: Pt, r: &RectI) -> bool {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+    let checks = [
+        (-dx, a.x - r.x as f64),
+        (dx, r.right() as f64 - a.x),
+        (-dy, a.y - r.y as f64),
+        (dy, r.bottom() as f64 - a.y),
+    ];
+    for (p, q) in checks {
+        if p == 0.0 {
+            if q <= 0.0 {
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    t1 - t0 > 1e-9
+}
+
+fn path_hits_rect(points: &[PointI], r: &RectI) -> bool {
+    points.windows(2).any(|w| {
+        let a = Pt { x: w[0].x as f64, y: w[0].y as f64 };
+        let b = Pt { x: w[1].x as f64, y: w[1].y as f64 };
+        segment_hits_rect(a, b, r)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::layout_diagram;
+    use crate::parser::{compile, parse_file};
+
+    fn routed(src: &str, mode: EdgeRouting) -> (Diagram, LayoutResult, Vec<EdgeRoute>) {
+        let diagram = compile(&parse_file(src).unwrap()).unwrap();
+        let cfg = LayoutConfig { edge_routing: mode, ..LayoutConfig::default() };
+        let layout = layout_diagram(&diagram, &cfg, "hierarchical");
+        let routes = route_edges(&diagram, &layout, &cfg);
+        (diagram, layout, routes)
+    }
+
+    /// Three nodes in a row with the middle one in the way
+    const BLOCKED: &str = "\
+class A {
+    @pos: (0, 0)
+}
+class B {
+    @pos: (300, 0)
+}
+class C {
+    @pos: (600, 0)
+}
+A --> C
+";
+
+    #[test]
+    fn test_straight_route_is_direct_when_clear() {
+        let (_, layout, routes) = routed("class A\nclass B\nA --> B\n", EdgeRouting::Straight);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].points.len(), 2);
+        let a = layout.node_world_bounds.values().next().unwrap();
+        assert!(a.w > 0);
+    }
+
+    #[test]
+    fn test_straight_route_avoids_obstacles() {
+        let (diagram, layout, routes) = routed(BLOCKED, EdgeRouting::Straight);
+        let b = layout.node_world_bounds[&diagram.nodes[1].nid];
+        let route = &routes[0];
+        assert!(route.points.len() > 2, "expected a detour, got {:?}", route.points);
+        assert!(!path_hits_rect(&route.points, &b));
+    }
+
+    #[test]
+    fn test_orthogonal_route_avoids_obstacles() {
+        let (diagram, layout, routes) = routed(BLOCKED, EdgeRouting::Orthogonal);
+        let b = layout.node_world_bounds[&diagram.nodes[1].nid];
+        let route = &routes[0];
+        assert!(!path_hits_rect(&route.points, &b));
+        for w in route.points.windows(2) {
+            assert!(w[0].x == w[1].x || w[0].y == w[1].y, "segment not axis-aligned: {:?}", w);
+        }
+    }
+
+    #[test]
+    fn test_orthogonal_route_ports_on_borders() {
+        let (diagram, layout, routes) = routed(BLOCKED, EdgeRouting::Orthogonal);
+        let a = layout.node_world_bounds[&diagram.nodes[0].nid];
+        let c = layout.node_world_bounds[&diagram.nodes[2].nid];
+        let first = routes[0].points[0];
+        let last = *routes[0].points.last().unwrap();
+        let on_border = |r: &RectI, p: PointI| {
+            (p.x == r.x || p.x == r.right() || p.y == r.y || p.y == r.bottom())
+                && p.x >= r.x && p.x <= r.right() && p.y >= r.y && p.y <= r.bottom()
+        };
+        assert!(on_border(&a, first));
+        assert!(on_border(&c, last));
+    }
+
+    #[test]
+    fn test_self_loop_and_label_anchor() {
+        let (_, _, routes) = routed("class A\nA --> A : self\n", EdgeRouting::Straight);
+        assert_eq!(routes[0].points.len(), 5);
+        let route = &routes[0];
+        assert_ne!(route.label_pos, route.points[0]);
+    }
+
+    #[test]
+    fn test_end_label_anchors_sit_beside_each_end() {
+        let pts = vec![PointI { x: 0, y: 0 }, PointI { x: 100, y: 0 }];
+        assert_eq!(end_label_pos(&pts), PointI { x: 18, y: 10 });
+        let reversed: Vec<PointI> = pts.iter().rev().copied().collect();
+        assert_eq!(end_label_pos(&reversed), PointI { x: 82, y: -10 });
+
+        let (_, _, routes) = routed("class A\nclass B\nA \"1\" --> \"*\" B\n", EdgeRouting::Straight);
+        let route = &routes[0];
+        assert_ne!(route.from_label_pos, route.to_label_pos);
+        assert_ne!(route.from_label_pos, route.label_pos);
+    }
+
+    #[test]
+    fn test_group_edges_attach_to_group_borders() {
+        let src = "group Model {\n    class User\n}\ngroup Persistence {\n    class Repo\n}\nModel ..> Persistence\n";
+        let (diagram, layout, routes) = routed(src, EdgeRouting::Orthogonal);
+        assert_eq!(routes.len(), 1);
+        let model = layout.group_world_bounds[&diagram.group_edges[0].from];
+        let persistence = layout.group_world_bounds[&diagram.group_edges[0].to];
+        let on_border = |r: &RectI, p: PointI| {
+            (p.x == r.x || p.x == r.right() || p.y == r.y || p.y == r.bottom())
+                && p.x >= r.x && p.x <= r.right() && p.y >= r.y && p.y <= r.bottom()
+        };
+        assert!(on_border(&model, routes[0].points[0]), "{:?} {:?}", model, routes[0].points);
+        assert!(on_border(&persistence, *routes[0].points.last().unwrap()));
+    }
+
+    #[test]
+    fn test_bus_joins_subclasses_of_a_parent() {
+        let src = "@layout: tree\nclass Shape\nclass Circle\nclass Square\nclass Line\nCircle --|> Shape\nShape <|-- Square\nLine --|> Shape\nCircle --> Square\n";
+        let ast = parse_file(src).unwrap();
+        let diagram = compile(&ast).unwrap();
+        let cfg = LayoutConfig { edge_routing: EdgeRouting::Bus, ..LayoutConfig::default() };
+        let layout = layout_diagram(&diagram, &cfg, ast.layout.as_deref().unwrap());
+        let routes = route_edges(&diagram, &layout, &cfg);
+        let shape = layout.node_world_bounds[&diagram.nodes[0].nid];
+        let head = PointI { x: shape.x + shape.w / 2, y: shape.bottom() };
+
+        // Every subclass reaches the parent at the same point; the outer ones over one
+        // horizontal line, the one right below it straight up. `Shape <|-- Square` starts at Shape
+        let to_parent = |i: usize| -> Vec<PointI> {
+            if i == 1 { routes[i].points.iter().rev().copied().collect() } else { routes[i].points.clone() }
+        };
+        let bus_y = to_parent(0)[1].y;
+        assert!(bus_y > shape.bottom());
+        for i in [0, 1, 2] {
+            assert_eq!(*to_parent(i).last().unwrap(), head, "{:?}", routes[i].points);
+        }
+        for i in [0, 2] {
+            let points = to_parent(i);
+            assert_eq!(points.len(), 4);
+            assert_eq!((points[1].y, points[2].y), (bus_y, bus_y));
+        }
+        assert_eq!(to_parent(1).len(), 2);
+        // Other relations are routed as usual
+        for w in routes[3].points.windows(2) {
+            assert!(w[0].x == w[1].x || w[0].y == w[1].y);
+        }
+    }
+
+    #[test]
+    fn test_bus_joins_subgroups_of_a_parent_group() {
+        let src = "@layout: tree\ngroup Base {\n    class A\n}\ngroup Left {\n    class B\n}\ngroup Right {\n    class C\n}\n\
+                   Left --|> Base\nRight --|> Base\n";
+        let ast = parse_file(src).unwrap();
+        let diagram = compile(&ast).unwrap();
+        let cfg = LayoutConfig { edge_routing: EdgeRouting::Bus, ..LayoutConfig::default() };
+        let layout = layout_diagram(&diagram, &cfg, ast.layout.as_deref().unwrap());
+        let routes = route_edges(&diagram, &layout, &cfg);
+        let base = layout.group_world_bounds[&diagram.group_edges[0].to];
+        let head = PointI { x: base.x + base.w / 2, y: base.bottom() };
+
+        assert_eq!(routes.len(), 2);
+        for (route, e) in routes.iter().zip(&diagram.group_edges) {
+            let sub = layout.group_world_bounds[&e.from];
+            assert_eq!(route.points.len(), 4, "{:?}", route.points);
+            assert_eq!(route.points[0], PointI { x: sub.x + sub.w / 2, y: sub.y });
+            assert_eq!(route.points[1].y, routes[0].points[1].y);
+            assert_eq!(*route.points.last().unwrap(), head);
+        }
+    }
+
+    #[test]
+    fn test_bus_does_not_cross_other_groups() {
+        // Only the border of G reaches under C2, not the node inside it
+        let src = "class P {\n    @pos: (300, 0)\n}\nclass C1 {\n    @pos: (0, 400)\n}\nclass C2 {\n    @pos: (600, 700)\n}\n\
+                   group G {\n    @pos: (0, 500)\n    class X {\n        @pos: (480, 0)\n    }\n}\nC1 --|> P\nC2 --|> P\n";
+        let (diagram, layout, routes) = routed(src, EdgeRouting::Bus);
+        let g = layout.group_world_bounds[&GroupId(1)];
+        let x = layout.node_world_bounds[&diagram.nodes[3].nid];
+        let c2 = layout.node_world_bounds[&diagram.nodes[2].nid];
+        assert!(x.right() < c2.x + c2.w / 2 && g.right() > c2.x + c2.w / 2);
+
+        for route in &routes[..2] {
+            assert!(!path_hits_rect(&route.points, &g), "{:?} crosses {:?}", route.points, g);
+        }
+    }
+
+    #[test]
+    fn test_point_along_midpoint() {
+        let pts = vec![PointI { x: 0, y: 0 }, PointI { x: 100, y: 0 }, PointI { x: 100, y: 100 }];
+        assert_eq!(point_along(&pts, 0.5), PointI { x: 100, y: 0 });
+    }
+
+    #[test]
+    fn test_routing_from_name() {
+        assert_eq!(EdgeRouting::from_name("orthogonal"), Some(EdgeRouting::Orthogonal));
+        assert_eq!(EdgeRouting::from_name("straight"), Some(EdgeRouting::Straight));
+        assert_eq!(EdgeRouting::from_name("bus"), Some(EdgeRouting::Bus));
+        assert_eq!(EdgeRouting::from_name("curvy"), None);
+    }
+}
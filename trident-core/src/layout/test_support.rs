@@ -0,0 +1,12 @@
+// Assertions shared by the layout tests.
+
+use super::RectI;
+
+/// Panic with both rectangles if any two of `rects` overlap
+pub(crate) fn assert_disjoint(rects: &[RectI]) {
+    for (i, a) in rects.iter().enumerate() {
+        for b in &rects[i + 1..] {
+            assert!(!a.overlaps(b), "{:?} overlaps {:?}", a, b);
+        }
+    }
+}
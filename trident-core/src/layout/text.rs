@@ -0,0 +1,200 @@
+// Text measurement for Trident diagrams.
+//
+// Node and note sizes, and the x-positions of their text, come from the
+// advance widths of the font the editor renders with, embedded below:
+// - The bundled `FiraCode-VF.woff2` is monospaced: every glyph it has advances
+//   1200 units at 1950 units per em, in every weight.
+// - Characters Fira Code has no glyph for are drawn in whatever fallback font
+//   the browser picks, so their widths are only an estimate: one average
+//   advance per script, taken from DejaVu Sans. Text in those scripts can come
+//   out somewhat wider or narrower than measured, glyph by glyph.
+// - Wide East Asian characters and emoji take a full em in any font, and
+//   combining marks take no room at all.
+//
+// Widths are kept in thousandths of an em, so a width in pixels is the sum of
+// the advances times the font size / 1000.
+
+/// Font size of the stereotype line
+pub const STEREOTYPE_FONT_SIZE: i32 = 10;
+/// Font size of a node's title
+pub const TITLE_FONT_SIZE: i32 = 12;
+/// Font size of body lines and notes
+pub const BODY_FONT_SIZE: i32 = 11;
+/// Font size of the type parameters in the template box
+pub const TEMPLATE_FONT_SIZE: i32 = 10;
+/// Font size of a group's title
+pub const GROUP_LABEL_FONT_SIZE: i32 = 11;
+
+/// CSS font stack whose metrics `text_width` uses
+pub const FONT_FAMILY: &str = "'Fira Code VF', 'Fira Code', ui-monospace, monospace";
+
+/// Advance of every Fira Code glyph (1200 / 1950 em)
+const FIRA_CODE_ADVANCE: u32 = 615;
+
+/// Blocks the bundled Fira Code has glyphs for
+const FIRA_CODE_RANGES: &[(char, char)] = &[
+    ('\u{0020}', '\u{02FF}'), // Latin, IPA, spacing modifiers
+    ('\u{0370}', '\u{052F}'), // Greek, Cyrillic
+    ('\u{1E00}', '\u{1EFF}'), // Latin Extended Additional
+    ('\u{2000}', '\u{23FF}'), // punctuation, currency, letterlike, arrows, math, technical
+    ('\u{2400}', '\u{243F}'), // control pictures
+    ('\u{2500}', '\u{25FF}'), // box drawing, blocks, geometric shapes
+    ('\u{2800}', '\u{28FF}'), // braille
+    ('\u{E0A0}', '\u{E0D4}'), // powerline symbols
+];
+
+/// Characters that take no room: combining marks, zero-width spaces and joiners, variation selectors
+const ZERO_WIDTH_RANGES: &[(char, char)] = &[
+    ('\u{0300}', '\u{036F}'),
+    ('\u{200B}', '\u{200F}'),
+    ('\u{2060}', '\u{2064}'),
+    ('\u{FE00}', '\u{FE0F}'),
+];
+
+/// East Asian wide characters and emoji, a full em each
+const WIDE_RANGES: &[(char, char)] = &[
+    ('\u{1100}', '\u{115F}'),
+    ('\u{2E80}', '\u{A4CF}'),
+    ('\u{AC00}', '\u{D7A3}'),
+    ('\u{F900}', '\u{FAFF}'),
+    ('\u{FE30}', '\u{FE4F}'),
+    ('\u{FF00}', '\u{FF60}'),
+    ('\u{FFE0}', '\u{FFE6}'),
+    ('\u{1F300}', '\u{1F64F}'),
+    ('\u{1F900}', '\u{1F9FF}'),
+    ('\u{20000}', '\u{3FFFD}'),
+];
+
+/// Estimated advance per script for characters Fira Code lacks: the average
+/// DejaVu Sans advance over the script's letters, not per-glyph widths
+const FALLBACK_ADVANCES: &[(char, char, u32)] = &[
+    ('\u{0530}', '\u{058F}', 661), // Armenian
+    ('\u{0590}', '\u{05FF}', 518), // Hebrew
+    ('\u{0600}', '\u{06FF}', 689), // Arabic
+    ('\u{10A0}', '\u{10FF}', 640), // Georgian
+    ('\u{2600}', '\u{26FF}', 861), // miscellaneous symbols
+    ('\u{2700}', '\u{27BF}', 834), // dingbats
+];
+
+/// Estimated advance for anything else (the DejaVu Sans average over printable ASCII)
+const FALLBACK_ADVANCE: u32 = 597;
+
+fn in_ranges(c: char, ranges: &[(char, char)]) -> bool {
+    ranges.iter().any(|&(start, end)| (start..=end).contains(&c))
+}
+
+/// Advance of one character in thousandths of an em
+fn advance(c: char) -> u32 {
+    if in_ranges(c, ZERO_WIDTH_RANGES) {
+        0
+    } else if in_ranges(c, FIRA_CODE_RANGES) {
+        FIRA_CODE_ADVANCE
+    } else if in_ranges(c, WIDE_RANGES) {
+        1000
+    } else {
+        FALLBACK_ADVANCES
+            .iter()
+            .find(|&&(start, end, _)| (start..=end).contains(&c))
+            .map_or(FALLBACK_ADVANCE, |&(_, _, adv)| adv)
+    }
+}
+
+/// Width of `text` at `font_size` in pixels, rounded up
+pub fn text_width(text: &str, font_size: i32) -> i32 {
+    let em: u32 = text.chars().map(advance).sum();
+    (em as u64 * font_size.max(0) as u64).div_ceil(1000) as i32
+}
+
+/// Break `text` into lines at most `max_width` wide, between words where
+/// possible; a word wider than a line is split between characters
+pub fn wrap_text(text: &str, font_size: i32, max_width: i32) -> Vec<String> {
+    let fits = |s: &str| text_width(s, font_size) <= max_width;
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let joined = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if line.is_empty() || fits(&joined) {
+            line = joined;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+
+        // Split what doesn't fit on a line of its own, keeping at least a character per line
+        while !fits(&line) {
+            let mut end = line.char_indices().nth(1).map_or(line.len(), |(i, _)| i);
+            for (i, c) in line.char_indices().skip(1) {
+                if !fits(&line[..i + c.len_utf8()]) {
+                    break;
+                }
+                end = i + c.len_utf8();
+            }
+            if end == line.len() {
+                break;
+            }
+            let rest = line.split_off(end);
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_width_uses_font_metrics() {
+        // Fira Code is monospaced: 40 characters at 11px
+        let line = "+ findAllByCustomerAndStatus(c, s): List";
+        assert_eq!(line.chars().count(), 40);
+        assert_eq!(text_width(line, 11), 271);
+        assert_eq!(text_width("«interface»", 10), 68);
+
+        // Wide characters take a full em, combining marks nothing, other scripts fall back
+        assert_eq!(text_width("日本", 10), 20);
+        assert_eq!(text_width("e\u{0301}", 10), text_width("e", 10));
+        assert_eq!(text_width("שלום", 10), 21);
+        assert_eq!(text_width("", 12), 0);
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let text = "+ process(order: Order, customer: Customer): Result";
+        let lines = wrap_text(text, 11, 160);
+        assert_eq!(lines, ["+ process(order: Order,", "customer: Customer):", "Result"]);
+        assert!(lines.iter().all(|l| text_width(l, 11) <= 160));
+
+        // Long words are split, short text is kept whole
+        let lines = wrap_text("AVeryLongIdentifierWithoutSpaces", 11, 100);
+        assert_eq!(lines.concat(), "AVeryLongIdentifierWithoutSpaces");
+        assert!(lines.len() > 1 && lines.iter().all(|l| text_width(l, 11) <= 100));
+        assert_eq!(wrap_text("short", 11, 100), ["short"]);
+    }
+
+    #[test]
+    fn test_node_size_fits_its_text() {
+        use crate::layout::{get_node_size, LayoutConfig};
+        use crate::output::{compile_to_output, TextElement};
+        use crate::parser::{compile, parse_file};
+
+        let line = "+ findAllByCustomerAndStatus(customer: Customer, status: Status): List<Order>";
+        let src = format!("class Repo {{\n    {}\n}}\n", line);
+        let diagram = compile(&parse_file(&src).unwrap()).unwrap();
+        let cfg = LayoutConfig::default();
+        let size = get_node_size(&diagram.nodes[0], &cfg);
+        assert_eq!(size.w, 8 + text_width(line, BODY_FONT_SIZE) + 8);
+
+        // With @wrap the line takes several rows instead, and every row fits
+        let output = compile_to_output(&format!("@wrap: 300\n{}", src));
+        let node = &output.nodes[0];
+        let rows: Vec<&str> = node.text_elements.iter().filter_map(|e| match e {
+            TextElement::BodyText { text, .. } => Some(text.as_str()),
+            _ => None,
+        }).collect();
+        assert!(rows.len() > 1);
+        assert_eq!(rows.join(" "), line);
+        assert!(node.bounds.w <= 8 + 300 + 8 && node.bounds.w >= cfg.class_size.w);
+        assert_eq!(node.bounds.h, size.h + (rows.len() as i32 - 1) * cfg.node_rendering.line_height);
+    }
+}
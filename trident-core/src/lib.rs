@@ -5,10 +5,19 @@
 mod parser;
 mod layout;
 mod output;
+mod formats;
+mod svg;
 mod wasm;
 
 // Re-export for external use
 pub use output::*;
 pub use wasm::*;
-pub use layout::{LayoutConfig, LayoutResult, RectI, SizeI};
+pub use svg::{render_svg, SvgOptions, SvgTheme};
+pub use formats::{ConversionWarning, Converted};
+pub use formats::dot::export_dot;
+pub use formats::mermaid::{export_mermaid, import_mermaid};
+pub use formats::plantuml::{export_plantuml, import_plantuml};
+pub use layout::{layout_diagram, route_edges, EdgeRoute, EdgeRouting, LayoutConfig, LayoutResult, RectI, SizeI, LAYOUT_ALGORITHMS};
 pub use parser::{PointI, Diagram, GroupId, NodeId};
+pub use parser::{parse_file, parse_file_recovering, compile, compile_recovering, emit_file, FileAst, ParseError, CompileError, TokenSpan};
+pub use parser::{parse_member, parse_members, Member, MemberKind, Param, Visibility};
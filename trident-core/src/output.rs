@@ -3,28 +3,38 @@
 //! These structs are serialized to JSON and sent to the React frontend
 //! for rendering the diagram.
 
-use crate::layout::{RectI, NodeRenderingConfig};
-use crate::parser::PointI;
+use crate::layout::{layout_diagram, route_edges, route_note_connectors, template_box_width, EdgeRoute, EdgeRouting, LayoutConfig, LayoutResult, RectI, NodeRenderingConfig, LAYOUT_ALGORITHMS, NOTE_FOLD, TEMPLATE_BOX_HEIGHT};
+use crate::layout::text::{text_width, BODY_FONT_SIZE, GROUP_LABEL_FONT_SIZE, STEREOTYPE_FONT_SIZE, TEMPLATE_FONT_SIZE, TITLE_FONT_SIZE};
+use crate::parser::{self, format_type_params, Diagram, Direction, FileAst, GroupId, Member, PointI, RelationEnd, Style, TokenSpan};
+use crate::parser::compile::Node;
 use serde::Serialize;
 
-/// Type of text element for rendering
+/// Type of text element for rendering.
+/// `x` is where the text starts (left-aligned) and `y` its baseline.
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum TextElement {
-    /// Stereotype line (modifiers and kind)
-    Stereotype { text: String, y: i32, font_size: i32 },
+    /// Stereotype line (modifiers and kind), centered
+    Stereotype { text: String, x: i32, y: i32, font_size: i32 },
     /// Title line (node label/id)
-    Title { text: String, y: i32, font_size: i32, italic: bool },
+    Title { text: String, x: i32, y: i32, font_size: i32, italic: bool },
     /// Separator line (---)
     Separator { x1: i32, y1: i32, x2: i32, y2: i32 },
-    /// Regular body text line
-    BodyText { text: String, y: i32, font_size: i32 },
+    /// Regular body text line; UML shows abstract members in italics and static ones underlined.
+    /// A wrapped line is one element per row.
+    BodyText { text: String, x: i32, y: i32, font_size: i32, italic: bool, underline: bool },
+    /// UML template box with the type parameters: a dashed rectangle over the top-right corner.
+    /// `x`/`y` is its top-left corner; `y` is negative as it straddles the top border.
+    TemplateParams { text: String, x: i32, y: i32, width: i32, height: i32, font_size: i32 },
 }
 
 /// A rendered node ready for React to display
 #[derive(Debug, Clone, Serialize)]
 pub struct NodeOutput {
+    /// Fully qualified name (`Outer.Inner.Config`), unique in the diagram
     pub id: String,
+    /// Unqualified name as declared
+    pub name: String,
     /// Node kind: "class" or "node"
     pub kind: String,
     /// Modifiers: "abstract", "interface", "enum", "rectangle", "circle", "diamond", etc.
@@ -41,33 +51,109 @@ pub struct NodeOutput {
     pub parent_offset: PointI,
     /// Whether this node was explicitly declared (false for implicit nodes from relations)
     pub explicit: bool,
+    /// Typed members parsed from the body, index-aligned with its lines
+    pub members: Vec<Member>,
+    /// Colors and line style overriding the theme
+    pub style: Style,
 }
 
-/// An edge between two nodes
+/// What the endpoints of an edge are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    /// `from`/`to` are node ids
+    Node,
+    /// `from`/`to` are group ids
+    Group,
+}
+
+/// An edge between two nodes, or between two named groups
 #[derive(Debug, Clone, Serialize)]
 pub struct EdgeOutput {
+    pub kind: EdgeKind,
     pub from: String,
     pub to: String,
     /// Arrow type as canonical string (e.g., "extends_left", "assoc_right")
     pub arrow: String,
     pub label: Option<String>,
+    /// Routed polyline in world coordinates: source port, bends, target port
+    pub points: Vec<PointI>,
+    /// Where to center the label
+    pub label_pos: PointI,
+    /// Multiplicity and role at the `from` end
+    pub from_end: RelationEnd,
+    /// Where to center the `from` end label
+    pub from_label_pos: PointI,
+    /// Multiplicity and role at the `to` end
+    pub to_end: RelationEnd,
+    /// Where to center the `to` end label
+    pub to_label_pos: PointI,
+    /// Colors and line style overriding the theme
+    pub style: Style,
+}
+
+/// A note, drawn as a sheet with a folded top-right corner
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteOutput {
+    /// Text lines with positions relative to the note's top-left corner
+    pub text_elements: Vec<TextElement>,
+    pub bounds: RectI,
+    /// Size of the folded corner
+    pub fold: i32,
+    /// Dashed line from the note to what it is attached to; empty for free-standing notes
+    pub connector: Vec<PointI>,
+    pub rendering_config: NodeRenderingConfig,
 }
 
 /// A group container
 #[derive(Debug, Clone, Serialize)]
 pub struct GroupOutput {
+    /// Fully qualified name
     pub id: String,
+    /// Unqualified name as declared, shown as the group's title
+    pub name: String,
+    /// Width of the title at `GROUP_LABEL_FONT_SIZE`
+    pub label_width: i32,
     pub bounds: RectI,
+    /// Colors and line style overriding the theme
+    pub style: Style,
+    pub rendering_config: NodeRenderingConfig,
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The statement was skipped
+    Error,
+    /// The statement was used, possibly with a fallback
+    Warning,
 }
 
-/// Error information for Monaco editor markers
+/// A problem in the source, positioned for Monaco editor markers
 #[derive(Debug, Clone, Serialize)]
-pub struct ErrorInfo {
+pub struct Diagnostic {
+    pub severity: Severity,
     pub message: String,
     pub line: usize,      // 1-based line number
-    pub column: usize,    // 1-based column number
+    pub column: usize,    // 1-based column in UTF-16 code units
     pub end_line: usize,  // 1-based end line (same as line for single-line errors)
-    pub end_column: usize, // 1-based end column
+    pub end_column: usize, // 1-based end column (exclusive) in UTF-16 code units
+}
+
+impl Diagnostic {
+    /// Marker over `span`, or over the whole line `line` of `input` when there is no token
+    fn new(severity: Severity, input: &str, message: &str, line: usize, span: Option<TokenSpan>) -> Self {
+        let (column, end_column) = match span {
+            Some(span) => (span.start_col_utf16, span.end_col_utf16),
+            None => {
+                let text = input.lines().nth(line.saturating_sub(1)).unwrap_or("");
+                // Highlight at least one character, e.g. at end of file
+                (1, (text.encode_utf16().count() + 1).max(2))
+            }
+        };
+        Diagnostic { severity, message: message.to_string(), line, column, end_line: line, end_column }
+    }
 }
 
 /// The combined output sent to React
@@ -79,9 +165,315 @@ pub struct DiagramOutput {
     pub nodes: Vec<NodeOutput>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub edges: Vec<EdgeOutput>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<NoteOutput>,
     /// List of implicit node IDs (for editor info diagnostics)
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub implicit_nodes: Vec<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<ErrorInfo>,
+    /// Parse and compile errors plus warnings, ordered by position
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagramOutput {
+    /// Whether any statement had to be skipped
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Parse, compile and lay out `input`.
+/// Statements with errors are skipped and the rest is still laid out; every problem
+/// is reported through `DiagramOutput::diagnostics`.
+pub fn compile_to_output(input: &str) -> DiagramOutput {
+    let (ast, parse_errors) = parser::parse_file_recovering(input);
+    let (diagram, compile_errors) = parser::compile_recovering(&ast);
+
+    let mut diagnostics: Vec<Diagnostic> = parse_errors
+        .iter()
+        .map(|e| Diagnostic::new(Severity::Error, input, &e.msg, e.line, e.span))
+        .chain(compile_errors.iter().map(|e| {
+            Diagnostic::new(Severity::Error, input, &e.msg, e.line, e.span)
+        }))
+        .collect();
+    diagnostics.extend(header_warnings(input, &ast));
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+
+    let mut output = diagram_to_output(&ast, &diagram);
+    output.diagnostics = diagnostics;
+    output
+}
+
+/// Warn about header directive values that fall back to a default
+fn header_warnings(input: &str, ast: &FileAst) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    let mut warn = |span: Option<TokenSpan>, message: String| {
+        let line = span.map(|s| s.line).unwrap_or(1);
+        warnings.push(Diagnostic::new(Severity::Warning, input, &message, line, span));
+    };
+    if let Some(layout) = &ast.layout
+        && !LAYOUT_ALGORITHMS.contains(&layout.as_str())
+    {
+        let expected = LAYOUT_ALGORITHMS.join(", ");
+        warn(ast.tokens.layout, format!("unknown layout '{}', using 'hierarchical' (expected one of: {})", layout, expected));
+    }
+    if let Some(routing) = &ast.routing
+        && EdgeRouting::from_name(routing).is_none()
+    {
+        let message = format!("unknown routing '{}', using 'straight' (expected 'straight', 'orthogonal' or 'bus')", routing);
+        warn(ast.tokens.routing, message);
+    }
+    if let Some(direction) = &ast.direction
+        && Direction::from_name(direction).is_none()
+    {
+        let message = format!("unknown direction '{}', using 'TB' (expected one of: {})", direction, Direction::NAMES.join(", "));
+        warn(ast.tokens.direction, message);
+    }
+    if let Some(wrap) = &ast.wrap
+        && wrap_width(wrap).is_none()
+    {
+        warn(ast.tokens.wrap, format!("invalid wrap width '{}', lines are not wrapped (expected a positive number of pixels)", wrap));
+    }
+    warnings
+}
+
+/// Layout configuration and algorithm name selected by the file's header directives
+pub fn layout_settings(ast: &FileAst) -> (LayoutConfig, &str) {
+    let mut cfg = LayoutConfig {
+        edge_routing: ast.routing.as_deref().and_then(EdgeRouting::from_name).unwrap_or_default(),
+        direction: ast.direction.as_deref().and_then(Direction::from_name).unwrap_or_default(),
+        ..LayoutConfig::default()
+    };
+    cfg.node_rendering.wrap_width = ast.wrap.as_deref().and_then(wrap_width);
+    // Use the layout algorithm specified in the AST, or default to hierarchical
+    let layout_name = ast.layout.as_deref().unwrap_or("hierarchical");
+    (cfg, layout_name)
+}
+
+/// The width set by a `@wrap` directive, if it is a positive number of pixels
+fn wrap_width(value: &str) -> Option<i32> {
+    value.parse().ok().filter(|&w| w > 0)
+}
+
+/// Lay out and route a compiled diagram according to the file's header directives
+pub fn diagram_to_output(ast: &FileAst, diagram: &Diagram) -> DiagramOutput {
+    let (cfg, layout_name) = layout_settings(ast);
+    let layout_result = layout_diagram(diagram, &cfg, layout_name);
+    let routes = route_edges(diagram, &layout_result, &cfg);
+    build_diagram_output(diagram, &cfg, &layout_result, &routes)
+}
+
+/// Build the renderer-facing output from a compiled and laid out diagram.
+/// `routes` must be index-aligned with `diagram.edges` followed by `diagram.group_edges` (see `route_edges`).
+pub fn build_diagram_output(diagram: &Diagram, cfg: &LayoutConfig, layout_result: &LayoutResult, routes: &[EdgeRoute]) -> DiagramOutput {
+    let rendering_config = &cfg.node_rendering;
+
+    // Build groups (only named groups, skip root and anonymous)
+    let groups: Vec<GroupOutput> = diagram.groups.iter()
+        .filter(|g| g.id.is_some() && g.gid != diagram.root)
+        .filter_map(|g| {
+            let bounds = layout_result.group_world_bounds.get(&g.gid).copied()?;
+            let name = g.id.as_ref()?.name().to_string();
+            Some(GroupOutput {
+                id: g.path.as_ref()?.0.clone(),
+                label_width: text_width(&name, GROUP_LABEL_FONT_SIZE),
+                name,
+                bounds,
+                style: g.style.clone(),
+                rendering_config: rendering_config.clone(),
+            })
+        })
+        .collect();
+
+    // Build nodes
+    let nodes: Vec<NodeOutput> = diagram.nodes.iter().map(|n| {
+        let bounds = layout_result.node_world_bounds.get(&n.nid).copied().unwrap_or(RectI { x: 0, y: 0, w: 0, h: 0 });
+        // Get parent group's world position for local coordinate calculation
+        let parent_world = layout_result.group_world_pos.get(&n.group).copied().unwrap_or(PointI { x: 0, y: 0 });
+        let text_elements = generate_text_elements(n, rendering_config, bounds.w);
+
+        NodeOutput {
+            id: n.path.0.clone(),
+            name: n.id.name().to_string(),
+            kind: n.kind.clone(),
+            modifiers: n.modifiers.clone(),
+            label: n.label.clone(),
+            text_elements,
+            rendering_config: rendering_config.clone(),
+            bounds,
+            has_pos: n.pos.is_some(),
+            parent_offset: parent_world,
+            explicit: n.explicit,
+            members: n.members.clone(),
+            style: n.style.clone(),
+        }
+    }).collect();
+
+    // Build edges
+    let mut edges: Vec<EdgeOutput> = diagram.edges.iter().zip(routes).map(|(e, route)| {
+        let from_id = diagram.nodes[e.from.0].path.0.clone();
+        let to_id = diagram.nodes[e.to.0].path.0.clone();
+        EdgeOutput {
+            kind: EdgeKind::Node,
+            from: from_id,
+            to: to_id,
+            arrow: e.arrow.clone(),
+            label: e.label.clone(),
+            points: route.points.clone(),
+            label_pos: route.label_pos,
+            from_end: e.from_end.clone(),
+            from_label_pos: route.from_label_pos,
+            to_end: e.to_end.clone(),
+            to_label_pos: route.to_label_pos,
+            style: e.style.clone(),
+        }
+    }).collect();
+
+    // Group edges are only created between named groups
+    let group_name = |gid: GroupId| diagram.groups[gid.0].path.as_ref().map(|path| path.0.clone()).unwrap_or_default();
+    edges.extend(diagram.group_edges.iter().zip(&routes[diagram.edges.len().min(routes.len())..]).map(|(e, route)| {
+        EdgeOutput {
+            kind: EdgeKind::Group,
+            from: group_name(e.from),
+            to: group_name(e.to),
+            arrow: e.arrow.clone(),
+            label: e.label.clone(),
+            points: route.points.clone(),
+            label_pos: route.label_pos,
+            from_end: e.from_end.clone(),
+            from_label_pos: route.from_label_pos,
+            to_end: e.to_end.clone(),
+            to_label_pos: route.to_label_pos,
+            style: e.style.clone(),
+        }
+    }));
+
+    // Build notes
+    let connectors = route_note_connectors(diagram, layout_result, routes);
+    let notes: Vec<NoteOutput> = diagram.notes.iter().zip(connectors).filter_map(|(note, connector)| {
+        let bounds = layout_result.note_world_bounds.get(&note.id).copied()?;
+        let lines = note.lines.iter().flat_map(|line| rendering_config.wrap(line, BODY_FONT_SIZE));
+        let text_elements = lines.enumerate().map(|(i, line)| TextElement::BodyText {
+            text: line,
+            x: rendering_config.padding,
+            y: rendering_config.padding + i as i32 * rendering_config.line_height + BODY_FONT_SIZE,
+            font_size: BODY_FONT_SIZE,
+            italic: false,
+            underline: false,
+        }).collect();
+        Some(NoteOutput { text_elements, bounds, fold: NOTE_FOLD, connector, rendering_config: rendering_config.clone() })
+    }).collect();
+
+    // Collect implicit node IDs for editor diagnostics
+    let implicit_nodes: Vec<String> = diagram.nodes.iter()
+        .filter(|n| !n.explicit)
+        .map(|n| n.path.0.clone())
+        .collect();
+
+    DiagramOutput { groups, nodes, edges, notes, implicit_nodes, diagnostics: Vec::new() }
+}
+
+/// Generate positioned text elements for a node `width` wide
+fn generate_text_elements(node: &Node, config: &NodeRenderingConfig, width: i32) -> Vec<TextElement> {
+    let mut elements = Vec::new();
+    let mut current_y = config.padding;
+
+    // Template box over the top-right corner, sticking out a little to the right
+    if !node.type_params.is_empty() {
+        let box_width = template_box_width(node, config);
+        elements.push(TextElement::TemplateParams {
+            text: format_type_params(&node.type_params),
+            x: width - box_width + config.padding,
+            y: -TEMPLATE_BOX_HEIGHT / 2,
+            width: box_width,
+            height: TEMPLATE_BOX_HEIGHT,
+            font_size: TEMPLATE_FONT_SIZE,
+        });
+        current_y += TEMPLATE_BOX_HEIGHT / 2;
+    }
+
+    // Add stereotype if present
+    let has_stereotypes = !node.modifiers.is_empty() || node.kind != "class";
+    if has_stereotypes {
+        let stereotypes = format_modifiers(&node.modifiers, &node.kind);
+        elements.push(TextElement::Stereotype {
+            x: (width - text_width(&stereotypes, STEREOTYPE_FONT_SIZE)) / 2,
+            text: stereotypes,
+            y: current_y + STEREOTYPE_FONT_SIZE, // Baseline for 10px font, centered in line_height
+            font_size: STEREOTYPE_FONT_SIZE,
+        });
+        current_y += config.line_height;
+    }
+
+    // Add title
+    let title = node.label.clone().unwrap_or_else(|| node.id.name().to_string());
+    let is_abstract = node.modifiers.contains(&"abstract".to_string());
+    elements.push(TextElement::Title {
+        text: title,
+        x: config.padding,
+        y: current_y + TITLE_FONT_SIZE, // Baseline for 12px font
+        font_size: TITLE_FONT_SIZE,
+        italic: is_abstract,
+    });
+    current_y += config.line_height;
+
+    // Always add separator line below title (centered in its line slot)
+    elements.push(TextElement::Separator {
+        x1: 0,
+        y1: current_y + config.line_height / 2,
+        x2: 9999,
+        y2: current_y + config.line_height / 2,
+    });
+    current_y += config.line_height;
+
+    // Add body lines in a simple column
+    for (line, member) in node.body_lines.iter().zip(&node.members) {
+        if is_separator_line(line) {
+            // Add separator line (centered in its line slot)
+            elements.push(TextElement::Separator {
+                x1: 0,
+                y1: current_y + config.line_height / 2,
+                x2: 9999,
+                y2: current_y + config.line_height / 2,
+            });
+            current_y += config.line_height;
+        } else {
+            // Add text rows (baseline positioned for 11px font)
+            for row in config.wrap(line, BODY_FONT_SIZE) {
+                elements.push(TextElement::BodyText {
+                    text: row,
+                    x: config.padding,
+                    y: current_y + BODY_FONT_SIZE,
+                    font_size: BODY_FONT_SIZE,
+                    italic: member.is_abstract,
+                    underline: member.is_static,
+                });
+                current_y += config.line_height;
+            }
+        }
+    }
+
+    elements
+}
+
+/// Format modifiers and kind for stereotype display
+pub(crate) fn format_modifiers(modifiers: &[String], kind: &str) -> String {
+    let mut parts = Vec::new();
+
+    // Add modifiers as stereotypes
+    for modifier in modifiers {
+        parts.push(format!("«{}»", modifier));
+    }
+
+    // Add kind as stereotype if not "class"
+    if kind != "class" {
+        parts.push(format!("«{}»", kind));
+    }
+
+    parts.join(" ")
+}
+
+/// Check if a line is a separator (---)
+pub(crate) fn is_separator_line(line: &str) -> bool {
+    line.trim().chars().all(|c| c == '-')
 }
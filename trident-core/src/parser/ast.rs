@@ -4,24 +4,54 @@
 // - Comments: %% ... (line comments)
 // - group { ... }              (anonymous, not rendered; layout scope)
 // - group IDENT { ... }        (named; visual name = IDENT)
-// - [modifiers] <node_kind> IDENT ["Display"]    (optionally with block)
+// - [modifiers] <node_kind> IDENT [<TYPE_PARAMS>] ["Display"]    (optionally with block)
 //   Examples:
 //     class Foo
+//     class Foo :::warning
 //     abstract class Bar
 //     interface Baz
 //     sealed enum Status
+//     interface Repository<T, ID extends Serializable>
+// - note "Text"              (free-standing)
+// - note for IDENT "Text"     (attached to a node or named group)
+// - note on IDENT --> IDENT "Text"    (attached to that relation)
+//   the text can also be written as a block, one line of text per line:
+//     note for Foo {
+//         Loaded lazily
+//         on first access
+//     }
+// - @layout: NAME / @routing: NAME / @direction: NAME header directives at the top of the file
 // - @pos: (INT, INT) allowed only inside the nearest node/group block (fixed, local)
+// - @direction: TB|LR|BT|RL inside a group block overrides the rank direction for its contents
+// - style directives inside node/group blocks: @fill, @stroke, @text_color (colors),
+//   @stroke_width (number) and @dash (lengths like `6 3`, or `none`)
+// - style classes: `style NAME { @fill: #fee; @stroke: #c00 }` (inline or as a block),
+//   applied with `class A :::NAME` or `@class: NAME, OTHER`; a style whose NAME is a
+//   node kind or modifier (`interface`, `abstract`) applies to every such node
 // - relations can be written with or without spaces:
 //     A-->B
 //     A --> B
 //     A<|--B : label
+//     A --> B : "quoted, may contain %% or \"quotes\""
+// - relations can carry a multiplicity and/or role at each end, as a quoted string:
+//     Order "1" *-- "1..* items" LineItem
+// - endpoints can be qualified with enclosing group names:
+//     Outer.Inner.Config --> Other.Config
+// - relations can be styled with the same directives, inline or as a block:
+//     A --> B { @stroke: red; @dash: 4 2 }
+//     A --> B {
+//         @stroke_width: 2
+//     }
 //
 // Notes / limitations:
-// - IDENT: [A-Za-z_][A-Za-z0-9_]*
-// - STRING: "..." (no escapes)
-// - Only one @pos per node/group block (duplicate is error)
-// - Relation endpoints must be IDENT (no qualification yet)
-
+// - IDENT: Unicode letter or _, then letters, digits or _; or `anything but backticks`
+// - STRING: "..." with \", \\ and \n escapes
+// - %% inside strings and backticks does not start a comment
+// - Only one @pos (or style directive of each kind) per block (duplicate is error)
+// - Relation endpoints are IDENT or a qualified name IDENT.IDENT... naming
+//   a node or group inside other named groups (resolved in compile.rs)
+
+use crate::parser::lexer::{find_comment_start, find_unquoted, is_path, parse_string, path_name, path_segment_tokens, take_ident, take_path};
 use crate::parser::types::*;
 use crate::parser::types::{get_arrow_registry, arrow_from_token};
 use std::fmt;
@@ -29,8 +59,10 @@ use std::fmt;
 #[derive(Debug, Clone)]
 pub struct ParseError {
     pub line: usize, // 1-based
-    pub col: usize,  // 1-based best-effort
+    pub col: usize,  // 1-based byte column
     pub msg: String,
+    /// The offending token, or None when the error isn't tied to one (e.g. end of file)
+    pub span: Option<TokenSpan>,
 }
 
 impl fmt::Display for ParseError {
@@ -41,18 +73,51 @@ impl fmt::Display for ParseError {
 impl std::error::Error for ParseError {}
 
 pub fn parse_file(input: &str) -> Result<FileAst, ParseError> {
+    let (ast, errors) = parse_file_recovering(input);
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(ast),
+    }
+}
+
+/// Parse `input`, skipping statements that fail to parse.
+/// Returns an AST of everything that did parse along with every error, in source order.
+/// Don't emit the AST back over the source when there are errors: skipped lines are lost.
+pub fn parse_file_recovering(input: &str) -> (FileAst, Vec<ParseError>) {
     let mut p = Parser::new(input);
     
-    // Try to parse @layout: directive at the start
-    let layout = p.try_parse_layout()?;
+    // Try to parse @layout:/@routing:/@direction:/@wrap: directives at the start
+    let header = p.parse_header();
     
-    let items = p.parse_items_until_end()?;
-    Ok(FileAst { layout, items })
+    let items = p.parse_items_until_end();
+    let ast = FileAst {
+        layout: header.layout,
+        routing: header.routing,
+        direction: header.direction,
+        wrap: header.wrap,
+        items,
+        tokens: header.tokens,
+        comments: header.comments,
+    };
+    (ast, p.errors)
+}
+
+/// Directives that may only appear at the top of a file
+#[derive(Default)]
+struct FileHeader {
+    layout: Option<String>,
+    routing: Option<String>,
+    direction: Option<String>,
+    wrap: Option<String>,
+    tokens: FileTokens,
+    comments: AttachedComments,
 }
 
 struct Parser<'a> {
     lines: Vec<&'a str>,
     i: usize,
+    /// Errors recovered from so far
+    errors: Vec<ParseError>,
 }
 
 impl<'a> Parser<'a> {
@@ -61,7 +126,7 @@ impl<'a> Parser<'a> {
             .lines()
             .map(|l| l.strip_suffix('\r').unwrap_or(l))
             .collect::<Vec<_>>();
-        Self { lines, i: 0 }
+        Self { lines, i: 0, errors: Vec::new() }
     }
 
     fn eof(&self) -> bool {
@@ -76,26 +141,60 @@ impl<'a> Parser<'a> {
         self.i += 1;
     }
 
-    fn err<T>(&self, col: usize, msg: impl Into<String>) -> Result<T, ParseError> {
-        Err(ParseError {
+    /// Span of `token` if it is a slice of the current line. The offset comes
+    /// from the addresses, so anything else (such as an owned copy) has no span.
+    fn span_of(&self, token: &str) -> Option<TokenSpan> {
+        let line = self.lines[self.i];
+        let start = (token.as_ptr() as usize).checked_sub(line.as_ptr() as usize)?;
+        let end = start + token.len();
+        (end <= line.len()).then(|| TokenSpan::from_byte_range(self.line_no(), line, start, end))
+    }
+
+    /// Error at end of file, which has no token to point at
+    fn eof_error(&self, msg: impl Into<String>) -> ParseError {
+        ParseError { line: self.line_no(), col: 1, msg: msg.into(), span: None }
+    }
+
+    /// Error pointing at `token`, a slice of the current line
+    fn err_at<T>(&self, token: &str, msg: impl Into<String>) -> Result<T, ParseError> {
+        Err(self.error_at(token, msg))
+    }
+
+    fn error_at(&self, token: &str, msg: impl Into<String>) -> ParseError {
+        let span = self.span_of(token);
+        ParseError {
             line: self.line_no(),
-            col: col.max(1),
+            col: span.map_or(1, |s| s.start_col),
             msg: msg.into(),
-        })
+            span,
+        }
     }
 
     fn current_line_wo_comment(&self) -> &'a str {
         let raw = self.lines[self.i];
-        match raw.find("%%") {
+        match find_comment_start(raw) {
             Some(idx) => &raw[..idx],
             None => raw,
         }
     }
 
+    /// Text after `%%` on the current line, if any
+    fn current_trailing_comment(&self) -> Option<String> {
+        let raw = self.lines[self.i];
+        find_comment_start(raw).map(|idx| raw[idx + 2..].to_string())
+    }
+
+    /// Remember the trailing comment of directive `name` on the current line
+    fn attach_directive_comment(&self, comments: &mut AttachedComments, name: &str) {
+        if let Some(text) = self.current_trailing_comment() {
+            comments.directives.push((name.to_string(), text));
+        }
+    }
+
     /// Check if line is only whitespace and/or a comment
     fn is_comment_or_empty_line(&self) -> bool {
         let raw = self.lines[self.i];
-        let without_comment = match raw.find("%%") {
+        let without_comment = match find_comment_start(raw) {
             Some(idx) => &raw[..idx],
             None => raw,
         };
@@ -105,7 +204,7 @@ impl<'a> Parser<'a> {
     /// Parse a comment line into CommentAst
     fn parse_comment_line(&self) -> Option<CommentAst> {
         let raw = self.lines[self.i];
-        if let Some(idx) = raw.find("%%") {
+        if let Some(idx) = find_comment_start(raw) {
             Some(CommentAst {
                 prefix: raw[..idx].to_string(),
                 text: raw[idx + 2..].to_string(),
@@ -121,46 +220,155 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Try to parse @layout: directive at the start of the file.
-    /// Skips leading comments and empty lines.
-    fn try_parse_layout(&mut self) -> Result<Option<String>, ParseError> {
-        // Save position in case we need to backtrack
-        let start_i = self.i;
-        
-        // Skip leading comments and empty lines
-        while !self.eof() && self.is_comment_or_empty_line() {
-            self.advance();
-        }
-        
-        if self.eof() {
-            self.i = start_i;
-            return Ok(None);
-        }
-        
-        let t = self.current_line_wo_comment().trim();
-        
-        if t.starts_with("@layout:") {
-            let layout_name = t.strip_prefix("@layout:").unwrap().trim().to_string();
-            if layout_name.is_empty() {
-                return self.err(1, "@layout: requires a layout name (e.g., 'hierarchical' or 'grid')");
+    /// Parse header directives (@layout:, @routing:, @direction:) at the start of the file.
+    /// Skips leading comments and empty lines. Invalid directives are recorded and skipped.
+    fn parse_header(&mut self) -> FileHeader {
+        let mut header = FileHeader::default();
+
+        loop {
+            // Save position in case we need to backtrack
+            let start_i = self.i;
+
+            // Skip leading comments and empty lines, keeping them in case a directive follows
+            let mut leading = Vec::new();
+            while !self.eof() && self.is_comment_or_empty_line() {
+                leading.extend(self.parse_comment_line());
+                self.advance();
             }
-            self.advance();
-            return Ok(Some(layout_name));
+
+            if self.eof() {
+                self.i = start_i;
+                return header;
+            }
+
+            let t = self.current_line_wo_comment().trim();
+            if ["@layout:", "@routing:", "@direction:", "@wrap:"].iter().any(|d| t.starts_with(d)) {
+                let index = header.layout.is_some() as usize
+                    + header.routing.is_some() as usize
+                    + header.direction.is_some() as usize
+                    + header.wrap.is_some() as usize;
+                header.comments.inner.extend(leading.into_iter().map(|c| (index, c)));
+            }
+
+            if let Some(rest) = t.strip_prefix("@layout:") {
+                let layout_name = rest.trim().to_string();
+                if header.layout.is_some() {
+                    self.errors.push(self.error_at(t, "duplicate @layout directive"));
+                } else if layout_name.is_empty() {
+                    let msg = "@layout: requires a layout name (e.g., 'hierarchical' or 'grid')";
+                    self.errors.push(self.error_at(t, msg));
+                } else {
+                    header.layout = Some(layout_name);
+                    header.tokens.layout = self.span_of(t);
+                    self.attach_directive_comment(&mut header.comments, "layout");
+                }
+                self.advance();
+                continue;
+            }
+
+            if let Some(rest) = t.strip_prefix("@routing:") {
+                let routing_name = rest.trim().to_string();
+                if header.routing.is_some() {
+                    self.errors.push(self.error_at(t, "duplicate @routing directive"));
+                } else if routing_name.is_empty() {
+                    let msg = "@routing: requires a routing mode (e.g., 'straight' or 'orthogonal')";
+                    self.errors.push(self.error_at(t, msg));
+                } else {
+                    header.routing = Some(routing_name);
+                    header.tokens.routing = self.span_of(t);
+                    self.attach_directive_comment(&mut header.comments, "routing");
+                }
+                self.advance();
+                continue;
+            }
+
+            if let Some(rest) = t.strip_prefix("@direction:") {
+                let direction_name = rest.trim().to_string();
+                if header.direction.is_some() {
+                    self.errors.push(self.error_at(t, "duplicate @direction directive"));
+                } else if direction_name.is_empty() {
+                    let msg = "@direction: requires a direction (TB, LR, BT or RL)";
+                    self.errors.push(self.error_at(t, msg));
+                } else {
+                    header.direction = Some(direction_name);
+                    header.tokens.direction = self.span_of(t);
+                    self.attach_directive_comment(&mut header.comments, "direction");
+                }
+                self.advance();
+                continue;
+            }
+
+            if let Some(rest) = t.strip_prefix("@wrap:") {
+                let width = rest.trim().to_string();
+                if header.wrap.is_some() {
+                    self.errors.push(self.error_at(t, "duplicate @wrap directive"));
+                } else if width.is_empty() {
+                    self.errors.push(self.error_at(t, "@wrap: requires a width in pixels (e.g., '240')"));
+                } else {
+                    header.wrap = Some(width);
+                    header.tokens.wrap = self.span_of(t);
+                    self.attach_directive_comment(&mut header.comments, "wrap");
+                }
+                self.advance();
+                continue;
+            }
+
+            // No more header directives, reset position
+            self.i = start_i;
+            return header;
         }
-        
-        // No @layout found, reset position
-        self.i = start_i;
-        Ok(None)
     }
 
-    fn parse_items_until_end(&mut self) -> Result<Vec<Stmt>, ParseError> {
+    fn parse_items_until_end(&mut self) -> Vec<Stmt> {
         let mut items = Vec::new();
         while !self.eof() {
-            if let Some(stmt) = self.parse_stmt_or_none()? {
-                items.push(stmt);
+            self.parse_stmt_into(&mut items);
+        }
+        items
+    }
+
+    /// Parse one statement into `items`; on error, record it and skip the statement
+    fn parse_stmt_into(&mut self, items: &mut Vec<Stmt>) {
+        let start = self.i;
+        match self.parse_stmt_or_none() {
+            Ok(Some(stmt)) => items.push(stmt),
+            Ok(None) => {}
+            Err(e) => {
+                self.errors.push(e);
+                self.skip_failed_stmt(start);
             }
         }
-        Ok(items)
+    }
+
+    /// Skip the header line of a statement that failed at line index `start`,
+    /// together with the `{ ... }` block it opens (if any)
+    fn skip_failed_stmt(&mut self, start: usize) {
+        if self.i != start {
+            // The header was consumed; the error is about the current line, so resume there
+            return;
+        }
+        let opens_block = self.current_line_wo_comment().trim_end().ends_with('{');
+        self.advance();
+        if !opens_block {
+            if !self.peek_next_nonempty_is_lbrace() {
+                return;
+            }
+            while self.current_line_wo_comment().trim() != "{" {
+                self.advance();
+            }
+            self.advance();
+        }
+
+        let mut depth = 1;
+        while !self.eof() && depth > 0 {
+            let t = self.current_line_wo_comment().trim();
+            if t == "}" {
+                depth -= 1;
+            } else if t.ends_with('{') {
+                depth += 1;
+            }
+            self.advance();
+        }
     }
 
     fn parse_stmt_or_none(&mut self) -> Result<Option<Stmt>, ParseError> {
@@ -186,7 +394,7 @@ impl<'a> Parser<'a> {
         }
 
         if t == "}" {
-            return self.err(1, "unexpected '}'");
+            return self.err_at(t, "unexpected '}'");
         }
 
         if starts_with_kw(t, "group") {
@@ -194,23 +402,24 @@ impl<'a> Parser<'a> {
             return Ok(Some(Stmt::Group(g)));
         }
 
+        if is_note_header(t) {
+            let n = self.parse_note()?;
+            return Ok(Some(Stmt::Note(n)));
+        }
+
+        if is_style_header(t) {
+            let s = self.parse_style_rule()?;
+            return Ok(Some(Stmt::Style(s)));
+        }
+
         // Try to parse as node declaration (with optional modifiers)
         if let Some(node) = self.try_parse_node()? {
             return Ok(Some(Stmt::Node(node)));
         }
 
         // Otherwise, relation
-        let start_line = self.line_no();
-        let rel = self.parse_relation_line(t).map_err(|mut e| {
-            // keep current line number
-            e.line = self.line_no();
-            e
-        })?;
-        self.advance();
-        Ok(Some(Stmt::Relation(RelationAst {
-            span: Some(Span { start_line, end_line: start_line }),
-            ..rel
-        })))
+        let rel = self.parse_relation()?;
+        Ok(Some(Stmt::Relation(rel)))
     }
 
     /// Try to parse a node declaration: [modifiers] <node_kind> IDENT ["Label"] [{ ... }]
@@ -283,10 +492,15 @@ impl<'a> Parser<'a> {
         
         // Determine final kind
         let kind = mapped_kind.unwrap_or(words[kind_idx]).to_string();
-        let id = Ident(id_str.to_string());
+        let id = Ident(path_name(id_str));
+        let tokens = NodeTokens {
+            keywords: words.iter().filter_map(|w| self.span_of(w)).collect(),
+            id: self.span_of(id_str),
+            ..NodeTokens::default()
+        };
         
         // Parse rest of line
-        self.parse_node_with_parts(modifiers, kind, original_kind, id, rest)
+        self.parse_node_with_parts(modifiers, kind, original_kind, id, tokens, rest)
     }
     
     /// Parse node after modifiers/kind/id are known
@@ -296,39 +510,66 @@ impl<'a> Parser<'a> {
         kind: String,
         original_kind: String,
         id: Ident,
+        mut tokens: NodeTokens,
         mut rest: &str,
     ) -> Result<Option<NodeAst>, ParseError> {
         let start_line = self.line_no();
+
+        // optional type parameter list: `<T, U extends Bound>`
+        let mut type_params = Vec::new();
+        if rest.starts_with('<') {
+            let list = take_type_params(rest).ok_or_else(|| self.error_at(rest, "unterminated type parameter list; expected '>'"))?;
+            type_params = TypeParam::parse_list(&list[1..list.len() - 1]).map_err(|msg| self.error_at(list, msg))?;
+            if type_params.is_empty() {
+                return self.err_at(list, "empty type parameter list");
+            }
+            tokens.type_params = self.span_of(list);
+            rest = rest[list.len()..].trim_start();
+        }
         
         // optional label string
         let mut label: Option<String> = None;
         if rest.starts_with('"') {
-            let (s, after) = parse_string_prefix(rest).map_err(|msg| ParseError {
-                line: self.line_no(),
-                col: 1,
-                msg,
-            })?;
+            let (s, after) = parse_string(rest).map_err(|msg| self.error_at(rest, msg))?;
+            tokens.label = self.span_of(&rest[..rest.len() - after.len()]);
             label = Some(s);
             rest = after.trim();
         }
 
+        // optional style classes: `:::warning :::bold`
+        let mut classes = Vec::new();
+        while let Some(after) = rest.strip_prefix(":::") {
+            let end = after.find(|c: char| c.is_whitespace() || c == '{' || c == ':').unwrap_or(after.len());
+            let name = &after[..end];
+            if !is_class_name(name) {
+                return self.err_at(&rest[..3 + end], "expected a style class name after ':::'");
+            }
+            classes.push(name.to_string());
+            tokens.classes.extend(self.span_of(name));
+            rest = after[end..].trim_start();
+        }
+
         // optional '{' on same line
         let mut has_lbrace = false;
         if rest.starts_with('{') {
             has_lbrace = true;
+            tokens.lbrace = self.span_of(&rest[..1]);
             rest = rest[1..].trim();
         }
         if !rest.is_empty() {
-            return self.err(1, "unexpected tokens in node declaration");
+            return self.err_at(rest, "unexpected tokens in node declaration");
         }
 
+        let mut comments = AttachedComments { trailing: self.current_trailing_comment(), ..AttachedComments::default() };
         self.advance(); // consume node header
 
         // no block => empty node
         if !has_lbrace {
             // maybe next line is '{' to start block
             if self.peek_next_nonempty_is_lbrace() {
-                self.consume_required_lbrace("node")?;
+                let mut skipped = Vec::new();
+                tokens.lbrace = self.consume_required_lbrace("node", &mut skipped)?;
+                comments.inner.extend(skipped.into_iter().map(|c| (0, c)));
                 has_lbrace = true;
             }
         }
@@ -341,31 +582,58 @@ impl<'a> Parser<'a> {
                 modifiers,
                 id,
                 label,
+                type_params,
+                classes,
                 pos: None,
                 width: None,
                 height: None,
+                style: Style::default(),
                 body_lines: Vec::new(),
                 span: Some(Span { start_line, end_line: start_line }),
+                tokens,
+                comments,
             }));
         }
 
         let mut pos: Option<PointI> = None;
         let mut width: Option<i32> = None;
         let mut height: Option<i32> = None;
+        let mut style = Style::default();
         let mut body_lines: Vec<String> = Vec::new();
 
         loop {
             if self.eof() {
-                return self.err(1, "unexpected end of file; missing '}' for node");
+                // Keep what we have so the node still shows up
+                self.errors.push(self.eof_error("unexpected end of file; missing '}' for node"));
+                return Ok(Some(NodeAst {
+                    kind,
+                    original_kind,
+                    modifiers,
+                    id,
+                    label,
+                    type_params,
+                    classes,
+                    pos,
+                    width,
+                    height,
+                    style,
+                    body_lines,
+                    span: Some(Span { start_line, end_line: self.lines.len() }),
+                    tokens,
+                    comments,
+                }));
             }
 
             let t = self.current_line_wo_comment().trim();
             if t.is_empty() {
+                comments.inner.extend(self.parse_comment_line().map(|c| (body_lines.len(), c)));
                 self.advance();
                 continue;
             }
             if t == "}" {
                 let end_line = self.line_no();
+                tokens.rbrace = self.span_of(t);
+                comments.closing = self.current_trailing_comment();
                 self.advance();
                 return Ok(Some(NodeAst {
                     kind,
@@ -373,55 +641,73 @@ impl<'a> Parser<'a> {
                     modifiers,
                     id,
                     label,
+                    type_params,
+                    classes,
                     pos,
                     width,
                     height,
+                    style,
                     body_lines,
                     span: Some(Span { start_line, end_line }),
+                    tokens,
+                    comments,
                 }));
             }
 
             if t.starts_with("@pos:") {
                 if pos.is_some() {
-                    return self.err(1, "duplicate @pos in node block");
+                    self.errors.push(self.error_at(t, "duplicate @pos in node block"));
+                } else {
+                    match parse_pos_line(t) {
+                        Ok(p) => pos = Some(p),
+                        Err(msg) => self.errors.push(self.error_at(t, msg)),
+                    }
+                    tokens.directives.extend(self.span_of(t));
+                    self.attach_directive_comment(&mut comments, "pos");
                 }
-                pos = Some(parse_pos_line(t).map_err(|msg| ParseError {
-                    line: self.line_no(),
-                    col: 1,
-                    msg,
-                })?);
                 self.advance();
                 continue;
             }
 
             if t.starts_with("@width:") {
                 if width.is_some() {
-                    return self.err(1, "duplicate @width in node block");
+                    self.errors.push(self.error_at(t, "duplicate @width in node block"));
+                } else {
+                    match parse_int_directive(t, "@width:") {
+                        Ok(w) => width = Some(w),
+                        Err(msg) => self.errors.push(self.error_at(t, msg)),
+                    }
+                    tokens.directives.extend(self.span_of(t));
+                    self.attach_directive_comment(&mut comments, "width");
                 }
-                width = Some(parse_int_directive(t, "@width:").map_err(|msg| ParseError {
-                    line: self.line_no(),
-                    col: 1,
-                    msg,
-                })?);
                 self.advance();
                 continue;
             }
 
             if t.starts_with("@height:") {
                 if height.is_some() {
-                    return self.err(1, "duplicate @height in node block");
+                    self.errors.push(self.error_at(t, "duplicate @height in node block"));
+                } else {
+                    match parse_int_directive(t, "@height:") {
+                        Ok(h) => height = Some(h),
+                        Err(msg) => self.errors.push(self.error_at(t, msg)),
+                    }
+                    tokens.directives.extend(self.span_of(t));
+                    self.attach_directive_comment(&mut comments, "height");
                 }
-                height = Some(parse_int_directive(t, "@height:").map_err(|msg| ParseError {
-                    line: self.line_no(),
-                    col: 1,
-                    msg,
-                })?);
+                self.advance();
+                continue;
+            }
+
+            if self.parse_style_directive(t, "node", &mut style, &mut tokens.directives, Some(&mut tokens.classes), Some(&mut comments)) {
                 self.advance();
                 continue;
             }
 
             // opaque line
             body_lines.push(t.to_string());
+            tokens.body_lines.extend(self.span_of(t));
+            comments.body.push(self.current_trailing_comment());
             self.advance();
         }
     }
@@ -434,6 +720,7 @@ impl<'a> Parser<'a> {
         let t = self.current_line_wo_comment().trim();
 
         // parse header: "group" [IDENT]? ["{"]?
+        let mut tokens = GroupTokens { keyword: self.span_of(&t[.."group".len()]), ..GroupTokens::default() };
         let mut rest = t.strip_prefix("group").unwrap().trim();
 
         let mut id: Option<Ident> = None;
@@ -441,130 +728,462 @@ impl<'a> Parser<'a> {
 
         if rest.starts_with('{') {
             has_lbrace = true;
+            tokens.lbrace = self.span_of(&rest[..1]);
             rest = rest[1..].trim();
             if !rest.is_empty() {
-                return self.err(1, "unexpected tokens after '{' in group header");
+                return self.err_at(rest, "unexpected tokens after '{' in group header");
             }
         } else if !rest.is_empty() {
             // expect IDENT or IDENT followed by '{'
             // Allow "group G{" or "group G {"
             let (ident_part, after_ident) = take_ident_prefix(rest);
             let Some(ident) = ident_part else {
-                return self.err(1, "expected '{' or group identifier after 'group'");
+                return self.err_at(rest, "expected '{' or group identifier after 'group'");
             };
-            id = Some(Ident(ident.to_string()));
+            id = Some(Ident(path_name(ident)));
+            tokens.id = self.span_of(ident);
             rest = after_ident.trim();
 
             if rest.starts_with('{') {
                 has_lbrace = true;
+                tokens.lbrace = self.span_of(&rest[..1]);
                 rest = rest[1..].trim();
             }
 
             if !rest.is_empty() {
-                return self.err(1, "unexpected tokens in group header");
+                return self.err_at(rest, "unexpected tokens in group header");
             }
         }
 
+        let mut comments = AttachedComments { trailing: self.current_trailing_comment(), ..AttachedComments::default() };
         self.advance(); // consume header line
 
-        if !has_lbrace {
-            self.consume_required_lbrace("group")?;
-        }
-
-        // parse body: allow @pos lines, comments, and nested statements
+        // parse body: allow @pos, @direction and style lines, comments, and nested statements
         let mut pos: Option<PointI> = None;
+        let mut style = Style::default();
+        let mut direction: Option<Direction> = None;
         let mut items: Vec<Stmt> = Vec::new();
 
+        if !has_lbrace {
+            let mut skipped = Vec::new();
+            tokens.lbrace = self.consume_required_lbrace("group", &mut skipped)?;
+            items.extend(skipped.into_iter().map(Stmt::Comment));
+        }
+
         loop {
             if self.eof() {
-                return self.err(1, "unexpected end of file; missing '}' for group");
+                // Keep the contents so they still show up
+                self.errors.push(self.eof_error("unexpected end of file; missing '}' for group"));
+                return Ok(GroupAst {
+                    id,
+                    pos,
+                    style,
+                    direction,
+                    items,
+                    span: Some(Span { start_line, end_line: self.lines.len() }),
+                    tokens,
+                    comments,
+                });
             }
 
             let t = self.current_line_wo_comment().trim();
             
             if t == "}" {
                 let end_line = self.line_no();
+                tokens.rbrace = self.span_of(t);
+                comments.closing = self.current_trailing_comment();
                 self.advance();
                 return Ok(GroupAst {
                     id,
                     pos,
+                    style,
+                    direction,
                     items,
                     span: Some(Span { start_line, end_line }),
+                    tokens,
+                    comments,
                 });
             }
 
             if t.starts_with("@pos:") {
                 if pos.is_some() {
-                    return self.err(1, "duplicate @pos in group block");
+                    self.errors.push(self.error_at(t, "duplicate @pos in group block"));
+                } else {
+                    match parse_pos_line(t) {
+                        Ok(p) => pos = Some(p),
+                        Err(msg) => self.errors.push(self.error_at(t, msg)),
+                    }
+                    tokens.directives.extend(self.span_of(t));
+                    self.attach_directive_comment(&mut comments, "pos");
                 }
-                pos = Some(parse_pos_line(t).map_err(|msg| ParseError {
-                    line: self.line_no(),
-                    col: 1,
-                    msg,
-                })?);
                 self.advance();
                 continue;
             }
 
-            if let Some(stmt) = self.parse_stmt_or_none()? {
-                items.push(stmt);
+            if let Some(rest) = t.strip_prefix("@direction:") {
+                if direction.is_some() {
+                    self.errors.push(self.error_at(t, "duplicate @direction in group block"));
+                } else {
+                    match Direction::from_name(rest.trim()) {
+                        Some(d) => direction = Some(d),
+                        None => {
+                            let msg = format!("unknown direction '{}', expected TB, LR, BT or RL", rest.trim());
+                            self.errors.push(self.error_at(t, msg));
+                        }
+                    }
+                    tokens.directives.extend(self.span_of(t));
+                    self.attach_directive_comment(&mut comments, "direction");
+                }
+                self.advance();
+                continue;
             }
+
+            if self.parse_style_directive(t, "group", &mut style, &mut tokens.directives, Some(&mut tokens.classes), Some(&mut comments)) {
+                self.advance();
+                continue;
+            }
+
+            self.parse_stmt_into(&mut items);
         }
     }
 
+    // note "text"
+    // note for TARGET "text"
+    // note on FROM ARROW TO "text"
+    // the text can also be a block of lines: note for TARGET { ... }
+    fn parse_note(&mut self) -> Result<NoteAst, ParseError> {
+        let start_line = self.line_no();
+        let t = self.current_line_wo_comment().trim();
+
+        let mut tokens = NoteTokens { keyword: self.span_of(&t[.."note".len()]), ..NoteTokens::default() };
+        let mut rest = t["note".len()..].trim_start();
+        let segment_spans = |name: &str| -> Vec<TokenSpan> {
+            path_segment_tokens(name).into_iter().filter_map(|s| self.span_of(s)).collect()
+        };
+
+        let mut target = NoteTarget::None;
+        if starts_with_kw(rest, "for") {
+            let after = rest["for".len()..].trim_start();
+            let Some((name, after)) = take_path(after) else {
+                return self.err_at(rest, "expected a node name after 'note for'");
+            };
+            tokens.target = self.span_of(name);
+            tokens.from_segments = segment_spans(name);
+            target = NoteTarget::Node(Ident(path_name(name)));
+            rest = after.trim_start();
+        } else if starts_with_kw(rest, "on") {
+            let after = rest["on".len()..].trim_start();
+            // The relation runs up to the text string or block
+            let end = find_unquoted(after, "\"").or_else(|| find_unquoted(after, "{")).unwrap_or(after.len());
+            let head = after[..end].trim_end();
+            let Some((from, arrow_token, arrow, to)) = split_relation_compact(head) else {
+                return self.err_at(rest, "expected a relation like A --> B after 'note on'");
+            };
+            tokens.target = self.span_of(head);
+            tokens.from_segments = segment_spans(from);
+            tokens.arrow = self.span_of(arrow_token);
+            tokens.to_segments = segment_spans(to);
+            target = NoteTarget::Relation { from: Ident(path_name(from)), arrow: arrow.to_string(), to: Ident(path_name(to)) };
+            rest = after[end..].trim_start();
+        }
+
+        let mut lines = Vec::new();
+        let mut has_lbrace = false;
+        if rest.starts_with('"') {
+            let (text, after) = parse_string(rest).map_err(|msg| self.error_at(rest, msg))?;
+            tokens.text = self.span_of(&rest[..rest.len() - after.len()]);
+            lines = text.split('\n').map(str::to_string).collect();
+            rest = after.trim_start();
+        } else if rest.starts_with('{') {
+            has_lbrace = true;
+            tokens.lbrace = self.span_of(&rest[..1]);
+            rest = rest[1..].trim_start();
+        }
+        if !rest.is_empty() {
+            return self.err_at(rest, "unexpected tokens in note");
+        }
+        if tokens.text.is_none() && !has_lbrace && !self.nonempty_line_is_lbrace(self.i + 1) {
+            return self.err_at(t, "expected the note text as a string or a '{' block");
+        }
+
+        let mut comments = AttachedComments { trailing: self.current_trailing_comment(), ..AttachedComments::default() };
+        self.advance(); // consume note header
+
+        if tokens.text.is_some() {
+            return Ok(NoteAst { target, lines, span: Some(Span { start_line, end_line: start_line }), tokens, comments });
+        }
+        if !has_lbrace {
+            let mut skipped = Vec::new();
+            tokens.lbrace = self.consume_required_lbrace("note", &mut skipped)?;
+            comments.inner.extend(skipped.into_iter().map(|c| (0, c)));
+        }
+
+        loop {
+            if self.eof() {
+                // Keep the text so the note still shows up
+                self.errors.push(self.eof_error("unexpected end of file; missing '}' for note"));
+                let span = Some(Span { start_line, end_line: self.lines.len() });
+                return Ok(NoteAst { target, lines, span, tokens, comments });
+            }
+
+            let t = self.current_line_wo_comment().trim();
+            if t.is_empty() {
+                comments.inner.extend(self.parse_comment_line().map(|c| (lines.len(), c)));
+                self.advance();
+                continue;
+            }
+            if t == "}" {
+                let span = Some(Span { start_line, end_line: self.line_no() });
+                tokens.rbrace = self.span_of(t);
+                comments.closing = self.current_trailing_comment();
+                self.advance();
+                return Ok(NoteAst { target, lines, span, tokens, comments });
+            }
+
+            lines.push(t.to_string());
+            tokens.body_lines.extend(self.span_of(t));
+            comments.body.push(self.current_trailing_comment());
+            self.advance();
+        }
+    }
+
+    // FROM ARROW TO [: label]
+    // optionally followed by style directives, inline: `{ @stroke: red; @dash: 4 2 }`,
+    // or one per line in a block opened at the end of the line
+    fn parse_relation(&mut self) -> Result<RelationAst, ParseError> {
+        let start_line = self.line_no();
+        let t = self.current_line_wo_comment().trim();
+        let (head, block) = split_style_block(t);
+        let mut rel = self.parse_relation_line(head)?;
+        let Some(block) = block else {
+            self.advance();
+            return Ok(RelationAst { span: Some(Span { start_line, end_line: start_line }), ..rel });
+        };
+
+        let block = self.parse_style_block(block, "relation", true, &mut rel.comments);
+        rel.style = block.style;
+        rel.tokens.lbrace = block.lbrace;
+        rel.tokens.rbrace = block.rbrace;
+        rel.tokens.directives = block.directives;
+        rel.tokens.classes = block.classes;
+        rel.span = Some(Span { start_line, end_line: block.end_line });
+        Ok(rel)
+    }
+
+    // style SELECTOR { @fill: #fee; @stroke: #c00 }
+    // or one directive per line in a block opened at the end of the line
+    fn parse_style_rule(&mut self) -> Result<StyleRuleAst, ParseError> {
+        let start_line = self.line_no();
+        let t = self.current_line_wo_comment().trim();
+
+        let mut tokens = StyleRuleTokens { keyword: self.span_of(&t[.."style".len()]), ..StyleRuleTokens::default() };
+        let rest = t["style".len()..].trim_start();
+        let end = rest.find(|c: char| c.is_whitespace() || c == '{').unwrap_or(rest.len());
+        let selector = &rest[..end];
+        if !is_class_name(selector) {
+            return self.err_at(selector, "expected a style class, node kind or modifier after 'style'");
+        }
+        tokens.selector = self.span_of(selector);
+
+        let block = rest[end..].trim_start();
+        if !block.starts_with('{') {
+            return self.err_at(if block.is_empty() { t } else { block }, "expected '{' after the style selector");
+        }
+        if block != "{" && !block.ends_with('}') {
+            return self.err_at(block, "expected '}' at the end of an inline style block");
+        }
+
+        let mut comments = AttachedComments { trailing: self.current_trailing_comment(), ..AttachedComments::default() };
+        let block = self.parse_style_block(block, "style", false, &mut comments);
+        tokens.lbrace = block.lbrace;
+        tokens.rbrace = block.rbrace;
+        tokens.directives = block.directives;
+        Ok(StyleRuleAst {
+            selector: selector.to_string(),
+            style: block.style,
+            span: Some(Span { start_line, end_line: block.end_line }),
+            tokens,
+            comments,
+        })
+    }
+
+    /// Parse the style directives of a block opening at `block` on the current line: an
+    /// inline `{ @a: x; @b: y }`, or a lone `{` followed by one directive per line and a
+    /// closing `}`. Consumes the block and adds the comments inside it to `comments`.
+    fn parse_style_block(&mut self, block: &str, ctx: &str, allow_classes: bool, comments: &mut AttachedComments) -> StyleBlock {
+        let mut parsed = StyleBlock {
+            style: Style::default(),
+            lbrace: self.span_of(&block[..1]),
+            rbrace: None,
+            directives: Vec::new(),
+            classes: Vec::new(),
+            end_line: self.line_no(),
+        };
+        if let Some(body) = block[1..].strip_suffix('}') {
+            parsed.rbrace = self.span_of(&block[block.len() - 1..]);
+            for directive in body.split(';').map(str::trim).filter(|d| !d.is_empty()) {
+                let classes = allow_classes.then_some(&mut parsed.classes);
+                if !self.parse_style_directive(directive, ctx, &mut parsed.style, &mut parsed.directives, classes, None) {
+                    self.errors.push(self.error_at(directive, "expected a style directive like @stroke: red"));
+                }
+            }
+            self.advance();
+            return parsed;
+        }
+        self.advance(); // consume the line with the '{'
+
+        loop {
+            if self.eof() {
+                self.errors.push(self.eof_error(format!("unexpected end of file; missing '}}' for {}", ctx)));
+                parsed.end_line = self.lines.len();
+                return parsed;
+            }
+
+            let t = self.current_line_wo_comment().trim();
+            if t.is_empty() {
+                comments.inner.extend(self.parse_comment_line().map(|c| (parsed.directives.len(), c)));
+                self.advance();
+                continue;
+            }
+            if t == "}" {
+                parsed.end_line = self.line_no();
+                parsed.rbrace = self.span_of(t);
+                comments.closing = self.current_trailing_comment();
+                self.advance();
+                return parsed;
+            }
+
+            let classes = allow_classes.then_some(&mut parsed.classes);
+            if !self.parse_style_directive(t, ctx, &mut parsed.style, &mut parsed.directives, classes, Some(comments)) {
+                self.errors.push(self.error_at(t, format!("only style directives are allowed in a {} block", ctx)));
+            }
+            self.advance();
+        }
+    }
+
+    /// Apply a style directive like `@fill: #fee` in a `ctx` block to `style`, recording its
+    /// span and, for directive lines, its trailing comment. `@class:` is only allowed where
+    /// `classes` collects the spans of its names. False if `t` isn't a style directive.
+    fn parse_style_directive(
+        &mut self,
+        t: &str,
+        ctx: &str,
+        style: &mut Style,
+        directives: &mut Vec<TokenSpan>,
+        classes: Option<&mut Vec<TokenSpan>>,
+        comments: Option<&mut AttachedComments>,
+    ) -> bool {
+        let Some((name, value)) = split_style_directive(t) else {
+            return false;
+        };
+        if name == "class" && classes.is_none() {
+            self.errors.push(self.error_at(t, format!("@class can't be used in a {} block", ctx)));
+            return true;
+        }
+        if style.value(name).is_some() {
+            self.errors.push(self.error_at(t, format!("duplicate @{} in {} block", name, ctx)));
+            return true;
+        }
+        if let Err(msg) = style.set(name, value) {
+            self.errors.push(self.error_at(t, msg));
+        } else if let Some(classes) = classes
+            && name == "class"
+        {
+            classes.extend(class_names(value).filter_map(|n| self.span_of(n)));
+        }
+        directives.extend(self.span_of(t));
+        if let Some(comments) = comments {
+            self.attach_directive_comment(comments, name);
+        }
+        true
+    }
+
     fn parse_relation_line(&self, line: &str) -> Result<RelationAst, ParseError> {
-        // Split label on first ':' (if any)
-        let (head, label) = match line.split_once(':') {
-            Some((a, b)) => {
-                let l = b.trim();
-                (
-                    a.trim(),
-                    if l.is_empty() {
-                        None
-                    } else {
-                        Some(l.to_string())
-                    },
-                )
+        // Split label on first ':' outside backticks (if any)
+        let (head, label) = match find_unquoted(line, ":") {
+            Some(idx) => {
+                let l = line[idx + 1..].trim();
+                (line[..idx].trim(), if l.is_empty() { None } else { Some(l) })
             }
             None => (line, None),
         };
 
-        let (from, arrow, to) = split_relation_compact(head).ok_or_else(|| ParseError {
-            line: self.line_no(),
-            col: 1,
-            msg: "invalid relation; expected like A-->B or A --> B".into(),
-        })?;
+        let parts = split_relation(head)
+            .ok_or_else(|| self.error_at(line, "invalid relation; expected like A-->B or A --> B"))?;
+        let end = |token: Option<&str>| {
+            token.map_or_else(RelationEnd::default, |t| {
+                RelationEnd::parse(&parse_string(t).map(|(text, _)| text).unwrap_or_default())
+            })
+        };
+
+        // A quoted label may contain ':' and '%%' and uses string escapes
+        let label_text = match label {
+            Some(l) if l.starts_with('"') => {
+                let (text, after) = parse_string(l).map_err(|msg| self.error_at(l, msg))?;
+                if !after.trim().is_empty() {
+                    return self.err_at(after.trim(), "unexpected tokens after relation label");
+                }
+                Some(text)
+            }
+            other => other.map(str::to_string),
+        };
 
         Ok(RelationAst {
-            from: Ident(from.to_string()),
-            arrow: arrow.to_string(),
-            to: Ident(to.to_string()),
-            label,
-            span: None, // Span is added by parse_stmt_or_none
+            from: Ident(path_name(parts.from)),
+            arrow: parts.arrow.to_string(),
+            to: Ident(path_name(parts.to)),
+            label: label_text,
+            from_end: end(parts.from_end),
+            to_end: end(parts.to_end),
+            style: Style::default(),
+            span: None, // Span is added by parse_relation
+            tokens: RelationTokens {
+                from: self.span_of(parts.from),
+                arrow: self.span_of(parts.arrow_token),
+                to: self.span_of(parts.to),
+                label: label.and_then(|l| self.span_of(l)),
+                from_end: parts.from_end.and_then(|t| self.span_of(t)),
+                to_end: parts.to_end.and_then(|t| self.span_of(t)),
+                from_segments: path_segment_tokens(parts.from).into_iter().filter_map(|t| self.span_of(t)).collect(),
+                to_segments: path_segment_tokens(parts.to).into_iter().filter_map(|t| self.span_of(t)).collect(),
+                ..RelationTokens::default()
+            },
+            comments: AttachedComments { trailing: self.current_trailing_comment(), ..AttachedComments::default() },
         })
     }
 
-    fn consume_required_lbrace(&mut self, ctx: &str) -> Result<(), ParseError> {
+    /// Consume a lone `{` line, returning its span. Comment and blank lines before
+    /// it, and a comment after it, are added to `comments`.
+    fn consume_required_lbrace(&mut self, ctx: &str, comments: &mut Vec<CommentAst>) -> Result<Option<TokenSpan>, ParseError> {
         while !self.eof() {
             let t = self.current_line_wo_comment().trim();
             if t.is_empty() {
+                comments.extend(self.parse_comment_line());
                 self.advance();
                 continue;
             }
             if t == "{" {
+                let span = self.span_of(t);
+                if let Some(text) = self.current_trailing_comment() {
+                    comments.push(CommentAst { prefix: String::new(), text });
+                }
                 self.advance();
-                return Ok(());
+                return Ok(span);
             }
-            return self.err(1, format!("expected '{{' to start {ctx} block"));
+            return self.err_at(t, format!("expected '{{' to start {ctx} block"));
         }
-        self.err(1, "unexpected end of file while looking for '{'")
+        Err(self.eof_error("unexpected end of file while looking for '{'"))
     }
 
     fn peek_next_nonempty_is_lbrace(&self) -> bool {
-        let mut j = self.i;
+        self.nonempty_line_is_lbrace(self.i)
+    }
+
+    /// Whether the first non-empty line from line index `j` on is a lone `{`
+    fn nonempty_line_is_lbrace(&self, mut j: usize) -> bool {
         while j < self.lines.len() {
             let raw = self.lines[j];
-            let wo = match raw.find("%%") {
+            let wo = match find_comment_start(raw) {
                 Some(idx) => &raw[..idx],
                 None => raw,
             };
@@ -587,64 +1206,47 @@ fn starts_with_kw(line: &str, kw: &str) -> bool {
         || line.starts_with(&(kw.to_string() + "{"))
 }
 
-fn is_ident(s: &str) -> bool {
-    let mut chars = s.chars();
-    let Some(first) = chars.next() else {
+/// Whether `line` starts a style rule rather than, say, a relation from a node named `style`
+fn is_style_header(line: &str) -> bool {
+    line.strip_prefix("style")
+        .and_then(|rest| rest.strip_prefix(char::is_whitespace))
+        .is_some_and(|rest| rest.trim_start().starts_with(|c: char| c.is_alphabetic() || c == '_'))
+}
+
+/// Whether `line` starts a note rather than, say, a relation from a node named `note`
+fn is_note_header(line: &str) -> bool {
+    let Some(rest) = line.strip_prefix("note") else {
         return false;
     };
-    if !(first.is_ascii_alphabetic() || first == '_') {
-        return false;
-    }
-    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    let trimmed = rest.trim_start();
+    trimmed.starts_with(['"', '{'])
+        || (trimmed.len() < rest.len() && (starts_with_kw(trimmed, "for") || starts_with_kw(trimmed, "on")))
 }
 
 fn take_ident_prefix(s: &str) -> (Option<&str>, &str) {
     let s = s.trim_start();
-    let mut end = 0usize;
+    match take_ident(s) {
+        Some((ident, rest)) => (Some(ident), rest),
+        None => (None, s),
+    }
+}
+
+/// The leading `<...>` of `s`, with nested angle brackets, if it is closed
+fn take_type_params(s: &str) -> Option<&str> {
+    let mut depth = 0;
     for (i, c) in s.char_indices() {
-        if i == 0 {
-            if !(c.is_ascii_alphabetic() || c == '_') {
-                return (None, s);
-            }
-            end = c.len_utf8();
-        } else {
-            if c.is_ascii_alphanumeric() || c == '_' {
-                end = i + c.len_utf8();
-            } else {
-                break;
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&s[..=i]);
+                }
             }
+            _ => {}
         }
     }
-    if end == 0 {
-        (None, s)
-    } else {
-        (Some(&s[..end]), &s[end..])
-    }
-}
-
-fn parse_string_prefix(s: &str) -> Result<(String, &str), String> {
-    let s = s.trim_start();
-    if !s.starts_with('"') {
-        return Err("expected string".into());
-    }
-    let mut chars = s.chars();
-    chars.next(); // opening quote
-    let mut out = String::new();
-    while let Some(c) = chars.next() {
-        if c == '"' {
-            // idx points to byte offset? we track by slicing using char iteration; simplest:
-            // find the closing quote in bytes:
-            let close = s[1..]
-                .find('"')
-                .ok_or_else(|| "unterminated string".to_string())?
-                + 1;
-            let content = &s[1..close];
-            let rest = &s[close + 1..];
-            return Ok((content.to_string(), rest));
-        }
-        out.push(c);
-    }
-    Err("unterminated string literal".into())
+    None
 }
 
 fn parse_pos_line(t: &str) -> Result<PointI, String> {
@@ -676,6 +1278,38 @@ fn parse_pos_line(t: &str) -> Result<PointI, String> {
     Ok(PointI { x, y })
 }
 
+/// Name and value of a style directive like `@fill: #fee`
+fn split_style_directive(t: &str) -> Option<(&'static str, &str)> {
+    let (name, value) = t.strip_prefix('@')?.split_once(':')?;
+    let name = Style::DIRECTIVES.into_iter().find(|d| *d == name)?;
+    Some((name, value))
+}
+
+/// Split the style block off the end of a relation line: an inline `{ @stroke: red }`,
+/// or a lone `{` opening a block on the following lines. A bare label runs to the
+/// end of the line, so a lone `{` only opens a block before the label or after a
+/// quoted one; `A --> B : {` is a relation labelled `{`.
+fn split_style_block(line: &str) -> (&str, Option<&str>) {
+    let label_at = find_unquoted(line, ":");
+    let opens_block = |at: usize| match label_at {
+        Some(colon) if colon < at => {
+            let label = line[colon + 1..at].trim();
+            label.starts_with('"') && parse_string(label).is_ok_and(|(_, rest)| rest.trim().is_empty())
+        }
+        _ => true,
+    };
+    let mut from = 0;
+    while let Some(i) = find_unquoted(&line[from..], "{") {
+        let at = from + i;
+        let inner = line[at + 1..].trim();
+        if (inner.is_empty() && opens_block(at)) || (inner.starts_with('@') && inner.ends_with('}')) {
+            return (line[..at].trim_end(), Some(&line[at..]));
+        }
+        from = at + 1;
+    }
+    (line, None)
+}
+
 /// Parse an integer directive like @width: 100 or @height: 60
 fn parse_int_directive(line: &str, prefix: &str) -> Result<i32, String> {
     let rest = line
@@ -686,14 +1320,71 @@ fn parse_int_directive(line: &str, prefix: &str) -> Result<i32, String> {
         .map_err(|_| format!("{} must be an integer", prefix))
 }
 
+/// The style directives of a `{ ... }` block and where they are
+struct StyleBlock {
+    style: Style,
+    lbrace: Option<TokenSpan>,
+    rbrace: Option<TokenSpan>,
+    directives: Vec<TokenSpan>,
+    /// Each class name in `@class:`
+    classes: Vec<TokenSpan>,
+    /// Line of the closing `}` (the last line at end of file)
+    end_line: usize,
+}
+
+/// Pieces of a relation head, all borrowed from the source line
+struct RelationParts<'a> {
+    from: &'a str,
+    /// Quoted multiplicity/role string after `from`, quotes included
+    from_end: Option<&'a str>,
+    arrow_token: &'a str,
+    /// Canonical arrow name
+    arrow: &'a str,
+    /// Quoted multiplicity/role string before `to`, quotes included
+    to_end: Option<&'a str>,
+    to: &'a str,
+}
+
+/// Parses a relation head, optionally with quoted multiplicity/role strings at either end:
+/// - "Order \"1\" *-- \"1..* items\" LineItem"
+/// - "A\"1\"-->\"*\"B"
+fn split_relation(s: &str) -> Option<RelationParts<'_>> {
+    let s = s.trim();
+    if find_unquoted(s, "\"").is_none() {
+        let (from, arrow_token, arrow, to) = split_relation_compact(s)?;
+        return Some(RelationParts { from, from_end: None, arrow_token, arrow, to_end: None, to });
+    }
+
+    let (from, rest) = take_path(s)?;
+    let (from_end, rest) = take_string_token(rest.trim_start());
+    let rest = rest.trim_start();
+    // Registry is sorted by token length (longest first)
+    let entry = get_arrow_registry().iter().find(|e| rest.starts_with(e.token))?;
+    let (arrow_token, rest) = rest.split_at(entry.token.len());
+    let (to_end, rest) = take_string_token(rest.trim_start());
+    let to = rest.trim();
+    if !is_path(to) {
+        return None;
+    }
+    Some(RelationParts { from, from_end, arrow_token, arrow: entry.canonical_name.as_str(), to_end, to })
+}
+
+/// Split a leading string literal (quotes included) off `s`, if there is a valid one
+fn take_string_token(s: &str) -> (Option<&str>, &str) {
+    match parse_string(s) {
+        Ok((_, after)) => (Some(&s[..s.len() - after.len()]), after),
+        Err(_) => (None, s),
+    }
+}
+
 /// Parses relations with or without spaces.
-/// Returns (from, arrow_canonical_name, to)
+/// Returns (from, arrow_token, arrow_canonical_name, to), all but the name borrowed from `s`
 /// Accepts:
 /// - "A-->B"
 /// - "A --> B"
 /// - "A<|--B"
 /// - "A <|-- B"
-fn split_relation_compact(s: &str) -> Option<(&str, &str, &str)> {
+fn split_relation_compact(s: &str) -> Option<(&str, &str, &str, &str)> {
     let s = s.trim();
     let registry = get_arrow_registry();
 
@@ -702,9 +1393,9 @@ fn split_relation_compact(s: &str) -> Option<(&str, &str, &str)> {
         let parts: Vec<&str> = s.split_whitespace().collect();
         if parts.len() == 3 {
             let (a, op, b) = (parts[0], parts[1], parts[2]);
-            if is_ident(a) && is_ident(b) {
+            if is_path(a) && is_path(b) {
                 if let Some(arrow_name) = arrow_from_token(op) {
-                    return Some((a, arrow_name, b));
+                    return Some((a, op, arrow_name, b));
                 }
             }
         }
@@ -713,12 +1404,12 @@ fn split_relation_compact(s: &str) -> Option<(&str, &str, &str)> {
     // Compact path: find any arrow token inside the string
     // Registry is already sorted by token length (longest first)
     for entry in registry.iter() {
-        if let Some(pos) = s.find(entry.token) {
+        if let Some(pos) = find_unquoted(s, entry.token) {
             let left = s[..pos].trim();
             let right = s[pos + entry.token.len()..].trim();
 
-            if is_ident(left) && is_ident(right) {
-                return Some((left, entry.canonical_name.as_str(), right));
+            if is_path(left) && is_path(right) {
+                return Some((left, &s[pos..pos + entry.token.len()], entry.canonical_name.as_str(), right));
             }
         }
     }
@@ -730,6 +1421,15 @@ fn split_relation_compact(s: &str) -> Option<(&str, &str, &str)> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_span_of_only_covers_slices_of_the_current_line() {
+        let p = Parser::new("class Foo\nclass Bar\n");
+        let line = p.lines[0];
+        assert_eq!(p.span_of(&line[6..]).map(|s| (s.start_col, s.end_col)), Some((7, 10)));
+        assert_eq!(p.span_of(&p.lines[1][6..]), None);
+        assert_eq!(p.span_of(&String::from("Foo")), None);
+    }
+
     #[test]
     fn test_parse_simple_class() {
         let input = "class Foo\n";
@@ -817,4 +1517,99 @@ mod tests {
             _ => panic!("Expected Relation"),
         }
     }
+
+    #[test]
+    fn test_relation_label_ending_in_a_brace() {
+        let ast = parse_file("A --> B : {\nclass C\n").unwrap();
+        assert_eq!(ast.items.len(), 2);
+        match &ast.items[0] {
+            Stmt::Relation(r) => {
+                assert_eq!(r.label.as_deref(), Some("{"));
+                assert!(r.style.is_empty());
+            }
+            _ => panic!("Expected Relation"),
+        }
+
+        // Before the label, or after a quoted one, a lone `{` still opens a block
+        for src in ["A --> B {\n    @stroke: red\n}\nclass C\n", "A --> B : \"uses\" {\n    @stroke: red\n}\nclass C\n"] {
+            let ast = parse_file(src).unwrap();
+            assert_eq!(ast.items.len(), 2, "{}", src);
+            match &ast.items[0] {
+                Stmt::Relation(r) => assert!(!r.style.is_empty(), "{}", src),
+                _ => panic!("Expected Relation"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_token_spans_in_bytes_and_utf16() {
+        let input = "@layout: grid\nclass Foo \"Fö😀\" {\n    + x: int\n    @pos: (1, 2)\n}\nFoo <|-- Bar : ok\n";
+        let ast = parse_file(input).unwrap();
+        let cols = |t: Option<TokenSpan>| t.map(|t| (t.line, t.start_col, t.end_col, t.start_col_utf16, t.end_col_utf16));
+        assert_eq!(cols(ast.tokens.layout), Some((1, 1, 14, 1, 14)));
+
+        let Stmt::Node(n) = &ast.items[0] else { panic!("Expected Node") };
+        assert_eq!(cols(n.tokens.keywords.first().copied()), Some((2, 1, 6, 1, 6)));
+        assert_eq!(cols(n.tokens.id), Some((2, 7, 10, 7, 10)));
+        // 'ö' is two bytes but one UTF-16 unit, the emoji four bytes but two units
+        assert_eq!(cols(n.tokens.label), Some((2, 11, 20, 11, 17)));
+        assert_eq!(cols(n.tokens.lbrace), Some((2, 21, 22, 18, 19)));
+        assert_eq!(cols(n.tokens.body_lines.first().copied()), Some((3, 5, 13, 5, 13)));
+        assert_eq!(cols(n.tokens.directives.first().copied()), Some((4, 5, 17, 5, 17)));
+        assert_eq!(cols(n.tokens.rbrace), Some((5, 1, 2, 1, 2)));
+
+        let Stmt::Relation(r) = &ast.items[1] else { panic!("Expected Relation") };
+        assert_eq!(cols(r.tokens.from), Some((6, 1, 4, 1, 4)));
+        assert_eq!(cols(r.tokens.arrow), Some((6, 5, 9, 5, 9)));
+        assert_eq!(cols(r.tokens.to), Some((6, 10, 13, 10, 13)));
+        assert_eq!(cols(r.tokens.label), Some((6, 16, 18, 16, 18)));
+    }
+
+    #[test]
+    fn test_errors_point_at_offending_token() {
+        let err = parse_file("class A {\n}\nclass B \"x\" junk\n").unwrap_err();
+        assert_eq!((err.line, err.col), (3, 13));
+        assert_eq!(err.span.map(|s| s.end_col), Some(17));
+
+        let err = parse_file("group G {\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.span.is_none());
+    }
+
+    #[test]
+    fn test_direction_directives() {
+        let (ast, errors) = parse_file_recovering("@direction: sideways\ngroup G {\n    @direction: up\n}\ngroup H {\n    @direction: RL\n    @direction: TB\n}\n");
+        // Unknown file directions only fall back with a warning later on
+        assert_eq!(ast.direction.as_deref(), Some("sideways"));
+        assert_eq!(ast.tokens.direction.map(|s| (s.line, s.start_col, s.end_col)), Some((1, 1, 21)));
+
+        let msgs: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.msg.as_str())).collect();
+        assert_eq!(
+            msgs,
+            [(3, "unknown direction 'up', expected TB, LR, BT or RL"), (7, "duplicate @direction in group block")]
+        );
+        let Stmt::Group(h) = &ast.items[1] else { panic!("Expected Group") };
+        assert_eq!(h.direction, Some(Direction::RL));
+    }
+
+    #[test]
+    fn test_recovering_parser_collects_every_error() {
+        let input = "class A {\n    @pos: nope\n    + x: int\n}\nclass B \"x\" junk {\n    + y\n}\nA -->\nA --> C\ngroup G {\n    class D\n";
+        let (ast, errors) = parse_file_recovering(input);
+
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [2, 5, 8, 12]);
+        assert!(errors[3].msg.contains("missing '}' for group"));
+
+        // Everything that parsed is kept: A with its body, the relation and the unterminated group
+        assert_eq!(ast.items.len(), 3);
+        let Stmt::Node(a) = &ast.items[0] else { panic!("Expected Node") };
+        assert_eq!(a.body_lines, ["+ x: int"]);
+        assert!(matches!(&ast.items[1], Stmt::Relation(r) if r.to.0 == "C"));
+        let Stmt::Group(g) = &ast.items[2] else { panic!("Expected Group") };
+        assert_eq!(g.items.len(), 1);
+
+        // The strict entry point reports the first error
+        assert_eq!(parse_file(input).unwrap_err().line, 2);
+    }
 }
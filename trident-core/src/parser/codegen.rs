@@ -4,7 +4,9 @@
 //! - 4 spaces for indentation
 //! - Always wrap brackets on their own lines for groups/nodes
 //! - Comments are preserved exactly as-is
+//! - Identifiers are backtick-quoted and strings escaped where needed
 
+use crate::parser::lexer::{find_comment_start, format_path, quote_string};
 use crate::parser::types::*;
 
 const INDENT: &str = "    "; // 4 spaces
@@ -12,12 +14,41 @@ const INDENT: &str = "    "; // 4 spaces
 /// Emit the entire file AST back to source code
 pub fn emit_file(ast: &FileAst) -> String {
     let mut out = String::new();
-    
+    let mut directive_index = 0;
+
     // Emit @layout directive if present
     if let Some(layout) = &ast.layout {
-        out.push_str(&format!("@layout: {}\n", layout));
+        emit_inner_comments(&ast.comments, directive_index, &mut out);
+        out.push_str(&format!("@layout: {}", layout));
+        push_line_end(ast.comments.directive("layout"), &mut out);
+        directive_index += 1;
     }
-    
+
+    // Emit @routing directive if present
+    if let Some(routing) = &ast.routing {
+        emit_inner_comments(&ast.comments, directive_index, &mut out);
+        out.push_str(&format!("@routing: {}", routing));
+        push_line_end(ast.comments.directive("routing"), &mut out);
+        directive_index += 1;
+    }
+
+    // Emit @direction directive if present
+    if let Some(direction) = &ast.direction {
+        emit_inner_comments(&ast.comments, directive_index, &mut out);
+        out.push_str(&format!("@direction: {}", direction));
+        push_line_end(ast.comments.directive("direction"), &mut out);
+        directive_index += 1;
+    }
+
+    // Emit @wrap directive if present
+    if let Some(wrap) = &ast.wrap {
+        emit_inner_comments(&ast.comments, directive_index, &mut out);
+        out.push_str(&format!("@wrap: {}", wrap));
+        push_line_end(ast.comments.directive("wrap"), &mut out);
+        directive_index += 1;
+    }
+    emit_remaining_inner_comments(&ast.comments, directive_index, &mut out);
+
     for stmt in &ast.items {
         emit_stmt(stmt, 0, &mut out);
     }
@@ -30,6 +61,8 @@ fn emit_stmt(stmt: &Stmt, indent: usize, out: &mut String) {
         Stmt::Group(g) => emit_group(g, indent, out),
         Stmt::Node(n) => emit_node(n, indent, out),
         Stmt::Relation(r) => emit_relation(r, indent, out),
+        Stmt::Note(n) => emit_note(n, indent, out),
+        Stmt::Style(s) => emit_style_rule(s, indent, out),
         Stmt::Comment(c) => emit_comment(c, out),
     }
 }
@@ -50,6 +83,43 @@ fn emit_comment(c: &CommentAst, out: &mut String) {
     }
 }
 
+/// End a generated line, re-attaching its trailing comment
+fn push_line_end(comment: Option<&str>, out: &mut String) {
+    if let Some(text) = comment {
+        out.push_str(" %%");
+        out.push_str(text);
+    }
+    out.push('\n');
+}
+
+/// Emit the attached comment lines that precede line `index`
+fn emit_inner_comments(comments: &AttachedComments, index: usize, out: &mut String) {
+    for (_, c) in comments.inner.iter().filter(|(i, _)| *i == index) {
+        emit_comment(c, out);
+    }
+}
+
+/// Emit attached comment lines whose line is gone (or that followed the last one)
+fn emit_remaining_inner_comments(comments: &AttachedComments, from: usize, out: &mut String) {
+    for (_, c) in comments.inner.iter().filter(|(i, _)| *i >= from) {
+        emit_comment(c, out);
+    }
+}
+
+/// Keep the comments of directives that were removed (e.g. `@pos` after a reset)
+/// as standalone comment lines, so no text is lost
+fn emit_orphaned_directive_comments(comments: &AttachedComments, present: &[&str], indent: usize, out: &mut String) {
+    for (name, text) in &comments.directives {
+        if !present.contains(&name.as_str()) {
+            out.push_str(&format!("{}%%{}\n", indent_str(indent), text));
+        }
+    }
+}
+
+fn has_orphaned_directive_comments(comments: &AttachedComments, present: &[&str]) -> bool {
+    comments.directives.iter().any(|(name, _)| !present.contains(&name.as_str()))
+}
+
 /// Generate the indent string for a given level
 fn indent_str(level: usize) -> String {
     INDENT.repeat(level)
@@ -61,26 +131,37 @@ fn emit_group(g: &GroupAst, indent: usize, out: &mut String) {
     
     // Group header
     if let Some(id) = &g.id {
-        out.push_str(&format!("{}group {} ", ind, id.0));
+        out.push_str(&format!("{}group {} ", ind, format_path(&id.0)));
     } else {
         out.push_str(&format!("{}group ", ind));
     }
     
     // Opening brace
-    out.push_str(&format!("{}{{\n", ind));
-    
-    // @pos if present
+    out.push('{');
+    push_line_end(g.comments.trailing.as_deref(), out);
+
+    // @pos, @direction and style directives if present
+    let mut present: Vec<&str> = g.pos.is_some().then_some("pos").into_iter().collect();
+    present.extend(g.direction.is_some().then_some("direction"));
+    present.extend(style_directives(&g.style));
     if let Some(pos) = &g.pos {
-        emit_pos(pos, indent + 1, out);
+        emit_pos(pos, indent + 1, g.comments.directive("pos"), out);
     }
-    
+    if let Some(direction) = g.direction {
+        out.push_str(&format!("{}@direction: {}", indent_str(indent + 1), direction.name()));
+        push_line_end(g.comments.directive("direction"), out);
+    }
+    emit_style(&g.style, indent + 1, &g.comments, out);
+    emit_orphaned_directive_comments(&g.comments, &present, indent + 1, out);
+
     // Items
     for stmt in &g.items {
         emit_stmt(stmt, indent + 1, out);
     }
-    
+
     // Closing brace
-    out.push_str(&format!("{}}}\n", ind));
+    out.push_str(&format!("{}}}", ind));
+    push_line_end(g.comments.closing.as_deref(), out);
 }
 
 /// Emit a node definition (class, interface, enum, etc.)
@@ -100,45 +181,73 @@ fn emit_node(n: &NodeAst, indent: usize, out: &mut String) {
     // Emit original_kind (preserves user's syntax: enum, diamond, etc.)
     header.push_str(&n.original_kind);
     header.push(' ');
-    header.push_str(&n.id.0);
-    
+    header.push_str(&format_path(&n.id.0));
+    if !n.type_params.is_empty() {
+        header.push_str(&format!("<{}>", format_type_params(&n.type_params)));
+    }
+
     // Label if present
     if let Some(label) = &n.label {
-        header.push_str(&format!(" \"{}\"", label));
+        header.push(' ');
+        header.push_str(&quote_string(label));
+    }
+    for class in &n.classes {
+        header.push_str(&format!(" :::{}", class));
     }
     
-    // If node has pos, width, height, or body_lines, emit with block
-    if n.pos.is_some() || n.width.is_some() || n.height.is_some() || !n.body_lines.is_empty() {
+    let mut present: Vec<&str> = [("pos", n.pos.is_some()), ("width", n.width.is_some()), ("height", n.height.is_some())]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+    present.extend(style_directives(&n.style));
+    let c = &n.comments;
+
+    // If node has directives, body_lines or comments inside the block, emit with block
+    let needs_block = !present.is_empty()
+        || !n.body_lines.is_empty()
+        || !c.inner.is_empty()
+        || c.closing.is_some()
+        || has_orphaned_directive_comments(c, &present);
+    if needs_block {
         out.push_str(&header);
-        out.push_str(" {\n");
-        
+        out.push_str(" {");
+        push_line_end(c.trailing.as_deref(), out);
+
         // @pos if present
         if let Some(pos) = &n.pos {
-            emit_pos(pos, indent + 1, out);
+            emit_pos(pos, indent + 1, c.directive("pos"), out);
         }
 
         // @width if present
         if let Some(w) = n.width {
             let ind = indent_str(indent + 1);
-            out.push_str(&format!("{}@width: {}\n", ind, w));
+            out.push_str(&format!("{}@width: {}", ind, w));
+            push_line_end(c.directive("width"), out);
         }
 
         // @height if present
         if let Some(h) = n.height {
             let ind = indent_str(indent + 1);
-            out.push_str(&format!("{}@height: {}\n", ind, h));
+            out.push_str(&format!("{}@height: {}", ind, h));
+            push_line_end(c.directive("height"), out);
         }
-        
-        // Body lines
-        for line in &n.body_lines {
-            out.push_str(&format!("{}{}\n", indent_str(indent + 1), line));
+        emit_style(&n.style, indent + 1, c, out);
+        emit_orphaned_directive_comments(c, &present, indent + 1, out);
+
+        // Body lines, with the comments around them
+        for (i, line) in n.body_lines.iter().enumerate() {
+            emit_inner_comments(c, i, out);
+            out.push_str(&format!("{}{}", indent_str(indent + 1), line));
+            push_line_end(c.body.get(i).and_then(|t| t.as_deref()), out);
         }
-        
-        out.push_str(&format!("{}}}\n", ind));
+        emit_remaining_inner_comments(c, n.body_lines.len(), out);
+
+        out.push_str(&format!("{}}}", ind));
+        push_line_end(c.closing.as_deref(), out);
     } else {
         // Simple node without block
         out.push_str(&header);
-        out.push('\n');
+        push_line_end(c.trailing.as_deref(), out);
     }
 }
 
@@ -149,20 +258,141 @@ fn emit_relation(r: &RelationAst, indent: usize, out: &mut String) {
     // Convert canonical arrow name to token
     let arrow_str = token_from_arrow(&r.arrow).unwrap_or("-->");
     
-    let mut line = format!("{}{} {} {}", ind, r.from.0, arrow_str, r.to.0);
-    
+    let mut line = format!("{}{}", ind, format_path(&r.from.0));
+    if !r.from_end.is_empty() {
+        line.push(' ');
+        line.push_str(&quote_string(&r.from_end.text()));
+    }
+    line.push(' ');
+    line.push_str(arrow_str);
+    if !r.to_end.is_empty() {
+        line.push(' ');
+        line.push_str(&quote_string(&r.to_end.text()));
+    }
+    line.push(' ');
+    line.push_str(&format_path(&r.to.0));
+
+    let c = &r.comments;
+    let inline_block = c.inner.is_empty() && c.closing.is_none() && c.directives.is_empty();
+    let no_block = r.style.is_empty() && inline_block;
     if let Some(label) = &r.label {
-        line.push_str(&format!(" : {}", label));
+        // Bare labels run to the end of the line (or an inline style block), so quote anything
+        // that wouldn't survive that, and labels followed by a block opened with a lone `{`
+        let opens_block = !no_block && (!inline_block || written_as_block(r.tokens.lbrace, r.tokens.rbrace));
+        if opens_block || label.contains("%%") || label.contains(['\n', '{']) || label.starts_with('"') || label.trim() != label {
+            line.push_str(&format!(" : {}", quote_string(label)));
+        } else {
+            line.push_str(&format!(" : {}", label));
+        }
     }
     
     out.push_str(&line);
-    out.push('\n');
+    if no_block {
+        push_line_end(c.trailing.as_deref(), out);
+        return;
+    }
+    emit_style_block(&r.style, written_as_block(r.tokens.lbrace, r.tokens.rbrace), indent, c, out);
+}
+
+/// Emit a style rule: `style warning { @fill: #fee }`
+fn emit_style_rule(s: &StyleRuleAst, indent: usize, out: &mut String) {
+    out.push_str(&format!("{}style {}", indent_str(indent), s.selector));
+    emit_style_block(&s.style, written_as_block(s.tokens.lbrace, s.tokens.rbrace), indent, &s.comments, out);
+}
+
+/// Whether braces were written on different lines
+fn written_as_block(lbrace: Option<TokenSpan>, rbrace: Option<TokenSpan>) -> bool {
+    matches!((lbrace, rbrace), (Some(l), Some(r)) if l.line != r.line)
+}
+
+/// Emit the `{ ... }` block of style directives that ends a relation or style rule line.
+/// Directives go inline unless they were written as a block or carry comments.
+fn emit_style_block(style: &Style, as_block: bool, indent: usize, c: &AttachedComments, out: &mut String) {
+    let present: Vec<&str> = style_directives(style).collect();
+    if !as_block && c.inner.is_empty() && c.closing.is_none() && c.directives.is_empty() {
+        let directives: Vec<String> = present.iter().map(|name| format!("@{}: {}", name, style.value(name).unwrap_or_default())).collect();
+        if directives.is_empty() {
+            out.push_str(" {}");
+        } else {
+            out.push_str(&format!(" {{ {} }}", directives.join("; ")));
+        }
+        push_line_end(c.trailing.as_deref(), out);
+        return;
+    }
+
+    out.push_str(" {");
+    push_line_end(c.trailing.as_deref(), out);
+    let inner_ind = indent_str(indent + 1);
+    for (i, name) in present.iter().enumerate() {
+        emit_inner_comments(c, i, out);
+        out.push_str(&format!("{}@{}: {}", inner_ind, name, style.value(name).unwrap_or_default()));
+        push_line_end(c.directive(name), out);
+    }
+    emit_remaining_inner_comments(c, present.len(), out);
+    emit_orphaned_directive_comments(c, &present, indent + 1, out);
+    out.push_str(&format!("{}}}", indent_str(indent)));
+    push_line_end(c.closing.as_deref(), out);
+}
+
+/// Emit a note, as a block when it was written as one
+fn emit_note(n: &NoteAst, indent: usize, out: &mut String) {
+    let ind = indent_str(indent);
+    let mut header = format!("{}note", ind);
+    match &n.target {
+        NoteTarget::None => {}
+        NoteTarget::Node(id) => header.push_str(&format!(" for {}", format_path(&id.0))),
+        NoteTarget::Relation { from, arrow, to } => {
+            let arrow = token_from_arrow(arrow).unwrap_or("-->");
+            header.push_str(&format!(" on {} {} {}", format_path(&from.0), arrow, format_path(&to.0)));
+        }
+    }
+
+    let c = &n.comments;
+    // Generated multi-line notes read better as a block, but only lines that
+    // survive being written bare can go in one
+    let wants_block = n.tokens.lbrace.is_some()
+        || (n.tokens.text.is_none() && n.lines.len() > 1)
+        || !c.inner.is_empty()
+        || c.closing.is_some();
+    let block_safe = n.lines.iter().all(|l| !l.is_empty() && l.trim() == l && l != "}" && find_comment_start(l).is_none());
+    if !(wants_block && block_safe) {
+        out.push_str(&format!("{} {}", header, quote_string(&n.lines.join("\n"))));
+        push_line_end(c.trailing.as_deref(), out);
+        return;
+    }
+
+    out.push_str(&header);
+    out.push_str(" {");
+    push_line_end(c.trailing.as_deref(), out);
+    for (i, line) in n.lines.iter().enumerate() {
+        emit_inner_comments(c, i, out);
+        out.push_str(&format!("{}{}", indent_str(indent + 1), line));
+        push_line_end(c.body.get(i).and_then(|t| t.as_deref()), out);
+    }
+    emit_remaining_inner_comments(c, n.lines.len(), out);
+    out.push_str(&format!("{}}}", ind));
+    push_line_end(c.closing.as_deref(), out);
+}
+
+/// Names of the style directives that are set, in the order they are written
+fn style_directives(style: &Style) -> impl Iterator<Item = &'static str> + '_ {
+    Style::DIRECTIVES.into_iter().filter(|name| style.value(name).is_some())
+}
+
+/// Emit one line per style directive that is set
+fn emit_style(style: &Style, indent: usize, comments: &AttachedComments, out: &mut String) {
+    let ind = indent_str(indent);
+    for name in style_directives(style) {
+        out.push_str(&format!("{}@{}: {}", ind, name, style.value(name).unwrap_or_default()));
+        push_line_end(comments.directive(name), out);
+    }
 }
 
 /// Emit a @pos line
-fn emit_pos(pos: &PointI, indent: usize, out: &mut String) {
+fn emit_pos(pos: &PointI, indent: usize, comment: Option<&str>, out: &mut String) {
     let ind = indent_str(indent);
-    out.push_str(&format!("{}@pos: ({}, {})\n", ind, pos.x, pos.y));
+    out.push_str(&format!("{}@pos: ({}, {})", ind, pos.x, pos.y));
+    push_line_end(comment, out);
 }
 
 #[cfg(test)]
@@ -236,6 +466,33 @@ mod tests {
         assert!(output.contains("class Foo"));
     }
 
+    #[test]
+    fn test_roundtrip_header_directives() {
+        let input = "@layout: grid\n@routing: orthogonal\nclass Foo\n";
+        let ast = parse_file(input).unwrap();
+        assert_eq!(ast.routing.as_deref(), Some("orthogonal"));
+        let output = emit_file(&ast);
+        assert!(output.starts_with("@layout: grid\n@routing: orthogonal\n"));
+    }
+
+    #[test]
+    fn test_roundtrip_direction() {
+        let input = "@layout: grid\n%% flow\n@direction: LR %% wide\ngroup G {\n    @pos: (1, 2)\n    @direction: BT %% up\n    class Foo\n}\n";
+        let ast = parse_file(input).unwrap();
+        assert_eq!(ast.direction.as_deref(), Some("LR"));
+        let Stmt::Group(g) = &ast.items[0] else { panic!("Expected Group") };
+        assert_eq!(g.direction, Some(Direction::BT));
+        assert_eq!(emit_file(&ast), input);
+    }
+
+    #[test]
+    fn test_roundtrip_wrap() {
+        let input = "@direction: LR\n@wrap: 240 %% narrow boxes\nclass Foo\n";
+        let ast = parse_file(input).unwrap();
+        assert_eq!(ast.wrap.as_deref(), Some("240"));
+        assert_eq!(emit_file(&ast), input);
+    }
+
     #[test]
     fn test_roundtrip_group() {
         let input = "group MyGroup\n{\n    class Foo\n}\n";
@@ -244,4 +501,240 @@ mod tests {
         assert!(output.contains("group MyGroup"));
         assert!(output.contains("class Foo"));
     }
+
+    #[test]
+    fn test_nested_group_braces_stay_on_the_header_line() {
+        let input = "group Outer {\n    group Inner {\n        class Foo\n    }\n    group {\n        class Bar\n    }\n}\n";
+        let output = emit_file(&parse_file(input).unwrap());
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_roundtrip_trailing_and_block_comments() {
+        let input = "\
+%% header note
+@layout: grid %% rows are easier to read
+class A %% entry point
+class B { %% block
+    @pos: (10, 20) %% placed by hand
+    %% fields
+    +id: int %% primary key
+
+    +name: string
+    %% last
+} %% end of B
+group G {
+    class C
+} %% end of G
+A --> B %% why
+";
+        let ast = parse_file(input).unwrap();
+        assert_eq!(emit_file(&ast), input);
+    }
+
+    #[test]
+    fn test_comments_survive_geometry_updates() {
+        let input = "class A {\n    @pos: (0, 0) %% pinned\n    +x: int %% kept\n}\nclass B\n";
+        let mut ast = parse_file(input).unwrap();
+
+        crate::parser::update_node_geometry(&mut ast, "A", 5, 6, Some(100), None);
+        let output = emit_file(&ast);
+        assert!(output.contains("    @pos: (5, 6) %% pinned\n"), "{}", output);
+        assert!(output.contains("    +x: int %% kept\n"), "{}", output);
+
+        // Dropping @pos keeps its comment as a standalone line
+        crate::parser::remove_node_position(&mut ast, "A");
+        let output = emit_file(&ast);
+        assert!(output.contains("    %% pinned\n"), "{}", output);
+        assert!(output.contains("+x: int %% kept"), "{}", output);
+    }
+
+    #[test]
+    fn test_roundtrip_escapes_and_quoted_identifiers() {
+        let input = "\
+class `com.example.User` \"say \\\"hi\\\" 100%% \\\\ done\\nnext\" %% note
+class Größe
+`com.example.User` --> Größe : \"50%% off\"
+`com.example.User` --> Größe : plain label
+";
+        let ast = parse_file(input).unwrap();
+        match &ast.items[0] {
+            Stmt::Node(n) => {
+                assert_eq!(n.id.name(), "com.example.User");
+                assert_eq!(n.label.as_deref(), Some("say \"hi\" 100%% \\ done\nnext"));
+                assert_eq!(n.comments.trailing.as_deref(), Some(" note"));
+            }
+            other => panic!("Expected Node, got {:?}", other),
+        }
+        match &ast.items[2] {
+            Stmt::Relation(r) => assert_eq!(r.label.as_deref(), Some("50%% off")),
+            other => panic!("Expected Relation, got {:?}", other),
+        }
+        assert_eq!(emit_file(&ast), input);
+    }
+
+    #[test]
+    fn test_labels_before_a_block_are_quoted() {
+        let input = "A --> B : uses {\n    @stroke: red\n}\n";
+        let mut ast = parse_file("A --> B {\n    @stroke: red\n}\n").unwrap();
+        let Stmt::Relation(r) = &mut ast.items[0] else { panic!("Expected Relation") };
+        r.label = Some("uses".to_string());
+        let out = emit_file(&ast);
+        assert_ne!(out, input);
+        assert_eq!(out, "A --> B : \"uses\" {\n    @stroke: red\n}\n");
+        assert_eq!(emit_file(&parse_file(&out).unwrap()), out);
+    }
+
+    #[test]
+    fn test_roundtrip_relation_ends() {
+        let input = "Order \"1\" *-- \"1..* items\" LineItem : contains\nA --> \"owner\" B\n";
+        let ast = parse_file(input).unwrap();
+        match &ast.items[0] {
+            Stmt::Relation(r) => {
+                assert_eq!(r.from_end, RelationEnd { multiplicity: Some("1".into()), role: None });
+                assert_eq!(r.to_end.multiplicity.as_deref(), Some("1..*"));
+                assert_eq!(r.to_end.role.as_deref(), Some("items"));
+                assert_eq!(r.label.as_deref(), Some("contains"));
+            }
+            other => panic!("Expected Relation, got {:?}", other),
+        }
+        match &ast.items[1] {
+            Stmt::Relation(r) => {
+                assert!(r.from_end.is_empty());
+                assert_eq!(r.to_end, RelationEnd { multiplicity: None, role: Some("owner".into()) });
+            }
+            other => panic!("Expected Relation, got {:?}", other),
+        }
+        assert_eq!(emit_file(&ast), input);
+        // Compact form parses the same
+        let compact = parse_file("Order\"1\"*--\"1..* items\"LineItem : contains\nA-->\"owner\"B\n").unwrap();
+        assert_eq!(emit_file(&compact), input);
+    }
+
+    #[test]
+    fn test_roundtrip_type_params() {
+        let input = "interface Repository<T, ID extends Serializable> \"Repo\" {\n    +find(id: ID) T\n}\nclass List<T>\nList --> Repository\n";
+        let ast = parse_file(input).unwrap();
+        match &ast.items[0] {
+            Stmt::Node(n) => {
+                assert_eq!(n.id.0, "Repository");
+                assert_eq!(n.type_params[0], TypeParam { name: "T".into(), bound: None });
+                assert_eq!(n.type_params[1].bound.as_deref(), Some("Serializable"));
+            }
+            other => panic!("Expected Node, got {:?}", other),
+        }
+        assert_eq!(emit_file(&ast), input);
+        assert_eq!(emit_file(&parse_file("class Map< K , V >\n").unwrap()), "class Map<K, V>\n");
+
+        assert!(parse_file("class List<T\n").unwrap_err().msg.contains("expected '>'"));
+        assert!(parse_file("class List<>\n").is_err());
+        assert!(parse_file("class List<T super X>\n").is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_notes() {
+        let input = "class A\nA --> B\nnote \"free\"\nnote for A \"one\\ntwo\" %% why\nnote on A --> B {\n    %% inside\n    first\n    second\n}\n";
+        let ast = parse_file(input).unwrap();
+        match &ast.items[3] {
+            Stmt::Note(n) => {
+                assert_eq!(n.target, NoteTarget::Node(Ident("A".into())));
+                assert_eq!(n.lines, ["one", "two"]);
+            }
+            other => panic!("Expected Note, got {:?}", other),
+        }
+        match &ast.items[4] {
+            Stmt::Note(n) => {
+                assert_eq!(n.target, NoteTarget::Relation { from: Ident("A".into()), arrow: "assoc_right".into(), to: Ident("B".into()) });
+                assert_eq!(n.lines, ["first", "second"]);
+            }
+            other => panic!("Expected Note, got {:?}", other),
+        }
+        assert_eq!(emit_file(&ast), input);
+
+        // A brace on the next line, and compact relations, parse the same
+        let ast = parse_file("note for A\n{\n    text\n}\nnote on A-->B{\n    x\n}\n").unwrap();
+        assert_eq!(emit_file(&ast), "note for A {\n    text\n}\nnote on A --> B {\n    x\n}\n");
+
+        // Generated notes only use a block when every line can be written bare
+        let generated = |lines: &[&str]| {
+            let note = NoteAst {
+                target: NoteTarget::None,
+                lines: lines.iter().map(|l| l.to_string()).collect(),
+                span: None,
+                tokens: Default::default(),
+                comments: Default::default(),
+            };
+            emit_file(&FileAst { layout: None, routing: None, direction: None, wrap: None, items: vec![Stmt::Note(note)], tokens: Default::default(), comments: Default::default() })
+        };
+        assert_eq!(generated(&["a", "b"]), "note {\n    a\n    b\n}\n");
+        assert_eq!(generated(&["a", "50%% off"]), "note \"a\\n50%% off\"\n");
+
+        assert!(parse_file("note for \"x\"\n").unwrap_err().msg.contains("expected a node name"));
+        assert!(parse_file("note on A \"x\"\n").unwrap_err().msg.contains("expected a relation"));
+        assert!(parse_file("note for A\n").unwrap_err().msg.contains("expected the note text"));
+        assert!(parse_file("note {\n    x\n").unwrap_err().msg.contains("missing '}' for note"));
+    }
+
+    #[test]
+    fn test_roundtrip_styles() {
+        let input = "group G {\n    @fill: #eef\n    class A {\n        @width: 120\n        @fill: #fee %% warm\n        @stroke: red\n        @dash: none\n        + x: int\n    }\n}\nA --> B : uses { @stroke: #c00; @stroke_width: 2.5 } %% hot path\nA ..> B {\n    %% dotted\n    @dash: 4 2 %% why\n}\n";
+        let ast = parse_file(input).unwrap();
+        let Stmt::Group(g) = &ast.items[0] else { panic!("Expected Group") };
+        assert_eq!(g.style.fill.as_deref(), Some("#eef"));
+        let Stmt::Node(n) = &g.items[0] else { panic!("Expected Node") };
+        assert_eq!(n.style, Style { fill: Some("#fee".into()), stroke: Some("red".into()), dash: Some(Vec::new()), ..Style::default() });
+        assert_eq!(n.body_lines, ["+ x: int"]);
+        let Stmt::Relation(r) = &ast.items[1] else { panic!("Expected Relation") };
+        assert_eq!(r.label.as_deref(), Some("uses"));
+        assert_eq!(r.style.stroke_width, Some(2.5));
+        assert_eq!(r.tokens.directives.len(), 2);
+        let Stmt::Relation(r) = &ast.items[2] else { panic!("Expected Relation") };
+        assert_eq!(r.style.dash, Some(vec![4.0, 2.0]));
+        assert_eq!(emit_file(&ast), input);
+
+        // Generated relation styles go inline, and labels with braces are quoted
+        let mut ast = parse_file("A --> B : Map{K}\n").unwrap();
+        let Stmt::Relation(r) = &mut ast.items[0] else { panic!("Expected Relation") };
+        assert_eq!(r.label.as_deref(), Some("Map{K}"));
+        r.style.dash = Some(vec![6.0, 3.0]);
+        r.style.text_color = Some("blue".into());
+        assert_eq!(emit_file(&ast), "A --> B : \"Map{K}\" { @text_color: blue; @dash: 6 3 }\n");
+
+        let errors = |input: &str| parse_file(input).unwrap_err().msg;
+        assert!(errors("class A {\n    @fill: red\n    @fill: blue\n}\n").contains("duplicate @fill in node block"));
+        assert!(errors("class A {\n    @stroke: #12\n}\n").contains("invalid @stroke color"));
+        assert!(errors("class A {\n    @stroke_width: -1\n}\n").contains("positive number"));
+        assert!(errors("A --> B { @dash: x }\n").contains("invalid @dash value"));
+        assert!(errors("A --> B { @pos: (1, 2) }\n").contains("expected a style directive"));
+        assert!(errors("A --> B {\n    + x\n}\n").contains("only style directives"));
+    }
+
+    #[test]
+    fn test_roundtrip_style_rules_and_classes() {
+        let input = "style warning { @fill: #fee; @stroke: #c00 }
+style interface { %% every interface
+    @dash: 4 2
+}
+style empty {}
+class A \"Alpha\" :::warning :::loud {\n    @class: bold, big-text\n}\nclass B:::warning\nA --> B { @class: warning; @stroke_width: 2 }\n";
+        let ast = parse_file(input).unwrap();
+        let Stmt::Style(s) = &ast.items[0] else { panic!("Expected Style") };
+        assert_eq!(s.selector, "warning");
+        assert_eq!(s.style.stroke.as_deref(), Some("#c00"));
+        let Stmt::Node(n) = &ast.items[3] else { panic!("Expected Node") };
+        assert_eq!(n.classes, ["warning", "loud"]);
+        assert_eq!(n.style.classes, ["bold", "big-text"]);
+        assert_eq!(n.tokens.classes.len(), 4);
+        assert_eq!(n.tokens.classes[3].start_col, 19);
+        let Stmt::Relation(r) = &ast.items[5] else { panic!("Expected Relation") };
+        assert_eq!(r.style.classes, ["warning"]);
+        assert_eq!(emit_file(&ast), input.replace("B:::warning", "B :::warning"));
+
+        let errors = |input: &str| parse_file(input).unwrap_err().msg;
+        assert!(errors("style warning { @class: other }\n").contains("@class can't be used in a style block"));
+        assert!(errors("style warning\n").contains("expected '{'"));
+        assert!(errors("style warning { @fill: red\n").contains("expected '}'"));
+        assert!(errors("class A :::\n").contains("expected a style class name"));
+        assert!(errors("class A {\n    @class: a.b\n}\n").contains("invalid @class value"));
+    }
 }
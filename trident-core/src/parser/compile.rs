@@ -4,10 +4,14 @@
 // What this does:
 // - Creates a synthetic root group
 // - Flattens nested GroupAst/NodeAst into indexed vectors with parent pointers
-// - Enforces global uniqueness:
-//     - node identifiers must be unique
-//     - named group identifiers must be unique
-// - Resolves RelationAst endpoints from Ident -> NodeId
+// - Treats named groups as namespaces and enforces uniqueness of
+//   fully qualified paths (`Outer.Inner.Config`):
+//     - node paths must be unique
+//     - named group paths must be unique
+// - Resolves RelationAst endpoints from Ident -> NodeId (or GroupId), see scope.rs
+// - Attaches notes to the node, group or relation they name
+// - Resolves the style cascade: rules for a node's kind and modifiers, then
+//   rules for the style classes it applies, then its own directives
 // - Preserves deterministic order using the original traversal order
 //
 // Assumptions:
@@ -16,7 +20,11 @@
 
 use std::collections::HashMap;
 
-use crate::parser::{FileAst, GroupAst, Ident, NodeAst, PointI, RelationAst, Stmt};
+use crate::parser::scope::{resolve, Resolution};
+use crate::parser::{
+    parse_members, token_from_arrow, Direction, FileAst, GroupAst, Ident, Member, NodeAst, NoteAst, NoteTarget, PointI, RelationAst,
+    RelationEnd, Stmt, Style, TokenSpan, TypeParam,
+};
 use serde::Serialize;
 
 #[derive(Debug, Clone)]
@@ -25,21 +33,33 @@ pub struct Diagram {
     pub groups: Vec<Group>,
     pub nodes: Vec<Node>,
     pub edges: Vec<Edge>,
+    /// Relations whose endpoints are both named groups (package dependencies)
+    pub group_edges: Vec<GroupEdge>,
+    pub notes: Vec<Note>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub struct GroupId(pub usize);
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub struct NodeId(pub usize);
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct NoteId(pub usize);
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Group {
     pub gid: GroupId,
     /// None => anonymous group
     pub id: Option<Ident>,
+    /// Fully qualified name (enclosing named groups and `id`); None for anonymous groups
+    pub path: Option<Ident>,
     pub parent: Option<GroupId>,
     pub pos: Option<PointI>, // local to parent
+    /// Colors and line style of the group box
+    pub style: Style,
+    /// Rank direction for the group's contents; None inherits the parent's
+    pub direction: Option<Direction>,
     pub children_groups: Vec<GroupId>,
     pub children_nodes: Vec<NodeId>,
     /// Stable traversal order index (assigned during compilation).
@@ -53,16 +73,24 @@ pub struct Node {
     pub kind: String,
     /// Modifiers: "abstract", "interface", "enum", "rectangle", "circle", "diamond", etc.
     pub modifiers: Vec<String>,
-    /// Unique identifier
+    /// Identifier as declared (unqualified)
     pub id: Ident,
+    /// Fully qualified name (enclosing named groups and `id`), unique in the diagram
+    pub path: Ident,
     pub label: Option<String>,
+    /// Generic type parameters, shown in the UML template box
+    pub type_params: Vec<TypeParam>,
     pub group: GroupId,
     pub pos: Option<PointI>, // local to group
     /// Custom width (from @width directive)
     pub width: Option<i32>,
     /// Custom height (from @height directive)
     pub height: Option<i32>,
+    /// Colors and line style from the style directives
+    pub style: Style,
     pub body_lines: Vec<String>,
+    /// Typed interpretation of each body line (index-aligned with `body_lines`)
+    pub members: Vec<Member>,
     /// Whether this node was explicitly declared (false for implicit nodes)
     pub explicit: bool,
     /// Stable traversal order index.
@@ -76,6 +104,50 @@ pub struct Edge {
     /// Arrow canonical name (e.g., "extends_left", "assoc_right")
     pub arrow: String,
     pub label: Option<String>,
+    /// Multiplicity and role at each end
+    pub from_end: RelationEnd,
+    pub to_end: RelationEnd,
+    /// Colors and line style from the relation's style block
+    pub style: Style,
+    /// Stable traversal order index.
+    pub order: usize,
+}
+
+/// A relation between two named groups, drawn between the group borders
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupEdge {
+    pub from: GroupId,
+    pub to: GroupId,
+    /// Arrow canonical name (e.g., "dependency_right")
+    pub arrow: String,
+    pub label: Option<String>,
+    pub from_end: RelationEnd,
+    pub to_end: RelationEnd,
+    pub style: Style,
+    /// Stable traversal order index.
+    pub order: usize,
+}
+
+/// What a note is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum NoteAnchor {
+    /// Free-standing note
+    None,
+    Node(NodeId),
+    Group(GroupId),
+    /// Index into `edges` followed by `group_edges` (the order `route_edges` uses)
+    Edge(usize),
+}
+
+/// A sticky note
+#[derive(Debug, Clone, Serialize)]
+pub struct Note {
+    pub id: NoteId,
+    pub anchor: NoteAnchor,
+    /// Lines of text
+    pub lines: Vec<String>,
+    /// Group the note is written in
+    pub group: GroupId,
     /// Stable traversal order index.
     pub order: usize,
 }
@@ -84,7 +156,9 @@ pub struct Edge {
 pub struct CompileError {
     pub msg: String,
     pub line: usize,  // 1-based line number
-    pub col: usize,   // 1-based column (usually 1)
+    pub col: usize,   // 1-based byte column
+    /// The offending identifier, when the AST carries its position
+    pub span: Option<TokenSpan>,
 }
 
 impl std::fmt::Display for CompileError {
@@ -94,19 +168,42 @@ impl std::fmt::Display for CompileError {
 }
 impl std::error::Error for CompileError {}
 
+/// Enum nodes carry "enum" as a modifier once compiled
+fn is_enum(modifiers: &[String]) -> bool {
+    modifiers.iter().any(|m| m == "enum")
+}
+
 pub fn compile(ast: &FileAst) -> Result<Diagram, CompileError> {
+    let (diagram, errors) = compile_recovering(ast);
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(diagram),
+    }
+}
+
+/// Compile as much of `ast` as possible.
+/// Duplicate node declarations are skipped and the contents of a duplicate group
+/// are compiled into its parent; every such problem is returned in source order.
+pub fn compile_recovering(ast: &FileAst) -> (Diagram, Vec<CompileError>) {
     let mut ctx = CompileCtx::new();
 
     // Create synthetic root group (order 0)
     let root = ctx.new_group(None, None, None);
 
+    // Style rules apply wherever they are written, so collect them first
+    ctx.collect_style_rules(&ast.items);
+
     // Walk file statements into root group
-    ctx.compile_items_into_group(&ast.items, root)?;
+    ctx.compile_items_into_group(&ast.items, root);
 
-    // Resolve edges after all nodes exist
-    ctx.resolve_edges()?;
+    // Resolve edges after all nodes exist, then the notes that refer to them
+    ctx.resolve_edges();
+    ctx.resolve_notes();
 
-    Ok(ctx.finish())
+    // Relations are resolved last; report everything in source order
+    let mut errors = std::mem::take(&mut ctx.errors);
+    errors.sort_by_key(|e| e.line);
+    (ctx.finish(), errors)
 }
 
 struct PendingEdge {
@@ -114,22 +211,55 @@ struct PendingEdge {
     to: Ident,
     arrow: String,
     label: Option<String>,
+    from_end: RelationEnd,
+    to_end: RelationEnd,
+    style: Style,
     order: usize,
+    /// Path of the named group the relation is written in
+    scope: Option<Ident>,
     line: usize,  // For error reporting
+    from_token: Option<TokenSpan>,
+    to_token: Option<TokenSpan>,
+}
+
+struct PendingNote {
+    target: NoteTarget,
+    lines: Vec<String>,
+    group: GroupId,
+    order: usize,
+    /// Path of the named group the note is written in
+    scope: Option<Ident>,
+    line: usize,
+    target_token: Option<TokenSpan>,
+}
+
+/// What a relation endpoint refers to
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Node(NodeId),
+    Group(GroupId),
 }
 
 struct CompileCtx {
     groups: Vec<Group>,
     nodes: Vec<Node>,
     edges: Vec<Edge>,
+    group_edges: Vec<GroupEdge>,
 
-    // For uniqueness checks and resolving
-    node_by_ident: HashMap<Ident, NodeId>,
-    group_by_ident: HashMap<Ident, GroupId>,
+    // For uniqueness checks and resolving, keyed by fully qualified path
+    node_by_path: HashMap<Ident, NodeId>,
+    group_by_path: HashMap<Ident, GroupId>,
 
     pending_edges: Vec<PendingEdge>,
+    notes: Vec<Note>,
+    pending_notes: Vec<PendingNote>,
+
+    /// Top-level style rules as (selector, style), in source order
+    style_rules: Vec<(String, Style)>,
 
     next_order: usize,
+
+    errors: Vec<CompileError>,
 }
 
 impl CompileCtx {
@@ -138,10 +268,15 @@ impl CompileCtx {
             groups: Vec::new(),
             nodes: Vec::new(),
             edges: Vec::new(),
-            node_by_ident: HashMap::new(),
-            group_by_ident: HashMap::new(),
+            group_edges: Vec::new(),
+            node_by_path: HashMap::new(),
+            group_by_path: HashMap::new(),
             pending_edges: Vec::new(),
+            notes: Vec::new(),
+            pending_notes: Vec::new(),
+            style_rules: Vec::new(),
             next_order: 0,
+            errors: Vec::new(),
         }
     }
 
@@ -151,6 +286,8 @@ impl CompileCtx {
             groups: self.groups,
             nodes: self.nodes,
             edges: self.edges,
+            group_edges: self.group_edges,
+            notes: self.notes,
         }
     }
 
@@ -168,11 +305,15 @@ impl CompileCtx {
     ) -> GroupId {
         let gid = GroupId(self.groups.len());
         let order = self.alloc_order();
+        let path = id.as_ref().map(|id| id.qualified(parent.and_then(|p| self.scope_of(p)).as_ref()));
         self.groups.push(Group {
             gid,
             id,
+            path,
             parent,
             pos,
+            style: Style::default(),
+            direction: None,
             children_groups: Vec::new(),
             children_nodes: Vec::new(),
             order,
@@ -195,61 +336,136 @@ impl CompileCtx {
     ) -> NodeId {
         let nid = NodeId(self.nodes.len());
         let order = self.alloc_order();
+        let members = parse_members(&body_lines, is_enum(&modifiers));
+        let path = id.qualified(self.scope_of(group).as_ref());
         self.nodes.push(Node {
             nid,
             kind,
             modifiers,
             id,
+            path,
             label,
+            type_params: Vec::new(),
             group,
             pos,
             width,
             height,
+            style: Style::default(),
             body_lines,
+            members,
             explicit,
             order,
         });
         nid
     }
 
-    fn compile_items_into_group(&mut self, items: &[Stmt], parent_gid: GroupId) -> Result<(), CompileError> {
+    /// Path of the namespace that names inside `gid` are declared in:
+    /// the nearest named group, or None at top level
+    fn scope_of(&self, gid: GroupId) -> Option<Ident> {
+        let mut group = &self.groups[gid.0];
+        loop {
+            if let Some(path) = &group.path {
+                return Some(path.clone());
+            }
+            group = &self.groups[group.parent?.0];
+        }
+    }
+
+    fn compile_items_into_group(&mut self, items: &[Stmt], parent_gid: GroupId) {
         for stmt in items {
             match stmt {
-                Stmt::Group(g) => self.compile_group(g, parent_gid)?,
-                Stmt::Node(n) => self.compile_node(n, parent_gid)?,
-                Stmt::Relation(r) => self.collect_relation(r)?,
+                Stmt::Group(g) => self.compile_group(g, parent_gid),
+                Stmt::Node(n) => {
+                    if let Err(e) = self.compile_node(n, parent_gid) {
+                        self.errors.push(e);
+                    }
+                }
+                Stmt::Relation(r) => self.collect_relation(r, parent_gid),
+                Stmt::Note(n) => self.collect_note(n, parent_gid),
+                // Top-level rules are collected up front
+                Stmt::Style(s) if self.groups[parent_gid.0].parent.is_some() => self.errors.push(CompileError {
+                    msg: "style blocks are only allowed at the top level".to_string(),
+                    line: s.span.map(|s| s.start_line).unwrap_or(1),
+                    col: s.tokens.keyword.map(|t| t.start_col).unwrap_or(1),
+                    span: s.tokens.keyword,
+                }),
+                Stmt::Style(_) => {}
                 Stmt::Comment(_) => {} // Comments don't affect the diagram
             }
         }
-        Ok(())
     }
 
-    fn compile_group(&mut self, g: &GroupAst, parent_gid: GroupId) -> Result<(), CompileError> {
-        // Uniqueness check for named groups
-        if let Some(id) = &g.id {
-            if self.group_by_ident.contains_key(id) {
-                return Err(CompileError {
-                    msg: format!("duplicate group identifier: {}", id.0),
-                    line: g.span.map(|s| s.start_line).unwrap_or(1),
-                    col: 1,
+    fn collect_style_rules(&mut self, items: &[Stmt]) {
+        for stmt in items {
+            if let Stmt::Style(s) = stmt {
+                self.style_rules.push((s.selector.clone(), s.style.clone()));
+            }
+        }
+    }
+
+    /// Final style of an element with the given kinds and modifiers, style classes
+    /// (`class_tokens` index-aligned where known) and own directives. Later rules win
+    /// field by field; unknown classes are reported.
+    fn cascade(&mut self, kinds: &[&str], classes: &[String], class_tokens: &[TokenSpan], own: &Style, line: usize) -> Style {
+        for (i, class) in classes.iter().enumerate() {
+            if !self.style_rules.iter().any(|(selector, _)| selector == class) {
+                let token = class_tokens.get(i).copied();
+                self.errors.push(CompileError {
+                    msg: format!("unknown style class '{}'", class),
+                    line: token.map(|t| t.line).unwrap_or(line),
+                    col: token.map(|t| t.start_col).unwrap_or(1),
+                    span: token,
                 });
             }
         }
 
+        let mut style = Style::default();
+        for (selector, rule) in &self.style_rules {
+            if kinds.contains(&selector.as_str()) {
+                style.merge(rule);
+            }
+        }
+        for (selector, rule) in &self.style_rules {
+            if classes.contains(selector) {
+                style.merge(rule);
+            }
+        }
+        style.merge(own);
+        style
+    }
+
+    fn compile_group(&mut self, g: &GroupAst, parent_gid: GroupId) {
+        // Uniqueness check for named groups
+        if let Some(id) = &g.id
+            && let path = id.qualified(self.scope_of(parent_gid).as_ref())
+            && self.group_by_path.contains_key(&path)
+        {
+            self.errors.push(CompileError {
+                msg: format!("duplicate group identifier: {}", path.0),
+                line: g.span.map(|s| s.start_line).unwrap_or(1),
+                col: g.tokens.id.map(|t| t.start_col).unwrap_or(1),
+                span: g.tokens.id,
+            });
+            // Keep the contents, just without the duplicate group around them
+            self.compile_items_into_group(&g.items, parent_gid);
+            return;
+        }
+
         let gid = self.new_group(g.id.clone(), Some(parent_gid), g.pos);
+        let line = g.span.map(|s| s.start_line).unwrap_or(1);
+        self.groups[gid.0].style = self.cascade(&[], &g.style.classes, &g.tokens.classes, &g.style, line);
+        self.groups[gid.0].direction = g.direction;
 
-        // Register group id if named
-        if let Some(id) = &self.groups[gid.0].id {
-            self.group_by_ident.insert(id.clone(), gid);
+        // Register group path if named
+        if let Some(path) = &self.groups[gid.0].path {
+            self.group_by_path.insert(path.clone(), gid);
         }
 
         // Link to parent
         self.groups[parent_gid.0].children_groups.push(gid);
 
         // Recurse into children
-        self.compile_items_into_group(&g.items, gid)?;
-
-        Ok(())
+        self.compile_items_into_group(&g.items, gid);
     }
 
     fn compile_node(&mut self, n: &NodeAst, parent_gid: GroupId) -> Result<(), CompileError> {
@@ -260,25 +476,36 @@ impl CompileCtx {
             modifiers.push(n.original_kind.clone());
         }
         
+        // Already explicitly declared - duplicate error
+        let path = n.id.qualified(self.scope_of(parent_gid).as_ref());
+        if self.node_by_path.get(&path).is_some_and(|nid| self.nodes[nid.0].explicit) {
+            return Err(CompileError {
+                msg: format!("duplicate node identifier: {}", path.0),
+                line: n.span.map(|s| s.start_line).unwrap_or(1),
+                col: n.tokens.id.map(|t| t.start_col).unwrap_or(1),
+                span: n.tokens.id,
+            });
+        }
+
+        let kinds: Vec<&str> = [n.kind.as_str()].into_iter().chain(modifiers.iter().map(String::as_str)).collect();
+        let classes: Vec<String> = n.classes.iter().chain(&n.style.classes).cloned().collect();
+        let line = n.span.map(|s| s.start_line).unwrap_or(1);
+        let style = self.cascade(&kinds, &classes, &n.tokens.classes, &n.style, line);
+
         // Check if node already exists (could be implicit from a relation)
-        if let Some(&existing_nid) = self.node_by_ident.get(&n.id) {
+        if let Some(&existing_nid) = self.node_by_path.get(&path) {
             let existing = &mut self.nodes[existing_nid.0];
-            if existing.explicit {
-                // Already explicitly declared - duplicate error
-                return Err(CompileError {
-                    msg: format!("duplicate node identifier: {}", n.id.0),
-                    line: n.span.map(|s| s.start_line).unwrap_or(1),
-                    col: 1,
-                });
-            }
-            
+
             // Upgrade implicit node to explicit
             existing.kind = n.kind.clone();
             existing.modifiers = modifiers;
             existing.label = n.label.clone();
+            existing.type_params = n.type_params.clone();
             existing.width = n.width;
             existing.height = n.height;
+            existing.style = style;
             existing.body_lines = n.body_lines.clone();
+            existing.members = parse_members(&existing.body_lines, is_enum(&existing.modifiers));
             existing.explicit = true;
             if n.pos.is_some() {
                 existing.pos = n.pos;
@@ -310,7 +537,9 @@ impl CompileCtx {
             true, // explicit
         );
 
-        self.node_by_ident.insert(n.id.clone(), nid);
+        self.nodes[nid.0].type_params = n.type_params.clone();
+        self.nodes[nid.0].style = style;
+        self.node_by_path.insert(path, nid);
 
         // Link to group
         self.groups[parent_gid.0].children_nodes.push(nid);
@@ -318,61 +547,383 @@ impl CompileCtx {
         Ok(())
     }
 
-    fn collect_relation(&mut self, r: &RelationAst) -> Result<(), CompileError> {
+    fn collect_relation(&mut self, r: &RelationAst, parent_gid: GroupId) {
         let order = self.alloc_order();
+        let scope = self.scope_of(parent_gid);
+        let line = r.span.map(|s| s.start_line).unwrap_or(1);
+        let style = self.cascade(&[], &r.style.classes, &r.tokens.classes, &r.style, line);
         self.pending_edges.push(PendingEdge {
             from: r.from.clone(),
             to: r.to.clone(),
             arrow: r.arrow.clone(),
             label: r.label.clone(),
+            from_end: r.from_end.clone(),
+            to_end: r.to_end.clone(),
+            style,
             order,
-            line: r.span.map(|s| s.start_line).unwrap_or(1),
+            scope,
+            line,
+            from_token: r.tokens.from,
+            to_token: r.tokens.to,
         });
-        Ok(())
     }
 
-    /// Get an existing node by identifier, or create an implicit one.
-    fn get_or_create_implicit_node(&mut self, id: &Ident) -> NodeId {
-        if let Some(&nid) = self.node_by_ident.get(id) {
-            return nid;
-        }
-        
-        // Create implicit node in root group (GroupId(0))
+    fn collect_note(&mut self, n: &NoteAst, parent_gid: GroupId) {
+        let order = self.alloc_order();
+        let scope = self.scope_of(parent_gid);
+        self.pending_notes.push(PendingNote {
+            target: n.target.clone(),
+            lines: n.lines.clone(),
+            group: parent_gid,
+            order,
+            scope,
+            line: n.span.map(|s| s.start_line).unwrap_or(1),
+            target_token: n.tokens.target,
+        });
+    }
+
+    /// Create an implicit node for a name that isn't declared
+    fn create_implicit_node(&mut self, id: Ident, group: GroupId) -> NodeId {
         let nid = self.new_node(
             "node".to_string(),
             vec!["rectangle".to_string()],
-            id.clone(),
+            id,
             None,
-            GroupId(0),
+            group,
             None,
             None,
             None,
             Vec::new(),
             false, // implicit
         );
-        self.node_by_ident.insert(id.clone(), nid);
-        self.groups[0].children_nodes.push(nid);
+        self.nodes[nid.0].style = self.cascade(&["node", "rectangle"], &[], &[], &Style::default(), 1);
+        self.node_by_path.insert(self.nodes[nid.0].path.clone(), nid);
+        self.groups[group.0].children_nodes.push(nid);
         nid
     }
 
-    fn resolve_edges(&mut self) -> Result<(), CompileError> {
+    /// Look up a name written inside the named group at path `scope`
+    fn resolve_name(&self, scope: Option<&Ident>, name: &Ident) -> Resolution<Target> {
+        resolve(
+            scope,
+            name,
+            |path| {
+                (self.node_by_path.get(path).map(|&nid| Target::Node(nid)))
+                    .or_else(|| self.group_by_path.get(path).map(|&gid| Target::Group(gid)))
+            },
+            self.nodes.iter().map(|n| (&n.path, Target::Node(n.nid))).chain(
+                self.groups.iter().filter_map(|g| Some((g.path.as_ref()?, Target::Group(g.gid)))),
+            ),
+        )
+    }
+
+    /// Turn a resolved relation endpoint into a node or group, reporting ambiguous names.
+    /// Undeclared names become implicit nodes: a plain name in the root group, a
+    /// qualified one in the group its qualification names.
+    fn endpoint(&mut self, resolution: Resolution<Target>, name: &Ident, pe: &PendingEdge, token: Option<TokenSpan>) -> Option<Target> {
+        let msg = match resolution {
+            Resolution::Found(target) => return Some(target),
+            Resolution::Ambiguous(paths) => {
+                let paths: Vec<&str> = paths.iter().map(|p| p.0.as_str()).collect();
+                format!("ambiguous name '{}'; could be {}", name.0, paths.join(", "))
+            }
+            Resolution::Missing => match name.split_last() {
+                (None, id) => return Some(Target::Node(self.create_implicit_node(id, GroupId(0)))),
+                (Some(parent), id) => match self.resolve_name(pe.scope.as_ref(), &parent) {
+                    Resolution::Found(Target::Group(gid)) => return Some(Target::Node(self.create_implicit_node(id, gid))),
+                    _ => format!("unknown group '{}' in '{}'", parent.0, name.0),
+                },
+            },
+        };
+        self.errors.push(CompileError {
+            msg,
+            line: pe.line,
+            col: token.map(|t| t.start_col).unwrap_or(1),
+            span: token,
+        });
+        None
+    }
+
+    fn resolve_edges(&mut self) {
         // Collect pending edges (drain to avoid borrow issues)
         let pending: Vec<_> = self.pending_edges.drain(..).collect();
         
         for pe in pending {
+            let from = self.resolve_name(pe.scope.as_ref(), &pe.from);
+            let to = self.resolve_name(pe.scope.as_ref(), &pe.to);
+
+            // Names that refer to a group connect the group itself, but only to another group
+            let is_group = |r: &Resolution<Target>| matches!(r, Resolution::Found(Target::Group(_)));
+            let is_node = |r: &Resolution<Target>| matches!(r, Resolution::Found(Target::Node(_)) | Resolution::Missing);
+            if (is_group(&from) && is_node(&to)) || (is_node(&from) && is_group(&to)) {
+                let (group, token) = if is_group(&from) { (&pe.from, pe.from_token) } else { (&pe.to, pe.to_token) };
+                self.errors.push(CompileError {
+                    msg: format!("'{}' is a group; relations can't connect a group and a node", group.0),
+                    line: pe.line,
+                    col: token.map(|t| t.start_col).unwrap_or(1),
+                    span: token,
+                });
+                continue;
+            }
+
             // Create implicit nodes if needed
-            let from = self.get_or_create_implicit_node(&pe.from);
-            let to = self.get_or_create_implicit_node(&pe.to);
-
-            self.edges.push(Edge {
-                from,
-                to,
-                arrow: pe.arrow,
-                label: pe.label,
-                order: pe.order,
-            });
+            let from = self.endpoint(from, &pe.from, &pe, pe.from_token);
+            let to = self.endpoint(to, &pe.to, &pe, pe.to_token);
+            match (from, to) {
+                (Some(Target::Node(from)), Some(Target::Node(to))) => self.edges.push(Edge {
+                    from,
+                    to,
+                    arrow: pe.arrow,
+                    label: pe.label,
+                    from_end: pe.from_end,
+                    to_end: pe.to_end,
+                    style: pe.style,
+                    order: pe.order,
+                }),
+                (Some(Target::Group(from)), Some(Target::Group(to))) => self.group_edges.push(GroupEdge {
+                    from,
+                    to,
+                    arrow: pe.arrow,
+                    label: pe.label,
+                    from_end: pe.from_end,
+                    to_end: pe.to_end,
+                    style: pe.style,
+                    order: pe.order,
+                }),
+                // Already reported
+                _ => {}
+            }
         }
+    }
 
-        Ok(())
+    /// Attach notes to what they name. Unlike relations, notes never create
+    /// implicit nodes; a note on a relation needs a relation with the same
+    /// endpoints and arrow.
+    fn resolve_notes(&mut self) {
+        for pn in std::mem::take(&mut self.pending_notes) {
+            let anchor = match &pn.target {
+                NoteTarget::None => Some(NoteAnchor::None),
+                NoteTarget::Node(name) => self.note_target(&pn, name).map(|target| match target {
+                    Target::Node(nid) => NoteAnchor::Node(nid),
+                    Target::Group(gid) => NoteAnchor::Group(gid),
+                }),
+                NoteTarget::Relation { from, arrow, to } => self.note_relation(&pn, from, arrow, to).map(NoteAnchor::Edge),
+            };
+            if let Some(anchor) = anchor {
+                self.notes.push(Note { id: NoteId(self.notes.len()), anchor, lines: pn.lines, group: pn.group, order: pn.order });
+            }
+        }
     }
-}
\ No newline at end of file
+
+    /// Index of the relation a `note on` refers to, in `edges` followed by `group_edges`
+    fn note_relation(&mut self, pn: &PendingNote, from: &Ident, arrow: &str, to: &Ident) -> Option<usize> {
+        let from_target = self.note_target(pn, from)?;
+        let to_target = self.note_target(pn, to)?;
+        let index = match (from_target, to_target) {
+            (Target::Node(f), Target::Node(t)) => {
+                self.edges.iter().position(|e| e.from == f && e.to == t && e.arrow == arrow)
+            }
+            (Target::Group(f), Target::Group(t)) => {
+                let i = self.group_edges.iter().position(|e| e.from == f && e.to == t && e.arrow == arrow);
+                i.map(|i| self.edges.len() + i)
+            }
+            _ => None,
+        };
+        if index.is_none() {
+            let arrow = token_from_arrow(arrow).unwrap_or(arrow);
+            self.note_error(pn, format!("no relation '{} {} {}' to attach the note to", from.0, arrow, to.0));
+        }
+        index
+    }
+
+    fn note_target(&mut self, pn: &PendingNote, name: &Ident) -> Option<Target> {
+        let msg = match self.resolve_name(pn.scope.as_ref(), name) {
+            Resolution::Found(target) => return Some(target),
+            Resolution::Ambiguous(paths) => {
+                let paths: Vec<&str> = paths.iter().map(|p| p.0.as_str()).collect();
+                format!("ambiguous name '{}'; could be {}", name.0, paths.join(", "))
+            }
+            Resolution::Missing => format!("unknown node '{}' in note", name.0),
+        };
+        self.note_error(pn, msg);
+        None
+    }
+
+    fn note_error(&mut self, pn: &PendingNote, msg: String) {
+        self.errors.push(CompileError {
+            msg,
+            line: pn.line,
+            col: pn.target_token.map(|t| t.start_col).unwrap_or(1),
+            span: pn.target_token,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_file;
+
+    #[test]
+    fn test_compile_recovering_skips_duplicates() {
+        let ast = parse_file("class A\ngroup G {\n    class B\n}\nclass A\ngroup G {\n    class C\n}\nA --> C\n").unwrap();
+        let (diagram, errors) = compile_recovering(&ast);
+
+        let messages: Vec<&str> = errors.iter().map(|e| e.msg.as_str()).collect();
+        assert_eq!(messages, ["duplicate node identifier: A", "duplicate group identifier: G"]);
+        assert_eq!((errors[0].line, errors[0].col), (5, 7));
+
+        // The duplicate group's contents move to its parent; the edge still resolves
+        let ids: Vec<&str> = diagram.nodes.iter().map(|n| n.id.0.as_str()).collect();
+        assert_eq!(ids, ["A", "B", "C"]);
+        assert_eq!(diagram.nodes[2].group, diagram.root);
+        assert_eq!(diagram.edges.len(), 1);
+
+        assert!(compile(&ast).is_err());
+    }
+
+    #[test]
+    fn test_errors_are_reported_in_source_order() {
+        // Notes resolve after every declaration, so this error is found last
+        let ast = parse_file("note for Missing \"x\"\nclass B\nclass B\n").unwrap();
+        let (_, errors) = compile_recovering(&ast);
+        let lines: Vec<usize> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [1, 3]);
+    }
+
+    #[test]
+    fn test_relations_between_groups() {
+        let ast = parse_file(
+            "group Model {\n    class User\n}\ngroup Persistence {\n    class Repo\n}\nModel ..> Persistence : uses\nUser --> Persistence\n",
+        )
+        .unwrap();
+        let (diagram, errors) = compile_recovering(&ast);
+
+        // No implicit nodes are created for the group names
+        let ids: Vec<&str> = diagram.nodes.iter().map(|n| n.id.0.as_str()).collect();
+        assert_eq!(ids, ["User", "Repo"]);
+        assert_eq!(diagram.group_edges.len(), 1);
+        let edge = &diagram.group_edges[0];
+        assert_eq!(diagram.groups[edge.from.0].id.as_ref().unwrap().0, "Model");
+        assert_eq!(diagram.groups[edge.to.0].id.as_ref().unwrap().0, "Persistence");
+        assert_eq!(edge.label.as_deref(), Some("uses"));
+
+        let messages: Vec<&str> = errors.iter().map(|e| e.msg.as_str()).collect();
+        assert_eq!(messages, ["'Persistence' is a group; relations can't connect a group and a node"]);
+        assert_eq!((errors[0].line, errors[0].col), (8, 10));
+    }
+
+    #[test]
+    fn test_groups_are_namespaces() {
+        let ast = parse_file(
+            "group Outer {\n    class Config\n    group Inner {\n        class Config\n        Config --> Outer.Config\n    }\n}\n\
+             group Other {\n    class Config\n    Config --> Outer.Inner.Config\n    Config --> Outer.Cache\n}\n\
+             Config --> Nowhere.X\ngroup Other {\n}\n",
+        )
+        .unwrap();
+        let (diagram, errors) = compile_recovering(&ast);
+
+        let paths: Vec<&str> = diagram.nodes.iter().map(|n| n.path.0.as_str()).collect();
+        assert_eq!(paths, ["Outer.Config", "Outer.Inner.Config", "Other.Config", "Outer.Cache"]);
+        assert!(diagram.nodes.iter().take(3).all(|n| n.id.0 == "Config"));
+
+        // Local names win over outer ones; qualified names reach across groups
+        let edges: Vec<(&str, &str)> =
+            diagram.edges.iter().map(|e| (diagram.nodes[e.from.0].path.0.as_str(), diagram.nodes[e.to.0].path.0.as_str())).collect();
+        assert_eq!(
+            edges,
+            [("Outer.Inner.Config", "Outer.Config"), ("Other.Config", "Outer.Inner.Config"), ("Other.Config", "Outer.Cache")]
+        );
+        // An undeclared qualified name becomes an implicit node in the named group
+        assert_eq!(diagram.groups[diagram.nodes[3].group.0].path.as_ref().unwrap().0, "Outer");
+
+        let messages: Vec<&str> = errors.iter().map(|e| e.msg.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "ambiguous name 'Config'; could be Other.Config, Outer.Config, Outer.Inner.Config",
+                "unknown group 'Nowhere' in 'Nowhere.X'",
+                "duplicate group identifier: Other",
+            ]
+        );
+        assert_eq!((errors[1].line, errors[1].col), (13, 12));
+    }
+
+    #[test]
+    fn test_notes_attach_to_nodes_groups_and_relations() {
+        let ast = parse_file(
+            "group G {\n    class A\n    note \"in G\"\n}\nA --> B\nG ..> H\ngroup H {\n}\n\
+             note for A \"a\"\nnote for G \"g\"\nnote on A --> B \"ab\"\nnote on G ..> H \"gh\"\n\
+             note for Missing \"x\"\nnote on B --> A \"x\"\n",
+        )
+        .unwrap();
+        let (diagram, errors) = compile_recovering(&ast);
+
+        let anchors: Vec<NoteAnchor> = diagram.notes.iter().map(|n| n.anchor).collect();
+        assert_eq!(
+            anchors,
+            [NoteAnchor::None, NoteAnchor::Node(NodeId(0)), NoteAnchor::Group(GroupId(1)), NoteAnchor::Edge(0), NoteAnchor::Edge(1)]
+        );
+        assert_eq!(diagram.groups[diagram.notes[0].group.0].path.as_ref().unwrap().0, "G");
+        // Notes never declare nodes
+        assert_eq!(diagram.nodes.len(), 2);
+
+        let messages: Vec<&str> = errors.iter().map(|e| e.msg.as_str()).collect();
+        assert_eq!(messages, ["unknown node 'Missing' in note", "no relation 'B --> A' to attach the note to"]);
+        assert_eq!(errors[0].line, 13);
+    }
+
+    #[test]
+    fn test_styles_reach_nodes_groups_and_edges() {
+        let ast = parse_file(
+            "A --> B { @stroke: red }\ngroup G {\n    @fill: #eef\n}\ngroup H {\n}\nG ..> H { @dash: none }\nclass A {\n    @fill: #fee\n}\n",
+        )
+        .unwrap();
+        let diagram = compile(&ast).unwrap();
+        // A's own declaration carries the style, though it comes after the relation
+        assert_eq!(diagram.nodes[0].style.fill.as_deref(), Some("#fee"));
+        assert!(diagram.nodes[1].style.is_empty());
+        assert_eq!(diagram.edges[0].style.stroke.as_deref(), Some("red"));
+        assert_eq!(diagram.groups[1].style.fill.as_deref(), Some("#eef"));
+        assert_eq!(diagram.group_edges[0].style.dash, Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_style_cascade() {
+        let ast = parse_file(
+            "style interface { @stroke: green; @dash: 4 2 }\nstyle warning { @fill: #fee; @stroke: #c00 }\n\
+             style loud { @stroke_width: 3 }\ninterface Shape :::warning {\n    @fill: white\n}\n\
+             abstract class Base {\n    @class: loud\n}\nBase ..|> Shape { @class: warning, loud }\n\
+             group G {\n    @class: loud\n}\nstyle abstract { @text_color: gray }\n",
+        )
+        .unwrap();
+        let diagram = compile(&ast).unwrap();
+
+        // Kind rule, then class rule, then the node's own directives
+        let shape = &diagram.nodes[0].style;
+        assert_eq!(shape.stroke.as_deref(), Some("#c00"));
+        assert_eq!(shape.dash, Some(vec![4.0, 2.0]));
+        assert_eq!(shape.fill.as_deref(), Some("white"));
+        assert!(shape.classes.is_empty());
+
+        // Modifier rules apply even when written after the node
+        let base = &diagram.nodes[1].style;
+        assert_eq!(base.text_color.as_deref(), Some("gray"));
+        assert_eq!(base.stroke_width, Some(3.0));
+
+        let edge = &diagram.edges[0].style;
+        assert_eq!((edge.fill.as_deref(), edge.stroke_width), (Some("#fee"), Some(3.0)));
+        assert_eq!(diagram.groups[1].style.stroke_width, Some(3.0));
+
+        let (_, errors) = compile_recovering(
+            &parse_file("class A :::nope
+group G {
+    style inner { @fill: red }
+}
+").unwrap(),
+        );
+        let messages: Vec<&str> = errors.iter().map(|e| e.msg.as_str()).collect();
+        assert_eq!(messages, ["unknown style class 'nope'", "style blocks are only allowed at the top level"]);
+        assert_eq!(errors[0].col, 12);
+    }
+}
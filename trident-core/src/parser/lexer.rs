@@ -0,0 +1,261 @@
+//! Quote-aware scanning of source lines.
+//!
+//! The parser works line by line, so instead of a token stream this module
+//! provides the primitives it needs: finding `%%`, `:` or arrow tokens
+//! outside of string literals and backtick-quoted identifiers, reading
+//! identifiers, and reading/writing escaped string literals.
+//!
+//! - Identifiers: a Unicode letter or `_`, then letters, digits or `_`;
+//!   anything else (spaces, dots, ...) can be written between backticks,
+//!   e.g. `` `com.example.User` ``
+//! - Qualified names: identifiers separated by `.`, e.g. `Outer.Inner.Config`.
+//!   Inside the AST they are kept as one string with backticks only around
+//!   segments that contain `.` or `"`, so a plain name is stored unchanged
+//! - Strings: `"..."` with `\"`, `\\` and `\n` escapes
+
+use std::borrow::Cow;
+
+/// Byte offset of the first `needle` that is not inside a string literal or
+/// backtick-quoted identifier
+pub fn find_unquoted(s: &str, needle: &str) -> Option<usize> {
+    scan_unquoted(s, needle).ok()
+}
+
+/// Like `find_unquoted`, but when `needle` isn't found, the offset of the quote
+/// that is still open at the end of `s`, if any
+fn scan_unquoted(s: &str, needle: &str) -> Result<usize, Option<usize>> {
+    // Offset and kind of the quote we are inside of
+    let mut open: Option<(usize, char)> = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match open {
+            Some((_, '"')) => match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => open = None,
+                _ => {}
+            },
+            Some(_) => {
+                if c == '`' {
+                    open = None;
+                }
+            }
+            None if s[i..].starts_with(needle) => return Ok(i),
+            None => {
+                if c == '"' || c == '`' {
+                    open = Some((i, c));
+                }
+            }
+        }
+    }
+    Err(open.map(|(i, _)| i))
+}
+
+/// Byte offset of the `%%` that starts a comment on this line, if any. A quote
+/// that is never closed (e.g. in a bare label) is taken as a plain character,
+/// so it doesn't hide a comment after it.
+pub fn find_comment_start(line: &str) -> Option<usize> {
+    let mut start = 0;
+    loop {
+        match scan_unquoted(&line[start..], "%%") {
+            Ok(i) => return Some(start + i),
+            Err(open) => start += open? + 1,
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Split a leading identifier off `s`, returning it as written (including
+/// backticks) and the remainder
+pub fn take_ident(s: &str) -> Option<(&str, &str)> {
+    if let Some(inner) = s.strip_prefix('`') {
+        let close = inner.find('`')?;
+        if close == 0 {
+            return None;
+        }
+        let end = close + 2;
+        return Some((&s[..end], &s[end..]));
+    }
+    let mut chars = s.char_indices();
+    let (_, first) = chars.next()?;
+    if !is_ident_start(first) {
+        return None;
+    }
+    let end = chars.find(|&(_, c)| !is_ident_continue(c)).map_or(s.len(), |(i, _)| i);
+    Some((&s[..end], &s[end..]))
+}
+
+/// The name an identifier token refers to, without backticks
+pub fn ident_name(token: &str) -> &str {
+    token.strip_prefix('`').and_then(|t| t.strip_suffix('`')).unwrap_or(token)
+}
+
+/// Split a leading, possibly qualified name off `s`, returning it as written
+/// and the remainder
+pub fn take_path(s: &str) -> Option<(&str, &str)> {
+    let (first, mut rest) = take_ident(s)?;
+    let mut len = first.len();
+    while let Some((segment, after)) = rest.strip_prefix('.').and_then(take_ident) {
+        len += 1 + segment.len();
+        rest = after;
+    }
+    Some((&s[..len], rest))
+}
+
+/// Whether `s` is exactly one, possibly qualified, name
+pub fn is_path(s: &str) -> bool {
+    matches!(take_path(s), Some((_, "")))
+}
+
+/// The `.`-separated segments of a qualified name, as written (including backticks)
+pub fn path_segment_tokens(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while let Some(dot) = find_unquoted(rest, ".") {
+        segments.push(&rest[..dot]);
+        rest = &rest[dot + 1..];
+    }
+    segments.push(rest);
+    segments
+}
+
+/// The names a qualified name is made of, without backticks
+pub fn path_segments(path: &str) -> Vec<&str> {
+    path_segment_tokens(path).into_iter().map(ident_name).collect()
+}
+
+/// Join names into the stored form of a qualified name
+pub fn join_path<'a>(segments: impl IntoIterator<Item = &'a str>) -> String {
+    let quoted: Vec<Cow<'_, str>> = segments
+        .into_iter()
+        .map(|s| if s.contains(['.', '"']) { Cow::Owned(format!("`{}`", s)) } else { Cow::Borrowed(s) })
+        .collect();
+    quoted.join(".")
+}
+
+/// The stored form of a name as written in the source
+pub fn path_name(token: &str) -> String {
+    join_path(path_segments(token))
+}
+
+/// Write a stored name as source text, quoting segments that aren't plain identifiers
+pub fn format_path(path: &str) -> String {
+    let formatted: Vec<Cow<'_, str>> = path_segments(path).into_iter().map(format_ident).collect();
+    formatted.join(".")
+}
+
+/// Whether `name` can be written as an identifier token at all. Backtick-quoted
+/// identifiers have no escapes and end at the line, so a name can't be empty or
+/// contain a backtick or line break
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['`', '\n', '\r'])
+}
+
+/// Write a name as an identifier token, quoting it with backticks if needed.
+/// The result only parses back if `is_valid_name(name)`
+pub fn format_ident(name: &str) -> Cow<'_, str> {
+    let plain = name.chars().next().is_some_and(is_ident_start) && name.chars().all(is_ident_continue);
+    if plain { Cow::Borrowed(name) } else { Cow::Owned(format!("`{}`", name)) }
+}
+
+/// Parse a string literal at the start of `s`, returning its unescaped
+/// contents and the remainder after the closing quote
+pub fn parse_string(s: &str) -> Result<(String, &str), String> {
+    let Some(body) = s.strip_prefix('"') else {
+        return Err("expected string".into());
+    };
+    let mut out = String::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((out, &body[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, '"')) => out.push('"'),
+                Some((_, '\\')) => out.push('\\'),
+                Some((_, 'n')) => out.push('\n'),
+                Some((_, other)) => return Err(format!("unknown escape sequence '\\{}' in string", other)),
+                None => break,
+            },
+            c => out.push(c),
+        }
+    }
+    Err("unterminated string literal".into())
+}
+
+/// Write `s` as a string literal, escaping quotes, backslashes and newlines
+pub fn quote_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_start_skips_strings_and_backticks() {
+        assert_eq!(find_comment_start("class A \"100%% done\" %% note"), Some(21));
+        assert_eq!(find_comment_start("class `50%%` %% x"), Some(13));
+        assert_eq!(find_comment_start("class A \"say \\\"%%\\\"\""), None);
+        assert_eq!(find_comment_start("A --> B %% why"), Some(8));
+        // Unterminated quotes in bare labels don't hide the comment
+        assert_eq!(find_comment_start("A --> B : 12\" screen %% why"), Some(21));
+        assert_eq!(find_comment_start("A --> B : it`s \"50%%\" %% why"), Some(22));
+        assert_eq!(find_comment_start("A --> B : it`s \"open"), None);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(take_ident("Größe {"), Some(("Größe", " {")));
+        assert_eq!(take_ident("`com.example.User`-->B"), Some(("`com.example.User`", "-->B")));
+        assert_eq!(take_ident("1abc"), None);
+        assert_eq!(take_ident("``"), None);
+        assert!(is_path("日本"));
+        assert!(!is_path("a b"));
+        assert_eq!(ident_name("`my node`"), "my node");
+        assert_eq!(format_ident("my node"), "`my node`");
+        assert_eq!(format_ident("Größe"), "Größe");
+        assert!(is_valid_name("my node"));
+        assert!(!is_valid_name("a`b") && !is_valid_name("") && !is_valid_name("a\nb"));
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        assert_eq!(take_path("Outer.Inner.Config..>B"), Some(("Outer.Inner.Config", "..>B")));
+        assert_eq!(take_path("A.`my node` x"), Some(("A.`my node`", " x")));
+        assert!(is_path("Outer.Config"));
+        assert!(!is_path("Outer."));
+        assert_eq!(path_name("A.`my node`"), "A.my node");
+        assert_eq!(path_name("`com.example.User`"), "`com.example.User`");
+        assert_eq!(path_segments("Pkg.`com.example.User`"), ["Pkg", "com.example.User"]);
+        assert_eq!(format_path("A.my node"), "A.`my node`");
+        assert_eq!(format_path("`a.b`"), "`a.b`");
+    }
+
+    #[test]
+    fn test_string_escapes_roundtrip() {
+        let (text, rest) = parse_string(r#""say \"hi\"\\n\nok" {"#).unwrap();
+        assert_eq!(text, "say \"hi\"\\n\nok");
+        assert_eq!(rest, " {");
+        assert_eq!(parse_string(&quote_string(&text)).unwrap().0, text);
+        assert!(parse_string(r#""bad \q""#).is_err());
+        assert!(parse_string(r#""open \""#).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! Typed view of class body lines.
+//!
+//! Body lines stay opaque strings in the AST so `emit_file` round-trips them
+//! untouched; this module interprets each one as a field, method, enum
+//! variant or separator. Accepted forms:
+//!   + name: Type                 (UML / TypeScript style)
+//!   + Type name                  (Java / Mermaid style)
+//!   # find(id: Id, opts): Result / + Result find(Id id) / + find(id) Result
+//!   {static}, `static` or a trailing `$` mark static members
+//!   {abstract}, `abstract` or a trailing `*` mark abstract members
+
+use serde::Serialize;
+
+/// What a body line declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberKind {
+    Field,
+    Method,
+    EnumVariant,
+    /// `---` line between compartments
+    Separator,
+}
+
+/// UML visibility marker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    /// `+`
+    Public,
+    /// `-`
+    Private,
+    /// `#`
+    Protected,
+    /// `~`
+    Package,
+}
+
+/// A method parameter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Param {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: Option<String>,
+}
+
+/// One interpreted class body line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Member {
+    pub kind: MemberKind,
+    pub visibility: Option<Visibility>,
+    /// Empty for separators
+    pub name: String,
+    /// Method parameters (empty for other kinds)
+    pub params: Vec<Param>,
+    /// Field type, or return type for methods
+    #[serde(rename = "type")]
+    pub type_name: Option<String>,
+    pub is_static: bool,
+    pub is_abstract: bool,
+}
+
+/// Interpret every body line of a node. `in_enum` lets bare names become enum variants.
+pub fn parse_members(lines: &[String], in_enum: bool) -> Vec<Member> {
+    lines.iter().map(|line| parse_member(line, in_enum)).collect()
+}
+
+/// Interpret a single body line
+pub fn parse_member(line: &str, in_enum: bool) -> Member {
+    let mut member = Member {
+        kind: MemberKind::Field,
+        visibility: None,
+        name: String::new(),
+        params: Vec::new(),
+        type_name: None,
+        is_static: false,
+        is_abstract: false,
+    };
+
+    let mut rest = line.trim();
+    if !rest.is_empty() && rest.chars().all(|c| c == '-') {
+        member.kind = MemberKind::Separator;
+        return member;
+    }
+
+    // Modifiers may come before or after the visibility marker
+    let mut forced: Option<MemberKind> = None;
+    loop {
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix("{static}").or_else(|| rest.strip_prefix("{classifier}")) {
+            member.is_static = true;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("{abstract}") {
+            member.is_abstract = true;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("{field}") {
+            forced = Some(MemberKind::Field);
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("{method}") {
+            forced = Some(MemberKind::Method);
+            rest = r;
+        } else if let Some(r) = strip_keyword(rest, "static") {
+            member.is_static = true;
+            rest = r;
+        } else if let Some(r) = strip_keyword(rest, "abstract") {
+            member.is_abstract = true;
+            rest = r;
+        } else if let Some(vis) = member.visibility.is_none().then(|| visibility_marker(rest)).flatten() {
+            member.visibility = Some(vis);
+            rest = &rest[1..];
+        } else {
+            break;
+        }
+    }
+
+    // Mermaid classifiers: `count$`, `area()*`
+    if let Some(r) = rest.strip_suffix('$') {
+        member.is_static = true;
+        rest = r.trim_end();
+    } else if let Some(r) = rest.strip_suffix('*') {
+        member.is_abstract = true;
+        rest = r.trim_end();
+    }
+
+    if in_enum && forced.is_none() && member.visibility.is_none() && let Some(name) = enum_variant_name(rest) {
+        member.kind = MemberKind::EnumVariant;
+        member.name = name.to_string();
+        return member;
+    }
+
+    let method = match forced {
+        Some(kind) => kind == MemberKind::Method,
+        None => rest.contains('('),
+    };
+    match (method, rest.find('(')) {
+        (true, Some(open)) => {
+            member.kind = MemberKind::Method;
+            let close = matching_paren(rest, open).unwrap_or(rest.len());
+            let before = rest[..open].trim();
+            let inner = &rest[open + 1..close.min(rest.len())];
+            let after = rest.get(close + 1..).unwrap_or("").trim();
+
+            // `Result find(...)` puts the return type first
+            let (type_name, name) = split_type_and_name(before);
+            member.name = name.to_string();
+            member.type_name = type_name.map(str::to_string);
+
+            let after = after.strip_prefix(':').unwrap_or(after).trim();
+            if !after.is_empty() {
+                member.type_name = Some(after.to_string());
+            }
+
+            member.params = split_top_level(inner, ',')
+                .into_iter()
+                .filter(|p| !p.trim().is_empty())
+                .map(|p| {
+                    let (name, type_name) = parse_typed(p);
+                    Param { name, type_name }
+                })
+                .collect();
+        }
+        (true, None) => {
+            member.kind = MemberKind::Method;
+            member.name = rest.to_string();
+        }
+        (false, _) => {
+            let (name, type_name) = parse_typed(rest);
+            member.name = name;
+            member.type_name = type_name;
+        }
+    }
+    member
+}
+
+fn visibility_marker(s: &str) -> Option<Visibility> {
+    let vis = match s.chars().next()? {
+        '+' => Visibility::Public,
+        '-' => Visibility::Private,
+        '#' => Visibility::Protected,
+        '~' => Visibility::Package,
+        _ => return None,
+    };
+    // A lone marker isn't a member
+    (s.len() > 1).then_some(vis)
+}
+
+/// Strip a leading keyword followed by whitespace
+fn strip_keyword<'a>(s: &'a str, kw: &str) -> Option<&'a str> {
+    let rest = s.strip_prefix(kw)?;
+    rest.starts_with(char::is_whitespace).then_some(rest)
+}
+
+/// `RED`, `RED = 1`, `RED("r")`, `RED,` -> `RED`
+fn enum_variant_name(s: &str) -> Option<&str> {
+    if s.contains(':') {
+        return None;
+    }
+    let end = s.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(s.len());
+    let name = &s[..end];
+    let tail = s[end..].trim_start();
+    let is_variant = !name.is_empty() && (tail.is_empty() || tail.starts_with(['(', '=', ',', ';']));
+    is_variant.then_some(name)
+}
+
+/// `name: Type` or `Type name` or just `name`
+fn parse_typed(s: &str) -> (String, Option<String>) {
+    let s = s.trim();
+    if let Some(colon) = find_top_level(s, ':') {
+        let ty = s[colon + 1..].trim();
+        return (s[..colon].trim().to_string(), (!ty.is_empty()).then(|| ty.to_string()));
+    }
+    let (ty, name) = split_type_and_name(s);
+    (name.to_string(), ty.map(str::to_string))
+}
+
+/// Split `Map<K, V> name` at the last top-level whitespace into (type, name)
+fn split_type_and_name(s: &str) -> (Option<&str>, &str) {
+    let mut depth = 0i32;
+    let mut split = None;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => split = Some(i),
+            _ => {}
+        }
+    }
+    match split {
+        Some(i) => (Some(s[..i].trim()), s[i..].trim()),
+        None => (None, s),
+    }
+}
+
+fn matching_paren(s: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn find_top_level(s: &str, needle: char) -> Option<usize> {
+    let mut depth = 0i32;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            c if c == needle && depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = s;
+    while let Some(i) = find_top_level(rest, sep) {
+        parts.push(&rest[..i]);
+        rest = &rest[i + sep.len_utf8()..];
+    }
+    parts.push(rest);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, ty: Option<&str>) -> Param {
+        Param { name: name.to_string(), type_name: ty.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_uml_style_members() {
+        let field = parse_member("+ someField: string", false);
+        assert_eq!(field.kind, MemberKind::Field);
+        assert_eq!(field.visibility, Some(Visibility::Public));
+        assert_eq!(field.name, "someField");
+        assert_eq!(field.type_name.as_deref(), Some("string"));
+
+        let method = parse_member("# find(id: Id, opts: Map<K, V>): Result<T>", false);
+        assert_eq!(method.kind, MemberKind::Method);
+        assert_eq!(method.visibility, Some(Visibility::Protected));
+        assert_eq!(method.name, "find");
+        assert_eq!(method.params, [param("id", Some("Id")), param("opts", Some("Map<K, V>"))]);
+        assert_eq!(method.type_name.as_deref(), Some("Result<T>"));
+    }
+
+    #[test]
+    fn test_java_and_mermaid_style_members() {
+        let field = parse_member("-List<int> ids", false);
+        assert_eq!(field.visibility, Some(Visibility::Private));
+        assert_eq!((field.name.as_str(), field.type_name.as_deref()), ("ids", Some("List<int>")));
+
+        let method = parse_member("+Result find(Id id, int n)", false);
+        assert_eq!(method.name, "find");
+        assert_eq!(method.type_name.as_deref(), Some("Result"));
+        assert_eq!(method.params, [param("id", Some("Id")), param("n", Some("int"))]);
+
+        let mermaid = parse_member("+getName() String", false);
+        assert_eq!((mermaid.name.as_str(), mermaid.type_name.as_deref()), ("getName", Some("String")));
+    }
+
+    #[test]
+    fn test_static_and_abstract_markers() {
+        for line in ["+ {static} count: int", "static count: int", "+count: int$"] {
+            let m = parse_member(line, false);
+            assert!(m.is_static && !m.is_abstract, "{}", line);
+            assert_eq!(m.name, "count", "{}", line);
+        }
+        for line in ["{abstract} area(): f64", "+ abstract area(): f64", "area() f64*"] {
+            let m = parse_member(line, false);
+            assert!(m.is_abstract && !m.is_static, "{}", line);
+            assert_eq!(m.kind, MemberKind::Method, "{}", line);
+            assert_eq!(m.name, "area", "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_enum_variants_and_separators() {
+        let members = parse_members(
+            &["Red".into(), "Green = 2".into(), "---".into(), "+ label(): string".into()],
+            true,
+        );
+        let kinds: Vec<MemberKind> = members.iter().map(|m| m.kind).collect();
+        assert_eq!(kinds, [MemberKind::EnumVariant, MemberKind::EnumVariant, MemberKind::Separator, MemberKind::Method]);
+        assert_eq!(members[1].name, "Green");
+
+        // Outside enums a bare name is a field
+        assert_eq!(parse_member("Red", false).kind, MemberKind::Field);
+    }
+}
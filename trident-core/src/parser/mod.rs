@@ -1,13 +1,18 @@
 mod ast;
 pub mod compile;
 mod codegen;
+mod lexer;
+mod member;
 mod update;
 pub mod types;
 mod rename;
+pub mod scope;
 
-pub use ast::parse_file;
-pub use compile::compile;
+pub use ast::{parse_file, parse_file_recovering, ParseError};
+pub use compile::{compile, compile_recovering};
 pub use codegen::emit_file;
+pub use lexer::{format_ident, is_valid_name};
+pub use member::{parse_member, parse_members, Member, MemberKind, Param, Visibility};
 pub use update::{
     update_group_position,
     remove_node_position,
@@ -16,5 +21,5 @@ pub use update::{
     update_node_geometry,
 };
 pub use types::*;
-pub use compile::{Diagram, GroupId, NodeId};
-pub use rename::{rename_symbol_in_ast, collect_symbols};
\ No newline at end of file
+pub use compile::{Diagram, GroupId, NodeId, NoteId, NoteAnchor, CompileError};
+pub use rename::{rename_symbol_in_ast, collect_symbols, find_symbol_references, symbol_at};
\ No newline at end of file
@@ -1,190 +1,247 @@
 //! Symbol renaming support for the Trident language.
 //!
 //! Provides functions to find all references to a symbol and rename them.
+//! Symbols are nodes and named groups, identified by their fully qualified path.
 
-use crate::parser::{FileAst, GroupAst, NodeAst, RelationAst, Stmt, Ident};
+use serde::Serialize;
+
+use crate::parser::lexer::{is_valid_name, join_path};
+use crate::parser::scope::{resolve, Resolution};
+use crate::parser::{FileAst, NoteTarget, Stmt, Ident, TokenSpan};
 
 /// A reference to a symbol in the source code.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SymbolReference {
     /// 1-based line number
     pub line: usize,
-    /// 1-based start column
+    /// 1-based start column (bytes)
     pub start_col: usize,
-    /// 1-based end column (exclusive)
+    /// 1-based end column (bytes, exclusive)
     pub end_col: usize,
+    /// 1-based start column in UTF-16 code units, for the editor
+    pub start_col_utf16: usize,
+    /// 1-based end column in UTF-16 code units (exclusive)
+    pub end_col_utf16: usize,
     /// The symbol text
     pub text: String,
 }
 
-/// Find all references to a symbol (node ID or group ID) in the AST.
-pub fn find_symbol_references(ast: &FileAst, symbol: &str) -> Vec<SymbolReference> {
-    let mut refs = Vec::new();
-    find_in_items(&ast.items, symbol, &mut refs);
-    refs
-}
-
-fn find_in_items(items: &[Stmt], symbol: &str, refs: &mut Vec<SymbolReference>) {
-    for stmt in items {
-        match stmt {
-            Stmt::Node(node) => find_in_node(node, symbol, refs),
-            Stmt::Group(group) => find_in_group(group, symbol, refs),
-            Stmt::Relation(rel) => find_in_relation(rel, symbol, refs),
-            Stmt::Comment(_) => {}
+impl SymbolReference {
+    fn new(span: TokenSpan, symbol: &str) -> Self {
+        SymbolReference {
+            line: span.line,
+            start_col: span.start_col,
+            end_col: span.end_col,
+            start_col_utf16: span.start_col_utf16,
+            end_col_utf16: span.end_col_utf16,
+            text: symbol.to_string(),
         }
     }
 }
 
-fn find_in_node(node: &NodeAst, symbol: &str, refs: &mut Vec<SymbolReference>) {
-    if node.id.0 == symbol {
-        if let Some(span) = &node.span {
-            // The node ID appears on the first line of the span
-            // We need to find the column position - it's after `kind` and any modifiers
-            refs.push(SymbolReference {
-                line: span.start_line,
-                start_col: 0, // Will be computed during rename
-                end_col: 0,
-                text: symbol.to_string(),
-            });
-        }
-    }
+/// Declarations and relation endpoints of a file, with every endpoint
+/// resolved the same way `compile` resolves it (see `scope`)
+struct SymbolTable {
+    /// Nodes and named groups, in source order
+    decls: Vec<Decl>,
+    /// Relation endpoints (`from` then `to`) and note targets, in source order
+    refs: Vec<Ref>,
 }
 
-fn find_in_group(group: &GroupAst, symbol: &str, refs: &mut Vec<SymbolReference>) {
-    // Check group ID
-    if let Some(id) = &group.id {
-        if id.0 == symbol {
-            if let Some(span) = &group.span {
-                refs.push(SymbolReference {
-                    line: span.start_line,
-                    start_col: 0,
-                    end_col: 0,
-                    text: symbol.to_string(),
-                });
-            }
+struct Decl {
+    /// Fully qualified path
+    path: Ident,
+    span: Option<TokenSpan>,
+}
+
+struct Ref {
+    /// Fully qualified path of what it refers to; None if it is ambiguous
+    target: Option<Ident>,
+    /// Number of segments as written
+    written: usize,
+    segments: Vec<TokenSpan>,
+}
+
+impl Ref {
+    /// Index of the written segment naming `symbol`, if the reference goes through it.
+    /// A written name is always a trailing part of the path it resolves to.
+    fn segment_of(&self, symbol: &Ident) -> Option<usize> {
+        let target = self.target.as_ref()?.segments();
+        let symbol = symbol.segments();
+        if !target.starts_with(&symbol) {
+            return None;
         }
+        (symbol.len() - 1).checked_sub(target.len() - self.written)
     }
-    // Recurse into group items
-    find_in_items(&group.items, symbol, refs);
 }
 
-fn find_in_relation(rel: &RelationAst, symbol: &str, refs: &mut Vec<SymbolReference>) {
-    if let Some(span) = &rel.span {
-        if rel.from.0 == symbol {
-            refs.push(SymbolReference {
-                line: span.start_line,
-                start_col: 0,
-                end_col: 0,
-                text: symbol.to_string(),
-            });
-        }
-        if rel.to.0 == symbol {
-            refs.push(SymbolReference {
-                line: span.start_line,
-                start_col: 0,
-                end_col: 0,
-                text: symbol.to_string(),
-            });
+impl SymbolTable {
+    fn new(ast: &FileAst) -> Self {
+        let mut table = SymbolTable { decls: Vec::new(), refs: Vec::new() };
+        let mut endpoints = Vec::new();
+        collect_from_items(&ast.items, None, &mut table.decls, &mut endpoints);
+        for (scope, name, segments) in endpoints {
+            let target = table.resolve(scope.as_ref(), name);
+            table.refs.push(Ref { target, written: name.segments().len(), segments });
         }
+        table
     }
-}
 
-/// Rename a symbol in the AST, returning the modified AST.
-pub fn rename_symbol_in_ast(ast: &mut FileAst, old_name: &str, new_name: &str) -> bool {
-    rename_in_items(&mut ast.items, old_name, new_name)
+    /// Path `name` refers to when written inside the group at path `scope`.
+    /// Undeclared names refer to the implicit node `compile` creates for them.
+    fn resolve(&self, scope: Option<&Ident>, name: &Ident) -> Option<Ident> {
+        let lookup = |path: &Ident| self.decls.iter().find(|d| d.path == *path).map(|d| &d.path);
+        match resolve(scope, name, lookup, self.decls.iter().map(|d| (&d.path, &d.path))) {
+            Resolution::Found(path) => Some(path.clone()),
+            Resolution::Ambiguous(_) => None,
+            Resolution::Missing => match name.split_last() {
+                (None, _) => Some(name.clone()),
+                (Some(parent), id) => Some(id.qualified(Some(&self.resolve(scope, &parent)?))),
+            },
+        }
+    }
 }
 
-fn rename_in_items(items: &mut [Stmt], old_name: &str, new_name: &str) -> bool {
-    let mut found = false;
-    for stmt in items.iter_mut() {
+fn collect_from_items<'a>(
+    items: &'a [Stmt],
+    scope: Option<&Ident>,
+    decls: &mut Vec<Decl>,
+    endpoints: &mut Vec<(Option<Ident>, &'a Ident, Vec<TokenSpan>)>,
+) {
+    for stmt in items {
         match stmt {
-            Stmt::Node(node) => {
-                if rename_in_node(node, old_name, new_name) {
-                    found = true;
-                }
-            }
+            Stmt::Node(node) => decls.push(Decl { path: node.id.qualified(scope), span: node.tokens.id }),
             Stmt::Group(group) => {
-                if rename_in_group(group, old_name, new_name) {
-                    found = true;
+                let path = group.id.as_ref().map(|id| id.qualified(scope));
+                if let Some(path) = &path {
+                    decls.push(Decl { path: path.clone(), span: group.tokens.id });
                 }
+                collect_from_items(&group.items, path.as_ref().or(scope), decls, endpoints);
             }
             Stmt::Relation(rel) => {
-                if rename_in_relation(rel, old_name, new_name) {
-                    found = true;
-                }
+                endpoints.push((scope.cloned(), &rel.from, rel.tokens.from_segments.clone()));
+                endpoints.push((scope.cloned(), &rel.to, rel.tokens.to_segments.clone()));
             }
-            Stmt::Comment(_) => {}
+            Stmt::Note(note) => match &note.target {
+                NoteTarget::None => {}
+                NoteTarget::Node(id) => endpoints.push((scope.cloned(), id, note.tokens.from_segments.clone())),
+                NoteTarget::Relation { from, to, .. } => {
+                    endpoints.push((scope.cloned(), from, note.tokens.from_segments.clone()));
+                    endpoints.push((scope.cloned(), to, note.tokens.to_segments.clone()));
+                }
+            },
+            Stmt::Style(_) | Stmt::Comment(_) => {}
         }
     }
-    found
 }
 
-fn rename_in_node(node: &mut NodeAst, old_name: &str, new_name: &str) -> bool {
-    if node.id.0 == old_name {
-        node.id = Ident(new_name.to_string());
-        true
-    } else {
-        false
-    }
+/// Find all references to a symbol (node or group) in the AST.
+/// `symbol` is a fully qualified path, or a name that matches a single declaration.
+/// In a qualified reference like `Outer.Config`, only the segment naming the
+/// symbol is returned. Statements without token positions (e.g. generated ones) are skipped.
+pub fn find_symbol_references(ast: &FileAst, symbol: &str) -> Vec<SymbolReference> {
+    let table = SymbolTable::new(ast);
+    let Some(target) = table.resolve(None, &Ident(symbol.to_string())) else {
+        return Vec::new();
+    };
+    let name = target.name();
+
+    let decls = table.decls.iter().filter(|d| d.path == target).filter_map(|d| d.span);
+    let refs = table.refs.iter().filter_map(|r| r.segments.get(r.segment_of(&target)?).copied());
+    let mut spans: Vec<TokenSpan> = decls.chain(refs).collect();
+    spans.sort_by_key(|s| (s.line, s.start_col));
+    spans.into_iter().map(|span| SymbolReference::new(span, name)).collect()
 }
 
-fn rename_in_group(group: &mut GroupAst, old_name: &str, new_name: &str) -> bool {
-    let mut found = false;
-    
-    // Rename group ID if it matches
-    if let Some(id) = &group.id {
-        if id.0 == old_name {
-            group.id = Some(Ident(new_name.to_string()));
-            found = true;
-        }
+/// Fully qualified path of the node or group whose name is at `line` and
+/// UTF-16 `column` (both 1-based), e.g. `Outer` when on the first segment of `Outer.Config`
+pub fn symbol_at(ast: &FileAst, line: usize, column: usize) -> Option<String> {
+    let contains = |s: &TokenSpan| s.line == line && (s.start_col_utf16..=s.end_col_utf16).contains(&column);
+    let table = SymbolTable::new(ast);
+    if let Some(decl) = table.decls.iter().find(|d| d.span.as_ref().is_some_and(contains)) {
+        return Some(decl.path.0.clone());
     }
-    
-    // Recurse into items
-    if rename_in_items(&mut group.items, old_name, new_name) {
-        found = true;
-    }
-    
-    found
+    table.refs.iter().find_map(|r| {
+        let i = r.segments.iter().position(contains)?;
+        let target = r.target.as_ref()?;
+        Some(target.prefix(target.segments().len() - r.written + i + 1).0)
+    })
 }
 
-fn rename_in_relation(rel: &mut RelationAst, old_name: &str, new_name: &str) -> bool {
-    let mut found = false;
-    
-    if rel.from.0 == old_name {
-        rel.from = Ident(new_name.to_string());
-        found = true;
-    }
-    
-    if rel.to.0 == old_name {
-        rel.to = Ident(new_name.to_string());
-        found = true;
+/// Rename a symbol in the AST, returning the modified AST.
+/// `old_name` is resolved like in `find_symbol_references`; `new_name` is unqualified.
+/// Returns false without changes if `new_name` can't be written in source.
+pub fn rename_symbol_in_ast(ast: &mut FileAst, old_name: &str, new_name: &str) -> bool {
+    if !is_valid_name(new_name) {
+        return false;
     }
-    
-    found
+    let table = SymbolTable::new(ast);
+    let Some(target) = table.resolve(None, &Ident(old_name.to_string())) else {
+        return false;
+    };
+    let mut ctx = RenameCtx { target, new_name, refs: table.refs.iter(), found: false };
+    ctx.rename_in_items(&mut ast.items, None);
+    ctx.found
 }
 
-/// Collect all defined symbol names (nodes and groups) from the AST.
-pub fn collect_symbols(ast: &FileAst) -> Vec<String> {
-    let mut symbols = Vec::new();
-    collect_from_items(&ast.items, &mut symbols);
-    symbols
+struct RenameCtx<'a> {
+    target: Ident,
+    new_name: &'a str,
+    /// Endpoints in the order `rename_in_items` visits them
+    refs: std::slice::Iter<'a, Ref>,
+    found: bool,
 }
 
-fn collect_from_items(items: &[Stmt], symbols: &mut Vec<String>) {
-    for stmt in items {
-        match stmt {
-            Stmt::Node(node) => {
-                symbols.push(node.id.0.clone());
-            }
-            Stmt::Group(group) => {
-                if let Some(id) = &group.id {
-                    symbols.push(id.0.clone());
+impl RenameCtx<'_> {
+    fn rename_in_items(&mut self, items: &mut [Stmt], scope: Option<&Ident>) {
+        for stmt in items.iter_mut() {
+            match stmt {
+                Stmt::Node(node) => {
+                    if node.id.qualified(scope) == self.target {
+                        node.id = Ident(join_path([self.new_name]));
+                        self.found = true;
+                    }
+                }
+                Stmt::Group(group) => {
+                    let path = group.id.as_ref().map(|id| id.qualified(scope));
+                    if path.as_ref() == Some(&self.target) {
+                        group.id = Some(Ident(join_path([self.new_name])));
+                        self.found = true;
+                    }
+                    self.rename_in_items(&mut group.items, path.as_ref().or(scope));
+                }
+                Stmt::Relation(rel) => {
+                    self.rename_endpoint(&mut rel.from);
+                    self.rename_endpoint(&mut rel.to);
                 }
-                collect_from_items(&group.items, symbols);
+                Stmt::Note(note) => match &mut note.target {
+                    NoteTarget::None => {}
+                    NoteTarget::Node(id) => self.rename_endpoint(id),
+                    NoteTarget::Relation { from, to, .. } => {
+                        self.rename_endpoint(from);
+                        self.rename_endpoint(to);
+                    }
+                },
+                Stmt::Style(_) | Stmt::Comment(_) => {}
             }
-            Stmt::Relation(_) | Stmt::Comment(_) => {}
         }
     }
+
+    fn rename_endpoint(&mut self, name: &mut Ident) {
+        let Some(index) = self.refs.next().and_then(|r| r.segment_of(&self.target)) else {
+            return;
+        };
+        let mut segments = name.segments();
+        segments[index] = self.new_name;
+        let renamed = join_path(segments);
+        *name = Ident(renamed);
+        self.found = true;
+    }
+}
+
+/// Collect all defined symbols (nodes and named groups) from the AST, as fully qualified paths.
+pub fn collect_symbols(ast: &FileAst) -> Vec<String> {
+    SymbolTable::new(ast).decls.into_iter().map(|d| d.path.0).collect()
 }
 
 #[cfg(test)]
@@ -222,6 +279,14 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_rename_rejects_names_that_cannot_be_written() {
+        let input = "class Foo\nFoo --> Foo\n";
+        let mut ast = parse_file(input).unwrap();
+        assert!(!rename_symbol_in_ast(&mut ast, "Foo", "a`b"));
+        assert_eq!(crate::parser::emit_file(&ast), input);
+    }
+
     #[test]
     fn test_rename_in_group() {
         let input = "group MyGroup {\n  class Foo\n}\nFoo --> Foo\n";
@@ -240,4 +305,51 @@ mod tests {
             _ => panic!("Expected relation"),
         }
     }
+
+    #[test]
+    fn test_qualified_references() {
+        let input = "group Outer {\n  class Config\n  group Inner {\n    class Config\n    Config --> Outer.Config\n  }\n}\nOuter.Inner.Config --> Outer.Config\n";
+        let mut ast = parse_file(input).unwrap();
+        assert_eq!(collect_symbols(&ast), ["Outer", "Outer.Config", "Outer.Inner", "Outer.Inner.Config"]);
+
+        // `Config` alone is ambiguous at the top level
+        assert!(find_symbol_references(&ast, "Config").is_empty());
+        let cols = |symbol: &str| -> Vec<_> {
+            find_symbol_references(&ast, symbol).iter().map(|r| (r.line, r.start_col, r.end_col)).collect()
+        };
+        assert_eq!(cols("Outer.Config"), [(2, 9, 15), (5, 22, 28), (8, 30, 36)]);
+        assert_eq!(cols("Outer.Inner.Config"), [(4, 11, 17), (5, 5, 11), (8, 13, 19)]);
+        assert_eq!(cols("Outer"), [(1, 7, 12), (5, 16, 21), (8, 1, 6), (8, 24, 29)]);
+
+        assert_eq!(symbol_at(&ast, 8, 8).as_deref(), Some("Outer.Inner"));
+        assert_eq!(symbol_at(&ast, 5, 6).as_deref(), Some("Outer.Inner.Config"));
+
+        assert!(rename_symbol_in_ast(&mut ast, "Outer.Inner", "Core"));
+        assert!(rename_symbol_in_ast(&mut ast, "Outer.Config", "Settings"));
+        let out = crate::parser::emit_file(&ast);
+        assert!(out.contains("group Core {\n        class Config\n        Config --> Outer.Settings\n"));
+        assert!(out.ends_with("Outer.Core.Config --> Outer.Settings\n"));
+    }
+
+    #[test]
+    fn test_find_symbol_references_columns() {
+        let input = "group Grp {\n  class Foo\n}\nBar-->Foo : uses\n";
+        let ast = parse_file(input).unwrap();
+
+        let refs = find_symbol_references(&ast, "Foo");
+        let cols: Vec<_> = refs.iter().map(|r| (r.line, r.start_col, r.end_col)).collect();
+        assert_eq!(cols, [(2, 9, 12), (4, 7, 10)]);
+
+        let refs = find_symbol_references(&ast, "Grp");
+        assert_eq!((refs[0].line, refs[0].start_col, refs[0].end_col), (1, 7, 10));
+    }
+
+    #[test]
+    fn test_rename_note_targets() {
+        let input = "class Foo\nFoo --> Bar\nnote for Foo \"x\"\nnote on Foo --> Bar \"y\"\n";
+        let mut ast = parse_file(input).unwrap();
+        assert!(rename_symbol_in_ast(&mut ast, "Foo", "Baz"));
+        assert!(matches!(&ast.items[2], Stmt::Note(n) if n.target == NoteTarget::Node(Ident("Baz".into()))));
+        assert!(matches!(&ast.items[3], Stmt::Note(n) if matches!(&n.target, NoteTarget::Relation { from, .. } if from.0 == "Baz")));
+    }
 }
@@ -0,0 +1,83 @@
+//! Name resolution for qualified identifiers.
+//!
+//! Named groups are namespaces: every node and named group is known by its
+//! fully qualified path, e.g. `Outer.Inner.Config` (anonymous groups don't
+//! add a segment). A reference is looked up relative to the group it is
+//! written in, then relative to each enclosing group up to the top level.
+//! If none of those match, it may still name a declaration anywhere in the
+//! file by a trailing part of its path, as long as only one matches.
+
+use crate::parser::Ident;
+
+/// Outcome of resolving a reference
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution<T> {
+    Found(T),
+    /// Several declarations end with the name; their full paths
+    Ambiguous(Vec<Ident>),
+    Missing,
+}
+
+/// Resolve `name`, written inside the named group at path `scope` (None at top level).
+/// `lookup` finds a declaration by its full path; `declared` lists every declaration
+/// for the fallback.
+pub fn resolve<'a, T: Copy>(
+    scope: Option<&Ident>,
+    name: &Ident,
+    lookup: impl Fn(&Ident) -> Option<T>,
+    declared: impl IntoIterator<Item = (&'a Ident, T)>,
+) -> Resolution<T> {
+    let scope_segments = scope.map(Ident::segments).unwrap_or_default();
+    for depth in (0..=scope_segments.len()).rev() {
+        let scope = (depth > 0).then(|| scope.map(|s| s.prefix(depth))).flatten();
+        if let Some(found) = lookup(&name.qualified(scope.as_ref())) {
+            return Resolution::Found(found);
+        }
+    }
+
+    let mut matches: Vec<(&Ident, T)> = declared.into_iter().filter(|(path, _)| path.ends_with(name)).collect();
+    match matches.len() {
+        0 => Resolution::Missing,
+        1 => Resolution::Found(matches[0].1),
+        _ => {
+            matches.sort_by(|a, b| a.0.0.cmp(&b.0.0));
+            Resolution::Ambiguous(matches.into_iter().map(|(path, _)| path.clone()).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> Ident {
+        Ident(s.to_string())
+    }
+
+    #[test]
+    fn test_resolution_order() {
+        let declared = [id("Config"), id("Outer.Config"), id("Outer.Inner.Config"), id("Other.Config"), id("Other.Db")];
+        let resolve_in = |scope: Option<&str>, name: &str| {
+            resolve(
+                scope.map(id).as_ref(),
+                &id(name),
+                |p| declared.iter().position(|d| d == p),
+                declared.iter().enumerate().map(|(i, d)| (d, i)),
+            )
+        };
+
+        // Innermost scope first, then outwards
+        assert_eq!(resolve_in(Some("Outer.Inner"), "Config"), Resolution::Found(2));
+        assert_eq!(resolve_in(Some("Outer"), "Config"), Resolution::Found(1));
+        assert_eq!(resolve_in(None, "Config"), Resolution::Found(0));
+        assert_eq!(resolve_in(Some("Outer.Inner"), "Other.Config"), Resolution::Found(3));
+        // Unique trailing part of a path anywhere in the file
+        assert_eq!(resolve_in(None, "Db"), Resolution::Found(4));
+        assert_eq!(resolve_in(None, "Inner.Config"), Resolution::Found(2));
+        assert_eq!(resolve_in(None, "Nope"), Resolution::Missing);
+
+        let without_top = &declared[1..];
+        let ambiguous = resolve(None, &id("Config"), |p| without_top.iter().position(|d| d == p), without_top.iter().map(|d| (d, 0)));
+        assert_eq!(ambiguous, Resolution::Ambiguous(vec![id("Other.Config"), id("Outer.Config"), id("Outer.Inner.Config")]));
+    }
+}
@@ -1,5 +1,7 @@
 use serde::Serialize;
 
+use crate::parser::lexer::{join_path, path_segments};
+
 /// Source location span for code generation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Span {
@@ -7,6 +9,138 @@ pub struct Span {
     pub end_line: usize,
 }
 
+/// Column range of a single token on one source line.
+///
+/// Columns are 1-based and end-exclusive. They are tracked both in bytes (for
+/// slicing the source in Rust) and in UTF-16 code units (what JavaScript
+/// strings and the Monaco editor count in).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct TokenSpan {
+    /// 1-based line number
+    pub line: usize,
+    pub start_col: usize,
+    pub end_col: usize,
+    pub start_col_utf16: usize,
+    pub end_col_utf16: usize,
+}
+
+impl TokenSpan {
+    /// Span of `text[start..end]` (byte offsets) where `text` is the whole of line `line`
+    pub fn from_byte_range(line: usize, text: &str, start: usize, end: usize) -> Self {
+        let utf16 = |byte: usize| text[..byte].encode_utf16().count() + 1;
+        TokenSpan {
+            line,
+            start_col: start + 1,
+            end_col: end + 1,
+            start_col_utf16: utf16(start),
+            end_col_utf16: utf16(end),
+        }
+    }
+}
+
+/// Token positions of the header directives
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileTokens {
+    /// Whole `@layout: NAME` directive
+    pub layout: Option<TokenSpan>,
+    /// Whole `@routing: NAME` directive
+    pub routing: Option<TokenSpan>,
+    /// Whole `@direction: NAME` directive
+    pub direction: Option<TokenSpan>,
+    /// Whole `@wrap: WIDTH` directive
+    pub wrap: Option<TokenSpan>,
+}
+
+/// Token positions inside a group declaration
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GroupTokens {
+    /// The `group` keyword
+    pub keyword: Option<TokenSpan>,
+    pub id: Option<TokenSpan>,
+    pub lbrace: Option<TokenSpan>,
+    pub rbrace: Option<TokenSpan>,
+    /// Whole `@pos:`, `@direction:` and style directive lines
+    pub directives: Vec<TokenSpan>,
+    /// Each style class name in `@class:`
+    pub classes: Vec<TokenSpan>,
+}
+
+/// Token positions inside a node declaration
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NodeTokens {
+    /// Modifiers followed by the kind keyword, in source order
+    pub keywords: Vec<TokenSpan>,
+    pub id: Option<TokenSpan>,
+    /// The type parameter list including its angle brackets
+    pub type_params: Option<TokenSpan>,
+    /// The label including its quotes
+    pub label: Option<TokenSpan>,
+    pub lbrace: Option<TokenSpan>,
+    pub rbrace: Option<TokenSpan>,
+    /// Whole `@pos:`, `@width:`, `@height:` and style directive lines, in source order
+    pub directives: Vec<TokenSpan>,
+    /// Each style class name, from `:::name` in the header and then `@class:`
+    pub classes: Vec<TokenSpan>,
+    /// One per entry in `body_lines`
+    pub body_lines: Vec<TokenSpan>,
+}
+
+/// Token positions inside a relation
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RelationTokens {
+    pub from: Option<TokenSpan>,
+    /// The arrow as written (e.g. `<|--`)
+    pub arrow: Option<TokenSpan>,
+    pub to: Option<TokenSpan>,
+    /// Label text after the `:`
+    pub label: Option<TokenSpan>,
+    /// Quoted multiplicity/role string next to each endpoint
+    pub from_end: Option<TokenSpan>,
+    pub to_end: Option<TokenSpan>,
+    /// Each `.`-separated segment of a qualified `from`/`to`
+    pub from_segments: Vec<TokenSpan>,
+    pub to_segments: Vec<TokenSpan>,
+    /// Braces around the style directives, if any
+    pub lbrace: Option<TokenSpan>,
+    pub rbrace: Option<TokenSpan>,
+    /// Each style directive, in source order
+    pub directives: Vec<TokenSpan>,
+    /// Each style class name in `@class:`
+    pub classes: Vec<TokenSpan>,
+}
+
+/// Token positions inside a style rule
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StyleRuleTokens {
+    /// The `style` keyword
+    pub keyword: Option<TokenSpan>,
+    pub selector: Option<TokenSpan>,
+    pub lbrace: Option<TokenSpan>,
+    pub rbrace: Option<TokenSpan>,
+    /// Each style directive, in source order
+    pub directives: Vec<TokenSpan>,
+}
+
+/// Token positions inside a note
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NoteTokens {
+    /// The `note` keyword
+    pub keyword: Option<TokenSpan>,
+    /// The node name after `for`, or the whole relation after `on`
+    pub target: Option<TokenSpan>,
+    /// Each segment of the node name after `for`, or of the relation's `from` after `on`
+    pub from_segments: Vec<TokenSpan>,
+    pub arrow: Option<TokenSpan>,
+    /// Each segment of the relation's `to`
+    pub to_segments: Vec<TokenSpan>,
+    /// The text string including its quotes
+    pub text: Option<TokenSpan>,
+    pub lbrace: Option<TokenSpan>,
+    pub rbrace: Option<TokenSpan>,
+    /// One per line of a block note
+    pub body_lines: Vec<TokenSpan>,
+}
+
 /// A comment line, preserving exact whitespace
 #[derive(Debug, Clone, Serialize)]
 pub struct CommentAst {
@@ -16,11 +150,47 @@ pub struct CommentAst {
     pub text: String,
 }
 
+/// Comments attached to a statement so that `emit_file` keeps them when the
+/// statement itself is regenerated. Comment-only lines between statements are
+/// `Stmt::Comment` items instead.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AttachedComments {
+    /// Text after `%%` at the end of the header line (or the `{` line)
+    pub trailing: Option<String>,
+    /// Text after `%%` at the end of directive lines, keyed by directive name
+    /// ("layout", "routing", "direction", "wrap", "pos", "width", "height", or a style directive like "fill")
+    pub directives: Vec<(String, String)>,
+    /// Comment-only and blank lines inside a node block or among the header
+    /// directives, with the index of the body line (or directive) they precede
+    pub inner: Vec<(usize, CommentAst)>,
+    /// Text after `%%` at the end of body lines, index-aligned with `body_lines`
+    pub body: Vec<Option<String>>,
+    /// Text after `%%` following the closing `}`
+    pub closing: Option<String>,
+}
+
+impl AttachedComments {
+    /// Trailing comment of the given directive, if any
+    pub fn directive(&self, name: &str) -> Option<&str> {
+        self.directives.iter().find(|(n, _)| n == name).map(|(_, text)| text.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct FileAst {
-    /// Layout algorithm to use: "hierarchical" (default) or "grid"
+    /// Layout algorithm to use: "hierarchical" (default), "grid", "force" or "tree"
     pub layout: Option<String>,
+    /// Edge routing mode: "straight" (default), "orthogonal" or "bus"
+    pub routing: Option<String>,
+    /// Rank direction: "TB" (default), "LR", "BT" or "RL"
+    pub direction: Option<String>,
+    /// Width in pixels at which body and note text wraps; lines are kept whole by default
+    pub wrap: Option<String>,
     pub items: Vec<Stmt>,
+    /// Column positions of the header directives
+    pub tokens: FileTokens,
+    /// Comments on and between the header directives
+    pub comments: AttachedComments,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -28,12 +198,54 @@ pub enum Stmt {
     Group(GroupAst),
     Node(NodeAst),
     Relation(RelationAst),
+    Note(NoteAst),
+    Style(StyleRuleAst),
     Comment(CommentAst),
 }
 
+/// A node or group name, possibly qualified with the names of enclosing
+/// groups (`Outer.Inner.Config`)
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub struct Ident(pub String);
 
+impl Ident {
+    /// The names this is made of, outermost first
+    pub fn segments(&self) -> Vec<&str> {
+        path_segments(&self.0)
+    }
+
+    /// The unqualified name, e.g. `Config` for `Outer.Config`
+    pub fn name(&self) -> &str {
+        self.segments().pop().unwrap_or_default()
+    }
+
+    /// This name inside the named group at path `scope` (None at top level)
+    pub fn qualified(&self, scope: Option<&Ident>) -> Ident {
+        match scope {
+            Some(scope) => Ident(format!("{}.{}", scope.0, self.0)),
+            None => self.clone(),
+        }
+    }
+
+    /// Whether the last segments of this path are `suffix`'s segments
+    pub fn ends_with(&self, suffix: &Ident) -> bool {
+        self.segments().ends_with(&suffix.segments())
+    }
+
+    /// The first `n` segments
+    pub fn prefix(&self, n: usize) -> Ident {
+        Ident(join_path(self.segments().into_iter().take(n)))
+    }
+
+    /// The qualification (None for a plain name) and the unqualified name
+    pub fn split_last(&self) -> (Option<Ident>, Ident) {
+        let segments = self.segments();
+        let n = segments.len();
+        let name = Ident(join_path(segments[n - 1..].iter().copied()));
+        ((n > 1).then(|| self.prefix(n - 1)), name)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
 pub struct PointI {
     pub x: i32,
@@ -46,9 +258,17 @@ pub struct GroupAst {
     pub id: Option<Ident>,
     /// local position relative to closest parent group (or root)
     pub pos: Option<PointI>,
+    /// Colors and line style from the style directives
+    pub style: Style,
+    /// Rank direction for the group's contents (`@direction:`); inherited when None
+    pub direction: Option<Direction>,
     pub items: Vec<Stmt>,
     /// Source span for round-tripping
     pub span: Option<Span>,
+    /// Column positions of the header tokens (empty for generated groups)
+    pub tokens: GroupTokens,
+    /// Header, directive and closing-brace comments (inner comment lines are items)
+    pub comments: AttachedComments,
 }
 
 /// A node declaration (class, interface, enum, etc.)
@@ -65,16 +285,26 @@ pub struct NodeAst {
     pub id: Ident,
     /// Display label (optional)
     pub label: Option<String>,
+    /// Generic type parameters (`class Repository<T, ID>`)
+    pub type_params: Vec<TypeParam>,
+    /// Style classes applied in the header (`class A :::warning`)
+    pub classes: Vec<String>,
     /// local position relative to closest parent group (or root)
     pub pos: Option<PointI>,
     /// Custom width (from @width directive)
     pub width: Option<i32>,
     /// Custom height (from @height directive)
     pub height: Option<i32>,
+    /// Colors and line style from the style directives
+    pub style: Style,
     /// opaque lines inside node block (renderer decides)
     pub body_lines: Vec<String>,
     /// Source span for round-tripping
     pub span: Option<Span>,
+    /// Column positions of the declaration tokens (empty for generated nodes)
+    pub tokens: NodeTokens,
+    /// Comments on the header, directives, body lines and inside the block
+    pub comments: AttachedComments,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -84,8 +314,325 @@ pub struct RelationAst {
     pub arrow: String,
     pub to: Ident,
     pub label: Option<String>,
+    /// Multiplicity and role at the `from` end (`A "1 owner" --> B`)
+    pub from_end: RelationEnd,
+    /// Multiplicity and role at the `to` end (`A --> "0..* items" B`)
+    pub to_end: RelationEnd,
+    /// Colors and line style from the `{ @stroke: red }` block after the relation
+    pub style: Style,
+    /// Source span for round-tripping
+    pub span: Option<Span>,
+    /// Column positions of the endpoints, arrow and label (empty for generated relations)
+    pub tokens: RelationTokens,
+    /// Comments at the end of the line and inside a style block
+    pub comments: AttachedComments,
+}
+
+/// What a note is attached to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum NoteTarget {
+    /// Free-standing `note "..."`
+    None,
+    /// `note for A "..."`: a node (or named group)
+    Node(Ident),
+    /// `note on A --> B "..."`: the relation with these endpoints and arrow
+    Relation { from: Ident, arrow: String, to: Ident },
+}
+
+/// A sticky note: `note "..."`, `note for A "..."` or `note on A --> B "..."`.
+/// The text is either a string (`\n` for line breaks) or the lines of a `{ ... }` block.
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteAst {
+    pub target: NoteTarget,
+    /// Lines of text
+    pub lines: Vec<String>,
     /// Source span for round-tripping
     pub span: Option<Span>,
+    /// Column positions of the target and text (empty for generated notes)
+    pub tokens: NoteTokens,
+    /// Comments on the header, the text lines and inside the block
+    pub comments: AttachedComments,
+}
+
+/// A top-level `style SELECTOR { ... }` block. The selector is a style class
+/// name (applied with `:::name` or `@class: name`), or a node kind or modifier
+/// like `interface` or `abstract` that applies to every node that has it.
+#[derive(Debug, Clone, Serialize)]
+pub struct StyleRuleAst {
+    pub selector: String,
+    pub style: Style,
+    /// Source span for round-tripping
+    pub span: Option<Span>,
+    /// Column positions of the selector, braces and directives (empty for generated rules)
+    pub tokens: StyleRuleTokens,
+    /// Comments at the end of the header and inside the block
+    pub comments: AttachedComments,
+}
+
+/// A generic type parameter of a node, shown in the UML template box:
+/// `T` or `T extends Comparable<T>`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TypeParam {
+    pub name: String,
+    /// Upper bound after `extends`, as written
+    pub bound: Option<String>,
+}
+
+impl TypeParam {
+    /// Parse the inside of a type parameter list: `T, U extends Map<K, V>`
+    pub fn parse_list(list: &str) -> Result<Vec<TypeParam>, String> {
+        let mut params = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in list.char_indices().chain([(list.len(), ',')]) {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                ',' if depth == 0 => {
+                    params.push(TypeParam::parse(list[start..i].trim())?);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+
+    fn parse(text: &str) -> Result<TypeParam, String> {
+        let (name, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        if name.is_empty() {
+            return Err("expected type parameter name".into());
+        }
+        if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("invalid type parameter name '{}'", name));
+        }
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Ok(TypeParam { name: name.to_string(), bound: None });
+        }
+        match rest.strip_prefix("extends").map(str::trim) {
+            Some(bound) if !bound.is_empty() && rest.len() > bound.len() + "extends".len() => {
+                Ok(TypeParam { name: name.to_string(), bound: Some(bound.to_string()) })
+            }
+            _ => Err(format!("expected 'extends' or ',' after type parameter '{}'", name)),
+        }
+    }
+}
+
+/// Type parameters as written between the angle brackets: `T, U extends Bound`
+pub fn format_type_params(params: &[TypeParam]) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|p| match &p.bound {
+            Some(bound) => format!("{} extends {}", p.name, bound),
+            None => p.name.clone(),
+        })
+        .collect();
+    params.join(", ")
+}
+
+/// UML multiplicity and role name at one end of a relation.
+/// Written as a quoted string next to the endpoint: `"1..*"`, `"items"` or `"1..* items"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RelationEnd {
+    pub multiplicity: Option<String>,
+    pub role: Option<String>,
+}
+
+impl RelationEnd {
+    /// Split the text of an end string; a leading word like `1`, `*`, `0..1` or `1..*`
+    /// is the multiplicity, anything else is the role
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let (first, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        if is_multiplicity(first) {
+            RelationEnd { multiplicity: Some(first.to_string()), role: non_empty(rest.trim()) }
+        } else {
+            RelationEnd { multiplicity: None, role: non_empty(text) }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.multiplicity.is_none() && self.role.is_none()
+    }
+
+    /// The end as written in source (without quotes)
+    pub fn text(&self) -> String {
+        match (&self.multiplicity, &self.role) {
+            (Some(m), Some(r)) => format!("{} {}", m, r),
+            (Some(t), None) | (None, Some(t)) => t.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+fn is_multiplicity(word: &str) -> bool {
+    !word.is_empty()
+        && word.chars().all(|c| c.is_ascii_digit() || matches!(c, '*' | '.' | ',' | 'n'))
+        && word.chars().any(|c| c.is_ascii_digit() || matches!(c, '*' | 'n'))
+}
+
+/// Visual overrides from the `@fill`, `@stroke`, `@text_color`, `@stroke_width`
+/// and `@dash` directives of a node, group or relation. Unset fields keep the
+/// renderer's theme colors.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Style {
+    /// Style classes applied with `@class:`; compiled styles have them resolved and cleared
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+    pub fill: Option<String>,
+    pub stroke: Option<String>,
+    pub text_color: Option<String>,
+    pub stroke_width: Option<f64>,
+    /// Dash pattern as alternating dash and gap lengths; empty for a solid line (`@dash: none`)
+    pub dash: Option<Vec<f64>>,
+}
+
+impl Style {
+    /// Directive names, in the order they are written back
+    pub const DIRECTIVES: [&'static str; 6] = ["class", "fill", "stroke", "text_color", "stroke_width", "dash"];
+
+    pub fn is_empty(&self) -> bool {
+        *self == Style::default()
+    }
+
+    /// Set the field for directive `name` from its value as written in source
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match name {
+            "class" => match class_names(value).map(|c| is_class_name(c).then(|| c.to_string())).collect::<Option<Vec<_>>>() {
+                Some(classes) if !classes.is_empty() => self.classes = classes,
+                _ => return Err(format!("invalid @class value '{}', expected class names like 'warning, bold'", value)),
+            },
+            "fill" => self.fill = Some(parse_color(name, value)?),
+            "stroke" => self.stroke = Some(parse_color(name, value)?),
+            "text_color" => self.text_color = Some(parse_color(name, value)?),
+            "stroke_width" => match value.parse::<f64>() {
+                Ok(w) if w > 0.0 && w.is_finite() => self.stroke_width = Some(w),
+                _ => return Err(format!("invalid @stroke_width value '{}', expected a positive number", value)),
+            },
+            "dash" => self.dash = Some(parse_dash(value)?),
+            _ => return Err(format!("unknown style directive @{}", name)),
+        }
+        Ok(())
+    }
+
+    /// The value of directive `name` as written back to source, if set
+    pub fn value(&self, name: &str) -> Option<String> {
+        match name {
+            "class" => (!self.classes.is_empty()).then(|| self.classes.join(", ")),
+            "fill" => self.fill.clone(),
+            "stroke" => self.stroke.clone(),
+            "text_color" => self.text_color.clone(),
+            "stroke_width" => self.stroke_width.map(|w| w.to_string()),
+            "dash" => self.dash.as_ref().map(|d| {
+                if d.is_empty() {
+                    "none".to_string()
+                } else {
+                    d.iter().map(f64::to_string).collect::<Vec<_>>().join(" ")
+                }
+            }),
+            _ => None,
+        }
+    }
+
+    /// Override the fields that `over` sets
+    pub fn merge(&mut self, over: &Style) {
+        let Style { classes: _, fill, stroke, text_color, stroke_width, dash } = over;
+        if fill.is_some() {
+            self.fill.clone_from(fill);
+        }
+        if stroke.is_some() {
+            self.stroke.clone_from(stroke);
+        }
+        if text_color.is_some() {
+            self.text_color.clone_from(text_color);
+        }
+        if stroke_width.is_some() {
+            self.stroke_width = *stroke_width;
+        }
+        if dash.is_some() {
+            self.dash.clone_from(dash);
+        }
+    }
+}
+
+/// The class names of an `@class:` value, separated by commas or spaces
+pub fn class_names(value: &str) -> impl Iterator<Item = &str> {
+    value.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty())
+}
+
+/// Whether `name` can name a style class: an identifier, which may also contain `-`
+pub fn is_class_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_') && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// A `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa` hex color, or a named color like `red`
+fn parse_color(name: &str, value: &str) -> Result<String, String> {
+    let valid = match value.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic()),
+    };
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!("invalid @{} color '{}', expected a hex color like #f80 or a color name", name, value))
+    }
+}
+
+/// Dash and gap lengths separated by spaces or commas, or `none`/`solid`
+fn parse_dash(value: &str) -> Result<Vec<f64>, String> {
+    if matches!(value, "none" | "solid") {
+        return Ok(Vec::new());
+    }
+    let lengths: Option<Vec<f64>> = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<f64>().ok().filter(|n| *n >= 0.0 && n.is_finite()))
+        .collect();
+    match lengths {
+        Some(lengths) if lengths.iter().any(|n| *n > 0.0) => Ok(lengths),
+        _ => Err(format!("invalid @dash value '{}', expected lengths like '6 3' or 'none'", value)),
+    }
+}
+
+/// Which way ranks flow in hierarchical layout (`@direction: LR`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum Direction {
+    /// Top to bottom
+    #[default]
+    TB,
+    /// Left to right
+    LR,
+    /// Bottom to top
+    BT,
+    /// Right to left
+    RL,
+}
+
+impl Direction {
+    pub const NAMES: [&'static str; 4] = ["TB", "LR", "BT", "RL"];
+
+    /// Parse a direction from its directive value
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "TB" => Some(Direction::TB),
+            "LR" => Some(Direction::LR),
+            "BT" => Some(Direction::BT),
+            "RL" => Some(Direction::RL),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    /// Whether ranks are laid out side by side instead of one below the other
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Direction::LR | Direction::RL)
+    }
 }
 
 // ============================================================================
@@ -2,13 +2,18 @@
 //!
 //! This module provides functions to update the position of nodes and groups
 //! in the AST, which can then be emitted back to source code.
+//!
+//! Nodes and named groups are looked up by their fully qualified path (the
+//! ids in the diagram output); an unqualified name also works while it only
+//! matches one declaration.
 
+use crate::parser::scope::{resolve, Resolution};
 use crate::parser::types::*;
 
 /// Update the position of a node by ID.
 /// Returns true if the node was found and updated.
 pub fn update_node_position(ast: &mut FileAst, node_id: &str, new_pos: PointI) -> bool {
-    find_and_update_node(&mut ast.items, node_id, new_pos)
+    find_node(&mut ast.items, node_id).map(|n| n.pos = Some(new_pos)).is_some()
 }
 
 /// Update the position of a group.
@@ -21,10 +26,18 @@ pub fn update_group_position(
     group_index: usize,
     new_pos: PointI,
 ) -> bool {
+    let group_path = match group_id {
+        Some(id) => match resolve_path(&ast.items, id, true) {
+            Some(path) => Some(path),
+            None => return false,
+        },
+        None => None,
+    };
     let mut current_index = 0;
     find_and_update_group(
         &mut ast.items,
-        group_id,
+        None,
+        group_path.as_ref(),
         group_index,
         &mut current_index,
         new_pos,
@@ -34,94 +47,98 @@ pub fn update_group_position(
 /// Update the size of a node by ID.
 /// Returns true if the node was found and updated.
 pub fn update_node_size(ast: &mut FileAst, node_id: &str, width: i32, height: i32) -> bool {
-    find_and_update_node_size(&mut ast.items, node_id, width, height)
+    find_node(&mut ast.items, node_id)
+        .map(|n| {
+            n.width = Some(width);
+            n.height = Some(height);
+        })
+        .is_some()
 }
 
 /// Update the geometry (position and size) of a node by ID.
 /// Returns true if the node was found and updated.
 pub fn update_node_geometry(ast: &mut FileAst, node_id: &str, x: i32, y: i32, width: Option<i32>, height: Option<i32>) -> bool {
-    find_and_update_node_geometry(&mut ast.items, node_id, PointI { x, y }, width, height)
+    let Some(n) = find_node(&mut ast.items, node_id) else {
+        return false;
+    };
+    n.pos = Some(PointI { x, y });
+    if let Some(w) = width {
+        n.width = Some(w);
+    }
+    if let Some(h) = height {
+        n.height = Some(h);
+    }
+    true
 }
 
-/// Recursively search for a node by ID and update its geometry
-fn find_and_update_node_geometry(items: &mut [Stmt], node_id: &str, new_pos: PointI, width: Option<i32>, height: Option<i32>) -> bool {
-    for stmt in items {
-        match stmt {
-            Stmt::Node(n) if n.id.0 == node_id => {
-                n.pos = Some(new_pos);
-                if let Some(w) = width {
-                    n.width = Some(w);
-                }
-                if let Some(h) = height {
-                    n.height = Some(h);
-                }
-                return true;
-            }
-            Stmt::Group(g) => {
-                if find_and_update_node_geometry(&mut g.items, node_id, new_pos, width, height) {
-                    return true;
-                }
-            }
-            _ => {}
-        }
+/// Fully qualified path of the node (or named group) `id` refers to
+fn resolve_path(items: &[Stmt], id: &str, group: bool) -> Option<Ident> {
+    let mut declared = Vec::new();
+    collect_paths(items, None, group, &mut declared);
+    let lookup = |path: &Ident| declared.iter().find(|d| *d == path);
+    match resolve(None, &Ident(id.to_string()), lookup, declared.iter().map(|d| (d, d))) {
+        Resolution::Found(path) => Some(path.clone()),
+        _ => None,
     }
-    false
 }
 
-/// Recursively search for a node by ID and update its size
-fn find_and_update_node_size(items: &mut [Stmt], node_id: &str, width: i32, height: i32) -> bool {
+/// Paths of every node declaration (or every named group, if `group`)
+fn collect_paths(items: &[Stmt], scope: Option<&Ident>, group: bool, out: &mut Vec<Ident>) {
     for stmt in items {
         match stmt {
-            Stmt::Node(n) if n.id.0 == node_id => {
-                n.width = Some(width);
-                n.height = Some(height);
-                return true;
-            }
+            Stmt::Node(n) if !group => out.push(n.id.qualified(scope)),
             Stmt::Group(g) => {
-                if find_and_update_node_size(&mut g.items, node_id, width, height) {
-                    return true;
+                let path = g.id.as_ref().map(|id| id.qualified(scope));
+                if group && let Some(path) = &path {
+                    out.push(path.clone());
                 }
+                collect_paths(&g.items, path.as_ref().or(scope), group, out);
             }
             _ => {}
         }
     }
-    false
 }
 
-/// Recursively search for a node by ID and update its position
-fn find_and_update_node(items: &mut [Stmt], node_id: &str, new_pos: PointI) -> bool {
+/// Find a node declaration by ID
+fn find_node<'a>(items: &'a mut [Stmt], node_id: &str) -> Option<&'a mut NodeAst> {
+    let path = resolve_path(items, node_id, false)?;
+    find_node_by_path(items, None, &path)
+}
+
+/// Recursively search for a node by fully qualified path
+fn find_node_by_path<'a>(items: &'a mut [Stmt], scope: Option<&Ident>, path: &Ident) -> Option<&'a mut NodeAst> {
     for stmt in items {
         match stmt {
-            Stmt::Node(n) if n.id.0 == node_id => {
-                n.pos = Some(new_pos);
-                return true;
-            }
+            Stmt::Node(n) if n.id.qualified(scope) == *path => return Some(n),
             Stmt::Group(g) => {
-                if find_and_update_node(&mut g.items, node_id, new_pos) {
-                    return true;
+                let group_path = g.id.as_ref().map(|id| id.qualified(scope));
+                if let Some(n) = find_node_by_path(&mut g.items, group_path.as_ref().or(scope), path) {
+                    return Some(n);
                 }
             }
             _ => {}
         }
     }
-    false
+    None
 }
 
 /// Recursively search for a group and update its position.
-/// For named groups, match by ID. For anonymous, match by traversal index.
+/// For named groups, match by path. For anonymous, match by traversal index.
 fn find_and_update_group(
     items: &mut [Stmt],
-    group_id: Option<&str>,
+    scope: Option<&Ident>,
+    group_path: Option<&Ident>,
     target_index: usize,
     current_index: &mut usize,
     new_pos: PointI,
     ) -> bool {
     for stmt in items {
         if let Stmt::Group(g) = stmt {
+            let path = g.id.as_ref().map(|id| id.qualified(scope));
             // Check if this is the target group
-            let is_match = match (group_id, &g.id) {
-                // Named group: match by ID
-                (Some(target_id), Some(current_id)) => current_id.0 == target_id,
+            let is_match = match (group_path, &path) {
+                // Named group: match by path
+                (Some(target_path), Some(current_path)) => current_path == target_path,
                 // Anonymous group: match by index
                 (None, None) => *current_index == target_index,
                 // Named looking for anonymous or vice versa: no match
@@ -136,7 +153,7 @@ fn find_and_update_group(
             *current_index += 1;
 
             // Recurse into children
-            if find_and_update_group(&mut g.items, group_id, target_index, current_index, new_pos) {
+            if find_and_update_group(&mut g.items, path.as_ref().or(scope), group_path, target_index, current_index, new_pos) {
                 return true;
             }
         }
@@ -147,26 +164,7 @@ fn find_and_update_group(
 /// Remove the position of a node by ID (unlock it).
 /// Returns true if the node was found and its position was removed.
 pub fn remove_node_position(ast: &mut FileAst, node_id: &str) -> bool {
-    find_and_remove_node_position(&mut ast.items, node_id)
-}
-
-/// Recursively search for a node by ID and remove its position
-fn find_and_remove_node_position(items: &mut [Stmt], node_id: &str) -> bool {
-    for stmt in items {
-        match stmt {
-            Stmt::Node(n) if n.id.0 == node_id => {
-                n.pos = None;
-                return true;
-            }
-            Stmt::Group(g) => {
-                if find_and_remove_node_position(&mut g.items, node_id) {
-                    return true;
-                }
-            }
-            _ => {}
-        }
-    }
-    false
+    find_node(&mut ast.items, node_id).map(|n| n.pos = None).is_some()
 }
 
 /// Remove all positions from all nodes and groups in the AST.
@@ -193,46 +191,57 @@ fn remove_all_positions_recursive(items: &mut [Stmt]) {
 
 /// Insert a simple node declaration for an implicit node.
 /// This is used when dragging an implicit node (created from a relation).
+/// A qualified `node_id` is declared inside the group it names.
 /// Returns true if the node was inserted (i.e., it didn't already exist).
 pub fn insert_implicit_node(ast: &mut FileAst, node_id: &str, pos: PointI) -> bool {
+    let path = Ident(node_id.to_string());
     // First check if node already exists
-    if node_exists(&ast.items, node_id) {
+    if find_node_by_path(&mut ast.items, None, &path).is_some() {
         return false;
     }
+    let (group_path, id) = path.split_last();
     
     // Create a simple node declaration
     let node = NodeAst {
         kind: "node".to_string(),
         original_kind: "node".to_string(),
         modifiers: Vec::new(), // Was vec!["rectangle"], now empty per user request
-        id: Ident(node_id.to_string()),
+        id,
         label: None,
+        type_params: Vec::new(),
+        classes: Vec::new(),
         pos: Some(pos),
         width: None,
         height: None,
+        style: Default::default(),
         body_lines: Vec::new(),
         span: None,
+        tokens: Default::default(),
+        comments: Default::default(),
     };
     
-    // Insert at the end of the file
-    ast.items.push(Stmt::Node(node));
+    // Insert at the end of its group, or of the file
+    match group_path.and_then(|p| find_group_items(&mut ast.items, None, &p)) {
+        Some(items) => items.push(Stmt::Node(node)),
+        None => ast.items.push(Stmt::Node(node)),
+    }
     true
 }
 
-/// Check if a node with the given ID exists in the AST
-fn node_exists(items: &[Stmt], node_id: &str) -> bool {
+/// Recursively search for a named group by fully qualified path
+fn find_group_items<'a>(items: &'a mut [Stmt], scope: Option<&Ident>, path: &Ident) -> Option<&'a mut Vec<Stmt>> {
     for stmt in items {
-        match stmt {
-            Stmt::Node(n) if n.id.0 == node_id => return true,
-            Stmt::Group(g) => {
-                if node_exists(&g.items, node_id) {
-                    return true;
-                }
+        if let Stmt::Group(g) = stmt {
+            let group_path = g.id.as_ref().map(|id| id.qualified(scope));
+            if group_path.as_ref() == Some(path) {
+                return Some(&mut g.items);
+            }
+            if let Some(found) = find_group_items(&mut g.items, group_path.as_ref().or(scope), path) {
+                return Some(found);
             }
-            _ => {}
         }
     }
-    false
+    None
 }
 
 #[cfg(test)]
@@ -349,6 +358,23 @@ class Bar {
         assert!(output.contains("@pos: (100, 200)"), "Bar's position should be preserved");
     }
 
+    #[test]
+    fn test_update_by_qualified_path() {
+        let input = "group A {\n    class Config\n    group Inner {\n    }\n}\ngroup B {\n    class Config\n}\n";
+        let mut ast = parse_file(input).unwrap();
+
+        // Ambiguous without qualification
+        assert!(!update_node_position(&mut ast, "Config", PointI { x: 1, y: 2 }));
+        assert!(update_node_position(&mut ast, "B.Config", PointI { x: 1, y: 2 }));
+        assert!(update_group_position(&mut ast, Some("A.Inner"), 0, PointI { x: 3, y: 4 }));
+        assert!(insert_implicit_node(&mut ast, "A.Inner.Cache", PointI { x: 5, y: 6 }));
+        assert!(!insert_implicit_node(&mut ast, "B.Config", PointI { x: 5, y: 6 }));
+
+        let output = emit_file(&ast);
+        assert!(output.contains("group B {\n    class Config {\n        @pos: (1, 2)\n    }\n}"));
+        assert!(output.contains("group Inner {\n        @pos: (3, 4)\n        node Cache {\n            @pos: (5, 6)\n        }\n    }"));
+    }
+
     #[test]
     fn test_update_node_size() {
         let input = "class Foo\n";
@@ -361,4 +387,21 @@ class Bar {
         assert!(output.contains("@width: 200"));
         assert!(output.contains("@height: 150"));
     }
+
+    #[test]
+    fn test_geometry_updates_keep_styles() {
+        let input = "group G {\n    @stroke: #888 %% border\n    class Foo {\n        @fill: #fee\n    }\n}\n";
+        let mut ast = parse_file(input).unwrap();
+
+        assert!(update_node_geometry(&mut ast, "G.Foo", 10, 20, Some(200), None));
+        assert!(update_group_position(&mut ast, Some("G"), 0, PointI { x: 1, y: 2 }));
+        let output = emit_file(&ast);
+        assert_eq!(
+            output,
+            "group G {\n    @pos: (1, 2)\n    @stroke: #888 %% border\n    class Foo {\n        @pos: (10, 20)\n        @width: 200\n        @fill: #fee\n    }\n}\n"
+        );
+
+        remove_all_positions(&mut ast);
+        assert_eq!(emit_file(&ast), "group G {\n    @stroke: #888 %% border\n    class Foo {\n        @width: 200\n        @fill: #fee\n    }\n}\n");
+    }
 }
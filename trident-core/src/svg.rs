@@ -0,0 +1,721 @@
+//! Native SVG rendering of a laid out diagram.
+//!
+//! Mirrors the React components (SVGNode, SVGShapeNode, SVGGroup, SVGEdges, SVGNote)
+//! so diagrams can be turned into images without a browser. Style directives
+//! override the theme colours per node, group and edge.
+
+use std::fmt::Write;
+
+use crate::layout::RectI;
+use crate::layout::text::{text_width, FONT_FAMILY, GROUP_LABEL_FONT_SIZE};
+use crate::output::{DiagramOutput, EdgeOutput, GroupOutput, NodeOutput, NoteOutput, TextElement};
+use crate::parser::{get_arrow_definition, HeadStyle, LineStyle, Style};
+
+/// Colour theme for SVG output (mirrors the canvas CSS variables in index.css)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SvgTheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// Options for `render_svg`
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    pub theme: SvgTheme,
+    /// Space around the diagram contents
+    pub padding: i32,
+    pub font_family: String,
+    /// Whether to paint the canvas background (transparent otherwise)
+    pub background: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            theme: SvgTheme::Light,
+            padding: 20,
+            font_family: FONT_FAMILY.to_string(),
+            background: true,
+        }
+    }
+}
+
+struct Palette {
+    bg: &'static str,
+    node_bg: &'static str,
+    border: &'static str,
+    text: &'static str,
+    text_muted: &'static str,
+    edge: &'static str,
+    marker_fill: &'static str,
+    note_bg: &'static str,
+}
+
+impl SvgTheme {
+    fn palette(self) -> Palette {
+        match self {
+            SvgTheme::Light => Palette {
+                bg: "#f5f5f5",
+                node_bg: "#ffffff",
+                border: "#d4d4d4",
+                text: "#525252",
+                text_muted: "#737373",
+                edge: "#737373",
+                marker_fill: "#ffffff",
+                note_bg: "#fefce8",
+            },
+            SvgTheme::Dark => Palette {
+                bg: "#171717",
+                node_bg: "#262626",
+                border: "#404040",
+                text: "#9ca3af",
+                text_muted: "#737373",
+                edge: "#888888",
+                marker_fill: "#1e1e1e",
+                note_bg: "#2e2a1a",
+            },
+        }
+    }
+}
+
+/// Border colour for class nodes, keyed by kind keyword (mirrored from SVGNode.tsx)
+fn border_color(node: &NodeOutput) -> &'static str {
+    match class_keyword_of(node) {
+        "interface" => "#22c55e",
+        "enum" => "#a855f7",
+        "struct" | "record" => "#f97316",
+        "trait" => "#06b6d4",
+        "object" => "#eab308",
+        _ => "#525252",
+    }
+}
+
+/// Title colour for class nodes, keyed by kind keyword (mirrored from SVGNode.tsx)
+fn title_color(node: &NodeOutput) -> &'static str {
+    match class_keyword_of(node) {
+        "interface" => "#86efac",
+        "enum" => "#d8b4fe",
+        "struct" | "record" => "#fdba74",
+        "trait" => "#67e8f9",
+        "object" => "#fde047",
+        _ => "#93c5fd",
+    }
+}
+
+/// The class keyword (interface, enum, ...) a class node was declared with, or "class"
+fn class_keyword_of(node: &NodeOutput) -> &str {
+    node.modifiers
+        .iter()
+        .rev()
+        .map(|m| m.as_str())
+        .find(|m| crate::parser::class_keyword(m).is_some())
+        .unwrap_or("class")
+}
+
+/// Supported node shapes for shape nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Rectangle,
+    Circle,
+    Diamond,
+}
+
+fn shape_of(node: &NodeOutput) -> Shape {
+    if node.modifiers.iter().any(|m| m == "circle") {
+        Shape::Circle
+    } else if node.modifiers.iter().any(|m| m == "diamond") {
+        Shape::Diamond
+    } else {
+        Shape::Rectangle
+    }
+}
+
+/// Render a diagram to a standalone SVG document.
+pub fn render_svg(output: &DiagramOutput, options: &SvgOptions) -> String {
+    let palette = options.theme.palette();
+    let view = content_bounds(output, options.padding);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        view.w, view.h, view.x, view.y, view.w, view.h
+    );
+    svg.push_str("<defs>\n");
+    write_markers(&mut svg, &palette, None);
+    // Markers take the colour of their line, so restyled edges get their own set
+    let mut stroke_colors: Vec<&str> = output.edges.iter().filter_map(|e| e.style.stroke.as_deref()).collect();
+    stroke_colors.sort_unstable();
+    stroke_colors.dedup();
+    for color in stroke_colors {
+        write_markers(&mut svg, &palette, Some(color));
+    }
+    svg.push_str("</defs>\n");
+
+    if options.background {
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            view.x, view.y, view.w, view.h, palette.bg
+        );
+    }
+
+    // Groups are listed parents first, so children are painted on top
+    for group in &output.groups {
+        write_group(&mut svg, group, &palette, options);
+    }
+    for node in &output.nodes {
+        write_node(&mut svg, node, &palette, options);
+    }
+    // Edges go above nodes so that arrowheads are never hidden
+    for edge in &output.edges {
+        write_edge(&mut svg, edge, &palette, options);
+    }
+    for note in &output.notes {
+        write_note(&mut svg, note, &palette, options);
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Union of all group, node and note bounds, expanded by `padding`
+fn content_bounds(output: &DiagramOutput, padding: i32) -> RectI {
+    let mut bb: Option<RectI> = None;
+    let rects = output.groups.iter().map(|g| g.bounds)
+        .chain(output.nodes.iter().map(|n| n.bounds))
+        .chain(output.notes.iter().map(|n| n.bounds));
+    for r in rects {
+        bb = Some(match bb {
+            Some(b) => b.union(&r),
+            None => r,
+        });
+    }
+    let bb = bb.unwrap_or(RectI { x: 0, y: 0, w: 0, h: 0 });
+    RectI {
+        x: bb.x - padding,
+        y: bb.y - padding,
+        w: bb.w + 2 * padding,
+        h: bb.h + 2 * padding,
+    }
+}
+
+/// Marker definitions (mirrored from EdgeDefs in SVGEdges.tsx), drawn in the theme's
+/// edge colour or in `color` for edges with a `@stroke`
+fn write_markers(svg: &mut String, p: &Palette, color: Option<&str>) {
+    let suffix = color.map(marker_suffix).unwrap_or_default();
+    let edge = escape_xml(color.unwrap_or(p.edge));
+    let _ = writeln!(
+        svg,
+        r#"<marker id="arrowhead{suffix}" markerWidth="8" markerHeight="8" refX="7" refY="4" orient="auto-start-reverse"><polyline points="1 1, 7 4, 1 7" fill="{fill}" stroke="{edge}" stroke-width="1"/><line x1="0" y1="4" x2="7" y2="4" stroke="{edge}" stroke-width="1"/></marker>"#,
+        fill = p.marker_fill,
+    );
+    let _ = writeln!(
+        svg,
+        r#"<marker id="rounded-arrowhead{suffix}" markerWidth="8" markerHeight="8" refX="3" refY="4" orient="auto-start-reverse"><path d="M 1 1 Q 5 4, 1 7" fill="none" stroke="{edge}" stroke-width="1"/></marker>"#,
+    );
+    let _ = writeln!(
+        svg,
+        r#"<marker id="triangle{suffix}" markerWidth="10" markerHeight="9" refX="9" refY="4.5" orient="auto-start-reverse"><polygon points="1 1, 9 4.5, 1 8" fill="{fill}" stroke="{edge}" stroke-width="1"/></marker>"#,
+        fill = p.marker_fill,
+    );
+    let _ = writeln!(
+        svg,
+        r#"<marker id="diamond{suffix}" markerWidth="13" markerHeight="10" refX="11" refY="5" orient="auto-start-reverse"><polygon points="0 5, 6 1, 12 5, 6 9" fill="{edge}"/></marker>"#,
+    );
+    let _ = writeln!(
+        svg,
+        r#"<marker id="diamond-empty{suffix}" markerWidth="13" markerHeight="10" refX="11" refY="5" orient="auto-start-reverse"><polygon points="0 5, 6 1, 12 5, 6 9" fill="{fill}" stroke="{edge}" stroke-width="1"/></marker>"#,
+        fill = p.marker_fill,
+    );
+}
+
+/// Marker id suffix for an edge colour, e.g. `-c00` for `#c00`
+fn marker_suffix(color: &str) -> String {
+    format!("-{}", color.trim_start_matches('#'))
+}
+
+/// `stroke`, `stroke-width` and, for dashed lines, `stroke-dasharray` attributes: the
+/// theme's `stroke`, `width` and `dash` (None for solid) unless the style overrides them
+fn stroke_attrs(style: &Style, stroke: &str, width: f64, dash: Option<&str>) -> String {
+    let mut attrs = format!(
+        r#"stroke="{}" stroke-width="{}""#,
+        escape_xml(style.stroke.as_deref().unwrap_or(stroke)),
+        num(style.stroke_width.unwrap_or(width))
+    );
+    let dash = match &style.dash {
+        Some(lengths) => (!lengths.is_empty()).then(|| lengths.iter().map(|n| num(*n)).collect::<Vec<_>>().join(" ")),
+        None => dash.map(str::to_string),
+    };
+    if let Some(dash) = dash {
+        let _ = write!(attrs, r#" stroke-dasharray="{}""#, dash);
+    }
+    attrs
+}
+
+/// A colour set by the style, else the theme colour
+fn or_theme<'a>(color: &'a Option<String>, theme: &'a str) -> String {
+    escape_xml(color.as_deref().unwrap_or(theme))
+}
+
+fn write_group(svg: &mut String, group: &GroupOutput, p: &Palette, options: &SvgOptions) {
+    let b = group.bounds;
+    let label_padding = 6;
+    let fill = or_theme(&group.style.fill, p.node_bg);
+    let _ = writeln!(
+        svg,
+        r#"<g transform="translate({}, {})">"#,
+        b.x, b.y
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect x="0" y="0" width="{}" height="{}" rx="6" ry="6" fill="{}" {}/>"#,
+        b.w, b.h, fill, stroke_attrs(&group.style, p.border, 1.0, None)
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect x="8" y="-10" width="{}" height="20" fill="{}"/>"#,
+        group.label_width + label_padding * 2,
+        fill
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="4" fill="{}" font-size="{}" font-family="{}">{}</text>"#,
+        8 + label_padding,
+        or_theme(&group.style.text_color, p.text_muted),
+        GROUP_LABEL_FONT_SIZE,
+        escape_xml(&options.font_family),
+        escape_xml(&group.name)
+    );
+    svg.push_str("</g>\n");
+}
+
+fn write_node(svg: &mut String, node: &NodeOutput, p: &Palette, options: &SvgOptions) {
+    let b = node.bounds;
+    let _ = writeln!(svg, r#"<g transform="translate({}, {})">"#, b.x, b.y);
+    if node.kind == "node" {
+        write_shape_node(svg, node, p, options);
+    } else {
+        write_class_node(svg, node, p, options);
+    }
+    svg.push_str("</g>\n");
+}
+
+/// Class-like node: box with stereotype, title, separators and body text
+fn write_class_node(svg: &mut String, node: &NodeOutput, p: &Palette, options: &SvgOptions) {
+    let (w, h) = (node.bounds.w, node.bounds.h);
+    let font = escape_xml(&options.font_family);
+    let padding = node.rendering_config.padding;
+    let style = &node.style;
+    let text = or_theme(&style.text_color, p.text);
+
+    let _ = writeln!(
+        svg,
+        r#"<rect x="0" y="0" width="{}" height="{}" rx="4" ry="4" fill="{}" {}/>"#,
+        w, h, or_theme(&style.fill, p.node_bg), stroke_attrs(style, border_color(node), 1.0, None)
+    );
+
+    for element in &node.text_elements {
+        match element {
+            TextElement::Stereotype { text: label, x, y, font_size } => {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" fill="{}" font-size="{}" font-family="{}" font-style="italic">{}</text>"#,
+                    x, y, text, font_size, font, escape_xml(label)
+                );
+            }
+            TextElement::Title { text: label, x, y, font_size, italic } => {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" fill="{}" font-size="{}" font-family="{}" font-weight="bold" font-style="{}">{}</text>"#,
+                    x,
+                    y,
+                    or_theme(&style.text_color, title_color(node)),
+                    font_size,
+                    font,
+                    if *italic { "italic" } else { "normal" },
+                    escape_xml(label)
+                );
+            }
+            TextElement::Separator { y1, y2, .. } => {
+                // Separators always span the full node width
+                let _ = writeln!(
+                    svg,
+                    r#"<line x1="0" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="1"/>"#,
+                    y1, w, y2, or_theme(&style.stroke, p.border)
+                );
+            }
+            TextElement::BodyText { text: line, x, y, font_size, italic, underline } => {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" fill="{}" font-size="{}" font-family="{}"{}{}>{}</text>"#,
+                    x,
+                    y,
+                    text,
+                    font_size,
+                    font,
+                    if *italic { r#" font-style="italic""# } else { "" },
+                    if *underline { r#" text-decoration="underline""# } else { "" },
+                    escape_xml(line)
+                );
+            }
+            TextElement::TemplateParams { text: params, x, y, width, height, font_size } => {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="1" stroke-dasharray="4 2"/>"#,
+                    x, y, width, height, p.node_bg, p.border
+                );
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{}" y="{}" fill="{}" font-size="{}" font-family="{}" font-style="italic">{}</text>"#,
+                    x + padding,
+                    y + height - 4,
+                    text,
+                    font_size,
+                    font,
+                    escape_xml(params)
+                );
+            }
+        }
+    }
+}
+
+/// Shape node: rectangle, circle or diamond with a centered label
+fn write_shape_node(svg: &mut String, node: &NodeOutput, p: &Palette, options: &SvgOptions) {
+    let (w, h) = (node.bounds.w, node.bounds.h);
+    let (cx, cy) = (w as f64 / 2.0, h as f64 / 2.0);
+    let label = node.label.as_deref().unwrap_or(&node.name);
+    let fill = or_theme(&node.style.fill, p.node_bg);
+    let stroke = stroke_attrs(&node.style, p.border, 1.0, None);
+
+    match shape_of(node) {
+        Shape::Circle => {
+            let _ = writeln!(
+                svg,
+                r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" fill="{}" {}/>"#,
+                num(cx), num(cy), num(cx - 1.0), num(cy - 1.0), fill, stroke
+            );
+        }
+        Shape::Diamond => {
+            let _ = writeln!(
+                svg,
+                r#"<polygon points="{cx},1 {r},{cy} {cx},{b} 1,{cy}" fill="{}" {}/>"#,
+                fill,
+                stroke,
+                cx = num(cx),
+                cy = num(cy),
+                r = w - 1,
+                b = h - 1
+            );
+        }
+        Shape::Rectangle => {
+            let _ = writeln!(
+                svg,
+                r#"<rect x="0" y="0" width="{}" height="{}" rx="4" ry="4" fill="{}" {}/>"#,
+                w, h, fill, stroke
+            );
+        }
+    }
+
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="middle" dominant-baseline="central" fill="{}" font-size="12" font-family="{}">{}</text>"#,
+        num(cx),
+        num(cy),
+        or_theme(&node.style.text_color, p.text),
+        escape_xml(&options.font_family),
+        escape_xml(label)
+    );
+}
+
+/// Marker id for a head style, if it draws anything
+fn marker_id(head: HeadStyle) -> Option<&'static str> {
+    match head {
+        HeadStyle::None => None,
+        HeadStyle::Arrow => Some("arrowhead"),
+        HeadStyle::RoundedArrow => Some("rounded-arrowhead"),
+        HeadStyle::Triangle => Some("triangle"),
+        HeadStyle::DiamondFilled => Some("diamond"),
+        HeadStyle::DiamondEmpty => Some("diamond-empty"),
+    }
+}
+
+/// Which end of the line gets the marker: (marker_start, marker_end).
+/// Diamonds sit at the owning ("from") end; everything else at the pointed-to end.
+fn edge_markers(arrow: &str) -> (Option<&'static str>, Option<&'static str>) {
+    let Some(entry) = get_arrow_definition(arrow) else {
+        return (None, None);
+    };
+    let def = &entry.definition;
+    let marker = marker_id(def.head_style);
+    let is_diamond = matches!(def.head_style, HeadStyle::DiamondFilled | HeadStyle::DiamondEmpty);
+    if is_diamond != def.is_left {
+        (marker, None)
+    } else {
+        (None, marker)
+    }
+}
+
+fn write_edge(svg: &mut String, edge: &EdgeOutput, p: &Palette, options: &SvgOptions) {
+    if edge.points.len() < 2 {
+        return;
+    }
+
+    let (marker_start, marker_end) = edge_markers(&edge.arrow);
+    let dashed = get_arrow_definition(&edge.arrow)
+        .map(|e| e.definition.line_style == LineStyle::Dashed)
+        .unwrap_or(false);
+
+    let mut points: Vec<(f64, f64)> = edge.points.iter().map(|pt| (pt.x as f64, pt.y as f64)).collect();
+
+    // Pull the line back where a marker sits so the head touches the border
+    const ARROW_OFFSET: f64 = 5.0;
+    if marker_start.is_some() {
+        points[0] = pull_back(points[0], points[1], ARROW_OFFSET);
+    }
+    if marker_end.is_some() {
+        let n = points.len();
+        points[n - 1] = pull_back(points[n - 1], points[n - 2], ARROW_OFFSET);
+    }
+
+    let coords: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", num(*x), num(*y))).collect();
+    let mut line = format!(
+        r#"<polyline points="{}" fill="none" {}"#,
+        coords.join(" "),
+        stroke_attrs(&edge.style, p.edge, 1.5, dashed.then_some("8,4"))
+    );
+    let suffix = edge.style.stroke.as_deref().map(marker_suffix).unwrap_or_default();
+    if let Some(id) = marker_start {
+        let _ = write!(line, r#" marker-start="url(#{}{})""#, id, suffix);
+    }
+    if let Some(id) = marker_end {
+        let _ = write!(line, r#" marker-end="url(#{}{})""#, id, suffix);
+    }
+    line.push_str("/>\n");
+    svg.push_str(&line);
+
+    let text = or_theme(&edge.style.text_color, p.text);
+    if let Some(label) = &edge.label {
+        let at = (edge.label_pos.x as f64, edge.label_pos.y as f64);
+        write_edge_label(svg, label, at, &text, p, options);
+    }
+    for (end, at) in [(&edge.from_end, edge.from_label_pos), (&edge.to_end, edge.to_label_pos)] {
+        if !end.is_empty() {
+            write_end_label(svg, &end.text(), (at.x as f64, at.y as f64), &text, options);
+        }
+    }
+}
+
+/// Note sheet with a folded top-right corner and a dashed connector to its target
+fn write_note(svg: &mut String, note: &NoteOutput, p: &Palette, options: &SvgOptions) {
+    if note.connector.len() >= 2 {
+        let coords: Vec<String> = note.connector.iter().map(|pt| format!("{},{}", pt.x, pt.y)).collect();
+        let _ = writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1" stroke-dasharray="4 2"/>"#,
+            coords.join(" "),
+            p.edge
+        );
+    }
+
+    let b = note.bounds;
+    let (w, h, fold) = (b.w, b.h, note.fold);
+    let _ = writeln!(svg, r#"<g transform="translate({}, {})">"#, b.x, b.y);
+    let _ = writeln!(
+        svg,
+        r#"<polygon points="0,0 {},0 {},{} {},{} 0,{}" fill="{}" stroke="{}" stroke-width="1"/>"#,
+        w - fold, w, fold, w, h, h, p.note_bg, p.border
+    );
+    let _ = writeln!(
+        svg,
+        r#"<path d="M {} 0 L {} {} L {} {}" fill="none" stroke="{}" stroke-width="1"/>"#,
+        w - fold, w - fold, fold, w, fold, p.border
+    );
+    let font = escape_xml(&options.font_family);
+    for element in &note.text_elements {
+        if let TextElement::BodyText { text, x, y, font_size, .. } = element {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" fill="{}" font-size="{}" font-family="{}">{}</text>"#,
+                x, y, p.text, font_size, font, escape_xml(text)
+            );
+        }
+    }
+    svg.push_str("</g>\n");
+}
+
+/// Move `end` towards `towards` by `by` pixels (at most the segment length)
+fn pull_back(end: (f64, f64), towards: (f64, f64), by: f64) -> (f64, f64) {
+    let (dx, dy) = (towards.0 - end.0, towards.1 - end.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    if len == 0.0 {
+        return end;
+    }
+    let by = by.min(len);
+    (end.0 + dx / len * by, end.1 + dy / len * by)
+}
+
+/// Edge label on a background box so it stays readable over the line
+fn write_edge_label(svg: &mut String, label: &str, at: (f64, f64), color: &str, p: &Palette, options: &SvgOptions) {
+    let font_size = 11.0;
+    let text_w = text_width(label, font_size as i32) as f64;
+    let (rect_w, rect_h) = (text_w + 8.0, font_size + 6.0);
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" rx="2" ry="2" fill="{}"/>"#,
+        num(at.0 - rect_w / 2.0),
+        num(at.1 - rect_h / 2.0),
+        num(rect_w),
+        num(rect_h),
+        p.bg
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" fill="{}" font-size="{}" font-family="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+        num(at.0),
+        num(at.1),
+        color,
+        font_size,
+        escape_xml(&options.font_family),
+        escape_xml(label)
+    );
+}
+
+/// Multiplicity/role next to an edge end, without a background box
+fn write_end_label(svg: &mut String, text: &str, at: (f64, f64), color: &str, options: &SvgOptions) {
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" fill="{}" font-size="10" font-family="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+        num(at.0),
+        num(at.1),
+        color,
+        escape_xml(&options.font_family),
+        escape_xml(text)
+    );
+}
+
+/// Format a coordinate, dropping the fraction when it is a whole number
+fn num(v: f64) -> String {
+    let rounded = (v * 100.0).round() / 100.0;
+    if rounded.fract() == 0.0 {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{}", rounded)
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::compile_to_output;
+
+    #[test]
+    fn test_render_svg_contains_nodes_and_edges() {
+        let output = compile_to_output("class A {\n    + name: string\n}\nclass B\nA --|> B : extends\n");
+        let svg = render_svg(&output, &SvgOptions::default());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(">A</text>"));
+        assert!(svg.contains(">+ name: string</text>"));
+        assert!(svg.contains("<polyline"));
+        assert!(svg.contains("marker-end=\"url(#triangle)\""));
+        assert!(svg.contains(">extends</text>"));
+    }
+
+    #[test]
+    fn test_render_svg_marker_placement() {
+        assert_eq!(edge_markers("compose_right"), (Some("diamond"), None));
+        assert_eq!(edge_markers("compose_left"), (None, Some("diamond")));
+        assert_eq!(edge_markers("assoc_left"), (Some("arrowhead"), None));
+        assert_eq!(edge_markers("line"), (None, None));
+    }
+
+    #[test]
+    fn test_render_svg_shapes_and_groups() {
+        let output = compile_to_output("group G {\n    circle C\n    diamond D\n}\nC --> D\n");
+        let svg = render_svg(&output, &SvgOptions { theme: SvgTheme::Dark, ..SvgOptions::default() });
+        assert!(svg.contains("<ellipse"));
+        assert!(svg.contains("<polygon points="));
+        assert!(svg.contains(">G</text>"));
+        assert!(svg.contains("#171717"));
+    }
+
+    #[test]
+    fn test_render_svg_escapes_text() {
+        let output = compile_to_output("class A \"List<T> & more\"\n");
+        let svg = render_svg(&output, &SvgOptions::default());
+        assert!(svg.contains("List&lt;T&gt; &amp; more"));
+    }
+
+    #[test]
+    fn test_render_svg_styles_static_and_abstract_members() {
+        let output = compile_to_output("class A {\n    + {static} count: int\n    + {abstract} area(): f64\n}\n");
+        let svg = render_svg(&output, &SvgOptions::default());
+        assert!(svg.contains(r#"text-decoration="underline">+ {static} count: int</text>"#));
+        assert!(svg.contains(r#"font-style="italic">+ {abstract} area(): f64</text>"#));
+    }
+
+    #[test]
+    fn test_render_svg_relation_end_labels() {
+        let output = compile_to_output("class Order\nclass Item\nOrder \"1\" *-- \"0..* items\" Item\n");
+        let svg = render_svg(&output, &SvgOptions::default());
+        let end_label = format!(r#"font-size="10" font-family="{}" text-anchor="middle" dominant-baseline="central">1</text>"#, escape_xml(FONT_FAMILY));
+        assert!(svg.contains(&end_label));
+        assert!(svg.contains(">0..* items</text>"));
+    }
+
+    #[test]
+    fn test_render_svg_template_box() {
+        let output = compile_to_output("class List<T extends Item>\nclass Item\nList --> Item\n");
+        let list = output.nodes.iter().find(|n| n.name == "List").unwrap();
+        assert!(matches!(&list.text_elements[0], TextElement::TemplateParams { text, y: -8, .. } if text == "T extends Item"));
+        let svg = render_svg(&output, &SvgOptions::default());
+        assert!(svg.contains(r#"stroke-dasharray="4 2""#));
+        assert!(svg.contains(r#"font-style="italic">T extends Item</text>"#));
+        assert!(svg.contains(">List</text>"));
+    }
+
+    #[test]
+    fn test_render_svg_notes() {
+        let output = compile_to_output("class A\nnote for A \"first\\nsecond\"\n");
+        let note = &output.notes[0];
+        assert_eq!(note.connector.len(), 2);
+        let svg = render_svg(&output, &SvgOptions::default());
+        assert!(svg.contains(&format!("<polygon points=\"0,0 {},0 {},10", note.bounds.w - 10, note.bounds.w)));
+        assert!(svg.contains(r#"stroke-width="1" stroke-dasharray="4 2"/>"#));
+        assert!(svg.contains(">first</text>"));
+        assert!(svg.contains(">second</text>"));
+    }
+
+    #[test]
+    fn test_render_svg_styles() {
+        let output = compile_to_output(
+            "group G {\n    @fill: #eef\n    class A {\n        @fill: #fee\n        @stroke: #c00\n        @text_color: navy\n    }\n    circle B {\n        @dash: 4 2\n        @stroke_width: 2\n    }\n}\nA ..> B { @stroke: #c00; @dash: none }\n",
+        );
+        let svg = render_svg(&output, &SvgOptions::default());
+        assert!(svg.contains(r##"rx="6" ry="6" fill="#eef" stroke="#d4d4d4" stroke-width="1"/>"##));
+        assert!(svg.contains(r##"rx="4" ry="4" fill="#fee" stroke="#c00" stroke-width="1"/>"##));
+        let title = format!(r#"fill="navy" font-size="12" font-family="{}" font-weight="bold" font-style="normal">A</text>"#, escape_xml(FONT_FAMILY));
+        assert!(svg.contains(&title));
+        assert!(svg.contains(r##"fill="#ffffff" stroke="#d4d4d4" stroke-width="2" stroke-dasharray="4 2"/>"##));
+        // `@dash: none` drops the dependency dashes; the arrowhead matches the line
+        assert!(svg.contains(r#"<marker id="arrowhead-c00""#));
+        assert!(svg.contains(r##"fill="none" stroke="#c00" stroke-width="1.5" marker-end="url(#arrowhead-c00)"/>"##));
+    }
+}
@@ -5,93 +5,7 @@
 use wasm_bindgen::prelude::*;
 use serde_json::to_string;
 
-use crate::layout::{layout_diagram, LayoutConfig, RectI};
-use crate::output::{DiagramOutput, NodeOutput, EdgeOutput, GroupOutput, ErrorInfo, TextElement};
-use crate::layout::NodeRenderingConfig;
-
-/// Generate positioned text elements for a node
-fn generate_text_elements(node: &crate::parser::compile::Node, config: &NodeRenderingConfig) -> Vec<TextElement> {
-    let mut elements = Vec::new();
-    let mut current_y = config.padding;
-
-    // Add stereotype if present
-    let has_stereotypes = !node.modifiers.is_empty() || node.kind != "class";
-    if has_stereotypes {
-        let stereotypes = format_modifiers(&node.modifiers, &node.kind);
-        elements.push(TextElement::Stereotype {
-            text: stereotypes,
-            y: current_y + 10, // Baseline for 10px font, centered in line_height
-            font_size: 10,
-        });
-        current_y += config.line_height;
-    }
-
-    // Add title
-    let title = node.label.as_ref().unwrap_or(&node.id.0).clone();
-    let is_abstract = node.modifiers.contains(&"abstract".to_string());
-    elements.push(TextElement::Title {
-        text: title,
-        y: current_y + 12, // Baseline for 12px font
-        font_size: 12,
-        italic: is_abstract,
-    });
-    current_y += config.line_height;
-
-    // Always add separator line below title (centered in its line slot)
-    elements.push(TextElement::Separator {
-        x1: 0,
-        y1: current_y + config.line_height / 2,
-        x2: 9999,
-        y2: current_y + config.line_height / 2,
-    });
-    current_y += config.line_height;
-
-    // Add body lines in a simple column
-    for line in &node.body_lines {
-        if is_separator_line(line) {
-            // Add separator line (centered in its line slot)
-            elements.push(TextElement::Separator {
-                x1: 0,
-                y1: current_y + config.line_height / 2,
-                x2: 9999,
-                y2: current_y + config.line_height / 2,
-            });
-            current_y += config.line_height;
-        } else {
-            // Add text line (baseline positioned for 11px font)
-            elements.push(TextElement::BodyText {
-                text: line.clone(),
-                y: current_y + 11,
-                font_size: 11,
-            });
-            current_y += config.line_height;
-        }
-    }
-
-    elements
-}
-
-/// Format modifiers and kind for stereotype display
-fn format_modifiers(modifiers: &[String], kind: &str) -> String {
-    let mut parts = Vec::new();
-
-    // Add modifiers as stereotypes
-    for modifier in modifiers {
-        parts.push(format!("«{}»", modifier));
-    }
-
-    // Add kind as stereotype if not "class"
-    if kind != "class" {
-        parts.push(format!("«{}»", kind));
-    }
-
-    parts.join(" ")
-}
-
-/// Check if a line is a separator (---)
-fn is_separator_line(line: &str) -> bool {
-    line.trim().chars().all(|c| c == '-')
-}
+use crate::output::{compile_to_output, Severity};
 use crate::parser::{self, PointI, get_arrow_registry};
 
 #[wasm_bindgen]
@@ -107,115 +21,13 @@ extern "C" {
 
 #[wasm_bindgen]
 pub fn compile_diagram(input: &str) -> String {
-    let ast = match parser::parse_file(input) {
-        Ok(ast) => ast,
-        Err(e) => {
-            console_error(&format!("Error parsing file: {:?}", e));
-            let error_output = DiagramOutput {
-                groups: vec![],
-                nodes: vec![],
-                edges: vec![],
-                implicit_nodes: vec![],
-                error: Some(ErrorInfo {
-                    message: e.msg.clone(),
-                    line: e.line,
-                    column: e.col,
-                    end_line: e.line,
-                    end_column: e.col + 1, // Highlight at least one character
-                }),
-            };
-            return serde_json::to_string(&error_output).unwrap();
-        }
-    };
-    let diagram = match parser::compile(&ast) {
-        Ok(diagram) => diagram,
-        Err(e) => {
-            console_error(&format!("Error compiling file: {:?}", e));
-            let error_output = DiagramOutput {
-                groups: vec![],
-                nodes: vec![],
-                edges: vec![],
-                implicit_nodes: vec![],
-                error: Some(ErrorInfo {
-                    message: e.msg.clone(),
-                    line: e.line,
-                    column: e.col,
-                    end_line: e.line,
-                    end_column: 1000, // Highlight the whole line
-                }),
-            };
-            return serde_json::to_string(&error_output).unwrap();
-        }
-    };
-    
-    // Use the layout algorithm specified in the AST, or default to hierarchical
-    let layout_name = ast.layout.as_deref().unwrap_or("hierarchical");
-    let layout_result = layout_diagram(&diagram, &LayoutConfig::default(), layout_name);
-    
-    // Build groups (only named groups, skip root and anonymous)
-    let groups: Vec<GroupOutput> = diagram.groups.iter()
-        .filter(|g| g.id.is_some() && g.gid != diagram.root)
-        .filter_map(|g| {
-            let bounds = layout_result.group_world_bounds.get(&g.gid).copied()?;
-            Some(GroupOutput {
-                id: g.id.as_ref()?.0.clone(),
-                bounds,
-            })
-        })
-        .collect();
-    
-    // Build nodes
-    let nodes: Vec<NodeOutput> = diagram.nodes.iter().map(|n| {
-        let bounds = layout_result.node_world_bounds.get(&n.nid).copied().unwrap_or(RectI { x: 0, y: 0, w: 0, h: 0 });
-        // Get parent group's world position for local coordinate calculation
-        let parent_world = layout_result.group_world_pos.get(&n.group).copied().unwrap_or(PointI { x: 0, y: 0 });
-        let rendering_config = NodeRenderingConfig {
-            padding: 8,
-            line_height: 14,
-            separator_spacing: 10,
-            char_width: 7,
-        };
-        let text_elements = generate_text_elements(n, &rendering_config);
-
-        NodeOutput {
-            id: n.id.0.clone(),
-            kind: n.kind.clone(),
-            modifiers: n.modifiers.clone(),
-            label: n.label.clone(),
-            text_elements,
-            rendering_config,
-            bounds,
-            has_pos: n.pos.is_some(),
-            parent_offset: parent_world,
-            explicit: n.explicit,
-        }
-    }).collect();
-    
-    // Build edges
-    let edges: Vec<EdgeOutput> = diagram.edges.iter().map(|e| {
-        let from_id = diagram.nodes[e.from.0].id.0.clone();
-        let to_id = diagram.nodes[e.to.0].id.0.clone();
-        EdgeOutput {
-            from: from_id,
-            to: to_id,
-            arrow: e.arrow.clone(),
-            label: e.label.clone(),
-        }
-    }).collect();
-    
-    // Collect implicit node IDs for editor diagnostics
-    let implicit_nodes: Vec<String> = diagram.nodes.iter()
-        .filter(|n| !n.explicit)
-        .map(|n| n.id.0.clone())
-        .collect();
-    
-    let output = DiagramOutput { groups, nodes, edges, implicit_nodes, error: None };
+    let output = compile_to_output(input);
+    for d in output.diagnostics.iter().filter(|d| d.severity == Severity::Error) {
+        console_error(&format!("Error compiling diagram at {}:{}: {}", d.line, d.column, d.message));
+    }
     to_string(&output).unwrap()
 }
 
-
-
-
 /// Update a group position and return the new source code.
 /// For named groups: pass the group_id.
 /// For anonymous groups: pass empty string for group_id and use the group_index.
@@ -331,6 +143,10 @@ pub fn rename_symbol(source: &str, old_name: &str, new_name: &str) -> String {
         }
     };
     
+    if !parser::is_valid_name(new_name) {
+        console_error(&format!("'{}' can't be used as a name", new_name));
+        return source.to_string();
+    }
     if parser::rename_symbol_in_ast(&mut ast, old_name, new_name) {
         parser::emit_file(&ast)
     } else {
@@ -339,6 +155,39 @@ pub fn rename_symbol(source: &str, old_name: &str, new_name: &str) -> String {
     }
 }
 
+/// Find every occurrence of a node or group identifier.
+/// Returns a JSON array of `{ line, start_col, end_col, start_col_utf16, end_col_utf16, text }`
+/// (empty if the source doesn't parse). The editor should use the UTF-16 columns.
+#[wasm_bindgen]
+pub fn find_references(source: &str, symbol: &str) -> String {
+    match parser::parse_file(source) {
+        Ok(ast) => {
+            let refs = parser::find_symbol_references(&ast, symbol);
+            serde_json::to_string(&refs).unwrap_or_else(|_| "[]".to_string())
+        }
+        Err(_) => "[]".to_string(),
+    }
+}
+
+/// Fully qualified path of the node or group whose name is under the cursor
+/// (1-based line and UTF-16 column), or an empty string if there is none.
+/// Pass the result to `find_references` / `rename_symbol`.
+#[wasm_bindgen]
+pub fn symbol_at(source: &str, line: usize, column: usize) -> String {
+    match parser::parse_file(source) {
+        Ok(ast) => parser::symbol_at(&ast, line, column).unwrap_or_default(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Write a name the way it has to appear in source, wrapped in backticks
+/// unless it is a plain identifier. Used when inserting renamed symbols.
+/// Returns `undefined` for names that can't be written (see `is_valid_name`).
+#[wasm_bindgen]
+pub fn format_identifier(name: &str) -> Option<String> {
+    parser::is_valid_name(name).then(|| parser::format_ident(name).into_owned())
+}
+
 /// Get the arrow registry as JSON.
 /// Returns all arrow definitions including auto-generated left variants.
 /// This is used by the TypeScript side for autocomplete, syntax highlighting, and rendering.
@@ -349,7 +198,7 @@ pub fn get_arrows() -> String {
 }
 
 /// Get all defined symbols (node IDs and group IDs) in the source.
-/// Returns a JSON array of strings.
+/// Returns a JSON array of fully qualified paths (`Outer.Config`).
 /// NOTE: This tries to parse the source and extract symbols even if there are errors.
 #[wasm_bindgen]
 pub fn get_symbols(source: &str) -> String {